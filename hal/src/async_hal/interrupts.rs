@@ -214,6 +214,32 @@ declare_multiple_interrupts!(ADC0: [ADC0_RESRDY, ADC0_OTHER]);
 declare_multiple_interrupts!(ADC1: [ADC1_RESRDY, ADC1_OTHER]);
 #[hal_cfg(any("adc-d11", "adc-d21"))]
 declare_interrupts!(ADC);
+
+// ----------  USB Interrupt ---------- //
+#[cfg(feature = "usb")]
+#[hal_cfg(any("usb-d11", "usb-d21"))]
+declare_interrupts!(USB);
+
+#[cfg(feature = "usb")]
+#[hal_cfg("usb-d5x")]
+declare_multiple_interrupts!(USB: [USB_OTHER, USB_SOF_HSOF, USB_TRCPT0, USB_TRCPT1]);
+
+// ----------  NVMCTRL Interrupt ---------- //
+#[hal_cfg("nvmctrl-d5x")]
+declare_interrupts!(NVMCTRL_1);
+
+// ----------  RTC Interrupt ---------- //
+#[hal_cfg("rtc")]
+declare_interrupts!(RTC);
+
+// ----------  ICM Interrupt ---------- //
+#[hal_cfg("icm")]
+declare_interrupts!(ICM);
+
+// ----------  TRNG Interrupt ---------- //
+#[hal_cfg("trng")]
+declare_interrupts!(TRNG);
+
 /// An interrupt source that may have one or many interrupt bindings.
 ///
 /// This trait may implemented directly when multiple interrupt sources are
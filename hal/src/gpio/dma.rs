@@ -0,0 +1,240 @@
+//! # DMA transfers to GPIO port groups
+//!
+//! The DMAC can write directly to the output registers of a [`Port`] group,
+//! which makes it possible to drive parallel buses (e.g. 8080-style LCD
+//! interfaces) or bit-banged protocols (e.g. WS2812 LEDs) without any CPU
+//! involvement.
+//!
+//! A DMA transfer writes entire beats to the destination register, so it can
+//! potentially change the level of every pin covered by the beat. To keep this
+//! safe, a [`PortLane`] takes ownership of *all* the pins in a naturally
+//! aligned byte, halfword or word of a [`Port`] group, and can then be used as
+//! a destination [`Buffer`] for a [`Transfer`].
+//!
+//! A [`PortLane`] can be created from a tuple of type-level [`Pin`]s with
+//! [`from_pins`](PortLane::from_pins), in which case the requirements are
+//! checked at compile-time, or from an array of [`DynPin`]s with
+//! [`new`](PortLane::new), in which case they are checked at run-time.
+//!
+//! ```
+//! let pins = Pins::new(peripherals.port);
+//! // PA16 - PA23 form the third byte lane of group A
+//! let lane = ByteLane::from_pins(
+//!     (
+//!         pins.pa16.into_push_pull_output(),
+//!         pins.pa17.into_push_pull_output(),
+//!         pins.pa18.into_push_pull_output(),
+//!         pins.pa19.into_push_pull_output(),
+//!         pins.pa20.into_push_pull_output(),
+//!         pins.pa21.into_push_pull_output(),
+//!         pins.pa22.into_push_pull_output(),
+//!         pins.pa23.into_push_pull_output(),
+//!     ),
+//!     OutputRegister::Out,
+//! );
+//!
+//! // Write one byte of the buffer to the bus for every overflow of TC0
+//! let xfer = Transfer::new(chan0, pixels, lane, false)
+//!     .unwrap()
+//!     .begin(TriggerSource::Tc0Ovf, TriggerAction::Burst);
+//! let (chan0, pixels, lane) = xfer.wait();
+//! ```
+//!
+//! The transfer speed is typically paced by using a TC or TCC overflow as the
+//! DMA trigger source, as shown above. Any other trigger source may be used as
+//! well.
+//!
+//! [`Port`]: crate::pac::Port
+//! [`Pin`]: super::Pin
+//! [`Transfer`]: crate::dmac::Transfer
+
+use crate::dmac::{Beat, Buffer};
+use crate::typelevel::Sealed;
+
+use super::dynpin::{DynGroup, DynPin, DynPinId, DynPinMode, Error};
+use super::group::{PinTuple, contiguous, same_group, writable};
+use super::reg::GROUP;
+
+//==============================================================================
+//  OutputRegister
+//==============================================================================
+
+/// Output registers of a [`Port`](crate::pac::Port) group
+///
+/// Writing to `OutSet`, `OutClr` or `OutTgl` only affects the pins whose bits
+/// are set in the written value, while writing to `Out` sets the level of
+/// every pin covered by the access.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OutputRegister {
+    /// Data output value register (`OUT`)
+    Out,
+    /// Data output value clear register (`OUTCLR`)
+    OutClr,
+    /// Data output value set register (`OUTSET`)
+    OutSet,
+    /// Data output value toggle register (`OUTTGL`)
+    OutTgl,
+}
+
+//==============================================================================
+//  LaneWidth
+//==============================================================================
+
+/// Supported widths of a [`PortLane`]
+///
+/// This trait is implemented for arrays of 8, 16 and 32 [`DynPin`]s, which
+/// respectively map to byte, halfword and word DMA beats.
+pub trait LaneWidth: Sealed {
+    /// DMA beat size used to access the lane
+    type Beat: Beat;
+}
+
+impl Sealed for [DynPin; 8] {}
+impl Sealed for [DynPin; 16] {}
+impl Sealed for [DynPin; 32] {}
+
+impl LaneWidth for [DynPin; 8] {
+    type Beat = u8;
+}
+
+impl LaneWidth for [DynPin; 16] {
+    type Beat = u16;
+}
+
+impl LaneWidth for [DynPin; 32] {
+    type Beat = u32;
+}
+
+//==============================================================================
+//  PortLane
+//==============================================================================
+
+/// A naturally aligned set of output pins within a single
+/// [`Port`](crate::pac::Port) group, usable as a DMA destination
+///
+/// See the [module-level documentation](self) for more details.
+pub struct PortLane<const N: usize>
+where
+    [DynPin; N]: LaneWidth,
+{
+    pins: [DynPin; N],
+    group: DynGroup,
+    offset: usize,
+    register: OutputRegister,
+}
+
+/// [`PortLane`] made of 8 pins, accessed with byte beats
+pub type ByteLane = PortLane<8>;
+
+/// [`PortLane`] made of 16 pins, accessed with halfword beats
+pub type HalfWordLane = PortLane<16>;
+
+/// [`PortLane`] made of 32 pins, accessed with word beats
+pub type WordLane = PortLane<32>;
+
+/// Check that the pins are outputs covering a naturally aligned lane of a
+/// single group
+const fn valid_lane<const N: usize>(ids: &[DynPinId; N], modes: &[DynPinMode; N]) -> bool {
+    ids[0].num as usize % N == 0 && same_group(ids) && contiguous(ids) && writable(modes)
+}
+
+impl<const N: usize> PortLane<N>
+where
+    [DynPin; N]: LaneWidth,
+{
+    /// Create a new [`PortLane`] from an array of [`DynPin`]s
+    ///
+    /// Every pin must be configured as an output, and the pins must be ordered
+    /// by increasing pin number. Together, they must cover a naturally aligned
+    /// byte, halfword or word of a single [`Port`](crate::pac::Port) group.
+    /// For instance, a [`ByteLane`] could be made of `PB08` to `PB15`, but not
+    /// of `PB04` to `PB11`.
+    ///
+    /// Bit `n` of every beat written by the DMAC sets the level of
+    /// `pins[n]`. If any of these requirements is not met, returns
+    /// [`Error::InvalidPinType`] along with the pins.
+    #[inline]
+    pub fn new(pins: [DynPin; N], register: OutputRegister) -> Result<Self, (Error, [DynPin; N])> {
+        let ids = pins.each_ref().map(DynPin::id);
+        let modes = pins.each_ref().map(DynPin::mode);
+        if valid_lane(&ids, &modes) {
+            Ok(Self::new_unchecked(pins, register))
+        } else {
+            Err((Error::InvalidPinType, pins))
+        }
+    }
+
+    /// Create a new [`PortLane`] from a tuple of type-level [`Pin`]s
+    ///
+    /// The pins must meet the same requirements as for [`new`](Self::new),
+    /// but they are checked at compile-time instead. Bit `n` of every beat
+    /// written by the DMAC sets the level of the `n`th pin of the tuple.
+    ///
+    /// [`Pin`]: super::Pin
+    #[inline]
+    pub fn from_pins<T: PinTuple<N>>(pins: T, register: OutputRegister) -> Self {
+        const {
+            assert!(
+                valid_lane(&T::IDS, &T::MODES),
+                "The pins of a PortLane must be outputs covering an aligned lane of a single group"
+            )
+        };
+        Self::new_unchecked(pins.into_dyn_pins(), register)
+    }
+
+    #[inline]
+    fn new_unchecked(pins: [DynPin; N], register: OutputRegister) -> Self {
+        let first = pins[0].id();
+        Self {
+            pins,
+            group: first.group,
+            offset: first.num as usize / 8,
+            register,
+        }
+    }
+
+    /// Return the [`OutputRegister`] targeted by DMA transfers
+    #[inline]
+    pub fn register(&self) -> OutputRegister {
+        self.register
+    }
+
+    /// Change the [`OutputRegister`] targeted by DMA transfers
+    #[inline]
+    pub fn set_register(&mut self, register: OutputRegister) {
+        self.register = register;
+    }
+
+    /// Release the pins
+    #[inline]
+    pub fn free(self) -> [DynPin; N] {
+        self.pins
+    }
+}
+
+unsafe impl<const N: usize> Buffer for PortLane<N>
+where
+    [DynPin; N]: LaneWidth,
+{
+    type Beat = <[DynPin; N] as LaneWidth>::Beat;
+
+    #[inline]
+    fn dma_ptr(&mut self) -> *mut Self::Beat {
+        let register = GROUP::get(self.group).output_register_ptr(self.register);
+        // Safety: The PORT registers can be accessed with byte, halfword or word
+        // accesses, and the lane is naturally aligned, so the resulting address
+        // stays within the targeted register.
+        unsafe { register.cast::<u8>().add(self.offset).cast() }
+    }
+
+    #[inline]
+    fn incrementing(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn buffer_len(&self) -> usize {
+        1
+    }
+}
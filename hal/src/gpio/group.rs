@@ -0,0 +1,589 @@
+//! # Multi-pin groups
+//!
+//! [`Pin`] and [`DynPin`] access one pin at a time, so updating an 8-bit
+//! parallel bus requires eight separate register writes, and the bus goes
+//! through intermediate states in between. This module provides types that
+//! take ownership of several pins within the same [`DynGroup`], so that they
+//! can be read with a single access to the `IN` register, and written with one
+//! access each to the `OUTSET` and `OUTCLR` registers.
+//!
+//! Two types are provided:
+//!
+//! * [`PinGroup`] accepts any set of pins within a group. Bit `n` of the
+//!   values read or written maps to the `n`th pin of the group, regardless of
+//!   its pin number, so reads and writes must gather and scatter the bits.
+//! * [`ContiguousPinGroup`] only accepts pins with consecutive pin numbers, in
+//!   increasing order. Reads and writes then reduce to a single shift and mask.
+//!
+//! Both types can be created from an array of [`DynPin`]s, in which case the
+//! requirements are checked at run-time, or from a tuple of type-level
+//! [`Pin`]s with [`from_pins`](PinGroup::from_pins), in which case the
+//! requirements are checked at compile-time.
+//!
+//! ```
+//! let pins = Pins::new(peripherals.port);
+//! // Data bus of a parallel display, PA16 to PA23
+//! let mut bus = ContiguousPinGroup::from_pins((
+//!     pins.pa16.into_push_pull_output(),
+//!     pins.pa17.into_push_pull_output(),
+//!     pins.pa18.into_push_pull_output(),
+//!     pins.pa19.into_push_pull_output(),
+//!     pins.pa20.into_push_pull_output(),
+//!     pins.pa21.into_push_pull_output(),
+//!     pins.pa22.into_push_pull_output(),
+//!     pins.pa23.into_push_pull_output(),
+//! ));
+//! bus.write(0xA5).unwrap();
+//!
+//! // Keypad columns, scattered across group B
+//! let cols = PinGroup::from_pins((
+//!     pins.pb02.into_pull_up_input(),
+//!     pins.pb07.into_pull_up_input(),
+//!     pins.pb09.into_pull_up_input(),
+//! ));
+//! let pressed = !cols.read().unwrap() & 0b111;
+//! ```
+
+use crate::typelevel::Sealed;
+
+use super::dynpin::*;
+use super::pin::*;
+use super::reg::GROUP;
+
+//==============================================================================
+//  PinTuple
+//==============================================================================
+
+/// Tuples of type-level [`Pin`]s that can be converted into a pin group
+///
+/// This trait is implemented for tuples of up to 32 [`Pin`]s. The
+/// [`DynPinId`]s of the pins are available as an associated constant, which
+/// allows the pin group types to validate them at compile-time.
+pub trait PinTuple<const SIZE: usize>: Sealed {
+    /// [`DynPinId`]s of the pins in the tuple
+    const IDS: [DynPinId; SIZE];
+    /// [`DynPinMode`]s of the pins in the tuple
+    const MODES: [DynPinMode; SIZE];
+
+    /// Erase the type-level information of each [`Pin`]
+    fn into_dyn_pins(self) -> [DynPin; SIZE];
+}
+
+macro_rules! pin_tuple {
+    ( $( $N:literal: ( $( $I:ident, $M:ident, $i:tt );+ ) )+ ) => {
+        $(
+            impl<$($I: PinId, $M: PinMode),+> Sealed for ($(Pin<$I, $M>,)+) {}
+
+            impl<$($I: PinId, $M: PinMode),+> PinTuple<$N> for ($(Pin<$I, $M>,)+) {
+                const IDS: [DynPinId; $N] = [$($I::DYN),+];
+                const MODES: [DynPinMode; $N] = [$($M::DYN),+];
+
+                #[inline]
+                fn into_dyn_pins(self) -> [DynPin; $N] {
+                    [$(self.$i.into()),+]
+                }
+            }
+        )+
+    };
+}
+
+pin_tuple!(
+    1: (I0, M0, 0)
+    2: (I0, M0, 0; I1, M1, 1)
+    3: (I0, M0, 0; I1, M1, 1; I2, M2, 2)
+    4: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3)
+    5: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4)
+    6: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5)
+    7: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6)
+    8: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7)
+    9: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8)
+    10: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9)
+    11: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10)
+    12: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11)
+    13: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12)
+    14: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13)
+    15: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14)
+    16: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15)
+    17: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16)
+    18: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17)
+    19: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18)
+    20: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18;
+        I19, M19, 19)
+    21: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18;
+        I19, M19, 19; I20, M20, 20)
+    22: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18;
+        I19, M19, 19; I20, M20, 20; I21, M21, 21)
+    23: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18;
+        I19, M19, 19; I20, M20, 20; I21, M21, 21; I22, M22, 22)
+    24: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18;
+        I19, M19, 19; I20, M20, 20; I21, M21, 21; I22, M22, 22; I23, M23, 23)
+    25: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18;
+        I19, M19, 19; I20, M20, 20; I21, M21, 21; I22, M22, 22; I23, M23, 23; I24, M24, 24)
+    26: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18;
+        I19, M19, 19; I20, M20, 20; I21, M21, 21; I22, M22, 22; I23, M23, 23; I24, M24, 24;
+        I25, M25, 25)
+    27: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18;
+        I19, M19, 19; I20, M20, 20; I21, M21, 21; I22, M22, 22; I23, M23, 23; I24, M24, 24;
+        I25, M25, 25; I26, M26, 26)
+    28: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18;
+        I19, M19, 19; I20, M20, 20; I21, M21, 21; I22, M22, 22; I23, M23, 23; I24, M24, 24;
+        I25, M25, 25; I26, M26, 26; I27, M27, 27)
+    29: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18;
+        I19, M19, 19; I20, M20, 20; I21, M21, 21; I22, M22, 22; I23, M23, 23; I24, M24, 24;
+        I25, M25, 25; I26, M26, 26; I27, M27, 27; I28, M28, 28)
+    30: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18;
+        I19, M19, 19; I20, M20, 20; I21, M21, 21; I22, M22, 22; I23, M23, 23; I24, M24, 24;
+        I25, M25, 25; I26, M26, 26; I27, M27, 27; I28, M28, 28; I29, M29, 29)
+    31: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18;
+        I19, M19, 19; I20, M20, 20; I21, M21, 21; I22, M22, 22; I23, M23, 23; I24, M24, 24;
+        I25, M25, 25; I26, M26, 26; I27, M27, 27; I28, M28, 28; I29, M29, 29; I30, M30, 30)
+    32: (I0, M0, 0; I1, M1, 1; I2, M2, 2; I3, M3, 3; I4, M4, 4; I5, M5, 5; I6, M6, 6;
+        I7, M7, 7; I8, M8, 8; I9, M9, 9; I10, M10, 10; I11, M11, 11; I12, M12, 12;
+        I13, M13, 13; I14, M14, 14; I15, M15, 15; I16, M16, 16; I17, M17, 17; I18, M18, 18;
+        I19, M19, 19; I20, M20, 20; I21, M21, 21; I22, M22, 22; I23, M23, 23; I24, M24, 24;
+        I25, M25, 25; I26, M26, 26; I27, M27, 27; I28, M28, 28; I29, M29, 29; I30, M30, 30;
+        I31, M31, 31)
+);
+
+//==============================================================================
+//  Validation
+//==============================================================================
+
+/// Check that all pins belong to the same [`DynGroup`]
+pub(super) const fn same_group<const SIZE: usize>(ids: &[DynPinId; SIZE]) -> bool {
+    let mut i = 1;
+    while i < SIZE {
+        if ids[i].group as u8 != ids[0].group as u8 {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Check that the pin numbers are consecutive and increasing
+pub(super) const fn contiguous<const SIZE: usize>(ids: &[DynPinId; SIZE]) -> bool {
+    let mut i = 1;
+    while i < SIZE {
+        if ids[i].num as usize != ids[0].num as usize + i {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Check that every pin is in a readable [`DynPinMode`]
+const fn readable<const SIZE: usize>(modes: &[DynPinMode; SIZE]) -> bool {
+    let mut i = 0;
+    while i < SIZE {
+        match modes[i] {
+            DynPinMode::Input(_) | DynPinMode::Output(DynOutput::Readable) => (),
+            _ => return false,
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Check that every pin is in a writable [`DynPinMode`]
+pub(super) const fn writable<const SIZE: usize>(modes: &[DynPinMode; SIZE]) -> bool {
+    let mut i = 0;
+    while i < SIZE {
+        if !matches!(modes[i], DynPinMode::Output(_)) {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+//==============================================================================
+//  GroupPins
+//==============================================================================
+
+/// Pins and cached register masks shared by both pin group types
+struct GroupPins<const SIZE: usize> {
+    pins: [DynPin; SIZE],
+    group: DynGroup,
+    mask: u32,
+    readable: bool,
+    writable: bool,
+}
+
+impl<const SIZE: usize> GroupPins<SIZE> {
+    /// Gather the pins, which must all be in the same [`DynGroup`]
+    #[inline]
+    fn new(pins: [DynPin; SIZE]) -> Self {
+        const { assert!(SIZE > 0 && SIZE <= 32, "A pin group must hold 1 to 32 pins") };
+        let ids = pins.each_ref().map(DynPin::id);
+        let modes = pins.each_ref().map(DynPin::mode);
+        let mask = ids.iter().fold(0, |mask, id| mask | 1 << id.num);
+        Self {
+            group: ids[0].group,
+            mask,
+            readable: readable(&modes),
+            writable: writable(&modes),
+            pins,
+        }
+    }
+
+    #[inline]
+    fn regs(&self) -> &GROUP {
+        GROUP::get(self.group)
+    }
+
+    #[inline]
+    fn ids(&self) -> [DynPinId; SIZE] {
+        self.pins.each_ref().map(DynPin::id)
+    }
+
+    #[inline]
+    fn read_in(&self) -> Result<u32, Error> {
+        if self.readable {
+            Ok(self.regs().read_in() & self.mask)
+        } else {
+            Err(Error::InvalidPinType)
+        }
+    }
+
+    #[inline]
+    fn read_out(&self) -> Result<u32, Error> {
+        if self.writable {
+            Ok(self.regs().read_out() & self.mask)
+        } else {
+            Err(Error::InvalidPinType)
+        }
+    }
+
+    /// Set the output level of every pin in the group
+    ///
+    /// The pins driven high are written to `OUTSET`, then the pins driven low
+    /// are written to `OUTCLR`. Pins outside of the group are left untouched.
+    #[inline]
+    fn write_out(&mut self, bits: u32) -> Result<(), Error> {
+        if self.writable {
+            let regs = self.regs();
+            // Safety: The mask only contains pins owned by this group
+            unsafe {
+                regs.set(bits & self.mask);
+                regs.clear(!bits & self.mask);
+            }
+            Ok(())
+        } else {
+            Err(Error::InvalidPinType)
+        }
+    }
+
+    #[inline]
+    fn toggle_out(&mut self, bits: u32) -> Result<(), Error> {
+        if self.writable {
+            // Safety: The mask only contains pins owned by this group
+            unsafe { self.regs().toggle(bits & self.mask) };
+            Ok(())
+        } else {
+            Err(Error::InvalidPinType)
+        }
+    }
+}
+
+//==============================================================================
+//  PinGroup
+//==============================================================================
+
+/// A set of pins within the same [`DynGroup`], accessed with port-level
+/// register reads and writes
+///
+/// Bit `n` of the values read or written corresponds to `pins[n]`. See the
+/// [module-level documentation](self) for more details.
+pub struct PinGroup<const SIZE: usize> {
+    inner: GroupPins<SIZE>,
+}
+
+impl<const SIZE: usize> PinGroup<SIZE> {
+    /// Create a new [`PinGroup`] from an array of [`DynPin`]s
+    ///
+    /// Returns [`Error::InvalidPinType`] along with the pins if they do not
+    /// all belong to the same [`DynGroup`].
+    #[inline]
+    pub fn new(pins: [DynPin; SIZE]) -> Result<Self, (Error, [DynPin; SIZE])> {
+        let ids = pins.each_ref().map(DynPin::id);
+        if same_group(&ids) {
+            let inner = GroupPins::new(pins);
+            Ok(Self { inner })
+        } else {
+            Err((Error::InvalidPinType, pins))
+        }
+    }
+
+    /// Create a new [`PinGroup`] from a tuple of type-level [`Pin`]s
+    ///
+    /// Fails to compile if the pins do not all belong to the same
+    /// [`DynGroup`].
+    #[inline]
+    pub fn from_pins<T: PinTuple<SIZE>>(pins: T) -> Self {
+        const {
+            assert!(
+                same_group(&T::IDS),
+                "All pins of a PinGroup must belong to the same group"
+            )
+        };
+        let inner = GroupPins::new(pins.into_dyn_pins());
+        Self { inner }
+    }
+
+    /// Return the [`DynPinId`] of each pin in the group
+    #[inline]
+    pub fn ids(&self) -> [DynPinId; SIZE] {
+        self.inner.ids()
+    }
+
+    /// Return the [`DynGroup`] the pins belong to
+    #[inline]
+    pub fn group(&self) -> DynGroup {
+        self.inner.group
+    }
+
+    /// Return the mask of the pins in the group, in `Port` register layout
+    #[inline]
+    pub fn port_mask(&self) -> u32 {
+        self.inner.mask
+    }
+
+    /// Read the input level of every pin with a single read of the `IN`
+    /// register
+    ///
+    /// Returns [`Error::InvalidPinType`] unless every pin is an input or a
+    /// readable output.
+    #[inline]
+    pub fn read(&self) -> Result<u32, Error> {
+        self.inner.read_in().map(|bits| self.gather(bits))
+    }
+
+    /// Read back the output level of every pin
+    ///
+    /// Returns [`Error::InvalidPinType`] unless every pin is an output.
+    #[inline]
+    pub fn read_output(&self) -> Result<u32, Error> {
+        self.inner.read_out().map(|bits| self.gather(bits))
+    }
+
+    /// Set the output level of every pin with one write to the `OUTSET`
+    /// register followed by one write to the `OUTCLR` register
+    ///
+    /// Returns [`Error::InvalidPinType`] unless every pin is an output.
+    #[inline]
+    pub fn write(&mut self, value: u32) -> Result<(), Error> {
+        let bits = self.scatter(value);
+        self.inner.write_out(bits)
+    }
+
+    /// Toggle the output level of every pin whose bit is set in `value`
+    ///
+    /// Returns [`Error::InvalidPinType`] unless every pin is an output.
+    #[inline]
+    pub fn toggle(&mut self, value: u32) -> Result<(), Error> {
+        let bits = self.scatter(value);
+        self.inner.toggle_out(bits)
+    }
+
+    /// Release the pins
+    #[inline]
+    pub fn free(self) -> [DynPin; SIZE] {
+        self.inner.pins
+    }
+
+    /// Convert from `Port` register layout to group layout
+    #[inline]
+    fn gather(&self, bits: u32) -> u32 {
+        self.inner
+            .pins
+            .iter()
+            .enumerate()
+            .fold(0, |value, (i, pin)| {
+                value | ((bits >> pin.id().num) & 1) << i
+            })
+    }
+
+    /// Convert from group layout to `Port` register layout
+    #[inline]
+    fn scatter(&self, value: u32) -> u32 {
+        self.inner
+            .pins
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, pin)| {
+                bits | ((value >> i) & 1) << pin.id().num
+            })
+    }
+}
+
+//==============================================================================
+//  ContiguousPinGroup
+//==============================================================================
+
+/// A set of consecutive pins within the same [`DynGroup`], accessed with
+/// port-level register reads and writes
+///
+/// Because the pins are consecutive, converting between the `Port` register
+/// layout and the group layout only requires a shift. Bit `n` of the values
+/// read or written corresponds to `pins[n]`. See the
+/// [module-level documentation](self) for more details.
+pub struct ContiguousPinGroup<const SIZE: usize> {
+    inner: GroupPins<SIZE>,
+    shift: u8,
+}
+
+impl<const SIZE: usize> ContiguousPinGroup<SIZE> {
+    /// Create a new [`ContiguousPinGroup`] from an array of [`DynPin`]s
+    ///
+    /// Returns [`Error::InvalidPinType`] along with the pins if they do not
+    /// all belong to the same [`DynGroup`], or if their pin numbers are not
+    /// consecutive and increasing.
+    #[inline]
+    pub fn new(pins: [DynPin; SIZE]) -> Result<Self, (Error, [DynPin; SIZE])> {
+        let ids = pins.each_ref().map(DynPin::id);
+        if same_group(&ids) && contiguous(&ids) {
+            Ok(Self::new_unchecked(pins))
+        } else {
+            Err((Error::InvalidPinType, pins))
+        }
+    }
+
+    /// Create a new [`ContiguousPinGroup`] from a tuple of type-level [`Pin`]s
+    ///
+    /// Fails to compile if the pins do not all belong to the same
+    /// [`DynGroup`], or if their pin numbers are not consecutive and
+    /// increasing.
+    #[inline]
+    pub fn from_pins<T: PinTuple<SIZE>>(pins: T) -> Self {
+        const {
+            assert!(
+                same_group(&T::IDS) && contiguous(&T::IDS),
+                "The pins of a ContiguousPinGroup must be consecutive and belong to the same group"
+            )
+        };
+        Self::new_unchecked(pins.into_dyn_pins())
+    }
+
+    #[inline]
+    fn new_unchecked(pins: [DynPin; SIZE]) -> Self {
+        let shift = pins[0].id().num;
+        let inner = GroupPins::new(pins);
+        Self { inner, shift }
+    }
+
+    /// Return the [`DynPinId`] of each pin in the group
+    #[inline]
+    pub fn ids(&self) -> [DynPinId; SIZE] {
+        self.inner.ids()
+    }
+
+    /// Return the [`DynGroup`] the pins belong to
+    #[inline]
+    pub fn group(&self) -> DynGroup {
+        self.inner.group
+    }
+
+    /// Return the mask of the pins in the group, in `Port` register layout
+    #[inline]
+    pub fn port_mask(&self) -> u32 {
+        self.inner.mask
+    }
+
+    /// Return the pin number of the first pin in the group
+    #[inline]
+    pub fn shift(&self) -> u8 {
+        self.shift
+    }
+
+    /// Read the input level of every pin with a single read of the `IN`
+    /// register
+    ///
+    /// Returns [`Error::InvalidPinType`] unless every pin is an input or a
+    /// readable output.
+    #[inline]
+    pub fn read(&self) -> Result<u32, Error> {
+        self.inner.read_in().map(|bits| bits >> self.shift)
+    }
+
+    /// Read back the output level of every pin
+    ///
+    /// Returns [`Error::InvalidPinType`] unless every pin is an output.
+    #[inline]
+    pub fn read_output(&self) -> Result<u32, Error> {
+        self.inner.read_out().map(|bits| bits >> self.shift)
+    }
+
+    /// Set the output level of every pin with one write to the `OUTSET`
+    /// register followed by one write to the `OUTCLR` register
+    ///
+    /// Returns [`Error::InvalidPinType`] unless every pin is an output.
+    #[inline]
+    pub fn write(&mut self, value: u32) -> Result<(), Error> {
+        self.inner.write_out(value << self.shift)
+    }
+
+    /// Toggle the output level of every pin whose bit is set in `value`
+    ///
+    /// Returns [`Error::InvalidPinType`] unless every pin is an output.
+    #[inline]
+    pub fn toggle(&mut self, value: u32) -> Result<(), Error> {
+        self.inner.toggle_out(value << self.shift)
+    }
+
+    /// Release the pins
+    #[inline]
+    pub fn free(self) -> [DynPin; SIZE] {
+        self.inner.pins
+    }
+}
+
+impl<const SIZE: usize> From<ContiguousPinGroup<SIZE>> for PinGroup<SIZE> {
+    #[inline]
+    fn from(group: ContiguousPinGroup<SIZE>) -> Self {
+        Self { inner: group.inner }
+    }
+}
@@ -51,6 +51,18 @@
 //! If needed, [`dynpin`] can be used to erase the type-level differences
 //! between pins. However, by doing so, pins must now be tracked at run-time,
 //! and each pin has a non-zero memory footprint.
+//!
+//! # Pin groups
+//!
+//! The [`group`] module gathers several pins of the same port group, so that
+//! they can be read or written with port-level register accesses. This is
+//! useful for parallel buses and keypad matrices.
+//!
+//! # DMA
+//!
+//! When the `dma` feature is enabled, the [`dma`] module allows the DMAC to
+//! write to the output registers of a port group, e.g. to drive a parallel
+//! bus without CPU involvement.
 
 pub mod pin;
 pub use pin::*;
@@ -58,4 +70,10 @@ pub use pin::*;
 pub mod dynpin;
 pub use dynpin::*;
 
+pub mod group;
+pub use group::*;
+
+#[cfg(feature = "dma")]
+pub mod dma;
+
 mod reg;
@@ -15,6 +15,9 @@ use crate::pac::Port;
 
 use super::dynpin::*;
 
+#[cfg(feature = "dma")]
+use super::dma::OutputRegister;
+
 //==============================================================================
 //  ModeFields
 //==============================================================================
@@ -188,6 +191,92 @@ pub(super) struct GROUP {
     _padding2: [u8; 32],
 }
 
+impl GROUP {
+    /// Pointer to the array of [`GROUP`] register blocks
+    const PTR: *const GROUP = Port::ptr() as *const _;
+
+    /// Return a shared reference to the [`GROUP`] register block for a given
+    /// [`DynGroup`]
+    #[inline]
+    #[hal_macro_helper]
+    pub(super) fn get(group: DynGroup) -> &'static GROUP {
+        let offset = match group {
+            DynGroup::A => 0,
+            #[hal_cfg("pin-group-b")]
+            DynGroup::B => 1,
+            #[hal_cfg("pin-group-c")]
+            DynGroup::C => 2,
+            #[hal_cfg("pin-group-d")]
+            DynGroup::D => 3,
+        };
+        // Safety: It is safe to create shared references to each PAC register
+        // or register block, because all registers are wrapped in
+        // `UnsafeCell`s. We should never create unique references to the
+        // registers, to prevent any risk of UB.
+        unsafe { &*Self::PTR.add(offset) }
+    }
+
+    /// Read the `IN` register
+    #[inline]
+    pub(super) fn read_in(&self) -> u32 {
+        self.in_.read().bits()
+    }
+
+    /// Read the `OUT` register
+    #[inline]
+    pub(super) fn read_out(&self) -> u32 {
+        self.out.read().bits()
+    }
+
+    /// Drive high every pin set in `mask`
+    ///
+    /// # Safety
+    ///
+    /// The caller must have exclusive control over every pin set in `mask`.
+    #[inline]
+    pub(super) unsafe fn set(&self, mask: u32) {
+        // Safety: Outset is a "mask" register, and the caller guarantees that
+        // it controls every pin in the mask
+        unsafe { self.outset.write(|w| w.bits(mask)) };
+    }
+
+    /// Drive low every pin set in `mask`
+    ///
+    /// # Safety
+    ///
+    /// The caller must have exclusive control over every pin set in `mask`.
+    #[inline]
+    pub(super) unsafe fn clear(&self, mask: u32) {
+        // Safety: Outclr is a "mask" register, and the caller guarantees that
+        // it controls every pin in the mask
+        unsafe { self.outclr.write(|w| w.bits(mask)) };
+    }
+
+    /// Toggle the output level of every pin set in `mask`
+    ///
+    /// # Safety
+    ///
+    /// The caller must have exclusive control over every pin set in `mask`.
+    #[inline]
+    pub(super) unsafe fn toggle(&self, mask: u32) {
+        // Safety: Outtgl is a "mask" register, and the caller guarantees that
+        // it controls every pin in the mask
+        unsafe { self.outtgl.write(|w| w.bits(mask)) };
+    }
+
+    /// Return the address of one of the output registers, for use by the DMAC
+    #[cfg(feature = "dma")]
+    #[inline]
+    pub(super) fn output_register_ptr(&self, reg: OutputRegister) -> *mut u32 {
+        match reg {
+            OutputRegister::Out => self.out.as_ptr(),
+            OutputRegister::OutClr => self.outclr.as_ptr(),
+            OutputRegister::OutSet => self.outset.as_ptr(),
+            OutputRegister::OutTgl => self.outtgl.as_ptr(),
+        }
+    }
+}
+
 //==============================================================================
 //  RegisterInterface
 //==============================================================================
@@ -227,26 +316,9 @@ pub(super) unsafe trait RegisterInterface {
     /// this type.
     fn id(&self) -> DynPinId;
 
-    /// Pointer to the array of [`GROUP`] register blocks
-    const GROUPS: *const GROUP = Port::ptr() as *const _;
-
     #[inline]
-    #[hal_macro_helper]
     fn group(&self) -> &GROUP {
-        let offset = match self.id().group {
-            DynGroup::A => 0,
-            #[hal_cfg("pin-group-b")]
-            DynGroup::B => 1,
-            #[hal_cfg("pin-group-c")]
-            DynGroup::C => 2,
-            #[hal_cfg("pin-group-d")]
-            DynGroup::D => 3,
-        };
-        // Safety: It is safe to create shared references to each PAC register
-        // or register block, because all registers are wrapped in
-        // `UnsafeCell`s. We should never create unique references to the
-        // registers, to prevent any risk of UB.
-        unsafe { &*Self::GROUPS.add(offset) }
+        GROUP::get(self.id().group)
     }
 
     #[inline]
@@ -61,6 +61,7 @@
 //!
 //! If high performance is required this might not be the most
 //! efficient way, then using the hardware directly might be better.
+//! The [`modes`] module does exactly that.
 //!
 //! This provides the ability to use other ciphers of the RustCrypto family,
 //! such as
@@ -118,6 +119,14 @@
 //! cipher.decrypt_block(&mut block);
 //! assert_eq!(block, block_copy);
 //! ```
+//!
+//! # Hardware modes of operation
+//!
+//! The [`modes`] module runs CBC, CFB, OFB, CTR and GCM on the peripheral's
+//! own chaining and GHASH logic, implementing the RustCrypto
+//! [`StreamCipher`](modes::StreamCipher) and
+//! [`AeadInPlace`](modes::AeadInPlace) traits. With the `dma` feature, the
+//! data registers can be fed by a pair of DMA channels.
 
 // Re-exports
 pub use pac::aes::ctrla::{
@@ -125,6 +134,8 @@ pub use pac::aes::ctrla::{
     Xorkeyselect,
 };
 
+pub mod modes;
+
 // Re-export Aes128 with hardware backing
 mod rustcrypto;
 pub use cipher::{
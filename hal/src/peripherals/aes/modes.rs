@@ -0,0 +1,1018 @@
+//! Hardware modes of operation
+//!
+//! The types in this module drive the AES peripheral's own chaining logic
+//! instead of running ECB block by block in software:
+//!
+//! * [`Ctr`] and [`Ofb`] implement [`StreamCipher`]
+//! * [`Cbc`] and [`Cfb`] encrypt and decrypt whole blocks, carrying the
+//!   chaining value from one call to the next
+//! * [`Gcm`] implements [`AeadInPlace`], using the hardware GHASH unit for
+//!   authentication
+//!
+//! Every mode owns the [`Aes`] peripheral and reconfigures it as required, so
+//! only one mode can be active at a time. Use `free` to get the peripheral
+//! back.
+//!
+//! # Key sizes
+//!
+//! The key size is selected by the length of the key array: `U16`, `U24` or
+//! `U32` bytes for AES-128, AES-192 and AES-256.
+//!
+//! # DMA
+//!
+//! With the `dma` feature, two DMA channels can be attached with
+//! `with_dma_channels`. Whole blocks are then moved in and out of the data
+//! registers by the DMAC, with the peripheral in automatic start mode. The
+//! DMAC moves 32-bit words, so DMA is only used when both the input and output
+//! buffers are word-aligned; other buffers silently fall back to CPU copies.
+//!
+//! ```no_run
+//! use atsamd_hal::aes::{
+//!     GenericArray,
+//!     modes::{AeadInPlace, Gcm},
+//! };
+//! use atsamd_hal::dmac::{AnyChannel, Ready};
+//!
+//! # fn example(
+//! #     aes: atsamd_hal::aes::Aes,
+//! #     rx: impl AnyChannel<Status = Ready>,
+//! #     tx: impl AnyChannel<Status = Ready>,
+//! # ) {
+//! let key = GenericArray::from_slice(&[0u8; 16]);
+//! let gcm = Gcm::new(aes, key).with_dma_channels(rx, tx);
+//!
+//! let nonce = GenericArray::from_slice(&[0u8; 12]);
+//! let mut frame = [0u8; 64];
+//! let tag = gcm
+//!     .encrypt_in_place_detached(nonce, b"header", &mut frame)
+//!     .unwrap();
+//! # }
+//! ```
+
+use core::cell::RefCell;
+use core::marker::PhantomData;
+
+use aead::{Nonce, Tag};
+use cipher::{StreamCipherError, inout::InOutBuf};
+
+use super::{
+    Aes, Aesmodeselect, ArrayLength, Cipherselect, GenericArray, Keysizeselect, Startmodeselect,
+    U16, U24, U32,
+};
+use crate::typelevel::{NoneT, Sealed};
+use cipher::consts::{U0, U12};
+
+pub use aead::{AeadCore, AeadInPlace};
+pub use cipher::StreamCipher;
+
+#[cfg(feature = "dma")]
+use crate::dmac::{AnyChannel, Beat, Buffer, BurstLength, Ready, TriggerAction, TriggerSource};
+
+/// AES block size in bytes
+const BLOCK_SIZE: usize = 16;
+
+type Block = [u8; BLOCK_SIZE];
+
+//==============================================================================
+// Errors
+//==============================================================================
+
+/// Errors returned by the AES modes of operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The buffer length is not a multiple of the 16 byte block size
+    BlockSize,
+    /// The message is longer than the hardware cipher length counter allows
+    TooLong,
+    /// A DMA transfer failed
+    #[cfg(feature = "dma")]
+    Dma(crate::dmac::Error),
+}
+
+#[cfg(feature = "dma")]
+impl From<crate::dmac::Error> for Error {
+    fn from(value: crate::dmac::Error) -> Self {
+        Self::Dma(value)
+    }
+}
+
+//==============================================================================
+// Key sizes
+//==============================================================================
+
+/// Type-level key length, in bytes
+///
+/// Implemented for `U16`, `U24` and `U32`.
+pub trait KeySize: ArrayLength<u8> + Sealed {
+    /// Matching `CTRLA.KEYSIZE` setting
+    const KEYSIZE: Keysizeselect;
+}
+
+impl KeySize for U16 {
+    const KEYSIZE: Keysizeselect = Keysizeselect::_128bit;
+}
+
+impl KeySize for U24 {
+    const KEYSIZE: Keysizeselect = Keysizeselect::_192bit;
+}
+
+impl KeySize for U32 {
+    const KEYSIZE: Keysizeselect = Keysizeselect::_256bit;
+}
+
+//==============================================================================
+// Register helpers
+//==============================================================================
+
+impl Aes {
+    /// Reset and enable the peripheral in the given mode, then load the key
+    fn configure(
+        &self,
+        mode: Aesmodeselect,
+        cipher: Cipherselect,
+        startmode: Startmodeselect,
+        keysize: Keysizeselect,
+        key: &[u8],
+    ) {
+        self.ctrla().write(|w| w.swrst().set_bit());
+        while self.ctrla().read().swrst().bit_is_set() {}
+
+        self.ctrla().write(|w| {
+            w.aesmode().variant(mode);
+            w.cfbs()._128bit();
+            w.cipher().variant(cipher);
+            w.startmode().variant(startmode);
+            w.keysize().variant(keysize);
+            w.enable().set_bit()
+        });
+
+        for (index, word) in key.chunks_exact(4).enumerate() {
+            let data = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+            self.aes().keyword(index).write(|w| unsafe { w.bits(data) });
+        }
+    }
+
+    /// Load a 16 byte initialization vector or counter block
+    fn load_iv(&self, iv: &Block) {
+        self.set_initialization_vector(block_to_words(iv));
+    }
+
+    /// Write one block to the data registers
+    fn write_block(&self, block: &Block) {
+        for word in block_to_words(block) {
+            self.set_data(word);
+        }
+    }
+
+    /// Read one block from the data registers
+    fn read_block(&self) -> Block {
+        let mut words = [0; 4];
+        for word in words.iter_mut() {
+            *word = self.get_data();
+        }
+        words_to_block(words)
+    }
+
+    /// Run one block through the peripheral using the CPU
+    ///
+    /// In automatic start mode, writing the last input word starts the
+    /// operation; in manual mode it is started explicitly.
+    fn process_block(&self, startmode: Startmodeselect, input: &Block) -> Block {
+        self.clear_enccmp();
+        self.write_block(input);
+        if startmode == Startmodeselect::Manual {
+            self.start();
+        }
+        while !self.read_enccmp() {}
+        self.read_block()
+    }
+
+    /// Multiply the data registers with the hash key into `GHASH`
+    fn gf_multiply(&self, block: &Block) {
+        self.clear_gfmcmp();
+        self.write_block(block);
+        self.gfmul();
+        while !self.read_gfmcmp() {}
+    }
+}
+
+#[inline]
+fn block_to_words(block: &Block) -> [u32; 4] {
+    let mut words = [0; 4];
+    for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    words
+}
+
+#[inline]
+fn words_to_block(words: [u32; 4]) -> Block {
+    let mut block = [0; BLOCK_SIZE];
+    for (bytes, word) in block.chunks_exact_mut(4).zip(words) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    block
+}
+
+/// Copy up to one block out of `data`, zero padding the remainder
+#[inline]
+fn padded_block(data: &[u8]) -> Block {
+    let mut block = [0; BLOCK_SIZE];
+    block[..data.len()].copy_from_slice(data);
+    block
+}
+
+/// Increment the rightmost 32 bits of a counter block, big-endian
+#[inline]
+fn inc32(counter: &mut Block, blocks: u32) {
+    let low = u32::from_be_bytes([counter[12], counter[13], counter[14], counter[15]]);
+    counter[12..].copy_from_slice(&low.wrapping_add(blocks).to_be_bytes());
+}
+
+//==============================================================================
+// Data path
+//==============================================================================
+
+/// Moves whole blocks through the data registers
+///
+/// Implemented for [`NoneT`] (CPU copies) and, with the `dma` feature, for
+/// [`DmaChannels`].
+pub trait DataPath: Sealed {
+    #[doc(hidden)]
+    const STARTMODE: Startmodeselect;
+
+    /// Process `buf`, whose length must be a multiple of the block size, with
+    /// the peripheral already configured.
+    #[doc(hidden)]
+    fn process(&mut self, aes: &Aes, buf: InOutBuf<'_, '_, u8>) -> Result<(), Error>;
+}
+
+impl DataPath for NoneT {
+    const STARTMODE: Startmodeselect = Startmodeselect::Manual;
+
+    fn process(&mut self, aes: &Aes, buf: InOutBuf<'_, '_, u8>) -> Result<(), Error> {
+        process_cpu(aes, Self::STARTMODE, buf);
+        Ok(())
+    }
+}
+
+fn process_cpu(aes: &Aes, startmode: Startmodeselect, mut buf: InOutBuf<'_, '_, u8>) {
+    for offset in (0..buf.len()).step_by(BLOCK_SIZE) {
+        let range = offset..offset + BLOCK_SIZE;
+        let input = padded_block(&buf.get_in()[range.clone()]);
+        let output = aes.process_block(startmode, &input);
+        buf.get_out()[range].copy_from_slice(&output);
+    }
+}
+
+/// Pair of DMA channels feeding the AES data registers
+///
+/// `rx` reads the output data, `tx` writes the input data.
+#[cfg(feature = "dma")]
+pub struct DmaChannels<R, T> {
+    rx: R,
+    tx: T,
+}
+
+#[cfg(feature = "dma")]
+impl<R, T> Sealed for DmaChannels<R, T> {}
+
+/// Non-incrementing pointer to the `INDATA` register
+#[cfg(feature = "dma")]
+struct DataRegister(*mut u32);
+
+#[cfg(feature = "dma")]
+unsafe impl Buffer for DataRegister {
+    type Beat = u32;
+
+    #[inline]
+    fn dma_ptr(&mut self) -> *mut Self::Beat {
+        self.0
+    }
+
+    #[inline]
+    fn incrementing(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn buffer_len(&self) -> usize {
+        1
+    }
+}
+
+/// Word-aligned memory region viewed as a run of DMA beats
+#[cfg(feature = "dma")]
+struct WordBuffer<T: Beat> {
+    ptr: *mut T,
+    len: usize,
+}
+
+#[cfg(feature = "dma")]
+unsafe impl<T: Beat> Buffer for WordBuffer<T> {
+    type Beat = T;
+
+    #[inline]
+    fn dma_ptr(&mut self) -> *mut Self::Beat {
+        // SAFETY: the end pointer is one past the end of the same allocation
+        unsafe { self.ptr.add(self.len) }
+    }
+
+    #[inline]
+    fn incrementing(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn buffer_len(&self) -> usize {
+        self.len
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<R, T> DataPath for DmaChannels<R, T>
+where
+    R: AnyChannel<Status = Ready>,
+    T: AnyChannel<Status = Ready>,
+{
+    const STARTMODE: Startmodeselect = Startmodeselect::Auto;
+
+    fn process(&mut self, aes: &Aes, mut buf: InOutBuf<'_, '_, u8>) -> Result<(), Error> {
+        let len = buf.len();
+        let (input, output) = buf.reborrow().into_raw();
+
+        if input as usize % 4 != 0 || output as usize % 4 != 0 {
+            process_cpu(aes, Self::STARTMODE, buf);
+            return Ok(());
+        }
+
+        // The DMAC moves at most `u16::MAX` beats per transfer; keep each
+        // chunk a whole number of blocks.
+        const MAX_CHUNK: usize = (u16::MAX as usize / 4) * BLOCK_SIZE;
+        let data = aes.indata().as_ptr();
+
+        let rx = self.rx.as_mut();
+        let tx = self.tx.as_mut();
+        rx.burst_length(BurstLength::_4beat);
+        tx.burst_length(BurstLength::_4beat);
+
+        let mut offset = 0;
+        while offset < len {
+            let chunk = MAX_CHUNK.min(len - offset);
+            let words = chunk / 4;
+
+            // SAFETY: `offset + chunk <= len`, so both regions stay within the
+            // caller's buffers. `process` does not return until both transfers
+            // are stopped, and the output region is never read by the input
+            // channel after the peripheral has consumed it, so in-place
+            // operation is sound.
+            unsafe {
+                let mut source = WordBuffer {
+                    ptr: input.add(offset) as *mut u32,
+                    len: words,
+                };
+                let mut dest = WordBuffer {
+                    ptr: output.add(offset) as *mut u32,
+                    len: words,
+                };
+
+                rx.transfer(
+                    &mut DataRegister(data),
+                    &mut dest,
+                    TriggerSource::AesRd,
+                    TriggerAction::Burst,
+                    None,
+                )?;
+                if let Err(e) = tx.transfer(
+                    &mut source,
+                    &mut DataRegister(data),
+                    TriggerSource::AesWr,
+                    TriggerAction::Burst,
+                    None,
+                ) {
+                    rx.stop();
+                    return Err(e.into());
+                }
+            }
+
+            while !(rx.xfer_complete() && tx.xfer_complete()) {
+                core::hint::spin_loop();
+            }
+
+            // Defensively disable channels
+            tx.stop();
+            rx.stop();
+
+            rx.xfer_success().and(tx.xfer_success())?;
+            offset += chunk;
+        }
+
+        Ok(())
+    }
+}
+
+//==============================================================================
+// Stream modes (CTR, OFB)
+//==============================================================================
+
+/// Keystream mode of operation, used with [`StreamMode`]
+pub trait KeystreamMode: Sealed {
+    #[doc(hidden)]
+    const MODE: Aesmodeselect;
+
+    /// Advance the chaining value past `blocks` blocks, the last of which
+    /// produced `keystream`
+    #[doc(hidden)]
+    fn advance(iv: &mut Block, keystream: &Block, blocks: u32);
+}
+
+/// Counter mode marker
+///
+/// The counter is the rightmost 32 bits of the counter block, incremented
+/// big-endian, matching `ctr::Ctr32BE`.
+pub enum CtrMode {}
+
+impl Sealed for CtrMode {}
+
+impl KeystreamMode for CtrMode {
+    const MODE: Aesmodeselect = Aesmodeselect::Counter;
+
+    fn advance(iv: &mut Block, _keystream: &Block, blocks: u32) {
+        inc32(iv, blocks);
+    }
+}
+
+/// Output feedback mode marker
+pub enum OfbMode {}
+
+impl Sealed for OfbMode {}
+
+impl KeystreamMode for OfbMode {
+    const MODE: Aesmodeselect = Aesmodeselect::Ofb;
+
+    fn advance(iv: &mut Block, keystream: &Block, _blocks: u32) {
+        *iv = *keystream;
+    }
+}
+
+/// Hardware keystream cipher
+///
+/// Use the [`Ctr`] and [`Ofb`] aliases. Keystream left over from a partial
+/// block is kept, so data can be fed in arbitrary lengths.
+pub struct StreamMode<M: KeystreamMode, K: KeySize, D: DataPath = NoneT> {
+    aes: Aes,
+    key: GenericArray<u8, K>,
+    iv: Block,
+    keystream: Block,
+    position: usize,
+    data: D,
+    _mode: PhantomData<M>,
+}
+
+/// AES in counter (CTR) mode
+pub type Ctr<K, D = NoneT> = StreamMode<CtrMode, K, D>;
+
+/// AES in output feedback (OFB) mode
+pub type Ofb<K, D = NoneT> = StreamMode<OfbMode, K, D>;
+
+impl<M: KeystreamMode, K: KeySize> StreamMode<M, K> {
+    /// Create a keystream cipher from a key and initialization vector (or
+    /// initial counter block)
+    #[inline]
+    pub fn new(aes: Aes, key: &GenericArray<u8, K>, iv: &GenericArray<u8, U16>) -> Self {
+        Self {
+            aes,
+            key: key.clone(),
+            iv: padded_block(iv),
+            keystream: [0; BLOCK_SIZE],
+            position: BLOCK_SIZE,
+            data: NoneT,
+            _mode: PhantomData,
+        }
+    }
+
+    /// Attach DMA channels used to move whole blocks
+    #[cfg(feature = "dma")]
+    #[inline]
+    pub fn with_dma_channels<R, T>(self, rx: R, tx: T) -> StreamMode<M, K, DmaChannels<R, T>>
+    where
+        R: AnyChannel<Status = Ready>,
+        T: AnyChannel<Status = Ready>,
+    {
+        StreamMode {
+            aes: self.aes,
+            key: self.key,
+            iv: self.iv,
+            keystream: self.keystream,
+            position: self.position,
+            data: DmaChannels { rx, tx },
+            _mode: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<M: KeystreamMode, K: KeySize, R, T> StreamMode<M, K, DmaChannels<R, T>>
+where
+    R: AnyChannel<Status = Ready>,
+    T: AnyChannel<Status = Ready>,
+{
+    /// Reclaim the DMA channels
+    #[inline]
+    pub fn take_dma_channels(self) -> (StreamMode<M, K>, R, T) {
+        (
+            StreamMode {
+                aes: self.aes,
+                key: self.key,
+                iv: self.iv,
+                keystream: self.keystream,
+                position: self.position,
+                data: NoneT,
+                _mode: PhantomData,
+            },
+            self.data.rx,
+            self.data.tx,
+        )
+    }
+}
+
+impl<M: KeystreamMode, K: KeySize, D: DataPath> StreamMode<M, K, D> {
+    /// Destroy the cipher and release the AES peripheral
+    #[inline]
+    pub fn free(self) -> Aes {
+        self.aes
+    }
+
+    fn apply(&mut self, buf: InOutBuf<'_, '_, u8>) -> Result<(), Error> {
+        // Use up keystream left over from the previous call
+        let available = (BLOCK_SIZE - self.position).min(buf.len());
+        let (mut head, rest) = buf.split_at(available);
+        head.xor_in2out(&self.keystream[self.position..self.position + available]);
+        self.position += available;
+
+        if rest.is_empty() {
+            return Ok(());
+        }
+
+        let whole = rest.len() - rest.len() % BLOCK_SIZE;
+        let (mut body, mut tail) = rest.split_at(whole);
+
+        self.aes.configure(
+            M::MODE,
+            Cipherselect::Enc,
+            D::STARTMODE,
+            K::KEYSIZE,
+            &self.key,
+        );
+        self.aes.load_iv(&self.iv);
+        self.aes.newmsg();
+
+        let mut blocks = (whole / BLOCK_SIZE) as u32;
+        let mut last = [0; BLOCK_SIZE];
+        if whole != 0 {
+            let input = padded_block(&body.get_in()[whole - BLOCK_SIZE..]);
+            self.data.process(&self.aes, body.reborrow())?;
+            let output = padded_block(&body.get_out()[whole - BLOCK_SIZE..]);
+            for ((k, i), o) in last.iter_mut().zip(input).zip(output) {
+                *k = i ^ o;
+            }
+        }
+
+        if !tail.is_empty() {
+            // Encrypting a zero block yields one block of raw keystream
+            self.keystream = self.aes.process_block(D::STARTMODE, &[0; BLOCK_SIZE]);
+            let remaining = tail.len();
+            tail.xor_in2out(&self.keystream[..remaining]);
+            self.position = remaining;
+            last = self.keystream;
+            blocks += 1;
+        }
+
+        M::advance(&mut self.iv, &last, blocks);
+        Ok(())
+    }
+}
+
+impl<M: KeystreamMode, K: KeySize, D: DataPath> StreamCipher for StreamMode<M, K, D> {
+    /// Apply the keystream to `buf`
+    ///
+    /// DMA transfer errors are reported as [`StreamCipherError`]; the cipher
+    /// state is unspecified afterwards.
+    fn try_apply_keystream_inout(
+        &mut self,
+        buf: InOutBuf<'_, '_, u8>,
+    ) -> Result<(), StreamCipherError> {
+        self.apply(buf).map_err(|_| StreamCipherError)
+    }
+}
+
+//==============================================================================
+// Block chaining modes (CBC, CFB)
+//==============================================================================
+
+/// Chained block mode of operation, used with [`ChainMode`]
+pub trait ChainingMode: Sealed {
+    #[doc(hidden)]
+    const MODE: Aesmodeselect;
+}
+
+/// Cipher block chaining mode marker
+pub enum CbcMode {}
+
+impl Sealed for CbcMode {}
+
+impl ChainingMode for CbcMode {
+    const MODE: Aesmodeselect = Aesmodeselect::Cbc;
+}
+
+/// 128-bit cipher feedback mode marker
+pub enum CfbMode {}
+
+impl Sealed for CfbMode {}
+
+impl ChainingMode for CfbMode {
+    const MODE: Aesmodeselect = Aesmodeselect::Cfb;
+}
+
+/// Hardware chained block cipher
+///
+/// Use the [`Cbc`] and [`Cfb`] aliases. Data must be a whole number of
+/// blocks; the chaining value is carried over between calls, so a long
+/// message can be processed in pieces. Padding is left to the caller.
+pub struct ChainMode<M: ChainingMode, K: KeySize, D: DataPath = NoneT> {
+    aes: Aes,
+    key: GenericArray<u8, K>,
+    iv: Block,
+    data: D,
+    _mode: PhantomData<M>,
+}
+
+/// AES in cipher block chaining (CBC) mode
+pub type Cbc<K, D = NoneT> = ChainMode<CbcMode, K, D>;
+
+/// AES in 128-bit cipher feedback (CFB) mode
+pub type Cfb<K, D = NoneT> = ChainMode<CfbMode, K, D>;
+
+impl<M: ChainingMode, K: KeySize> ChainMode<M, K> {
+    /// Create a chained block cipher from a key and initialization vector
+    #[inline]
+    pub fn new(aes: Aes, key: &GenericArray<u8, K>, iv: &GenericArray<u8, U16>) -> Self {
+        Self {
+            aes,
+            key: key.clone(),
+            iv: padded_block(iv),
+            data: NoneT,
+            _mode: PhantomData,
+        }
+    }
+
+    /// Attach DMA channels used to move whole blocks
+    #[cfg(feature = "dma")]
+    #[inline]
+    pub fn with_dma_channels<R, T>(self, rx: R, tx: T) -> ChainMode<M, K, DmaChannels<R, T>>
+    where
+        R: AnyChannel<Status = Ready>,
+        T: AnyChannel<Status = Ready>,
+    {
+        ChainMode {
+            aes: self.aes,
+            key: self.key,
+            iv: self.iv,
+            data: DmaChannels { rx, tx },
+            _mode: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<M: ChainingMode, K: KeySize, R, T> ChainMode<M, K, DmaChannels<R, T>>
+where
+    R: AnyChannel<Status = Ready>,
+    T: AnyChannel<Status = Ready>,
+{
+    /// Reclaim the DMA channels
+    #[inline]
+    pub fn take_dma_channels(self) -> (ChainMode<M, K>, R, T) {
+        (
+            ChainMode {
+                aes: self.aes,
+                key: self.key,
+                iv: self.iv,
+                data: NoneT,
+                _mode: PhantomData,
+            },
+            self.data.rx,
+            self.data.tx,
+        )
+    }
+}
+
+impl<M: ChainingMode, K: KeySize, D: DataPath> ChainMode<M, K, D> {
+    /// Destroy the cipher and release the AES peripheral
+    #[inline]
+    pub fn free(self) -> Aes {
+        self.aes
+    }
+
+    /// Encrypt `buf` in place
+    #[inline]
+    pub fn encrypt(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.encrypt_inout(buf.into())
+    }
+
+    /// Decrypt `buf` in place
+    #[inline]
+    pub fn decrypt(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.decrypt_inout(buf.into())
+    }
+
+    /// Encrypt from the input to the output half of `buf`
+    pub fn encrypt_inout(&mut self, mut buf: InOutBuf<'_, '_, u8>) -> Result<(), Error> {
+        let len = self.begin(Cipherselect::Enc, buf.len())?;
+        if len == 0 {
+            return Ok(());
+        }
+        self.data.process(&self.aes, buf.reborrow())?;
+        // The next chaining value is the last ciphertext block
+        self.iv = padded_block(&buf.get_out()[len - BLOCK_SIZE..]);
+        Ok(())
+    }
+
+    /// Decrypt from the input to the output half of `buf`
+    pub fn decrypt_inout(&mut self, buf: InOutBuf<'_, '_, u8>) -> Result<(), Error> {
+        let len = self.begin(Cipherselect::Dec, buf.len())?;
+        if len == 0 {
+            return Ok(());
+        }
+        // Grab the last ciphertext block before it may be overwritten
+        let next = padded_block(&buf.get_in()[len - BLOCK_SIZE..]);
+        self.data.process(&self.aes, buf)?;
+        self.iv = next;
+        Ok(())
+    }
+
+    fn begin(&mut self, cipher: Cipherselect, len: usize) -> Result<usize, Error> {
+        if len % BLOCK_SIZE != 0 {
+            return Err(Error::BlockSize);
+        }
+        if len != 0 {
+            self.aes
+                .configure(M::MODE, cipher, D::STARTMODE, K::KEYSIZE, &self.key);
+            self.aes.load_iv(&self.iv);
+            self.aes.newmsg();
+        }
+        Ok(len)
+    }
+}
+
+//==============================================================================
+// GCM
+//==============================================================================
+
+/// AES in Galois/counter mode (GCM)
+///
+/// Uses 96-bit nonces and 128-bit tags. The hash subkey is derived once when
+/// the cipher is created; GHASH runs in hardware. The cipher is not `Sync`:
+/// the peripheral is reprogrammed for every message.
+pub struct Gcm<K: KeySize, D: DataPath = NoneT> {
+    aes: Aes,
+    key: GenericArray<u8, K>,
+    hashkey: [u32; 4],
+    data: RefCell<D>,
+}
+
+impl<K: KeySize> Gcm<K> {
+    /// Create a GCM cipher from a key
+    pub fn new(aes: Aes, key: &GenericArray<u8, K>) -> Self {
+        // H = E(K, 0^128)
+        aes.configure(
+            Aesmodeselect::Ecb,
+            Cipherselect::Enc,
+            Startmodeselect::Manual,
+            K::KEYSIZE,
+            key,
+        );
+        let hashkey = block_to_words(&aes.process_block(Startmodeselect::Manual, &[0; BLOCK_SIZE]));
+
+        Self {
+            aes,
+            key: key.clone(),
+            hashkey,
+            data: RefCell::new(NoneT),
+        }
+    }
+
+    /// Attach DMA channels used to move the payload
+    #[cfg(feature = "dma")]
+    #[inline]
+    pub fn with_dma_channels<R, T>(self, rx: R, tx: T) -> Gcm<K, DmaChannels<R, T>>
+    where
+        R: AnyChannel<Status = Ready>,
+        T: AnyChannel<Status = Ready>,
+    {
+        Gcm {
+            aes: self.aes,
+            key: self.key,
+            hashkey: self.hashkey,
+            data: RefCell::new(DmaChannels { rx, tx }),
+        }
+    }
+}
+
+#[cfg(feature = "dma")]
+impl<K: KeySize, R, T> Gcm<K, DmaChannels<R, T>>
+where
+    R: AnyChannel<Status = Ready>,
+    T: AnyChannel<Status = Ready>,
+{
+    /// Reclaim the DMA channels
+    #[inline]
+    pub fn take_dma_channels(self) -> (Gcm<K>, R, T) {
+        let DmaChannels { rx, tx } = self.data.into_inner();
+        (
+            Gcm {
+                aes: self.aes,
+                key: self.key,
+                hashkey: self.hashkey,
+                data: RefCell::new(NoneT),
+            },
+            rx,
+            tx,
+        )
+    }
+}
+
+impl<K: KeySize, D: DataPath> Gcm<K, D> {
+    /// Destroy the cipher and release the AES peripheral
+    #[inline]
+    pub fn free(self) -> Aes {
+        self.aes
+    }
+
+    /// Configure the peripheral for one GCM phase and restore the hash state
+    fn phase(&self, mode: Aesmodeselect, cipher: Cipherselect, startmode: Startmodeselect) {
+        self.aes
+            .configure(mode, cipher, startmode, K::KEYSIZE, &self.key);
+        // Writing the key clears GHASH; the hash key must follow it
+        self.aes.set_hashkey(self.hashkey);
+    }
+
+    /// Encrypt or decrypt `buffer` in place and compute the tag
+    fn crypt(
+        &self,
+        cipher: Cipherselect,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<Tag<Self>, Error> {
+        let ciplen = u32::try_from(buffer.len()).map_err(|_| Error::TooLong)?;
+        let mut data = self.data.borrow_mut();
+
+        // J0 = IV || 0^31 || 1
+        let mut j0 = [0; BLOCK_SIZE];
+        j0[..12].copy_from_slice(nonce);
+        j0[15] = 1;
+
+        // Associated data
+        self.phase(
+            Aesmodeselect::Gcm,
+            Cipherselect::Enc,
+            Startmodeselect::Manual,
+        );
+        for chunk in associated_data.chunks(BLOCK_SIZE) {
+            self.aes.gf_multiply(&padded_block(chunk));
+        }
+        let mut ghash = self.aes.get_ghash();
+
+        // Payload
+        if !buffer.is_empty() {
+            self.phase(Aesmodeselect::Gcm, cipher, D::STARTMODE);
+            let mut counter = j0;
+            inc32(&mut counter, 1);
+            self.aes.load_iv(&counter);
+            self.aes.set_ciplen(ciplen);
+            self.aes.set_ghash(ghash);
+            self.aes.newmsg();
+
+            // Everything but the last block goes through the data path; the
+            // last block is flagged as the end of the message.
+            let last = (buffer.len() - 1) / BLOCK_SIZE * BLOCK_SIZE;
+            let (body, tail) = buffer.split_at_mut(last);
+            data.process(&self.aes, body.into())?;
+
+            let input = padded_block(tail);
+            self.aes.clear_enccmp();
+            self.aes.clear_gfmcmp();
+            if D::STARTMODE == Startmodeselect::Manual {
+                self.aes.write_block(&input);
+                self.aes
+                    .ctrlb()
+                    .write(|w| w.start().set_bit().eom().set_bit());
+            } else {
+                self.aes.eom();
+                self.aes.write_block(&input);
+            }
+            while !self.aes.read_enccmp() {}
+            let output = self.aes.read_block();
+            tail.copy_from_slice(&output[..tail.len()]);
+
+            while !self.aes.read_gfmcmp() {}
+            ghash = self.aes.get_ghash();
+        }
+
+        // Length block: len(A) || len(C), in bits
+        let mut lengths = [0; BLOCK_SIZE];
+        lengths[..8].copy_from_slice(&(associated_data.len() as u64 * 8).to_be_bytes());
+        lengths[8..].copy_from_slice(&(buffer.len() as u64 * 8).to_be_bytes());
+        self.phase(
+            Aesmodeselect::Gcm,
+            Cipherselect::Enc,
+            Startmodeselect::Manual,
+        );
+        self.aes.set_ghash(ghash);
+        self.aes.gf_multiply(&lengths);
+        let s = words_to_block(self.aes.get_ghash());
+
+        // T = GCTR(J0, S)
+        self.phase(
+            Aesmodeselect::Counter,
+            Cipherselect::Enc,
+            Startmodeselect::Manual,
+        );
+        self.aes.load_iv(&j0);
+        self.aes.newmsg();
+        let tag = self.aes.process_block(Startmodeselect::Manual, &s);
+
+        Ok(GenericArray::clone_from_slice(&tag))
+    }
+}
+
+impl<K: KeySize, D: DataPath> AeadCore for Gcm<K, D> {
+    type NonceSize = U12;
+    type TagSize = U16;
+    type CiphertextOverhead = U0;
+}
+
+impl<K: KeySize, D: DataPath> AeadInPlace for Gcm<K, D> {
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> aead::Result<Tag<Self>> {
+        self.crypt(Cipherselect::Enc, nonce, associated_data, buffer)
+            .map_err(|_| aead::Error)
+    }
+
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &Tag<Self>,
+    ) -> aead::Result<()> {
+        let expected = self
+            .crypt(Cipherselect::Dec, nonce, associated_data, buffer)
+            .map_err(|_| aead::Error)?;
+
+        // Constant time comparison
+        let diff = expected
+            .iter()
+            .zip(tag.iter())
+            .fold(0, |acc, (a, b)| acc | (a ^ b));
+
+        if diff == 0 {
+            Ok(())
+        } else {
+            // Restore the ciphertext rather than release unauthenticated
+            // plaintext
+            let _ = self.crypt(Cipherselect::Enc, nonce, associated_data, buffer);
+            Err(aead::Error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inc32_wraps_low_word_only() {
+        let mut counter = [0xff; BLOCK_SIZE];
+        inc32(&mut counter, 1);
+        assert_eq!(&counter[..12], &[0xff; 12]);
+        assert_eq!(&counter[12..], &[0; 4]);
+
+        let mut counter = [0; BLOCK_SIZE];
+        inc32(&mut counter, 0x0102);
+        assert_eq!(&counter[12..], &[0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn block_word_round_trip() {
+        let block: Block = core::array::from_fn(|i| i as u8);
+        assert_eq!(block_to_words(&block)[0], 0x0302_0100);
+        assert_eq!(words_to_block(block_to_words(&block)), block);
+    }
+}
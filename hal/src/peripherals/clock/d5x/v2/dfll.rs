@@ -778,10 +778,13 @@ mod settings {
     impl Settings for Usb {
         #[inline]
         fn all(&self) -> All {
+            // The datasheet recommends disabling the chill cycle in USB
+            // recovery mode
             All {
                 usb_recovery: true,
                 src_freq: 1_000.Hz(),
                 mult_factor: 48_000,
+                chill_cycle: false,
                 ..All::default()
             }
         }
@@ -1182,6 +1185,40 @@ where
         (Enabled::new(dfll), old)
     }
 
+    /// Switch the [`Dfll`] to USB clock recovery mode while it remains enabled
+    ///
+    /// This is a shorthand for [`into_mode`] with [`FromUsb`]. In this mode,
+    /// the `Dfll` is locked to the 1 kHz start-of-frame packets sent by the
+    /// USB host, which is accurate enough to clock the USB peripheral without
+    /// a crystal. Until the device is attached and receives SOF packets, the
+    /// `Dfll` keeps running at its current frequency.
+    ///
+    /// Returns the previous [`Mode`], which releases any resource it holds.
+    ///
+    /// ```no_run
+    /// # use atsamd_hal::{clock::v2::{clock_system_at_reset, pclk::Pclk}, pac::Peripherals};
+    /// # let mut pac = Peripherals::take().unwrap();
+    /// # let (mut buses, clocks, tokens) = clock_system_at_reset(
+    /// #     pac.oscctrl,
+    /// #     pac.osc32kctrl,
+    /// #     pac.gclk,
+    /// #     pac.mclk,
+    /// #     &mut pac.nvmctrl,
+    /// # );
+    /// // Gclk0 is sourced from the Dfll at reset
+    /// let (dfll, _open_loop) = clocks.dfll.into_usb_recovery();
+    /// let (pclk_usb, gclk0) = Pclk::enable(tokens.pclks.usb, clocks.gclk0);
+    /// let apb_usb = buses.apb.enable(tokens.apbs.usb);
+    /// // Then create the bus with
+    /// // UsbBus::new_v2(pclk_usb, apb_usb, clocks.ahbs.usb, pins.pa24, pins.pa25, pac.usb)
+    /// ```
+    ///
+    /// [`into_mode`]: EnabledDfll::into_mode
+    #[inline]
+    pub fn into_usb_recovery(self) -> (EnabledDfll<FromUsb, N>, M) {
+        self.into_mode(FromUsb, |_| {})
+    }
+
     /// Test whether the [`Dfll`] is ready
     ///
     /// reads OSCCTRL STATUS DFLLRDY bit
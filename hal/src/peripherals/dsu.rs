@@ -3,9 +3,16 @@
 //! This module allows users to interact with a DSU peripheral.
 //!
 //! - Run a CRC32 checksum over memory
+//! - Identify the device, die and silicon revision
+//! - Run the memory built-in self-test (MBIST) over RAM
+//! - Query debugger presence and the security state
 #![warn(missing_docs)]
 
-use crate::pac::{self, Pac};
+use atsamd_hal_macros::hal_cfg;
+
+use crate::pac;
+#[hal_cfg("dsu-d5x")]
+use crate::pac::Pac;
 
 /// Device Service Unit
 pub struct Dsu {
@@ -31,15 +38,121 @@ pub enum Error {
     PacUnlockFailed,
     /// CRC32 operation failed
     CrcFailed,
+    /// MBIST detected a memory fault
+    MbistFailed(MbistFailure),
     /// Hardware-generated errors
     Peripheral(PeripheralError),
 }
 
+/// Memory fault found by [`Dsu::mbist`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MbistFailure {
+    /// Address of the failing word
+    pub address: u32,
+    /// Index of the failing bit within the word
+    pub bit: u8,
+    /// Phase of the MBIST algorithm in which the fault was detected
+    pub phase: u8,
+}
+
+/// Product series decoded from the device identification
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Series {
+    /// SAM D11
+    Samd11,
+    /// SAM D21
+    Samd21,
+    /// SAM D51
+    Samd51,
+    /// SAM E51
+    Same51,
+    /// SAM E53
+    Same53,
+    /// SAM E54
+    Same54,
+    /// Combination of processor, family and series not known to the HAL
+    Unknown,
+}
+
+/// Device identification, decoded from the `DID` register
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeviceId {
+    /// Processor, `1` for Cortex-M0+ and `6` for Cortex-M4
+    pub processor: u8,
+    /// Product family
+    pub family: u8,
+    /// Product series
+    pub series: u8,
+    /// Die number
+    pub die: u8,
+    /// Silicon revision, `0` being revision A
+    pub revision: u8,
+    /// Device select, identifying the exact variant (flash size and pin
+    /// count) in the device selection table of the datasheet
+    pub variant: u8,
+}
+
+impl DeviceId {
+    /// Decode a raw `DID` register value
+    #[inline]
+    pub const fn from_bits(bits: u32) -> Self {
+        Self {
+            processor: (bits >> 28) as u8 & 0x0f,
+            family: (bits >> 23) as u8 & 0x1f,
+            series: (bits >> 16) as u8 & 0x3f,
+            die: (bits >> 12) as u8 & 0x0f,
+            revision: (bits >> 8) as u8 & 0x0f,
+            variant: bits as u8,
+        }
+    }
+
+    /// Product series of the device
+    pub fn product_series(&self) -> Series {
+        match (self.processor, self.family, self.series) {
+            (1, 0, 1) => Series::Samd21,
+            (1, 0, 3) => Series::Samd11,
+            (6, 0, 6) => Series::Samd51,
+            (6, 3, 1) => Series::Same51,
+            (6, 3, 3) => Series::Same53,
+            (6, 3, 4) => Series::Same54,
+            _ => Series::Unknown,
+        }
+    }
+
+    /// Silicon revision as letter, as used in the errata
+    #[inline]
+    pub fn revision_letter(&self) -> char {
+        (b'A' + self.revision) as char
+    }
+}
+
 /// NVM result type
 pub type Result<T> = core::result::Result<T, Error>;
 
 impl Dsu {
     /// Unlock the DSU and instantiate peripheral
+    #[hal_cfg(any("dsu-d11", "dsu-d21"))]
+    #[inline]
+    pub fn new(dsu: pac::Dsu, pac1: &pac::Pac1) -> Result<Self> {
+        // The DSU is bit 1 of PAC1 and write protected out of reset
+        const DSU_WP: u32 = 1 << 1;
+
+        // Attempt to unlock DSU
+        pac1.wpclr().write(|w| unsafe { w.bits(DSU_WP) });
+
+        // Check if DSU was unlocked
+        if pac1.wpset().read().bits() & DSU_WP != 0 {
+            Err(Error::PacUnlockFailed)
+        } else {
+            Ok(Self { dsu })
+        }
+    }
+
+    /// Unlock the DSU and instantiate peripheral
+    #[hal_cfg("dsu-d5x")]
     #[inline]
     pub fn new(dsu: pac::Dsu, pac: &Pac) -> Result<Self> {
         // Attempt to unlock DSU
@@ -154,4 +267,121 @@ impl Dsu {
             Ok(!self.dsu.data().read().data().bits())
         }
     }
+
+    /// Read the device identification
+    #[inline]
+    pub fn device_id(&self) -> DeviceId {
+        DeviceId::from_bits(self.dsu.did().read().bits())
+    }
+
+    /// Run the memory built-in self-test over a RAM region
+    ///
+    /// - `address` is the start of the region; must be word-aligned
+    /// - `length` is the length of the region in bytes; must be word-aligned
+    ///
+    /// On a fault, the address of the failing word, the failing bit and the
+    /// test phase are reported in [`Error::MbistFailed`].
+    ///
+    /// # Safety
+    ///
+    /// MBIST overwrites the tested memory. Nothing in the region, including
+    /// the stack of the calling code, may be in use, and its content is
+    /// undefined afterwards.
+    pub unsafe fn mbist(&mut self, address: u32, length: u32) -> Result<()> {
+        if address % 4 != 0 {
+            return Err(Error::AlignmentError);
+        }
+
+        if length % 4 != 0 {
+            return Err(Error::AlignmentError);
+        }
+
+        self.set_address(address / 4)?;
+        self.set_length(length / 4)?;
+
+        // Clear the status flags indicating termination of the operation
+        self.dsu
+            .statusa()
+            .write(|w| w.done().set_bit().fail().set_bit());
+
+        // Start the self-test
+        self.dsu.ctrl().write(|w| w.mbist().set_bit());
+
+        while !self.is_done() {}
+
+        if self.bus_error() {
+            self.clear_bus_error();
+            return Err(Error::Peripheral(PeripheralError::BusError));
+        }
+
+        if self.has_failed() {
+            // ADDR holds the failing word, DATA the bit index in bits 4:0 and
+            // the algorithm phase in bits 11:8
+            let data = self.dsu.data().read().bits();
+            Err(Error::MbistFailed(MbistFailure {
+                address: self.dsu.addr().read().bits() & !0b11,
+                bit: (data & 0x1f) as u8,
+                phase: ((data >> 8) & 0x0f) as u8,
+            }))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check if a debugger is attached
+    #[inline]
+    pub fn debugger_present(&self) -> bool {
+        self.dsu.statusb().read().dbgpres().bit_is_set()
+    }
+
+    /// Check if the device is protected by the security bit
+    ///
+    /// A protected device only gives the debugger access to the DSU, and
+    /// can only be unprotected by a chip erase.
+    #[inline]
+    pub fn is_protected(&self) -> bool {
+        self.dsu.statusb().read().prot().bit_is_set()
+    }
+
+    /// Check if chip erase is locked
+    ///
+    /// Once locked, a debugger can no longer issue a chip erase to remove the
+    /// protection.
+    #[hal_cfg("dsu-d5x")]
+    #[inline]
+    pub fn is_chip_erase_locked(&self) -> bool {
+        self.dsu.statusb().read().celck().bit_is_set()
+    }
+
+    /// Check if hot-plugging of a debugger is enabled
+    #[inline]
+    pub fn hot_plugging_enabled(&self) -> bool {
+        self.dsu.statusb().read().hpe().bit_is_set()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_id_is_decoded() {
+        let d21 = DeviceId::from_bits(0x1001_0305);
+        assert_eq!(d21.product_series(), Series::Samd21);
+        assert_eq!(d21.revision_letter(), 'D');
+        assert_eq!(d21.variant, 0x05);
+
+        assert_eq!(
+            DeviceId::from_bits(0x1003_0106).product_series(),
+            Series::Samd11
+        );
+        assert_eq!(
+            DeviceId::from_bits(0x6006_0205).product_series(),
+            Series::Samd51
+        );
+        assert_eq!(
+            DeviceId::from_bits(0x6184_0200).product_series(),
+            Series::Same54
+        );
+    }
 }
@@ -42,6 +42,13 @@
 //! let mut extint = eic_channels.2.with_pin(button);
 //! ```
 //!
+//! ## Non-maskable interrupt
+//!
+//! On SAMD21 and SAMx5x, `PA08` can be used as non-maskable interrupt (NMI)
+//! pin, which is separate from the EXTINT channels. Split the [`Eic`] with
+//! [`Eic::split_with_nmi`] to also obtain the [`NmiToken`], and turn it into
+//! an [`Nmi`] with [`Nmi::new`].
+//!
 //! ## `async` operation <span class="stab portability" title="Available on crate feature `async` only"><code>async</code></span>
 //!
 //! [`ExtInt`]s can be used for async operations. Configuring the [`Eic`] in
@@ -450,3 +457,140 @@ macro_rules! define_split_future {
 impl Eic<EicFuture> {
     with_num_channels!(define_split_future);
 }
+
+/// Sense configuration of the [`Nmi`]
+#[hal_cfg(all("pa08", any("eic-d21", "eic-d5x")))]
+pub type NmiSense = pac::eic::nmictrl::Nmisenseselect;
+
+/// Token granting access to the NMI registers of the EIC.
+///
+/// It is handed out once, together with the EXTINT channels, by
+/// [`Eic::split_with_nmi`].
+#[hal_cfg(all("pa08", any("eic-d21", "eic-d5x")))]
+pub struct NmiToken(());
+
+#[hal_cfg(all("pa08", any("eic-d21", "eic-d5x")))]
+impl Eic {
+    /// Split the EIC into individual channels and the [`NmiToken`].
+    #[inline]
+    pub fn split_with_nmi(self) -> (Channels, NmiToken) {
+        (self.split(), NmiToken(()))
+    }
+}
+
+#[hal_cfg(all("pa08", any("eic-d21", "eic-d5x")))]
+#[cfg(feature = "async")]
+impl Eic<EicFuture> {
+    /// Split the EIC into individual channels and the [`NmiToken`].
+    #[inline]
+    pub fn split_with_nmi(self) -> (FutureChannels, NmiToken) {
+        (self.split(), NmiToken(()))
+    }
+}
+
+/// Non-maskable interrupt pin.
+///
+/// The NMI is sensed on `PA08` by the EIC, independently of the EXTINT
+/// channels, and raises the `NonMaskableInt` exception, which preempts every
+/// other interrupt. Create it with [`Nmi::new`] from the [`NmiToken`].
+///
+/// Critical sections do not mask the NMI, so the exception handler cannot
+/// share the [`Nmi`] through a mutex. Use the associated functions
+/// [`Nmi::is_pending`] and [`Nmi::clear`] instead, which only access the
+/// NMIFLAG register:
+///
+/// ```no_run
+/// use atsamd_hal::eic::Nmi;
+///
+/// #[cortex_m_rt::exception]
+/// fn NonMaskableInt() {
+///     if Nmi::is_pending() {
+///         Nmi::clear();
+///         // Emergency stop
+///     }
+/// }
+/// ```
+///
+/// The SAMD11 EIC also has the NMICTRL register, but its NMI pin is not
+/// described in the pin tables of this HAL, where `PA08` carries EXTINT[6]
+/// instead. [`Nmi`] is therefore only provided on SAMD21 and SAMx5x.
+#[hal_cfg(all("pa08", any("eic-d21", "eic-d5x")))]
+pub struct Nmi<C: crate::gpio::InterruptConfig = crate::gpio::Floating> {
+    token: NmiToken,
+    pin: Pin<crate::gpio::PA08, crate::gpio::Interrupt<C>>,
+}
+
+#[hal_cfg(all("pa08", any("eic-d21", "eic-d5x")))]
+impl<C: crate::gpio::InterruptConfig> Nmi<C> {
+    /// Claim `PA08` as non-maskable interrupt pin.
+    ///
+    /// The NMI is created with sensing disabled; configure it with
+    /// [`Nmi::sense`].
+    pub fn new(token: NmiToken, pin: Pin<crate::gpio::PA08, crate::gpio::Interrupt<C>>) -> Self {
+        let mut nmi = Self { token, pin };
+        nmi.sense(NmiSense::None);
+        Nmi::clear();
+        nmi
+    }
+
+    #[inline]
+    fn nmictrl(&mut self) -> &pac::eic::Nmictrl {
+        // Safety: The `NmiToken` is a singleton with exclusive access to the
+        // NMICTRL register, which is not touched by the EXTINT channels.
+        unsafe { (*pac::Eic::PTR).nmictrl() }
+    }
+
+    /// Set the edge or level that triggers the NMI.
+    ///
+    /// [`NmiSense::None`] disables the NMI.
+    pub fn sense(&mut self, sense: NmiSense) {
+        self.nmictrl().modify(|_, w| w.nmisense().variant(sense));
+    }
+
+    /// Enable or disable the majority filter of the NMI pin.
+    pub fn filter(&mut self, filter: bool) {
+        self.nmictrl().modify(|_, w| w.nmifilten().bit(filter));
+    }
+
+    /// Enable or disable asynchronous edge detection.
+    ///
+    /// Asynchronous detection works without the EIC clock, so that the NMI
+    /// can wake the device from sleep modes where the clock is stopped.
+    #[hal_cfg("eic-d5x")]
+    pub fn asynchronous(&mut self, asynchronous: bool) {
+        self.nmictrl()
+            .modify(|_, w| w.nmiasynch().bit(asynchronous));
+    }
+
+    /// Disable the NMI and release the token and the pin.
+    pub fn free(mut self) -> (NmiToken, Pin<crate::gpio::PA08, crate::gpio::Interrupt<C>>) {
+        self.sense(NmiSense::None);
+        (self.token, self.pin)
+    }
+}
+
+#[hal_cfg(all("pa08", any("eic-d21", "eic-d5x")))]
+impl Nmi {
+    /// Returns `true` if the NMI has been triggered and not yet cleared.
+    ///
+    /// This can be called from the `NonMaskableInt` exception handler.
+    #[inline]
+    pub fn is_pending() -> bool {
+        // Safety: NMIFLAG is only read here, and written by `clear`.
+        let eic = unsafe { &*pac::Eic::PTR };
+        eic.nmiflag().read().nmi().bit_is_set()
+    }
+
+    /// Clear the NMI flag.
+    ///
+    /// This can be called from the `NonMaskableInt` exception handler. The
+    /// flag has to be cleared before returning from the handler, otherwise
+    /// a level triggered NMI is raised again.
+    #[inline]
+    pub fn clear() {
+        // Safety: Writing a one to NMIFLAG.NMI only clears the flag, which
+        // does not affect any other EIC configuration.
+        let eic = unsafe { &*pac::Eic::PTR };
+        eic.nmiflag().write(|w| w.nmi().set_bit());
+    }
+}
@@ -108,6 +108,16 @@
 //! * [`RegionConfiguration::set_wrap()`] to `true` only for the last region if
 //!   continuous monitoring is desired
 //!
+//! ### Streaming hashing
+//!
+//! [`IcmSha`] hashes arbitrary buffers incrementally with SHA-1, SHA-224 or
+//! SHA-256, chaining the intermediate hash through the user initial hash
+//! value. It implements the RustCrypto [`digest::Update`] and
+//! [`digest::FixedOutput`] traits, and with the `async` feature
+//! [`IcmSha::into_future`] waits for the `ICM` interrupt instead of polling.
+//! It takes over the whole ICM, so it can't be combined with region
+//! monitoring.
+//!
 //! ## Examples
 //!
 //! ### Calculate SHA1, SHA224 and SHA256 sums, then switch to memory monitor
@@ -1674,3 +1684,510 @@ impl RegionNext {
         RegionNext { rnext: 0 }
     }
 }
+
+//==============================================================================
+// Streaming SHA
+//==============================================================================
+
+/// Converts SHA initial hash values to the byte order expected by `UIHVAL`
+const fn uihval_words(h: [u32; 8]) -> [u32; 8] {
+    let mut words = [0; 8];
+    let mut i = 0;
+    while i < 8 {
+        words[i] = h[i].swap_bytes();
+        i += 1;
+    }
+    words
+}
+
+/// SHA algorithm computed by [`IcmSha`]
+pub trait ShaAlgorithm: Sealed {
+    /// Length of the digest in bytes
+    type OutputSize: digest::generic_array::ArrayLength<u8> + 'static;
+    /// Algorithm run by the ICM engine for each chunk of blocks
+    const ENGINE: icm_algorithm;
+    /// Initial hash value, in the byte order expected by `UIHVAL`
+    const INITIAL_STATE: [u32; 8];
+}
+
+/// SHA-1 marker for [`IcmSha`]
+pub enum Sha1 {}
+
+impl Sealed for Sha1 {}
+
+impl ShaAlgorithm for Sha1 {
+    type OutputSize = digest::consts::U20;
+    const ENGINE: icm_algorithm = icm_algorithm::Sha1;
+    const INITIAL_STATE: [u32; 8] = uihval_words([
+        0x6745_2301,
+        0xefcd_ab89,
+        0x98ba_dcfe,
+        0x1032_5476,
+        0xc3d2_e1f0,
+        0,
+        0,
+        0,
+    ]);
+}
+
+/// SHA-224 marker for [`IcmSha`]
+///
+/// Intermediate chunks run on the SHA-256 engine, since the SHA-224 engine
+/// only writes back the 7 digest words and chaining requires all 8.
+pub enum Sha224 {}
+
+impl Sealed for Sha224 {}
+
+impl ShaAlgorithm for Sha224 {
+    type OutputSize = digest::consts::U28;
+    const ENGINE: icm_algorithm = icm_algorithm::Sha256;
+    const INITIAL_STATE: [u32; 8] = uihval_words([
+        0xc105_9ed8,
+        0x367c_d507,
+        0x3070_dd17,
+        0xf70e_5939,
+        0xffc0_0b31,
+        0x6858_1511,
+        0x64f9_8fa7,
+        0xbefa_4fa4,
+    ]);
+}
+
+/// SHA-256 marker for [`IcmSha`]
+pub enum Sha256 {}
+
+impl Sealed for Sha256 {}
+
+impl ShaAlgorithm for Sha256 {
+    type OutputSize = digest::consts::U32;
+    const ENGINE: icm_algorithm = icm_algorithm::Sha256;
+    const INITIAL_STATE: [u32; 8] = uihval_words([
+        0x6a09_e667,
+        0xbb67_ae85,
+        0x3c6e_f372,
+        0xa54f_f53a,
+        0x510e_527f,
+        0x9b05_688c,
+        0x1f83_d9ab,
+        0x5be0_cd19,
+    ]);
+}
+
+/// Errors returned by [`IcmSha`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ShaError {
+    /// The ICM reported a bus error while reading the data
+    ///
+    /// The data has to be located in memory the ICM can access, such as SRAM
+    /// or flash. The hasher state is lost and has to be reset.
+    BusError,
+}
+
+/// SHA block size in bytes
+const SHA_BLOCK_SIZE: usize = 64;
+
+/// Largest number of blocks the ICM processes from a single descriptor
+const MAX_TRANSFER_BLOCKS: usize = 1 << 16;
+
+/// Region descriptor used by [`IcmSha`], aligned as required by `DSCR`
+#[repr(C, align(64))]
+struct ShaDescriptor(MainRegionDesc<Region0>);
+
+/// Word aligned block buffer, since the ICM reads regions word by word
+#[repr(C, align(4))]
+struct ShaBlocks<const N: usize>([u8; N]);
+
+/// Streaming SHA-1, SHA-224 or SHA-256 hasher running on the ICM
+///
+/// Data is hashed in chunks of whole 64 byte blocks, chaining the
+/// intermediate hash between chunks through the user initial hash value
+/// (`UIHVAL`). Word aligned input is read by the ICM in place, anything else
+/// goes through an internal block buffer. Message padding is done in
+/// software on finalization.
+///
+/// The hasher implements [`digest::Update`] and [`digest::FixedOutput`], which
+/// busy-wait for each chunk. The `async` feature adds [`IcmShaFuture`],
+/// completing chunks from the `ICM` interrupt.
+///
+/// The region descriptor and hash area live inside the hasher, and the ICM
+/// is only running while a method borrows it, so the hasher doesn't need to
+/// be `static`.
+///
+/// ```no_run
+/// # use atsamd_hal::{pac::Peripherals, icm::*};
+/// use digest::{FixedOutput, Update};
+///
+/// let peripherals = Peripherals::take().unwrap();
+/// let mut sha = IcmSha::<Sha256>::new(Icm::new(peripherals.icm));
+/// sha.update(b"firmware chunk 1");
+/// sha.update(b"firmware chunk 2");
+/// let digest = sha.finalize_fixed();
+/// ```
+pub struct IcmSha<A: ShaAlgorithm> {
+    icm: Icm,
+    descriptor: ShaDescriptor,
+    hash_area: HashArea,
+    state: [u32; 8],
+    buffer: ShaBlocks<SHA_BLOCK_SIZE>,
+    buffered: usize,
+    length: u64,
+    algorithm: PhantomData<A>,
+}
+
+impl<A: ShaAlgorithm> IcmSha<A> {
+    /// Create a new hasher
+    ///
+    /// The ICM is reset and reconfigured for every chunk, any previous
+    /// configuration is lost.
+    #[inline]
+    pub fn new(icm: Icm) -> Self {
+        Self {
+            icm,
+            descriptor: ShaDescriptor(MainRegionDesc::new_region0()),
+            hash_area: HashArea::default(),
+            state: A::INITIAL_STATE,
+            buffer: ShaBlocks([0; SHA_BLOCK_SIZE]),
+            buffered: 0,
+            length: 0,
+            algorithm: PhantomData,
+        }
+    }
+
+    /// Reset the ICM and return it
+    #[inline]
+    pub fn free(mut self) -> Icm {
+        self.icm.swrst();
+        self.icm
+    }
+
+    /// Hash `data`, returning an error if the ICM reports a bus error
+    pub fn try_update(&mut self, mut data: &[u8]) -> Result<(), ShaError> {
+        self.length = self.length.wrapping_add(data.len() as u64);
+        while let Some((blocks, count)) = self.next_chunk(&mut data) {
+            self.start(blocks, count, false);
+            let status = loop {
+                if let Some(status) = self.status() {
+                    break status;
+                }
+            };
+            self.complete(status)?;
+        }
+        Ok(())
+    }
+
+    /// Finish the hash, write the digest to `out` and reset the hasher
+    pub fn try_finalize_into_reset(
+        &mut self,
+        out: &mut digest::Output<Self>,
+    ) -> Result<(), ShaError> {
+        let (blocks, count) = self.padding();
+        self.start(blocks.0.as_ptr(), count, false);
+        let status = loop {
+            if let Some(status) = self.status() {
+                break status;
+            }
+        };
+        let result = self.complete(status);
+        if result.is_ok() {
+            self.output(out);
+        }
+        self.reset_state();
+        result
+    }
+
+    /// Restart hashing from the initial hash value
+    #[inline]
+    fn reset_state(&mut self) {
+        self.state = A::INITIAL_STATE;
+        self.buffered = 0;
+        self.length = 0;
+    }
+
+    /// Find the next chunk of whole blocks to hash, consuming `data`
+    ///
+    /// Returns the start address and number of blocks. Data not filling a
+    /// block is kept in the block buffer.
+    fn next_chunk(&mut self, data: &mut &[u8]) -> Option<(*const u8, usize)> {
+        let aligned = (data.as_ptr() as usize) % 4 == 0;
+        if self.buffered == 0 && aligned && data.len() >= SHA_BLOCK_SIZE {
+            let count = (data.len() / SHA_BLOCK_SIZE).min(MAX_TRANSFER_BLOCKS);
+            let (chunk, rest) = data.split_at(count * SHA_BLOCK_SIZE);
+            *data = rest;
+            return Some((chunk.as_ptr(), count));
+        }
+        let take = (SHA_BLOCK_SIZE - self.buffered).min(data.len());
+        let (chunk, rest) = data.split_at(take);
+        self.buffer.0[self.buffered..self.buffered + take].copy_from_slice(chunk);
+        self.buffered += take;
+        *data = rest;
+        if self.buffered == SHA_BLOCK_SIZE {
+            self.buffered = 0;
+            Some((self.buffer.0.as_ptr(), 1))
+        } else {
+            None
+        }
+    }
+
+    /// Build the final padded block(s) from the buffered data
+    fn padding(&self) -> (ShaBlocks<{ 2 * SHA_BLOCK_SIZE }>, usize) {
+        let mut blocks = ShaBlocks([0; 2 * SHA_BLOCK_SIZE]);
+        blocks.0[..self.buffered].copy_from_slice(&self.buffer.0[..self.buffered]);
+        blocks.0[self.buffered] = 0x80;
+        let count = if self.buffered < SHA_BLOCK_SIZE - 8 {
+            1
+        } else {
+            2
+        };
+        let end = count * SHA_BLOCK_SIZE;
+        blocks.0[end - 8..end].copy_from_slice(&self.length.wrapping_mul(8).to_be_bytes());
+        (blocks, count)
+    }
+
+    /// Start hashing `count` blocks at `blocks`, chained from the current
+    /// state
+    fn start(&mut self, blocks: *const u8, count: usize, interrupt: bool) {
+        self.icm.swrst();
+        self.icm
+            .cfg()
+            .write(|w| w.uihash().set_bit().ualgo().variant(A::ENGINE));
+        self.icm.set_user_initial_hash_value(self.state);
+
+        let mut rcfg = RegionConfiguration::default();
+        rcfg.set_eom(true);
+        rcfg.set_rhien(false);
+        rcfg.set_beien(false);
+        let descriptor = &mut self.descriptor.0;
+        descriptor.set_region_address(blocks);
+        descriptor.set_region_configuration(rcfg);
+        descriptor.rctrl = RegionControl {
+            trsize: (count - 1) as u16,
+        };
+        descriptor.set_region_next(RegionNext::default());
+        self.icm.set_dscr_addr(&self.descriptor.0);
+        self.icm.set_hash_addr(&self.hash_area);
+
+        // Discard any stale status
+        let _ = self.icm.isr().read();
+        if interrupt {
+            self.icm
+                .ier()
+                .write(|w| unsafe { w.rhc().bits(1).rbe().bits(1) });
+        }
+        // The descriptor and data have to be in memory before the ICM reads them
+        core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+        self.icm.ctrl().write(|w| unsafe { w.rmen().bits(1) });
+        self.icm.enable();
+    }
+
+    /// Check whether the current chunk is done
+    ///
+    /// Reading `ISR` clears it, so a returned status is only reported once.
+    #[inline]
+    fn status(&self) -> Option<Result<(), ShaError>> {
+        let isr = self.icm.isr().read();
+        if isr.rbe().bits() & 1 != 0 {
+            Some(Err(ShaError::BusError))
+        } else if isr.rhc().bits() & 1 != 0 {
+            Some(Ok(()))
+        } else {
+            None
+        }
+    }
+
+    /// Stop the ICM and pick up the intermediate hash
+    fn complete(&mut self, status: Result<(), ShaError>) -> Result<(), ShaError> {
+        self.icm.disable();
+        core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+        if status.is_ok() {
+            for (state, hash) in self.state.iter_mut().zip(&self.hash_area.region0) {
+                // Safety: written by the ICM, which is now stopped
+                *state = unsafe { core::ptr::read_volatile(hash) };
+            }
+        }
+        status
+    }
+
+    /// Write the digest from the current state
+    fn output(&self, out: &mut digest::Output<Self>) {
+        for (bytes, word) in out.chunks_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_le_bytes()[..bytes.len()]);
+        }
+    }
+}
+
+impl<A: ShaAlgorithm> digest::OutputSizeUser for IcmSha<A> {
+    type OutputSize = A::OutputSize;
+}
+
+impl<A: ShaAlgorithm> digest::HashMarker for IcmSha<A> {}
+
+/// # Panics
+///
+/// Panics if the ICM reports a bus error, use [`IcmSha::try_update`] to handle
+/// it.
+impl<A: ShaAlgorithm> digest::Update for IcmSha<A> {
+    fn update(&mut self, data: &[u8]) {
+        self.try_update(data).expect("ICM bus error while hashing");
+    }
+}
+
+/// # Panics
+///
+/// Panics if the ICM reports a bus error, use
+/// [`IcmSha::try_finalize_into_reset`] to handle it.
+impl<A: ShaAlgorithm> digest::FixedOutput for IcmSha<A> {
+    fn finalize_into(mut self, out: &mut digest::Output<Self>) {
+        self.try_finalize_into_reset(out)
+            .expect("ICM bus error while hashing");
+    }
+}
+
+impl<A: ShaAlgorithm> digest::Reset for IcmSha<A> {
+    #[inline]
+    fn reset(&mut self) {
+        self.reset_state();
+    }
+}
+
+impl<A: ShaAlgorithm> digest::FixedOutputReset for IcmSha<A> {
+    fn finalize_into_reset(&mut self, out: &mut digest::Output<Self>) {
+        self.try_finalize_into_reset(out)
+            .expect("ICM bus error while hashing");
+    }
+}
+
+/// Interrupt handler for the `ICM` interrupt, used by [`IcmShaFuture`]
+#[cfg(feature = "async")]
+pub struct InterruptHandler {
+    _private: (),
+}
+
+#[cfg(feature = "async")]
+impl Sealed for InterruptHandler {}
+
+#[cfg(feature = "async")]
+static ICM_WAKER: embassy_sync::waitqueue::AtomicWaker =
+    embassy_sync::waitqueue::AtomicWaker::new();
+
+#[cfg(feature = "async")]
+impl crate::async_hal::interrupts::Handler<crate::async_hal::interrupts::ICM> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let icm = unsafe { &*crate::pac::Icm::ptr() };
+        let imr = icm.imr().read();
+        if (imr.rhc().bits() | imr.rbe().bits()) & 1 != 0 {
+            // Leave ISR alone, reading it would clear the status for the task
+            icm.idr()
+                .write(|w| unsafe { w.rhc().bits(1).rbe().bits(1) });
+            ICM_WAKER.wake();
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<A: ShaAlgorithm> IcmSha<A> {
+    /// Turn an [`IcmSha`] into an [`IcmShaFuture`]
+    ///
+    /// The `ICM` interrupt has to be bound to [`InterruptHandler`]. It is
+    /// enabled in the NVIC by this method.
+    #[inline]
+    pub fn into_future<I>(self, _interrupts: I) -> IcmShaFuture<A>
+    where
+        I: crate::async_hal::interrupts::Binding<
+                crate::async_hal::interrupts::ICM,
+                InterruptHandler,
+            >,
+    {
+        use crate::async_hal::interrupts::{ICM, Interrupt};
+
+        ICM::unpend();
+        unsafe { ICM::enable() };
+        IcmShaFuture { sha: self }
+    }
+}
+
+/// `async` version of [`IcmSha`]
+///
+/// Create this struct by calling [`IcmSha::into_future`]. Dropping a pending
+/// `update` or `finalize` future resets the ICM, and the hasher state is
+/// lost.
+#[cfg(feature = "async")]
+pub struct IcmShaFuture<A: ShaAlgorithm> {
+    sha: IcmSha<A>,
+}
+
+/// Resets the ICM if an operation is cancelled while the ICM is running
+#[cfg(feature = "async")]
+struct ResetOnDrop;
+
+#[cfg(feature = "async")]
+impl Drop for ResetOnDrop {
+    fn drop(&mut self) {
+        let icm = unsafe { &*crate::pac::Icm::ptr() };
+        icm.ctrl().write(|w| w.swrst().set_bit());
+    }
+}
+
+#[cfg(feature = "async")]
+impl<A: ShaAlgorithm> IcmShaFuture<A> {
+    /// Hash `data`
+    pub async fn update(&mut self, mut data: &[u8]) -> Result<(), ShaError> {
+        self.sha.length = self.sha.length.wrapping_add(data.len() as u64);
+        while let Some((blocks, count)) = self.sha.next_chunk(&mut data) {
+            self.run(blocks, count).await?;
+        }
+        Ok(())
+    }
+
+    /// Finish the hash, returning the digest and resetting the hasher
+    pub async fn finalize_reset(&mut self) -> Result<digest::Output<IcmSha<A>>, ShaError> {
+        let (blocks, count) = self.sha.padding();
+        let result = self.run(blocks.0.as_ptr(), count).await;
+        let mut out = digest::Output::<IcmSha<A>>::default();
+        if result.is_ok() {
+            self.sha.output(&mut out);
+        }
+        self.sha.reset_state();
+        result.map(|()| out)
+    }
+
+    /// Restart hashing from the initial hash value
+    #[inline]
+    pub fn reset(&mut self) {
+        self.sha.reset_state();
+    }
+
+    /// Return the underlying [`IcmSha`]
+    #[inline]
+    pub fn free(self) -> IcmSha<A> {
+        self.sha
+    }
+
+    /// Hash `count` blocks at `blocks`, waiting for the `ICM` interrupt
+    async fn run(&mut self, blocks: *const u8, count: usize) -> Result<(), ShaError> {
+        use core::task::Poll;
+
+        let guard = ResetOnDrop;
+        self.sha.start(blocks, count, true);
+        let status = core::future::poll_fn(|cx| {
+            ICM_WAKER.register(cx.waker());
+            match self.sha.status() {
+                Some(status) => Poll::Ready(status),
+                None => {
+                    self.sha
+                        .icm
+                        .ier()
+                        .write(|w| unsafe { w.rhc().bits(1).rbe().bits(1) });
+                    Poll::Pending
+                }
+            }
+        })
+        .await;
+        core::mem::forget(guard);
+        self.sha
+            .icm
+            .idr()
+            .write(|w| unsafe { w.rhc().bits(1).rbe().bits(1) });
+        self.sha.complete(status)
+    }
+}
@@ -40,7 +40,7 @@ pub mod clock {}
 #[hal_module("aes")]
 pub mod aes {}
 
-#[hal_module("dsu-d5x")]
+#[hal_module("dsu")]
 pub mod dsu {}
 
 #[hal_module("pukcc")]
@@ -55,7 +55,10 @@ pub mod trng {}
 #[hal_module("icm")]
 pub mod icm {}
 
-#[hal_module("nvmctrl-d5x")]
+#[hal_module(
+    any("nvmctrl-d11", "nvmctrl-d21") => "nvm/d11.rs",
+    "nvmctrl-d5x" => "nvm/mod.rs",
+)]
 pub mod nvm {}
 
 #[cfg(feature = "can")]
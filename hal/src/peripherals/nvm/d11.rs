@@ -0,0 +1,661 @@
+//! # Non-volatile Memory Controller
+//!
+//! This module allows users to interact with the non-volatile memory
+//! controller of SAMD11 and SAMD21 devices.
+//!
+//! The flash memory is organized in rows of four pages. Pages are written
+//! through a page buffer, while erasure always applies to a whole row. The
+//! flash is also split into 16 lock regions, which can be locked and unlocked
+//! at runtime; the lock state after reset is taken from the user row.
+//!
+//! Some devices (such as SAMD21 variants D and L) feature an additional
+//! read-while-write (RWW) EEPROM section, separate from the main flash. It
+//! can be erased and written while the CPU keeps executing from the main
+//! flash. See [`Nvm::rww_eeprom`].
+//!
+//! Module features:
+//! - Erase & write over non-volatile memory in a device.
+//! - Lock & unlock flash regions
+//! - Read & modify the user row
+//! - Access to the RWW EEPROM section
+//! - Typed fuse configuration (More in [`fuses`] module)
+//! - NOR flash storage in a flash region (More in [`storage`] module)
+#![warn(missing_docs)]
+
+pub mod fuses;
+pub mod storage;
+
+pub use fuses::Fuses;
+pub use storage::FlashRegion;
+
+use crate::pac::Nvmctrl;
+use crate::pac::nvmctrl::ctrla::Cmdselect;
+use core::ops::Range;
+use core::ptr::addr_of;
+
+use bitfield::bitfield;
+
+/// Retrieve a total NVM size using HW registers
+#[inline(always)]
+pub fn retrieve_flash_size() -> u32 {
+    // Safety: PARAM is a read-only register
+    let nvm_params = unsafe { (*Nvmctrl::ptr()).param().read() };
+    if !nvm_params.psz().is_64() {
+        unreachable!("NVM page size is always expected to be 64 bytes");
+    }
+    nvm_params.nvmp().bits() as u32 * PAGESIZE
+}
+
+/// Retrieve the RWW EEPROM size using HW registers
+///
+/// Returns 0 on devices without a RWW EEPROM section.
+#[inline(always)]
+pub fn retrieve_rww_eeprom_size() -> u32 {
+    // Safety: PARAM is a read-only register
+    let nvm_params = unsafe { (*Nvmctrl::ptr()).param().read().bits() };
+    // PARAM.RWWEEP (bits 31:20) is not described in the PACs
+    (nvm_params >> 20) * PAGESIZE
+}
+
+/// Size of a page in bytes
+pub const PAGESIZE: u32 = 64;
+
+/// Size of a row in bytes
+pub const ROWSIZE: u32 = PAGESIZE * 4;
+
+/// Number of lock regions of the flash
+pub const REGIONS_COUNT: u32 = 16;
+
+/// RWW EEPROM row erase command, missing from the PACs
+const CMD_RWWEEER: u8 = 0x1a;
+
+/// RWW EEPROM page write command, missing from the PACs
+const CMD_RWWEEWP: u8 = 0x1c;
+
+/// Non-volatile memory controller
+pub struct Nvm {
+    /// PAC peripheral
+    nvm: Nvmctrl,
+}
+
+/// Errors generated by the NVM peripheral
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PeripheralError {
+    /// NVM error
+    NvmError,
+    /// Locked error
+    LockError,
+    /// Programming error
+    ProgrammingError,
+}
+
+/// Driver errors
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// Address range outside of flash
+    NonFlash,
+    /// The device has no RWW EEPROM section
+    NoRwwEeprom,
+    /// Errors generated by hardware
+    Peripheral(PeripheralError),
+    /// An alignment requirement was not fulfilled
+    Alignment,
+}
+
+/// NVM result type
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Page write and row erase commands of a memory section
+#[derive(Copy, Clone, Debug)]
+enum Section {
+    /// Main address space flash, or the user row when using the auxiliary
+    /// commands
+    Flash { write: Cmdselect, erase: Cmdselect },
+    /// RWW EEPROM section
+    RwwEeprom,
+}
+
+impl Section {
+    const MAIN: Self = Self::Flash {
+        write: Cmdselect::Wp,
+        erase: Cmdselect::Er,
+    };
+
+    const USER_ROW: Self = Self::Flash {
+        write: Cmdselect::Wap,
+        erase: Cmdselect::Ear,
+    };
+
+    #[inline]
+    fn write_command(&self) -> u8 {
+        match self {
+            Self::Flash { write, .. } => *write as u8,
+            Self::RwwEeprom => CMD_RWWEEWP,
+        }
+    }
+
+    #[inline]
+    fn erase_command(&self) -> u8 {
+        match self {
+            Self::Flash { erase, .. } => *erase as u8,
+            Self::RwwEeprom => CMD_RWWEEER,
+        }
+    }
+}
+
+impl Nvm {
+    /// Pointer to the user row region of the flash memory
+    pub const USER_ROW_ADDR: *const [u8; ROWSIZE as usize] = 0x0080_4000 as _;
+
+    /// Start address of the RWW EEPROM section
+    pub const RWW_EEPROM_ADDR: u32 = 0x0040_0000;
+
+    /// Create a new NVM controller
+    ///
+    /// Pages are only written on explicit commands, so automatic page writes
+    /// are disabled.
+    #[inline]
+    pub fn new(nvm: Nvmctrl) -> Self {
+        nvm.ctrlb().modify(|_, w| w.manw().set_bit());
+        Self { nvm }
+    }
+
+    /// Releases the NvmCtrl resource
+    #[inline]
+    pub fn free(self) -> Nvmctrl {
+        self.nvm
+    }
+
+    /// Raw access to the registers.
+    ///
+    /// # Safety
+    ///
+    /// The abstraction assumes that it has exclusive ownership of the
+    /// registers. Direct access can break such assumptions.
+    pub unsafe fn registers(&self) -> &Nvmctrl {
+        &self.nvm
+    }
+
+    /// Check if the security bit is set
+    #[inline]
+    pub fn is_security_bit_set(&self) -> bool {
+        self.nvm.status().read().sb().bit()
+    }
+
+    /// Enable security bit
+    ///
+    /// It locks the chip from external access for code security. Consult the
+    /// datasheet for more details.
+    ///
+    /// In order to disable it, chip erase command must be issued through the
+    /// debugger.
+    #[inline]
+    pub fn enable_security_bit(&mut self) -> Result<()> {
+        self.command_sync(Cmdselect::Ssb as u8)
+    }
+
+    /// Set address for reading/writing
+    ///
+    /// The ADDR register holds a 16-bit word address.
+    #[inline]
+    fn set_address(&mut self, address: u32) {
+        unsafe {
+            self.nvm
+                .addr()
+                .write(|w| w.addr().bits((address >> 1) & 0x003f_ffff));
+        }
+    }
+
+    /// Execute a command, wait until it is done and check error states
+    #[inline]
+    fn command_sync(&mut self, command: u8) -> Result<()> {
+        // Wait until INTFLAG.READY
+        while !self.nvm.intflag().read().ready().bit() {}
+
+        // Safety: commands missing from the PAC are issued by their raw value
+        self.nvm
+            .ctrla()
+            .write(|w| unsafe { w.cmdex().key().cmd().bits(command) });
+
+        // Wait until INTFLAG.READY
+        while !self.nvm.intflag().read().ready().bit() {}
+
+        self.manage_error_states()
+    }
+
+    /// Read the peripheral state to check error flags and clear the up
+    /// afterwards
+    #[inline]
+    fn manage_error_states(&mut self) -> Result<()> {
+        let read_status = self.nvm.status().read();
+        // Check LOCKE first as it is more specific than PROGE
+        let state = if read_status.locke().bit_is_set() {
+            Err(Error::Peripheral(PeripheralError::LockError))
+        } else if read_status.proge().bit_is_set() {
+            Err(Error::Peripheral(PeripheralError::ProgrammingError))
+        } else if read_status.nvme().bit_is_set() {
+            Err(Error::Peripheral(PeripheralError::NvmError))
+        } else {
+            Ok(())
+        };
+
+        // Clear error flags
+        self.nvm
+            .status()
+            .write(|w| w.locke().set_bit().proge().set_bit().nvme().set_bit());
+        self.nvm.intflag().write(|w| w.error().set_bit());
+
+        state
+    }
+
+    /// Enable/disable region lock
+    ///
+    /// Flash memory is split into 16 regions. The 16 bits of the `mask`
+    /// determine if each region should be locked (if its bit is 0) and prevent
+    /// writing and erasing rows, or unlocked (if its bit is 1) and allow
+    /// writing and erasing rows.
+    ///
+    /// Less significant bits represent lower addresses, more significant bits
+    /// represent higher addresses.
+    ///
+    /// The lock state set here is lost on reset, where it is reloaded from the
+    /// `nvm_locks` field of the user row.
+    #[inline]
+    pub fn region_lock(&mut self, mask: u16) -> Result<()> {
+        let region_size = retrieve_flash_size() / REGIONS_COUNT;
+        for i in 0..REGIONS_COUNT {
+            self.set_address(i * region_size);
+            let protect = mask & (1 << i) == 0;
+            self.command_sync(if protect {
+                Cmdselect::Lr as u8
+            } else {
+                Cmdselect::Ur as u8
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Current lock state of the flash regions
+    ///
+    /// A bit of the returned mask is 0 if the region is locked and 1 if it is
+    /// unlocked, as in [`Self::region_lock`].
+    #[inline]
+    pub fn unlocked_regions(&self) -> u16 {
+        self.nvm.lock().read().lock().bits()
+    }
+
+    /// Read the user row from the flash memory
+    #[inline]
+    pub fn read_user_row(&self) -> UserRow {
+        let mut user_row = RawUserRow([0_u8; ROWSIZE as usize]);
+        // Safety:
+        // - You need Nvm or Nvmctrl to modify the memory, so the &self singleton is
+        //   enough to prevent concurrent modification.
+        // - Underlying [u8; 256] has no reserved bit patterns.
+        // Note: the user row is accessed through the iterator in order to avoid poor
+        // codegen for `read_volatile` call on the array pointer.
+        user_row
+            .0
+            .iter_mut()
+            .zip((0..ROWSIZE as isize).map(|i| unsafe {
+                Self::USER_ROW_ADDR
+                    .cast::<u8>()
+                    .wrapping_offset(i)
+                    .read_volatile()
+            }))
+            .for_each(|(l, r)| *l = r);
+        user_row
+    }
+
+    /// Modify the NVM User Row
+    ///
+    /// User is expected to provide a closure that modifies the user row
+    /// according to the user's needs.
+    ///
+    /// This method will read the current user row, call the closure on it,
+    /// *erase the row in the flash memory* and *write it* back again.
+    ///
+    /// Erasure and flashing is skipped if the user row stays the same after
+    /// calling the closure on it.
+    ///
+    /// # Safety
+    ///
+    /// Power loss between the erase and the write will result in *data loss*,
+    /// leaving the fuses (BOD33, watchdog, boot protection) in their erased
+    /// state.
+    ///
+    /// Reserved fields must keep their factory values, otherwise the device
+    /// might stop behaving correctly!
+    #[inline]
+    pub unsafe fn modify_user_row(
+        &mut self,
+        f: impl FnOnce(&mut UserRow),
+    ) -> Result<UserRowStatus> {
+        let original = self.read_user_row();
+        let mut modified = original.clone();
+
+        f(&mut modified);
+
+        if original != modified {
+            let address = Self::USER_ROW_ADDR as u32;
+            let source = addr_of!(modified.0) as u32;
+            unsafe {
+                self.erase(Section::USER_ROW, address, 1)?;
+                self.write(Section::USER_ROW, address, source, ROWSIZE / 4)?;
+            }
+
+            Ok(UserRowStatus::Updated)
+        } else {
+            Ok(UserRowStatus::Skipped)
+        }
+    }
+
+    /// Write to the main address space flash memory from a slice
+    ///
+    /// This call will fail if area that is being written to is
+    /// - outside of the main address space flash area
+    /// - in a locked region
+    ///
+    /// `destination` has to be 4 bytes aligned. The area has to be erased
+    /// beforehand.
+    ///
+    /// # Safety
+    ///
+    /// Writes to the main address space flash area containing currently
+    /// executed application are unsound.
+    #[inline]
+    pub unsafe fn write_flash_from_slice(
+        &mut self,
+        destination: *mut u32,
+        source_slice: &[u32],
+    ) -> Result<()> {
+        // Safety: prerequisites bubbled up to the method signature
+        unsafe {
+            self.write_flash(
+                destination,
+                source_slice.as_ptr(),
+                source_slice.len() as u32,
+            )
+        }
+    }
+
+    /// Write to the main address space flash memory
+    ///
+    /// This call will fail if area that is being written to is
+    /// - outside of the main address space flash area
+    /// - in a locked region
+    ///
+    /// `destination` has to be 4 bytes aligned.
+    /// `source` has to be 4 bytes aligned.
+    /// The area has to be erased beforehand.
+    ///
+    /// # Safety
+    ///
+    /// Writes to the main address space flash area containing currently
+    /// executed application are unsound.
+    #[inline]
+    pub unsafe fn write_flash(
+        &mut self,
+        destination: *mut u32,
+        source: *const u32,
+        words: u32,
+    ) -> Result<()> {
+        let destination = destination as u32;
+        let length = words * core::mem::size_of::<u32>() as u32;
+        if contains_non_flash_memory_area(&(destination..destination + length)) {
+            return Err(Error::NonFlash);
+        }
+
+        // Safety: prerequisites bubbled up to the method signature
+        unsafe { self.write(Section::MAIN, destination, source as u32, words) }
+    }
+
+    /// Erase the portion of the main address space flash memory
+    ///
+    /// Erase granularity is expressed in rows (4 pages == 256 bytes).
+    /// `address` is aligned down to a row boundary.
+    ///
+    /// This call will fail if area that is being erased is
+    /// - outside of the main address space flash area
+    /// - in a locked region
+    ///
+    /// # Safety
+    ///
+    /// Erasure of the main address space flash area containing currently
+    /// executed application is unsound.
+    #[inline]
+    pub unsafe fn erase_flash(&mut self, address: *mut u32, rows: u32) -> Result<()> {
+        let address = address as u32;
+        let flash_address = address - address % ROWSIZE;
+        if contains_non_flash_memory_area(&(flash_address..flash_address + rows * ROWSIZE)) {
+            return Err(Error::NonFlash);
+        }
+
+        // Safety: prerequisites bubbled up to the method signature
+        unsafe { self.erase(Section::MAIN, address, rows) }
+    }
+
+    /// Write words to a memory section through the page buffer
+    ///
+    /// # Safety
+    ///
+    /// The destination range must have been checked to lie within `section`.
+    #[inline]
+    unsafe fn write(
+        &mut self,
+        section: Section,
+        destination_address: u32,
+        source_address: u32,
+        words: u32,
+    ) -> Result<()> {
+        // Length of memory step
+        let step_size = core::mem::size_of::<u32>() as u32;
+        // Length of data in bytes
+        let length = words * step_size;
+
+        if source_address % step_size != 0 || destination_address % step_size != 0 {
+            return Err(Error::Alignment);
+        }
+
+        let read_addresses = source_address..(source_address + length);
+        let write_addresses = destination_address..(destination_address + length);
+
+        self.command_sync(Cmdselect::Pbc as u8)?;
+        // Track whether we have unwritten data in the page buffer
+        let mut dirty = false;
+        for (destination_address, source_address) in write_addresses
+            .step_by(step_size as usize)
+            .zip(read_addresses.step_by(step_size as usize))
+        {
+            // Write to memory, 32 bits, 1 word.
+            // The data is placed in the page buffer and ADDR is updated automatically.
+            // Memory is not written until the write page command is issued later.
+            unsafe {
+                let value = core::ptr::read_volatile(source_address as *const u32);
+                core::ptr::write_volatile(destination_address as *mut u32, value);
+            }
+            dirty = true;
+
+            // If we are about to cross a page boundary (and run out of page buffer), write
+            // to flash
+            if destination_address % PAGESIZE >= PAGESIZE - step_size {
+                self.command_sync(section.write_command())?;
+                dirty = false;
+            }
+        }
+
+        if dirty {
+            // Write last page
+            self.command_sync(section.write_command())?
+        }
+
+        Ok(())
+    }
+
+    /// Erase rows of a memory section
+    ///
+    /// # Safety
+    ///
+    /// The erased range must have been checked to lie within `section`.
+    #[inline]
+    unsafe fn erase(&mut self, section: Section, address: u32, rows: u32) -> Result<()> {
+        // Align to row boundary
+        let flash_address = address - address % ROWSIZE;
+        let range_to_erase = flash_address..(flash_address + rows * ROWSIZE);
+
+        for address in range_to_erase.step_by(ROWSIZE as usize) {
+            // Set target address to current row offset
+            self.set_address(address);
+
+            // Erase row, wait for completion
+            self.command_sync(section.erase_command())?
+        }
+
+        Ok(())
+    }
+
+    /// Retrieve the RWW EEPROM section
+    ///
+    /// Fails with [`Error::NoRwwEeprom`] on devices without one.
+    #[inline]
+    pub fn rww_eeprom(&mut self) -> Result<RwwEeprom<'_>> {
+        match retrieve_rww_eeprom_size() {
+            0 => Err(Error::NoRwwEeprom),
+            size => Ok(RwwEeprom { nvm: self, size }),
+        }
+    }
+}
+
+#[inline]
+fn contains_non_flash_memory_area(input: &Range<u32>) -> bool {
+    input.end > retrieve_flash_size()
+}
+
+/// Read-while-write EEPROM section
+///
+/// The section is located at [`Nvm::RWW_EEPROM_ADDR`] and addressed here by
+/// offsets from its start. As the main flash, it is erased by rows of 256
+/// bytes and written by pages of 64 bytes, but the CPU does not stall on
+/// flash accesses while it is being erased or written. It has its own set of
+/// lock regions, controlled by the `eeprom` fuse of the user row.
+pub struct RwwEeprom<'a> {
+    nvm: &'a mut Nvm,
+    size: u32,
+}
+
+impl RwwEeprom<'_> {
+    /// Size of the section in bytes
+    #[inline]
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    #[inline]
+    fn check_range(&self, offset: u32, length: u32) -> Result<()> {
+        match offset.checked_add(length) {
+            Some(end) if end <= self.size => Ok(()),
+            _ => Err(Error::NonFlash),
+        }
+    }
+
+    /// Read bytes from the section, starting at `offset`
+    #[inline]
+    pub fn read(&self, offset: u32, buffer: &mut [u8]) -> Result<()> {
+        self.check_range(offset, buffer.len() as u32)?;
+        let base = (Nvm::RWW_EEPROM_ADDR + offset) as *const u8;
+        for (i, byte) in buffer.iter_mut().enumerate() {
+            // Safety: the range was checked to be within the section
+            *byte = unsafe { base.add(i).read_volatile() };
+        }
+        Ok(())
+    }
+
+    /// Erase `rows` rows of the section, starting from the row containing
+    /// `offset`
+    #[inline]
+    pub fn erase_rows(&mut self, offset: u32, rows: u32) -> Result<()> {
+        let offset = offset - offset % ROWSIZE;
+        self.check_range(offset, rows * ROWSIZE)?;
+        // Safety: the range was checked to be within the section
+        unsafe {
+            self.nvm
+                .erase(Section::RwwEeprom, Nvm::RWW_EEPROM_ADDR + offset, rows)
+        }
+    }
+
+    /// Write words to the section, starting at `offset`
+    ///
+    /// `offset` has to be 4 bytes aligned. The area has to be erased
+    /// beforehand.
+    #[inline]
+    pub fn write(&mut self, offset: u32, data: &[u32]) -> Result<()> {
+        let words = data.len() as u32;
+        self.check_range(offset, words * 4)?;
+        // Safety: the range was checked to be within the section
+        unsafe {
+            self.nvm.write(
+                Section::RwwEeprom,
+                Nvm::RWW_EEPROM_ADDR + offset,
+                data.as_ptr() as u32,
+                words,
+            )
+        }
+    }
+}
+
+/// The outcome of [`Nvm::modify_user_row`]
+#[derive(Copy, Clone, Debug)]
+pub enum UserRowStatus {
+    /// User row has been updated
+    Updated,
+    /// Update has been skipped; expected value is already present.
+    Skipped,
+}
+
+/// Type alias to the user row with a concrete underlying storage type
+pub type UserRow = RawUserRow<[u8; ROWSIZE as usize]>;
+
+bitfield! {
+    /// Raw user row POD struct that exposes bitfields via methods
+    #[derive(Clone, PartialEq, Eq)]
+    pub struct RawUserRow([u8]);
+    impl Debug;
+    u8;
+    /// Access the `bootprot` field
+    pub bootprot, set_bootprot: 2, 0;
+    /// Access the `reserved_0` field
+    pub reserved_0, set_reserved_0: 3;
+    /// Access the `eeprom` field
+    pub eeprom, set_eeprom: 6, 4;
+    /// Access the `reserved_1` field
+    pub reserved_1, set_reserved_1: 7;
+    /// Access the `bod33_level` field
+    pub bod33_level, set_bod33_level: 13, 8;
+    /// Access the `bod33_enable` field
+    pub bod33_enable, set_bod33_enable: 14;
+    /// Access the `bod33_action` field
+    pub bod33_action, set_bod33_action: 16, 15;
+    /// Access the `reserved_2` field
+    pub reserved_2, set_reserved_2: 24, 17;
+    /// Access the `wdt_enable` field
+    pub wdt_enable, set_wdt_enable: 25;
+    /// Access the `wdt_always_on` field
+    pub wdt_always_on, set_wdt_always_on: 26;
+    /// Access the `wdt_period` field
+    pub wdt_period, set_wdt_period: 30, 27;
+    /// Access the `wdt_window` field
+    pub wdt_window, set_wdt_window: 34, 31;
+    /// Access the `wdt_ewoffset` field
+    pub wdt_ewoffset, set_wdt_ewoffset: 38, 35;
+    /// Access the `wdt_wen` field
+    pub wdt_wen, set_wdt_wen: 39;
+    /// Access the `bod33_hysteresis` field
+    pub bod33_hysteresis, set_bod33_hysteresis: 40;
+    /// Access the `reserved_3` field
+    pub reserved_3, set_reserved_3: 47, 41;
+    /// Access the `nvm_locks` field
+    pub u16, nvm_locks, set_nvm_locks: 63, 48;
+}
@@ -0,0 +1,511 @@
+//! # Fuse configuration
+//!
+//! The fuses of the device (boot protection, EEPROM emulation, BOD33 and
+//! watchdog defaults, region locks) are loaded from the NVM user page, also
+//! known as user row, on reset. [`Fuses`] is a typed model of these fields,
+//! that can be read with [`Nvm::fuses`] and programmed with
+//! [`Nvm::program_fuses`].
+//!
+//! Programming performs a read-modify-write of the user page: only the fields
+//! described by [`Fuses`] are modified, so factory calibration and reserved
+//! bits are preserved. Values that cannot be encoded in the fuses, or
+//! combinations that do not make sense, are refused with a [`FuseError`]
+//! before anything is erased.
+//!
+//! [`Fuses`] implements [`Debug`] and [`PartialEq`], so the current and the
+//! requested configuration can be compared and logged before programming.
+
+use atsamd_hal_macros::{hal_cfg, hal_macro_helper};
+
+use super::{Error, Nvm};
+#[hal_cfg(any("nvmctrl-d11", "nvmctrl-d21"))]
+use super::{UserRow, UserRowStatus, retrieve_flash_size};
+#[hal_cfg("nvmctrl-d5x")]
+use super::{Userpage, UserpageStatus};
+use crate::watchdog::WatchdogTimeout;
+
+/// Errors of the fuse configuration
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FuseError {
+    /// A field holds a value that cannot be represented in the fuses
+    InvalidValue {
+        /// Name of the field
+        field: &'static str,
+        /// Offending value
+        value: u32,
+    },
+    /// The watchdog is configured as always-on, but not enabled
+    AlwaysOnWithoutEnable,
+    /// The watchdog early warning happens after the watchdog timeout
+    EarlyWarningAfterTimeout,
+    /// The boot protected area and the EEPROM emulation area overlap
+    ReservedAreasOverlap,
+    /// Programming the user page failed
+    Nvm(Error),
+}
+
+impl From<Error> for FuseError {
+    fn from(value: Error) -> Self {
+        Self::Nvm(value)
+    }
+}
+
+/// Fuse result type
+pub type Result<T> = core::result::Result<T, FuseError>;
+
+#[inline]
+fn invalid<T>(field: &'static str, value: impl Into<u32>) -> Result<T> {
+    Err(FuseError::InvalidValue {
+        field,
+        value: value.into(),
+    })
+}
+
+/// Watchdog configuration loaded on reset
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WatchdogFuses {
+    /// Watchdog is enabled on reset
+    pub enable: bool,
+    /// Watchdog cannot be disabled until the next power cycle
+    pub always_on: bool,
+    /// Window mode is enabled
+    pub window_mode: bool,
+    /// Time-out period
+    pub period: WatchdogTimeout,
+    /// Closed window period, in window mode
+    pub window: WatchdogTimeout,
+    /// Offset of the early warning interrupt from the start of the period
+    pub early_warning_offset: WatchdogTimeout,
+}
+
+impl WatchdogFuses {
+    fn validate(&self) -> Result<()> {
+        if self.always_on && !self.enable {
+            return Err(FuseError::AlwaysOnWithoutEnable);
+        }
+        if !self.window_mode && self.early_warning_offset as u8 >= self.period as u8 {
+            return Err(FuseError::EarlyWarningAfterTimeout);
+        }
+        Ok(())
+    }
+}
+
+/// Decode a watchdog period field
+fn watchdog_timeout(field: &'static str, bits: u8) -> Result<WatchdogTimeout> {
+    use WatchdogTimeout::*;
+    Ok(match bits {
+        0 => Cycles8,
+        1 => Cycles16,
+        2 => Cycles32,
+        3 => Cycles64,
+        4 => Cycles128,
+        5 => Cycles256,
+        6 => Cycles512,
+        7 => Cycles1K,
+        8 => Cycles2K,
+        9 => Cycles4K,
+        10 => Cycles8K,
+        11 => Cycles16K,
+        _ => return invalid(field, bits),
+    })
+}
+
+/// Action taken by BOD33 when the supply drops below its level
+#[hal_macro_helper]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Bod33Action {
+    /// No action
+    None,
+    /// The device is reset
+    Reset,
+    /// An interrupt is raised
+    Interrupt,
+    /// The device switches to the backup domain
+    #[hal_cfg("nvmctrl-d5x")]
+    Backup,
+}
+
+#[hal_macro_helper]
+impl Bod33Action {
+    fn from_bits(bits: u8) -> Result<Self> {
+        Ok(match bits {
+            0 => Self::None,
+            1 => Self::Reset,
+            2 => Self::Interrupt,
+            #[hal_cfg("nvmctrl-d5x")]
+            3 => Self::Backup,
+            _ => return invalid("bod33.action", bits),
+        })
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Reset => 1,
+            Self::Interrupt => 2,
+            #[hal_cfg("nvmctrl-d5x")]
+            Self::Backup => 3,
+        }
+    }
+}
+
+/// BOD33 configuration loaded on reset
+#[hal_cfg("nvmctrl-d5x")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Bod33Fuses {
+    /// BOD33 is enabled on reset
+    pub enable: bool,
+    /// Threshold level, `0..=255`. See the datasheet for the voltages.
+    pub level: u8,
+    /// Action taken below the threshold
+    pub action: Bod33Action,
+    /// Hysteresis level, `0..=15`
+    pub hysteresis: u8,
+}
+
+/// BOD33 configuration loaded on reset
+#[hal_cfg(any("nvmctrl-d11", "nvmctrl-d21"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Bod33Fuses {
+    /// BOD33 is enabled on reset
+    pub enable: bool,
+    /// Threshold level, `0..=63`. See the datasheet for the voltages.
+    pub level: u8,
+    /// Action taken below the threshold
+    pub action: Bod33Action,
+    /// Hysteresis is enabled
+    pub hysteresis: bool,
+}
+
+/// Typed fuse configuration of the user page
+#[hal_cfg("nvmctrl-d5x")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Fuses {
+    /// Size in bytes of the boot protected area at the start of the flash,
+    /// a multiple of 8 KiB up to 120 KiB
+    pub boot_protection_size: u32,
+    /// Number of flash blocks per bank allocated to SmartEEPROM, `0..=10`.
+    /// 0 disables SmartEEPROM.
+    pub smart_eeprom_blocks: u8,
+    /// Size in bytes of a SmartEEPROM virtual page, a power of two from 4 to
+    /// 512
+    pub smart_eeprom_page_size: u16,
+    /// RAM ECC is disabled
+    pub ram_ecc_disable: bool,
+    /// BOD33 configuration
+    pub bod33: Bod33Fuses,
+    /// Watchdog configuration
+    pub watchdog: WatchdogFuses,
+    /// Region lock mask loaded on reset, as in [`Nvm::region_lock`]
+    pub region_locks: u32,
+}
+
+#[hal_cfg("nvmctrl-d5x")]
+impl Fuses {
+    /// Decode the fuses from a user page
+    pub fn from_userpage(userpage: &Userpage) -> Result<Self> {
+        let sblk = userpage.see_sblk();
+        if sblk > 10 {
+            return invalid("smart_eeprom_blocks", sblk);
+        }
+        Ok(Self {
+            boot_protection_size: (15 - userpage.nvm_bootloader_size() as u32) * 8192,
+            smart_eeprom_blocks: sblk,
+            smart_eeprom_page_size: 4 << userpage.see_psz(),
+            ram_ecc_disable: userpage.ram_ecc_disable(),
+            bod33: Bod33Fuses {
+                enable: !userpage.bod33_disable(),
+                level: userpage.bod33_level(),
+                action: Bod33Action::from_bits(userpage.bod33_action())?,
+                hysteresis: userpage.bod33_hysteresis(),
+            },
+            watchdog: WatchdogFuses {
+                enable: userpage.wdt_enable(),
+                always_on: userpage.wdt_always_on(),
+                window_mode: userpage.wdt_wen(),
+                period: watchdog_timeout("watchdog.period", userpage.wdt_period())?,
+                window: watchdog_timeout("watchdog.window", userpage.wdt_window())?,
+                early_warning_offset: watchdog_timeout(
+                    "watchdog.early_warning_offset",
+                    userpage.wdt_ewoffset(),
+                )?,
+            },
+            region_locks: userpage.nvm_locks(),
+        })
+    }
+
+    /// Check that the configuration can be encoded in the fuses
+    pub fn validate(&self) -> Result<()> {
+        if self.boot_protection_size % 8192 != 0 || self.boot_protection_size > 15 * 8192 {
+            return invalid("boot_protection_size", self.boot_protection_size);
+        }
+        if self.smart_eeprom_blocks > 10 {
+            return invalid("smart_eeprom_blocks", self.smart_eeprom_blocks);
+        }
+        if !self.smart_eeprom_page_size.is_power_of_two()
+            || !(4..=512).contains(&self.smart_eeprom_page_size)
+        {
+            return invalid("smart_eeprom_page_size", self.smart_eeprom_page_size);
+        }
+        if self.bod33.hysteresis > 15 {
+            return invalid("bod33.hysteresis", self.bod33.hysteresis);
+        }
+        self.watchdog.validate()
+    }
+
+    /// Encode the fuses into a user page, leaving the other fields untouched
+    pub fn apply(&self, userpage: &mut Userpage) -> Result<()> {
+        self.validate()?;
+        userpage.set_nvm_bootloader_size(15 - (self.boot_protection_size / 8192) as u8);
+        userpage.set_see_sblk(self.smart_eeprom_blocks);
+        userpage.set_see_psz(self.smart_eeprom_page_size.trailing_zeros() as u8 - 2);
+        userpage.set_ram_ecc_disable(self.ram_ecc_disable);
+        userpage.set_bod33_disable(!self.bod33.enable);
+        userpage.set_bod33_level(self.bod33.level);
+        userpage.set_bod33_action(self.bod33.action.bits());
+        userpage.set_bod33_hysteresis(self.bod33.hysteresis);
+        userpage.set_wdt_enable(self.watchdog.enable);
+        userpage.set_wdt_always_on(self.watchdog.always_on);
+        userpage.set_wdt_wen(self.watchdog.window_mode);
+        userpage.set_wdt_period(self.watchdog.period as u8);
+        userpage.set_wdt_window(self.watchdog.window as u8);
+        userpage.set_wdt_ewoffset(self.watchdog.early_warning_offset as u8);
+        userpage.set_nvm_locks(self.region_locks);
+        Ok(())
+    }
+}
+
+#[hal_cfg("nvmctrl-d5x")]
+impl Nvm {
+    /// Read the fuse configuration from the user page
+    #[inline]
+    pub fn fuses(&self) -> Result<Fuses> {
+        Fuses::from_userpage(&self.read_userpage())
+    }
+
+    /// Program the fuse configuration into the user page
+    ///
+    /// The configuration is validated first. Fields of the user page not
+    /// described by [`Fuses`] are preserved. Programming is skipped if the
+    /// fuses already hold the configuration. New values take effect after
+    /// the next reset.
+    ///
+    /// # Safety
+    ///
+    /// Power loss between the erase and the write of the user page will
+    /// result in *data loss*, including factory calibration settings. See
+    /// [`Nvm::modify_userpage`].
+    #[inline]
+    pub unsafe fn program_fuses(&mut self, fuses: &Fuses) -> Result<UserpageStatus> {
+        let mut userpage = self.read_userpage();
+        fuses.apply(&mut userpage)?;
+        // Safety: requirements bubbled up to the method signature
+        Ok(unsafe { self.modify_userpage(|current| *current = userpage)? })
+    }
+}
+
+/// Typed fuse configuration of the user row
+#[hal_cfg(any("nvmctrl-d11", "nvmctrl-d21"))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Fuses {
+    /// Size in bytes of the boot protected area at the start of the flash:
+    /// 0, or a power of two from 512 bytes to 32 KiB
+    pub boot_protection_size: u32,
+    /// Size in bytes of the EEPROM emulation area at the end of the flash:
+    /// 0, or a power of two from 256 bytes to 16 KiB
+    pub eeprom_size: u32,
+    /// BOD33 configuration
+    pub bod33: Bod33Fuses,
+    /// Watchdog configuration
+    pub watchdog: WatchdogFuses,
+    /// Region lock mask loaded on reset, as in [`Nvm::region_lock`]
+    pub region_locks: u16,
+}
+
+/// Decode a size field, where 7 stands for 0 and each decrement doubles the
+/// size from `smallest`
+#[hal_cfg(any("nvmctrl-d11", "nvmctrl-d21"))]
+#[inline]
+fn decode_size(bits: u8, smallest: u32) -> u32 {
+    match bits {
+        7 => 0,
+        bits => smallest << (6 - bits),
+    }
+}
+
+/// Encode a size field, see [`decode_size`]
+#[hal_cfg(any("nvmctrl-d11", "nvmctrl-d21"))]
+#[inline]
+fn encode_size(field: &'static str, size: u32, smallest: u32) -> Result<u8> {
+    match size {
+        0 => Ok(7),
+        size if size.is_power_of_two() && (smallest..=smallest << 6).contains(&size) => {
+            Ok(6 - (size / smallest).trailing_zeros() as u8)
+        }
+        size => invalid(field, size),
+    }
+}
+
+#[hal_cfg(any("nvmctrl-d11", "nvmctrl-d21"))]
+impl Fuses {
+    /// Decode the fuses from a user row
+    pub fn from_user_row(user_row: &UserRow) -> Result<Self> {
+        Ok(Self {
+            boot_protection_size: decode_size(user_row.bootprot(), 512),
+            eeprom_size: decode_size(user_row.eeprom(), 256),
+            bod33: Bod33Fuses {
+                enable: user_row.bod33_enable(),
+                level: user_row.bod33_level(),
+                action: Bod33Action::from_bits(user_row.bod33_action())?,
+                hysteresis: user_row.bod33_hysteresis(),
+            },
+            watchdog: WatchdogFuses {
+                enable: user_row.wdt_enable(),
+                always_on: user_row.wdt_always_on(),
+                window_mode: user_row.wdt_wen(),
+                period: watchdog_timeout("watchdog.period", user_row.wdt_period())?,
+                window: watchdog_timeout("watchdog.window", user_row.wdt_window())?,
+                early_warning_offset: watchdog_timeout(
+                    "watchdog.early_warning_offset",
+                    user_row.wdt_ewoffset(),
+                )?,
+            },
+            region_locks: user_row.nvm_locks(),
+        })
+    }
+
+    /// Check that the configuration can be encoded in the fuses
+    ///
+    /// Whether the boot protected and EEPROM emulation areas fit in the flash
+    /// is only checked by [`Nvm::program_fuses`], as it depends on the device.
+    pub fn validate(&self) -> Result<()> {
+        encode_size("boot_protection_size", self.boot_protection_size, 512)?;
+        encode_size("eeprom_size", self.eeprom_size, 256)?;
+        if self.bod33.level > 63 {
+            return invalid("bod33.level", self.bod33.level);
+        }
+        self.watchdog.validate()
+    }
+
+    /// Encode the fuses into a user row, leaving the other fields untouched
+    pub fn apply(&self, user_row: &mut UserRow) -> Result<()> {
+        self.validate()?;
+        user_row.set_bootprot(encode_size(
+            "boot_protection_size",
+            self.boot_protection_size,
+            512,
+        )?);
+        user_row.set_eeprom(encode_size("eeprom_size", self.eeprom_size, 256)?);
+        user_row.set_bod33_enable(self.bod33.enable);
+        user_row.set_bod33_level(self.bod33.level);
+        user_row.set_bod33_action(self.bod33.action.bits());
+        user_row.set_bod33_hysteresis(self.bod33.hysteresis);
+        user_row.set_wdt_enable(self.watchdog.enable);
+        user_row.set_wdt_always_on(self.watchdog.always_on);
+        user_row.set_wdt_wen(self.watchdog.window_mode);
+        user_row.set_wdt_period(self.watchdog.period as u8);
+        user_row.set_wdt_window(self.watchdog.window as u8);
+        user_row.set_wdt_ewoffset(self.watchdog.early_warning_offset as u8);
+        user_row.set_nvm_locks(self.region_locks);
+        Ok(())
+    }
+}
+
+#[hal_cfg(any("nvmctrl-d11", "nvmctrl-d21"))]
+impl Nvm {
+    /// Read the fuse configuration from the user row
+    #[inline]
+    pub fn fuses(&self) -> Result<Fuses> {
+        Fuses::from_user_row(&self.read_user_row())
+    }
+
+    /// Program the fuse configuration into the user row
+    ///
+    /// The configuration is validated first, including that the boot
+    /// protected and EEPROM emulation areas fit in the flash of the device.
+    /// Fields of the user row not described by [`Fuses`] are preserved.
+    /// Programming is skipped if the fuses already hold the configuration.
+    /// New values take effect after the next reset.
+    ///
+    /// # Safety
+    ///
+    /// Power loss between the erase and the write of the user row will result
+    /// in *data loss*. See [`Nvm::modify_user_row`].
+    #[inline]
+    pub unsafe fn program_fuses(&mut self, fuses: &Fuses) -> Result<UserRowStatus> {
+        if fuses.boot_protection_size + fuses.eeprom_size > retrieve_flash_size() {
+            return Err(FuseError::ReservedAreasOverlap);
+        }
+        let mut user_row = self.read_user_row();
+        fuses.apply(&mut user_row)?;
+        // Safety: requirements bubbled up to the method signature
+        Ok(unsafe { self.modify_user_row(|current| *current = user_row)? })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[hal_cfg("nvmctrl-d5x")]
+    #[test]
+    fn fuses_round_trip_preserves_calibration() {
+        let mut userpage = super::super::RawUserpage([0xff_u8; 512]);
+        userpage.set_bod12_calibration_parameters(0x2aa);
+        userpage.set_wdt_period(11);
+        userpage.set_wdt_window(11);
+        userpage.set_wdt_ewoffset(10);
+        userpage.set_bod33_action(1);
+        userpage.set_see_sblk(0);
+        let mut fuses = Fuses::from_userpage(&userpage).unwrap();
+        fuses.boot_protection_size = 16384;
+        fuses.smart_eeprom_blocks = 1;
+        fuses.smart_eeprom_page_size = 32;
+        fuses.apply(&mut userpage).unwrap();
+        assert_eq!(Fuses::from_userpage(&userpage).unwrap(), fuses);
+        assert_eq!(userpage.bod12_calibration_parameters(), 0x2aa);
+        assert_eq!(userpage.nvm_bootloader_size(), 13);
+        assert_eq!(userpage.see_psz(), 3);
+    }
+
+    #[hal_cfg(any("nvmctrl-d11", "nvmctrl-d21"))]
+    #[test]
+    fn sizes_are_encoded() {
+        assert_eq!(encode_size("", 0, 512), Ok(7));
+        assert_eq!(encode_size("", 512, 512), Ok(6));
+        assert_eq!(encode_size("", 32768, 512), Ok(0));
+        assert!(encode_size("", 1024 + 512, 512).is_err());
+        assert!(encode_size("", 65536, 512).is_err());
+        for bits in 0..=7 {
+            let size = decode_size(bits, 256);
+            assert_eq!(encode_size("", size, 256), Ok(bits));
+        }
+    }
+
+    #[test]
+    fn watchdog_combinations_are_checked() {
+        let mut watchdog = WatchdogFuses {
+            enable: false,
+            always_on: true,
+            window_mode: false,
+            period: WatchdogTimeout::Cycles1K,
+            window: WatchdogTimeout::Cycles1K,
+            early_warning_offset: WatchdogTimeout::Cycles512,
+        };
+        assert_eq!(watchdog.validate(), Err(FuseError::AlwaysOnWithoutEnable));
+        watchdog.enable = true;
+        assert_eq!(watchdog.validate(), Ok(()));
+        watchdog.early_warning_offset = WatchdogTimeout::Cycles1K;
+        assert_eq!(
+            watchdog.validate(),
+            Err(FuseError::EarlyWarningAfterTimeout)
+        );
+    }
+}
@@ -18,9 +18,18 @@
 //! Module features:
 //! - Erase & write over non-volatile memory in a device.
 //! - Swap banks
+//! - Typed fuse configuration (More in [`fuses`] module)
+//! - Dual-bank firmware update with rollback (More in [`update`] module)
+//! - NOR flash storage in a flash region (More in [`storage`] module)
 #![warn(missing_docs)]
 
+pub mod fuses;
 pub mod smart_eeprom;
+pub mod storage;
+pub mod update;
+
+pub use fuses::Fuses;
+pub use storage::FlashRegion;
 
 use crate::pac::Nvmctrl;
 pub use crate::pac::nvmctrl::ctrla::Prmselect;
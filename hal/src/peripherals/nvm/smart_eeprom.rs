@@ -21,17 +21,32 @@
 //! populated from proper bits in NVM controller user page on power-on-reset. By
 //! default, `SBLK` property is set to `0`, effectively disabling SmartEEPROM.
 //!
-//! One of possible safe ways to change user page content is to use `OpenOCD`
-//! custom commands. `atsame5x`'s `OpenOCD` driver supports `atsame5 userpage`
+//! These bits can be programmed with [`Nvm::configure_smart_eeprom`].
+//! Alternatively, `atsame5x`'s `OpenOCD` driver supports `atsame5 userpage`
 //! command. To access it from GDB, it has to be preceded with a `monitor`
 //! clause.
 //!
 //! To access [`SmartEeprom`] struct, call [`Nvm::smart_eeprom`] method to
 //! retrieve its instance.
+//!
+//! Endurance:
+//! By default, every write is committed to the flash immediately. In
+//! [`WriteMode::Buffered`], writes are gathered in the page buffer and only
+//! committed when another page is written or on [`SmartEeprom::flush`],
+//! which spares flash cycles for frequently updated data. Each time the
+//! active sector is full, its content is reallocated to the other sector,
+//! which is then erased. [`SmartEeprom::status`] and [`WearCounters`] allow
+//! to keep track of these reallocations.
+//!
+//! [`SmartEeprom`] implements the [`embedded_storage`] `ReadStorage` trait,
+//! and the `Storage` trait as well when unlocked.
 
 use core::marker::PhantomData;
 
-use super::Nvm;
+use embedded_storage::{ReadStorage, Storage};
+
+use super::fuses;
+use super::{Nvm, UserpageStatus};
 use crate::pac::{Nvmctrl, nvmctrl::ctrlb::Cmdselect};
 use crate::typelevel::Sealed;
 
@@ -67,9 +82,11 @@ pub enum SmartEepromRetrievalFailure {
     /// SmartEEPROM is disabled and user page is misconfigured. [`More details
     /// in module-level documentation`](self).
     Disabled,
-    /// Support for disabled automatic page reallocation is not implemented.
+    /// Formerly returned when automatic page reallocation was disabled.
+    #[deprecated(note = "never returned, see `SmartEeprom::automatic_reallocation`")]
     DisabledAutomaticPageReallocationNotSupported,
-    /// Support for buffered writes to NVM is not implemented.
+    /// Formerly returned when buffered writes were enabled.
+    #[deprecated(note = "never returned, see `SmartEeprom::write_mode`")]
     BufferedWritesNotSupported,
     /// `SBLK` must be in range `1..=10`. `SBLK` is represented by 4 bits in a
     /// user page which means that it can be between `0` and `15`. Documentation
@@ -81,6 +98,78 @@ pub enum SmartEepromRetrievalFailure {
     },
 }
 
+/// Enum representing possible failure modes of SmartEEPROM accesses through
+/// the [`embedded_storage`] traits.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SmartEepromAccessFailure {
+    /// Accessed range exceeds the SmartEEPROM virtual size.
+    OutOfBounds,
+}
+
+/// Write mode of SmartEEPROM
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WriteMode {
+    /// Every write is committed to the flash immediately
+    Unbuffered,
+    /// Writes are gathered in the page buffer, and committed to the flash
+    /// when a write targets another page or on [`SmartEeprom::flush`]
+    Buffered,
+}
+
+/// SmartEEPROM sector, each made of `SBLK` flash blocks
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Sector {
+    /// Sector 0
+    Sector0,
+    /// Sector 1
+    Sector1,
+}
+
+/// Snapshot of the SmartEEPROM status, read from `Nvmctrl.SEESTAT`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SmartEepromStatus {
+    /// Sector currently holding the data
+    pub active_sector: Sector,
+    /// Page buffer holds data not yet committed to the flash
+    pub page_buffer_loaded: bool,
+    /// SmartEEPROM is busy
+    pub busy: bool,
+    /// SmartEEPROM section is locked
+    pub locked: bool,
+    /// SmartEEPROM register address space is locked
+    pub register_locked: bool,
+    /// Number of flash blocks per sector (`SBLK`)
+    pub blocks: u8,
+    /// Size of a virtual page in bytes (`PSZ`)
+    pub page_size: u16,
+}
+
+/// Wear counters of SmartEEPROM
+///
+/// The hardware does not count reallocations, so the counters are updated by
+/// calling [`SmartEeprom::update_wear`] regularly, at least once per
+/// reallocation. The fields are public so that the counters can be persisted
+/// and restored.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WearCounters {
+    /// Number of observed sector reallocations
+    pub reallocations: u32,
+    /// Number of flash blocks erased by the reallocations
+    pub erased_blocks: u32,
+    /// Number of updates that found the SmartEEPROM overflow flag set, i.e.
+    /// that saw at least one write refused since the previous update because
+    /// the active sector was full while automatic reallocation was disabled.
+    /// The hardware does not count the refused writes themselves.
+    pub overflows: u32,
+    /// Active sector at the last update
+    pub last_sector: Option<Sector>,
+}
+
 /// Enum encapsulating different modes SmartEEPROM can be in.
 pub enum SmartEepromMode<'a> {
     /// SmartEEPROM is locked
@@ -106,12 +195,6 @@ impl<'a> SmartEepromMode<'a> {
     pub(super) fn retrieve(nvm: &'a mut Nvm) -> Result<'a> {
         use SmartEepromMode as Mode;
         use SmartEepromRetrievalFailure::*;
-        if nvm.nvm.seecfg().read().aprdis().bit_is_set() {
-            return Err(DisabledAutomaticPageReallocationNotSupported);
-        }
-        if nvm.nvm.seecfg().read().wmode().is_buffered() {
-            return Err(BufferedWritesNotSupported);
-        }
         let sblk = nvm.nvm.seestat().read().sblk().bits() as u32;
         let psz = nvm.nvm.seestat().read().psz().bits() as u32;
         let virtual_size = match (sblk, psz) {
@@ -199,6 +282,122 @@ impl<'a, T: SmartEepromState> SmartEeprom<'a, T> {
             iter: unsafe { self.get_slice().iter() },
         }
     }
+
+    /// Returns the current status of SmartEEPROM.
+    pub fn status(&self) -> SmartEepromStatus {
+        let seestat = self.nvm.nvm.seestat().read();
+        SmartEepromStatus {
+            active_sector: if seestat.asees().bit_is_set() {
+                Sector::Sector1
+            } else {
+                Sector::Sector0
+            },
+            page_buffer_loaded: seestat.load().bit_is_set(),
+            busy: seestat.busy().bit_is_set(),
+            locked: seestat.lock().bit_is_set(),
+            register_locked: seestat.rlock().bit_is_set(),
+            blocks: seestat.sblk().bits(),
+            page_size: 4 << seestat.psz().bits(),
+        }
+    }
+
+    /// Returns the current write mode.
+    pub fn write_mode(&self) -> WriteMode {
+        if self.nvm.nvm.seecfg().read().wmode().is_buffered() {
+            WriteMode::Buffered
+        } else {
+            WriteMode::Unbuffered
+        }
+    }
+
+    /// Returns `true` if full sectors are reallocated automatically.
+    pub fn automatic_reallocation(&self) -> bool {
+        self.nvm.nvm.seecfg().read().aprdis().bit_is_clear()
+    }
+
+    /// Updates `counters` with the reallocations and overflows that happened
+    /// since the last update.
+    pub fn update_wear(&mut self, counters: &mut WearCounters) {
+        let status = self.status();
+        if counters
+            .last_sector
+            .is_some_and(|sector| sector != status.active_sector)
+        {
+            counters.reallocations = counters.reallocations.wrapping_add(1);
+            counters.erased_blocks = counters.erased_blocks.wrapping_add(status.blocks as u32);
+        }
+        counters.last_sector = Some(status.active_sector);
+
+        let intflag = self.nvm.nvm.intflag().read();
+        if intflag.seesovf().bit_is_set() {
+            counters.overflows = counters.overflows.wrapping_add(1);
+        }
+        self.nvm
+            .nvm
+            .intflag()
+            .write(|w| w.seesovf().set_bit().seesfull().set_bit());
+    }
+
+    /// Waits asynchronously until SmartEEPROM is ready to be accessed.
+    ///
+    /// The `NVMCTRL_1` interrupt has to be bound to [`InterruptHandler`]. It
+    /// is enabled in the NVIC by this method.
+    #[cfg(feature = "async")]
+    pub async fn wait_until_ready<I>(&self, _irqs: I)
+    where
+        I: crate::async_hal::interrupts::Binding<
+                crate::async_hal::interrupts::NVMCTRL_1,
+                InterruptHandler,
+            >,
+    {
+        use crate::async_hal::interrupts::{Interrupt, NVMCTRL_1};
+        use core::task::Poll;
+
+        NVMCTRL_1::unpend();
+        unsafe { NVMCTRL_1::enable() };
+        let nvmctrl = &self.nvm.nvm;
+        core::future::poll_fn(|cx| {
+            if nvmctrl.seestat().read().busy().bit_is_clear() {
+                return Poll::Ready(());
+            }
+            SMART_EEPROM_WAKER.register(cx.waker());
+            nvmctrl.intflag().write(|w| w.seewrc().set_bit());
+            nvmctrl.intenset().write(|w| w.seewrc().set_bit());
+            if nvmctrl.seestat().read().busy().bit_is_clear() {
+                nvmctrl.intenclr().write(|w| w.seewrc().set_bit());
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Checks that `length` bytes at `offset` fit in SmartEEPROM.
+    fn check_bounds(
+        &self,
+        offset: u32,
+        length: usize,
+    ) -> core::result::Result<(), SmartEepromAccessFailure> {
+        match (offset as usize).checked_add(length) {
+            Some(end) if end <= self.virtual_size => Ok(()),
+            _ => Err(SmartEepromAccessFailure::OutOfBounds),
+        }
+    }
+}
+
+impl<T: SmartEepromState> ReadStorage for SmartEeprom<'_, T> {
+    type Error = SmartEepromAccessFailure;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> core::result::Result<(), Self::Error> {
+        self.check_bounds(offset, bytes.len())?;
+        self.get(offset as usize, bytes);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.virtual_size
+    }
 }
 
 /// Trait generalizing over primitive types that are permitted to be used as
@@ -250,6 +449,51 @@ impl<'a> SmartEeprom<'a, Unlocked> {
         }
     }
 
+    /// Sets the write mode.
+    ///
+    /// When leaving [`WriteMode::Buffered`], data remaining in the page
+    /// buffer is flushed first.
+    pub fn set_write_mode(&mut self, mode: WriteMode) -> super::Result<()> {
+        if mode == WriteMode::Unbuffered {
+            self.flush()?;
+        }
+        self.nvm.nvm.seecfg().modify(|_, w| match mode {
+            WriteMode::Unbuffered => w.wmode().unbuffered(),
+            WriteMode::Buffered => w.wmode().buffered(),
+        });
+        Ok(())
+    }
+
+    /// Commits the data held in the page buffer to the flash.
+    ///
+    /// This is a no-op in [`WriteMode::Unbuffered`].
+    pub fn flush(&mut self) -> super::Result<()> {
+        wait_if_busy();
+        if self.nvm.nvm.seestat().read().load().bit_is_clear() {
+            return Ok(());
+        }
+        self.nvm.command_sync(Cmdselect::Seeflush)
+    }
+
+    /// Enables or disables the automatic reallocation of full sectors.
+    ///
+    /// With automatic reallocation disabled, writes to a full sector are
+    /// discarded (counted in [`WearCounters::overflows`]) until
+    /// [`Self::reallocate`] is called. This allows to schedule the sector
+    /// erasure at a convenient time.
+    pub fn set_automatic_reallocation(&mut self, enabled: bool) {
+        self.nvm
+            .nvm
+            .seecfg()
+            .modify(|_, w| w.aprdis().bit(!enabled));
+    }
+
+    /// Reallocates the data to the other sector and erases the active one.
+    pub fn reallocate(&mut self) -> super::Result<()> {
+        wait_if_busy();
+        self.nvm.command_sync(Cmdselect::Seeraloc)
+    }
+
     /// Locks SmartEEPROM, allowing only to perform read operations
     pub fn lock(self) -> SmartEeprom<'a, Locked> {
         // Panic case should never happen as we wait for STATUS.READY
@@ -267,6 +511,14 @@ impl<'a> SmartEeprom<'a, Unlocked> {
     }
 }
 
+impl Storage for SmartEeprom<'_, Unlocked> {
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> core::result::Result<(), Self::Error> {
+        self.check_bounds(offset, bytes.len())?;
+        self.set(offset as usize, bytes);
+        Ok(())
+    }
+}
+
 impl<'a> SmartEeprom<'a, Locked> {
     /// Unlocks SmartEEPROM, allowing to perform both read and write operations
     pub fn unlock(self) -> SmartEeprom<'a, Unlocked> {
@@ -285,6 +537,60 @@ impl<'a> SmartEeprom<'a, Locked> {
     }
 }
 
+/// Interrupt handler for the SmartEEPROM interrupt (`NVMCTRL_1`), used by
+/// [`SmartEeprom::wait_until_ready`].
+#[cfg(feature = "async")]
+pub struct InterruptHandler {
+    _private: (),
+}
+
+#[cfg(feature = "async")]
+impl Sealed for InterruptHandler {}
+
+#[cfg(feature = "async")]
+static SMART_EEPROM_WAKER: embassy_sync::waitqueue::AtomicWaker =
+    embassy_sync::waitqueue::AtomicWaker::new();
+
+#[cfg(feature = "async")]
+impl crate::async_hal::interrupts::Handler<crate::async_hal::interrupts::NVMCTRL_1>
+    for InterruptHandler
+{
+    unsafe fn on_interrupt() {
+        let nvmctrl = unsafe { &*Nvmctrl::ptr() };
+        if nvmctrl.intenset().read().seewrc().bit_is_set()
+            && nvmctrl.intflag().read().seewrc().bit_is_set()
+        {
+            nvmctrl.intenclr().write(|w| w.seewrc().set_bit());
+            SMART_EEPROM_WAKER.wake();
+        }
+    }
+}
+
+impl Nvm {
+    /// Configures the SmartEEPROM geometry in the user page.
+    ///
+    /// `blocks` is the number of flash blocks per sector (`SBLK`, `0..=10`,
+    /// 0 disabling SmartEEPROM), and `page_size` the size of a virtual page
+    /// in bytes (`PSZ`, a power of two from 4 to 512). The new geometry takes
+    /// effect after the next reset, and the current SmartEEPROM content is
+    /// lost.
+    ///
+    /// # Safety
+    ///
+    /// See [`Nvm::program_fuses`].
+    pub unsafe fn configure_smart_eeprom(
+        &mut self,
+        blocks: u8,
+        page_size: u16,
+    ) -> fuses::Result<UserpageStatus> {
+        let mut fuses = fuses::Fuses::from_userpage(&self.read_userpage())?;
+        fuses.smart_eeprom_blocks = blocks;
+        fuses.smart_eeprom_page_size = page_size;
+        // Safety: requirements bubbled up to the method signature
+        unsafe { self.program_fuses(&fuses) }
+    }
+}
+
 /// A type representing an immutable iterator over SmartEEPROM address space
 pub struct SmartEepromIter<'a, TP: SmartEepromPointableSize> {
     iter: core::slice::Iter<'a, TP>,
@@ -0,0 +1,223 @@
+//! # Flash storage
+//!
+//! [`FlashRegion`] gives safe access to a portion of the main address space
+//! flash, through the [`embedded_storage`] NOR flash traits. Crates such as
+//! `sequential-storage` or `ekv` can then store data in it.
+//!
+//! The region is taken out of the flash once, with the unsafe
+//! [`Nvm::flash_region`] method. Reads, writes and erasures within the region
+//! are then safe, as the caller has guaranteed that it does not hold the
+//! executed application.
+//!
+//! The asynchronous traits of `embedded-storage-async` are implemented as
+//! well when the `async` feature is enabled. NVM operations stall the CPU
+//! anyway, so these complete without yielding.
+
+use core::ops::Range;
+
+use atsamd_hal_macros::hal_cfg;
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash, check_erase, check_read,
+    check_write,
+};
+
+#[hal_cfg(any("nvmctrl-d11", "nvmctrl-d21"))]
+use super::ROWSIZE;
+#[hal_cfg("nvmctrl-d5x")]
+use super::{BLOCKSIZE, QUADWORDSIZE, WriteGranularity};
+use super::{Error, Nvm, Result, retrieve_flash_size};
+
+/// Erasure granularity of the flash
+#[hal_cfg("nvmctrl-d5x")]
+const ERASE_SIZE: u32 = BLOCKSIZE;
+/// Erasure granularity of the flash
+#[hal_cfg(any("nvmctrl-d11", "nvmctrl-d21"))]
+const ERASE_SIZE: u32 = ROWSIZE;
+
+/// Write granularity of the flash
+///
+/// The flash of SAMx5x devices is ECC protected per quad word, which must
+/// therefore be written at once.
+#[hal_cfg("nvmctrl-d5x")]
+const WRITE_SIZE: u32 = QUADWORDSIZE;
+/// Write granularity of the flash
+#[hal_cfg(any("nvmctrl-d11", "nvmctrl-d21"))]
+const WRITE_SIZE: u32 = 4;
+
+/// Number of words copied to the page buffer per write command
+const CHUNK_WORDS: usize = 16;
+
+impl NorFlashError for Error {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Error::Alignment => NorFlashErrorKind::NotAligned,
+            Error::NonFlash => NorFlashErrorKind::OutOfBounds,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+impl From<NorFlashErrorKind> for Error {
+    fn from(kind: NorFlashErrorKind) -> Self {
+        match kind {
+            NorFlashErrorKind::NotAligned => Error::Alignment,
+            _ => Error::NonFlash,
+        }
+    }
+}
+
+impl Nvm {
+    /// Retrieve a region of the main address space flash memory as NOR flash
+    /// storage
+    ///
+    /// `range` holds flash addresses, and must be aligned to the erase size
+    /// of the flash: rows of 256 bytes on SAMD11/SAMD21, blocks of 8192 bytes
+    /// on SAMx5x.
+    ///
+    /// # Safety
+    ///
+    /// The region must not contain the currently executed application, nor
+    /// any data accessed through other means while the [`FlashRegion`]
+    /// exists.
+    #[inline]
+    pub unsafe fn flash_region(&mut self, range: Range<u32>) -> Result<FlashRegion<'_>> {
+        if range.start > range.end || range.end > retrieve_flash_size() {
+            return Err(Error::NonFlash);
+        }
+        if range.start % ERASE_SIZE != 0 || range.end % ERASE_SIZE != 0 {
+            return Err(Error::Alignment);
+        }
+        Ok(FlashRegion {
+            nvm: self,
+            start: range.start,
+            length: range.end - range.start,
+        })
+    }
+}
+
+/// Region of the main address space flash memory, used as NOR flash storage
+///
+/// Offsets passed to the [`embedded_storage`] traits are relative to the
+/// start of the region. See [`Nvm::flash_region`].
+pub struct FlashRegion<'a> {
+    nvm: &'a mut Nvm,
+    start: u32,
+    length: u32,
+}
+
+impl FlashRegion<'_> {
+    /// Address of the start of the region in flash
+    #[inline]
+    pub fn address(&self) -> u32 {
+        self.start
+    }
+
+    /// Length of the region in bytes
+    #[inline]
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// Write words to the flash at `address`
+    #[hal_cfg("nvmctrl-d5x")]
+    #[inline]
+    fn write_words(&mut self, address: u32, words: &[u32]) -> Result<()> {
+        // Safety: `Nvm::flash_region` guarantees the region does not contain
+        // the executed application
+        unsafe {
+            self.nvm
+                .write_flash_from_slice(address as *mut u32, words, WriteGranularity::QuadWord)
+        }
+    }
+
+    /// Write words to the flash at `address`
+    #[hal_cfg(any("nvmctrl-d11", "nvmctrl-d21"))]
+    #[inline]
+    fn write_words(&mut self, address: u32, words: &[u32]) -> Result<()> {
+        // Safety: `Nvm::flash_region` guarantees the region does not contain
+        // the executed application
+        unsafe { self.nvm.write_flash_from_slice(address as *mut u32, words) }
+    }
+}
+
+impl ErrorType for FlashRegion<'_> {
+    type Error = Error;
+}
+
+impl ReadNorFlash for FlashRegion<'_> {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<()> {
+        check_read(self, offset, bytes.len())?;
+        let base = (self.start + offset) as *const u8;
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            // Safety: the range was checked to be within the region
+            *byte = unsafe { base.add(i).read_volatile() };
+        }
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.length as usize
+    }
+}
+
+impl NorFlash for FlashRegion<'_> {
+    const WRITE_SIZE: usize = WRITE_SIZE as usize;
+    const ERASE_SIZE: usize = ERASE_SIZE as usize;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<()> {
+        check_erase(self, from, to)?;
+        let units = (to - from) / ERASE_SIZE;
+        if units == 0 {
+            return Ok(());
+        }
+        // Safety: `Nvm::flash_region` guarantees the region does not
+        // contain the executed application
+        unsafe { self.nvm.erase_flash((self.start + from) as *mut u32, units) }
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<()> {
+        check_write(self, offset, bytes.len())?;
+        // `bytes` is not necessarily word aligned, so it is copied to the page
+        // buffer through an intermediate buffer
+        let mut address = self.start + offset;
+        let mut words = [0_u32; CHUNK_WORDS];
+        for chunk in bytes.chunks(CHUNK_WORDS * 4) {
+            let count = chunk.len() / 4;
+            for (word, source) in words.iter_mut().zip(chunk.chunks_exact(4)) {
+                *word = u32::from_le_bytes([source[0], source[1], source[2], source[3]]);
+            }
+            self.write_words(address, &words[..count])?;
+            address += chunk.len() as u32;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_storage_async::nor_flash::ReadNorFlash for FlashRegion<'_> {
+    const READ_SIZE: usize = <Self as ReadNorFlash>::READ_SIZE;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<()> {
+        ReadNorFlash::read(self, offset, bytes)
+    }
+
+    fn capacity(&self) -> usize {
+        ReadNorFlash::capacity(self)
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_storage_async::nor_flash::NorFlash for FlashRegion<'_> {
+    const WRITE_SIZE: usize = <Self as NorFlash>::WRITE_SIZE;
+    const ERASE_SIZE: usize = <Self as NorFlash>::ERASE_SIZE;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<()> {
+        NorFlash::erase(self, from, to)
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<()> {
+        NorFlash::write(self, offset, bytes)
+    }
+}
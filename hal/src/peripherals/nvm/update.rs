@@ -0,0 +1,587 @@
+//! # Dual-bank firmware update
+//!
+//! [`DualBankUpdater`] implements the usual A/B update workflow on top of the
+//! bank mechanism of the NVM controller:
+//!
+//! 1. At startup, [`DualBankUpdater::boot_check`] accounts for the boot
+//!    attempt of an image that has not been confirmed yet, and rolls back to
+//!    the previous image once its attempts are exhausted.
+//! 2. Once the application considers itself healthy, it calls
+//!    [`DualBankUpdater::confirm`].
+//! 3. A new image is streamed into the inactive bank with
+//!    [`DualBankUpdater::begin`], [`DualBankUpdater::write`] and
+//!    [`DualBankUpdater::finish`].
+//! 4. The image is verified with [`DualBankUpdater::verify_crc32`] or
+//!    [`DualBankUpdater::verify_signature`].
+//! 5. [`DualBankUpdater::swap`] records a pending boot and swaps the banks.
+//!
+//! The boot state survives resets and bank swaps, as it is kept in either the
+//! user page ([`UserpageBootState`]) or SmartEEPROM
+//! ([`SmartEepromBootState`]). Images that hang rather than crash are only
+//! caught if the watchdog is enabled, so that they get reset and accounted
+//! for again.
+//!
+//! ```no_run
+//! use atsamd_hal::nvm::update::{BootStatus, DualBankUpdater, SmartEepromBootState};
+//! # fn example(nvm: &mut atsamd_hal::nvm::Nvm) -> Result<(), atsamd_hal::nvm::update::Error> {
+//! let mut updater = DualBankUpdater::new(nvm, SmartEepromBootState::new(0));
+//! if let BootStatus::Trial { .. } = updater.boot_check()? {
+//!     // Self-test the new image before confirming it
+//!     updater.confirm()?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use core::convert::Infallible;
+
+use digest::{FixedOutputReset, Reset, Update};
+use embedded_storage::{ReadStorage, Storage};
+
+use super::smart_eeprom::SmartEepromMode;
+use super::{BLOCKSIZE, Bank, Nvm, QUADWORDSIZE, WriteGranularity};
+use crate::dsu::Dsu;
+use crate::pukcc::{EcdsaSignatureVerificationFailure, Pukcc, curves::Curve};
+
+/// Size of the serialized boot state in bytes
+pub const BOOT_STATE_SIZE: usize = 8;
+
+/// Size of [`userpage1`](super::RawUserpage::userpage1_as_slice), the user
+/// page minus the 20 bytes of fuses at its start
+const USERPAGE1_SIZE: usize = core::mem::size_of::<super::Userpage>() - 20;
+
+/// Largest offset of the boot state record within `userpage1`
+const MAX_USERPAGE_OFFSET: usize = USERPAGE1_SIZE - BOOT_STATE_SIZE;
+
+/// Marker identifying a valid boot state record
+const BOOT_STATE_MAGIC: [u8; 4] = *b"ABUP";
+
+/// Errors of the dual-bank update workflow
+#[derive(Debug)]
+pub enum Error {
+    /// NVM operation failed
+    Nvm(super::Error),
+    /// CRC32 computation failed
+    Dsu(crate::dsu::Error),
+    /// Image does not fit in the inactive bank
+    ImageTooLarge,
+    /// More data was written than announced in [`DualBankUpdater::begin`]
+    ImageOverflow,
+    /// Operation requires a completely written image
+    ImageIncomplete,
+    /// CRC32 of the image does not match the expected value
+    ChecksumMismatch {
+        /// Expected CRC32
+        expected: u32,
+        /// CRC32 computed over the inactive bank
+        actual: u32,
+    },
+    /// Signature of the image is invalid
+    Signature(EcdsaSignatureVerificationFailure),
+    /// [`DualBankUpdater::swap`] was called on an image that was not
+    /// verified
+    NotVerified,
+    /// [`DualBankUpdater::swap`] was called with `max_attempts` set to 0
+    InvalidMaxAttempts,
+    /// SmartEEPROM is not available to hold the boot state
+    SmartEepromUnavailable,
+    /// Boot state record does not fit in its storage
+    BootStateOutOfBounds,
+}
+
+impl From<super::Error> for Error {
+    fn from(value: super::Error) -> Self {
+        Self::Nvm(value)
+    }
+}
+
+impl From<crate::dsu::Error> for Error {
+    fn from(value: crate::dsu::Error) -> Self {
+        Self::Dsu(value)
+    }
+}
+
+/// Update result type
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Persistent state of the update workflow
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootState {
+    /// Running image is confirmed
+    Idle,
+    /// Running image has not been confirmed yet
+    Pending {
+        /// Boot attempts made so far
+        attempts: u8,
+        /// Boot attempts allowed before rolling back
+        max_attempts: u8,
+    },
+    /// Previous image failed to boot and the banks were swapped back
+    RolledBack,
+}
+
+impl BootState {
+    /// Serialize the state into a record
+    fn to_bytes(self) -> [u8; BOOT_STATE_SIZE] {
+        let (tag, attempts, max_attempts) = match self {
+            Self::Idle => (0, 0, 0),
+            Self::Pending {
+                attempts,
+                max_attempts,
+            } => (1, attempts, max_attempts),
+            Self::RolledBack => (2, 0, 0),
+        };
+        let [m0, m1, m2, m3] = BOOT_STATE_MAGIC;
+        [
+            m0,
+            m1,
+            m2,
+            m3,
+            tag,
+            attempts,
+            max_attempts,
+            Self::check(tag, attempts, max_attempts),
+        ]
+    }
+
+    /// Deserialize a record. Erased or corrupted records read as
+    /// [`BootState::Idle`].
+    fn from_bytes(bytes: &[u8; BOOT_STATE_SIZE]) -> Self {
+        let [m0, m1, m2, m3, tag, attempts, max_attempts, check] = *bytes;
+        if [m0, m1, m2, m3] != BOOT_STATE_MAGIC || check != Self::check(tag, attempts, max_attempts)
+        {
+            return Self::Idle;
+        }
+        match tag {
+            1 => Self::Pending {
+                attempts,
+                max_attempts,
+            },
+            2 => Self::RolledBack,
+            _ => Self::Idle,
+        }
+    }
+
+    #[inline]
+    fn check(tag: u8, attempts: u8, max_attempts: u8) -> u8 {
+        tag ^ attempts ^ max_attempts ^ 0x5a
+    }
+}
+
+/// Non-volatile storage for the [`BootState`]
+///
+/// The storage must survive bank swaps, so it cannot live in the main address
+/// space flash.
+pub trait BootStateStore {
+    /// Read the boot state record
+    fn load(&mut self, nvm: &mut Nvm) -> Result<[u8; BOOT_STATE_SIZE]>;
+    /// Write the boot state record
+    fn store(&mut self, nvm: &mut Nvm, record: &[u8; BOOT_STATE_SIZE]) -> Result<()>;
+}
+
+/// Boot state kept in the general purpose section of the user page
+pub struct UserpageBootState {
+    offset: usize,
+}
+
+impl UserpageBootState {
+    /// Keep the boot state at `offset` within
+    /// [`userpage1`](super::RawUserpage::userpage1_as_slice)
+    ///
+    /// # Safety
+    ///
+    /// The user page is erased and rewritten whenever the boot state changes,
+    /// which happens on every unconfirmed boot. The requirements of
+    /// [`Nvm::modify_userpage`] apply.
+    #[inline]
+    pub unsafe fn new(offset: usize) -> Self {
+        Self { offset }
+    }
+
+    #[inline]
+    fn range(&self) -> Result<core::ops::Range<usize>> {
+        if self.offset <= MAX_USERPAGE_OFFSET {
+            Ok(self.offset..self.offset + BOOT_STATE_SIZE)
+        } else {
+            Err(Error::BootStateOutOfBounds)
+        }
+    }
+}
+
+impl BootStateStore for UserpageBootState {
+    fn load(&mut self, nvm: &mut Nvm) -> Result<[u8; BOOT_STATE_SIZE]> {
+        let range = self.range()?;
+        let mut record = [0; BOOT_STATE_SIZE];
+        record.copy_from_slice(&nvm.read_userpage().userpage1_as_slice()[range]);
+        Ok(record)
+    }
+
+    fn store(&mut self, nvm: &mut Nvm, record: &[u8; BOOT_STATE_SIZE]) -> Result<()> {
+        let range = self.range()?;
+        // Safety: requirements bubbled up to `UserpageBootState::new`
+        unsafe {
+            nvm.modify_userpage(|userpage| {
+                userpage.userpage1_as_slice_mut()[range].copy_from_slice(record)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Boot state kept in SmartEEPROM
+pub struct SmartEepromBootState {
+    offset: u32,
+}
+
+impl SmartEepromBootState {
+    /// Keep the boot state at byte `offset` of SmartEEPROM
+    #[inline]
+    pub fn new(offset: u32) -> Self {
+        Self { offset }
+    }
+}
+
+impl BootStateStore for SmartEepromBootState {
+    fn load(&mut self, nvm: &mut Nvm) -> Result<[u8; BOOT_STATE_SIZE]> {
+        let mut record = [0; BOOT_STATE_SIZE];
+        let result = match nvm.smart_eeprom() {
+            Ok(SmartEepromMode::Locked(mut eeprom)) => eeprom.read(self.offset, &mut record),
+            Ok(SmartEepromMode::Unlocked(mut eeprom)) => eeprom.read(self.offset, &mut record),
+            Err(_) => return Err(Error::SmartEepromUnavailable),
+        };
+        result.map_err(|_| Error::BootStateOutOfBounds)?;
+        Ok(record)
+    }
+
+    fn store(&mut self, nvm: &mut Nvm, record: &[u8; BOOT_STATE_SIZE]) -> Result<()> {
+        let result = match nvm.smart_eeprom() {
+            Ok(SmartEepromMode::Locked(eeprom)) => {
+                let mut eeprom = eeprom.unlock();
+                let result = eeprom.write(self.offset, record);
+                eeprom.lock();
+                result
+            }
+            Ok(SmartEepromMode::Unlocked(mut eeprom)) => eeprom.write(self.offset, record),
+            Err(_) => return Err(Error::SmartEepromUnavailable),
+        };
+        result.map_err(|_| Error::BootStateOutOfBounds)
+    }
+}
+
+/// Outcome of [`DualBankUpdater::boot_check`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootStatus {
+    /// Running image is confirmed
+    Normal,
+    /// Running image is on trial and has to be confirmed with
+    /// [`DualBankUpdater::confirm`]
+    Trial {
+        /// Boot attempt, starting at 1
+        attempt: u8,
+        /// Boot attempts allowed before rolling back
+        max_attempts: u8,
+    },
+    /// Running image was restored after the new one failed to boot
+    RolledBack,
+}
+
+/// Dual-bank firmware updater
+///
+/// See the [module-level documentation](self).
+pub struct DualBankUpdater<'a, S: BootStateStore> {
+    nvm: &'a mut Nvm,
+    store: S,
+    /// Announced length of the image
+    length: u32,
+    /// Bytes written to the inactive bank so far
+    written: u32,
+    /// Bytes not yet written, as flash is written a quad word at a time
+    buffer: [u8; QUADWORDSIZE as usize],
+    buffered: usize,
+    verified: bool,
+}
+
+impl<'a, S: BootStateStore> DualBankUpdater<'a, S> {
+    /// Create an updater keeping its boot state in `store`
+    #[inline]
+    pub fn new(nvm: &'a mut Nvm, store: S) -> Self {
+        Self {
+            nvm,
+            store,
+            length: 0,
+            written: 0,
+            buffer: [0xff; QUADWORDSIZE as usize],
+            buffered: 0,
+            verified: false,
+        }
+    }
+
+    /// Release the boot state storage
+    #[inline]
+    pub fn free(self) -> S {
+        self.store
+    }
+
+    /// Read the boot state
+    #[inline]
+    pub fn boot_state(&mut self) -> Result<BootState> {
+        Ok(BootState::from_bytes(&self.store.load(self.nvm)?))
+    }
+
+    #[inline]
+    fn set_boot_state(&mut self, state: BootState) -> Result<()> {
+        if self.boot_state()? != state {
+            self.store.store(self.nvm, &state.to_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Account for the current boot
+    ///
+    /// To be called early during startup. If the running image is on trial,
+    /// its boot attempt counter is incremented. Once the attempts are
+    /// exhausted, the banks are swapped back to the previous image and this
+    /// method does not return.
+    pub fn boot_check(&mut self) -> Result<BootStatus> {
+        match self.boot_state()? {
+            BootState::Idle => Ok(BootStatus::Normal),
+            BootState::RolledBack => {
+                self.set_boot_state(BootState::Idle)?;
+                Ok(BootStatus::RolledBack)
+            }
+            BootState::Pending {
+                attempts,
+                max_attempts,
+            } if attempts >= max_attempts => {
+                self.set_boot_state(BootState::RolledBack)?;
+                // Safety: the inactive bank holds the image that was running
+                // before the update, and that was confirmed
+                unsafe { self.nvm.bank_swap() }
+            }
+            BootState::Pending {
+                attempts,
+                max_attempts,
+            } => {
+                let attempt = attempts + 1;
+                self.set_boot_state(BootState::Pending {
+                    attempts: attempt,
+                    max_attempts,
+                })?;
+                Ok(BootStatus::Trial {
+                    attempt,
+                    max_attempts,
+                })
+            }
+        }
+    }
+
+    /// Confirm the running image, so that it is kept on the following boots
+    #[inline]
+    pub fn confirm(&mut self) -> Result<()> {
+        self.set_boot_state(BootState::Idle)
+    }
+
+    /// Largest image that fits in the inactive bank
+    ///
+    /// Blocks at the end of the bank reserved for SmartEEPROM are excluded.
+    #[inline]
+    pub fn capacity(&self) -> u32 {
+        // Safety: read only access to the SmartEEPROM status
+        let sblk = unsafe { self.nvm.registers() }
+            .seestat()
+            .read()
+            .sblk()
+            .bits() as u32;
+        Bank::Inactive.length() - sblk * BLOCKSIZE
+    }
+
+    /// Start the update with an image of `length` bytes
+    ///
+    /// The blocks of the inactive bank covering the image are erased.
+    pub fn begin(&mut self, length: u32) -> Result<()> {
+        if length > self.capacity() {
+            return Err(Error::ImageTooLarge);
+        }
+        self.length = length;
+        self.written = 0;
+        self.buffered = 0;
+        self.verified = false;
+        let blocks = length.div_ceil(BLOCKSIZE);
+        // Safety: the inactive bank never contains the executed application
+        unsafe {
+            self.nvm
+                .erase_flash(Bank::Inactive.address() as *mut u32, blocks)?;
+        }
+        Ok(())
+    }
+
+    /// Append `data` to the image
+    pub fn write(&mut self, mut data: &[u8]) -> Result<()> {
+        let received = self.written + self.buffered as u32 + data.len() as u32;
+        if received > self.length {
+            return Err(Error::ImageOverflow);
+        }
+        self.verified = false;
+        while !data.is_empty() {
+            let count = (self.buffer.len() - self.buffered).min(data.len());
+            self.buffer[self.buffered..self.buffered + count].copy_from_slice(&data[..count]);
+            self.buffered += count;
+            data = &data[count..];
+            if self.buffered == self.buffer.len() {
+                self.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the buffered bytes, padded with `0xFF`, to the flash
+    fn flush(&mut self) -> Result<()> {
+        if self.buffered == 0 {
+            return Ok(());
+        }
+        self.buffer[self.buffered..].fill(0xff);
+        let mut words = [0_u32; (QUADWORDSIZE / 4) as usize];
+        for (word, bytes) in words.iter_mut().zip(self.buffer.chunks_exact(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        let destination = (Bank::Inactive.address() + self.written) as *mut u32;
+        // Safety: the inactive bank never contains the executed application
+        unsafe {
+            self.nvm
+                .write_flash_from_slice(destination, &words, WriteGranularity::QuadWord)?;
+        }
+        self.written += self.buffered as u32;
+        self.buffered = 0;
+        Ok(())
+    }
+
+    /// Complete the image, writing any buffered data
+    pub fn finish(&mut self) -> Result<()> {
+        self.flush()?;
+        if self.written != self.length {
+            return Err(Error::ImageIncomplete);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn check_complete(&self) -> Result<()> {
+        if self.written != self.length || self.buffered != 0 {
+            Err(Error::ImageIncomplete)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Image written to the inactive bank
+    ///
+    /// Only the bytes flushed to the flash are included until
+    /// [`Self::finish`] is called.
+    #[inline]
+    pub fn image(&self) -> &[u8] {
+        // Safety: the inactive bank is mapped in the address space and holds
+        // `written` bytes that are not modified while `self` is borrowed
+        unsafe {
+            core::slice::from_raw_parts(
+                Bank::Inactive.address() as *const u8,
+                self.written as usize,
+            )
+        }
+    }
+
+    /// Verify the image against its CRC32
+    ///
+    /// The CRC32 covers the image padded with `0xFF` to a multiple of 4 bytes.
+    pub fn verify_crc32(&mut self, dsu: &mut Dsu, expected: u32) -> Result<()> {
+        self.check_complete()?;
+        let actual = dsu.crc32(Bank::Inactive.address(), self.length.next_multiple_of(4))?;
+        if actual != expected {
+            return Err(Error::ChecksumMismatch { expected, actual });
+        }
+        self.verified = true;
+        Ok(())
+    }
+
+    /// Verify the ECDSA signature of the image
+    ///
+    /// The image is hashed with `hasher`, which must implement the hash
+    /// function the image was signed with, e.g. a software SHA-256 or the
+    /// [`IcmSha`](crate::icm::IcmSha) hardware hasher. Any state of `hasher`
+    /// is discarded first. See [`Pukcc::zp_ecdsa_verify_signature`] for the
+    /// other parameters.
+    pub fn verify_signature<C, D>(
+        &mut self,
+        pukcc: &Pukcc,
+        hasher: &mut D,
+        signature: &[u8],
+        public_key: &[u8],
+    ) -> Result<()>
+    where
+        C: Curve,
+        D: Update + FixedOutputReset,
+    {
+        self.check_complete()?;
+        Reset::reset(hasher);
+        Update::update(hasher, self.image());
+        let hash = hasher.finalize_fixed_reset();
+        pukcc
+            .zp_ecdsa_verify_signature::<C>(signature, &hash, public_key)
+            .map_err(Error::Signature)?;
+        self.verified = true;
+        Ok(())
+    }
+
+    /// Boot the new image
+    ///
+    /// The image is put on trial for `max_attempts` boots, and the banks are
+    /// swapped. The processor is reset, so this method only returns on
+    /// failure. `max_attempts` must be at least 1.
+    pub fn swap(&mut self, max_attempts: u8) -> Result<Infallible> {
+        if max_attempts == 0 {
+            return Err(Error::InvalidMaxAttempts);
+        }
+        self.check_complete()?;
+        if !self.verified {
+            return Err(Error::NotVerified);
+        }
+        self.set_boot_state(BootState::Pending {
+            attempts: 0,
+            max_attempts,
+        })?;
+        // Safety: the inactive bank holds a complete and verified image
+        unsafe { self.nvm.bank_swap() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boot_state_round_trip() {
+        for state in [
+            BootState::Idle,
+            BootState::Pending {
+                attempts: 2,
+                max_attempts: 3,
+            },
+            BootState::RolledBack,
+        ] {
+            assert_eq!(BootState::from_bytes(&state.to_bytes()), state);
+        }
+    }
+
+    #[test]
+    fn erased_or_corrupted_boot_state_is_idle() {
+        assert_eq!(BootState::from_bytes(&[0xff; 8]), BootState::Idle);
+        let mut record = BootState::Pending {
+            attempts: 1,
+            max_attempts: 3,
+        }
+        .to_bytes();
+        record[5] = 2;
+        assert_eq!(BootState::from_bytes(&record), BootState::Idle);
+    }
+}
@@ -0,0 +1,443 @@
+//! # RustCrypto ECDSA integration
+//!
+//! [`SigningKey`] and [`VerifyingKey`] expose the [`Pukcc`] ECDSA services
+//! through the [`signature`] traits ([`PrehashSigner`], [`PrehashVerifier`],
+//! [`Signer`], [`Verifier`], [`DigestSigner`], [`DigestVerifier`] and
+//! [`Keypair`]), producing and consuming [`Signature`]s of the `ecdsa` crate.
+//! Code written against e.g. `p256::ecdsa` can therefore offload signing and
+//! verification to the hardware.
+//!
+//! RustCrypto curve types are mapped to PUKCC parameter sets from [`curves`]
+//! by [`PukccCurve`]. Implementations are provided behind the following
+//! features:
+//! - `p256`: [`p256::NistP256`] ([`curves::Nist256p`])
+//! - `p384`: [`p384::NistP384`] ([`curves::Nist384p`])
+//! - `k256`: [`k256::Secp256k1`] ([`curves::Secp256k1`]); signatures are
+//!   normalized to low-S form as required by `k256` verifiers
+//!
+//! Signing nonces are drawn from a [`CryptoRng`] owned by the [`SigningKey`],
+//! typically [`Trng`](crate::trng::Trng).
+//!
+//! ```ignore
+//! # use atsamd_hal::pukcc::{Pukcc, ecdsa::*};
+//! # use atsamd_hal::trng::Trng;
+//! # fn f(pukcc: &Pukcc, trng: Trng, secret: &p256::FieldBytes) {
+//! let signing_key = SigningKey::<p256::NistP256, _>::from_bytes(pukcc, secret, trng).unwrap();
+//! let signature: p256::ecdsa::Signature = signing_key.sign(b"message");
+//! signing_key
+//!     .verifying_key()
+//!     .verify(b"message", &signature)
+//!     .unwrap();
+//! # }
+//! ```
+
+use core::cell::RefCell;
+
+use ::ecdsa::hazmat::{DigestPrimitive, bits2field};
+use ::ecdsa::{SignatureBytes, SignatureSize};
+use digest::Digest;
+use elliptic_curve::generic_array::ArrayLength;
+use elliptic_curve::sec1::{Coordinates, EncodedPoint, ModulusSize};
+use elliptic_curve::zeroize::Zeroize;
+use elliptic_curve::{FieldBytes, FieldBytesSize, PrimeCurve, SecretKey};
+use rand_core::CryptoRng;
+
+use super::curves::{self, Curve};
+use super::{
+    KeyGenerationFailure, PointMultiplicationFailure, PublicKeyValidationFailure, Pukcc,
+    scalar_in_range,
+};
+use crate::typelevel::Sealed;
+
+pub use ::ecdsa::Signature;
+pub use signature::hazmat::{PrehashSigner, PrehashVerifier};
+pub use signature::{DigestSigner, DigestVerifier, Keypair, Signer, Verifier};
+
+/// Size of a stack buffer able to hold an uncompressed public key
+const PUBLIC_KEY_BUFFER_LENGTH: usize = 2 * curves::MAX_MOD_LENGTH as usize;
+
+/// A RustCrypto curve that PUKCC provides ECDSA for
+///
+/// `FieldBytesSize` of the curve equals both [`Curve::MOD_LENGTH`] and
+/// [`Curve::SCALAR_LENGTH`] of [`PukccCurve::Curve`].
+pub trait PukccCurve: PrimeCurve + Sealed {
+    /// Matching PUKCC parameter set
+    type Curve: Curve;
+
+    /// Whether produced signatures are normalized to `s <= n / 2`
+    const LOW_S: bool = false;
+}
+
+#[cfg(feature = "p256")]
+impl Sealed for p256::NistP256 {}
+
+#[cfg(feature = "p256")]
+impl PukccCurve for p256::NistP256 {
+    type Curve = curves::Nist256p;
+}
+
+#[cfg(feature = "p384")]
+impl Sealed for p384::NistP384 {}
+
+#[cfg(feature = "p384")]
+impl PukccCurve for p384::NistP384 {
+    type Curve = curves::Nist384p;
+}
+
+#[cfg(feature = "k256")]
+impl Sealed for k256::Secp256k1 {}
+
+#[cfg(feature = "k256")]
+impl PukccCurve for k256::Secp256k1 {
+    type Curve = curves::Secp256k1;
+    const LOW_S: bool = true;
+}
+
+/// ECDSA verifying key backed by PUKCC
+pub struct VerifyingKey<'a, C: PukccCurve> {
+    pukcc: &'a Pukcc,
+    x: FieldBytes<C>,
+    y: FieldBytes<C>,
+}
+
+impl<'a, C> VerifyingKey<'a, C>
+where
+    C: PukccCurve,
+    FieldBytesSize<C>: ModulusSize,
+{
+    /// Create a verifying key from affine coordinates.
+    ///
+    /// The point is validated with [`Pukcc::zp_ec_validate_public_key`].
+    pub fn from_affine_coordinates(
+        pukcc: &'a Pukcc,
+        x: &FieldBytes<C>,
+        y: &FieldBytes<C>,
+    ) -> Result<Self, KeyFailure> {
+        let key = Self {
+            pukcc,
+            x: x.clone(),
+            y: y.clone(),
+        };
+        let mut buffer = [0; PUBLIC_KEY_BUFFER_LENGTH];
+        pukcc.zp_ec_validate_public_key::<C::Curve>(key.public_key(&mut buffer))?;
+        Ok(key)
+    }
+
+    /// Create a verifying key from a SEC1 encoded point.
+    ///
+    /// Only uncompressed points are supported.
+    pub fn from_encoded_point(
+        pukcc: &'a Pukcc,
+        point: &EncodedPoint<C>,
+    ) -> Result<Self, KeyFailure> {
+        match point.coordinates() {
+            Coordinates::Uncompressed { x, y } => Self::from_affine_coordinates(pukcc, x, y),
+            Coordinates::Identity => Err(KeyFailure::InvalidEncoding),
+            Coordinates::Compact { .. } | Coordinates::Compressed { .. } => {
+                Err(KeyFailure::UnsupportedEncoding)
+            }
+        }
+    }
+
+    /// Create a verifying key from SEC1 encoded bytes.
+    ///
+    /// Only uncompressed points are supported.
+    pub fn from_sec1_bytes(pukcc: &'a Pukcc, bytes: &[u8]) -> Result<Self, KeyFailure> {
+        let point =
+            EncodedPoint::<C>::from_bytes(bytes).map_err(|_| KeyFailure::InvalidEncoding)?;
+        Self::from_encoded_point(pukcc, &point)
+    }
+
+    /// Uncompressed SEC1 encoding of the key
+    pub fn to_encoded_point(&self) -> EncodedPoint<C> {
+        EncodedPoint::<C>::from_affine_coordinates(&self.x, &self.y, false)
+    }
+
+    /// `X || Y` layout expected by [`Pukcc`]
+    fn public_key<'b>(&self, buffer: &'b mut [u8]) -> &'b [u8] {
+        let length = self.x.len();
+        buffer[..length].copy_from_slice(&self.x);
+        buffer[length..2 * length].copy_from_slice(&self.y);
+        &buffer[..2 * length]
+    }
+}
+
+impl<C: PukccCurve> Clone for VerifyingKey<'_, C> {
+    fn clone(&self) -> Self {
+        Self {
+            pukcc: self.pukcc,
+            x: self.x.clone(),
+            y: self.y.clone(),
+        }
+    }
+}
+
+impl<C> PrehashVerifier<Signature<C>> for VerifyingKey<'_, C>
+where
+    C: PukccCurve,
+    FieldBytesSize<C>: ModulusSize,
+    SignatureSize<C>: ArrayLength<u8>,
+{
+    fn verify_prehash(
+        &self,
+        prehash: &[u8],
+        signature: &Signature<C>,
+    ) -> Result<(), signature::Error> {
+        let hash = bits2field::<C>(prehash)?;
+        let mut buffer = [0; PUBLIC_KEY_BUFFER_LENGTH];
+        self.pukcc
+            .zp_ecdsa_verify_signature::<C::Curve>(
+                &signature.to_bytes(),
+                &hash,
+                self.public_key(&mut buffer),
+            )
+            .map_err(|_| signature::Error::new())
+    }
+}
+
+impl<C, D> DigestVerifier<D, Signature<C>> for VerifyingKey<'_, C>
+where
+    C: PukccCurve,
+    D: Digest,
+    FieldBytesSize<C>: ModulusSize,
+    SignatureSize<C>: ArrayLength<u8>,
+{
+    fn verify_digest(&self, digest: D, signature: &Signature<C>) -> Result<(), signature::Error> {
+        self.verify_prehash(&digest.finalize(), signature)
+    }
+}
+
+impl<C> Verifier<Signature<C>> for VerifyingKey<'_, C>
+where
+    C: PukccCurve + DigestPrimitive,
+    FieldBytesSize<C>: ModulusSize,
+    SignatureSize<C>: ArrayLength<u8>,
+{
+    fn verify(&self, msg: &[u8], signature: &Signature<C>) -> Result<(), signature::Error> {
+        self.verify_digest(C::Digest::new_with_prefix(msg), signature)
+    }
+}
+
+/// ECDSA signing key backed by PUKCC
+///
+/// Nonces are drawn from the owned entropy source `R`.
+pub struct SigningKey<'a, C: PukccCurve, R> {
+    pukcc: &'a Pukcc,
+    secret: FieldBytes<C>,
+    verifying_key: VerifyingKey<'a, C>,
+    entropy_source: RefCell<R>,
+}
+
+impl<'a, C, R> SigningKey<'a, C, R>
+where
+    C: PukccCurve,
+    R: CryptoRng,
+    FieldBytesSize<C>: ModulusSize,
+{
+    /// Create a signing key from a big endian secret scalar.
+    ///
+    /// The scalar must lie in `[1, n - 1]`. Its public key is derived on
+    /// creation.
+    pub fn from_bytes(
+        pukcc: &'a Pukcc,
+        secret: &FieldBytes<C>,
+        entropy_source: R,
+    ) -> Result<Self, KeyFailure> {
+        if !scalar_in_range(secret, &C::Curve::ORDER_POINT[4..]) {
+            return Err(KeyFailure::ScalarOutOfRange);
+        }
+        let mut buffer = [0; PUBLIC_KEY_BUFFER_LENGTH];
+        let length = secret.len();
+        pukcc.zp_ec_derive_public_key::<C::Curve>(&mut buffer[..2 * length], secret)?;
+        Ok(Self::from_parts(pukcc, secret, &buffer, entropy_source))
+    }
+
+    /// Create a signing key from an `elliptic_curve` secret key
+    pub fn from_secret_key(
+        pukcc: &'a Pukcc,
+        secret_key: &SecretKey<C>,
+        entropy_source: R,
+    ) -> Result<Self, KeyFailure> {
+        Self::from_bytes(pukcc, &secret_key.to_bytes(), entropy_source)
+    }
+
+    /// Generate a fresh key pair with [`Pukcc::zp_ec_generate_key_pair`],
+    /// drawing the secret from `entropy_source`
+    pub fn random(pukcc: &'a Pukcc, mut entropy_source: R) -> Result<Self, KeyFailure> {
+        let mut secret = FieldBytes::<C>::default();
+        let mut buffer = [0; PUBLIC_KEY_BUFFER_LENGTH];
+        let length = secret.len();
+        pukcc.zp_ec_generate_key_pair::<C::Curve>(
+            &mut secret,
+            &mut buffer[..2 * length],
+            &mut entropy_source,
+        )?;
+        let key = Self::from_parts(pukcc, &secret, &buffer, entropy_source);
+        secret.zeroize();
+        Ok(key)
+    }
+
+    fn from_parts(
+        pukcc: &'a Pukcc,
+        secret: &FieldBytes<C>,
+        public_key: &[u8],
+        entropy_source: R,
+    ) -> Self {
+        let length = secret.len();
+        Self {
+            pukcc,
+            secret: secret.clone(),
+            verifying_key: VerifyingKey {
+                pukcc,
+                x: FieldBytes::<C>::clone_from_slice(&public_key[..length]),
+                y: FieldBytes::<C>::clone_from_slice(&public_key[length..2 * length]),
+            },
+            entropy_source: RefCell::new(entropy_source),
+        }
+    }
+
+    /// Big endian secret scalar
+    pub fn to_bytes(&self) -> FieldBytes<C> {
+        self.secret.clone()
+    }
+
+    /// Verifying key of this signing key
+    pub fn verifying_key(&self) -> &VerifyingKey<'a, C> {
+        &self.verifying_key
+    }
+}
+
+impl<C: PukccCurve, R> Drop for SigningKey<'_, C, R> {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+impl<'a, C, R> Keypair for SigningKey<'a, C, R>
+where
+    C: PukccCurve,
+    R: CryptoRng,
+    FieldBytesSize<C>: ModulusSize,
+{
+    type VerifyingKey = VerifyingKey<'a, C>;
+
+    fn verifying_key(&self) -> Self::VerifyingKey {
+        self.verifying_key.clone()
+    }
+}
+
+impl<C, R> PrehashSigner<Signature<C>> for SigningKey<'_, C, R>
+where
+    C: PukccCurve,
+    R: CryptoRng,
+    FieldBytesSize<C>: ModulusSize,
+    SignatureSize<C>: ArrayLength<u8>,
+{
+    fn sign_prehash(&self, prehash: &[u8]) -> Result<Signature<C>, signature::Error> {
+        let hash = bits2field::<C>(prehash)?;
+        let mut signature = SignatureBytes::<C>::default();
+        let mut k = FieldBytes::<C>::default();
+        let result = self.pukcc.zp_ecdsa_sign_with_entropy::<C::Curve>(
+            &mut signature,
+            &hash,
+            &self.secret,
+            &mut k,
+            &mut *self.entropy_source.borrow_mut(),
+        );
+        k.zeroize();
+        result.map_err(|_| signature::Error::new())?;
+        if C::LOW_S {
+            let length = k.len();
+            normalize_s(&mut signature[length..], &C::Curve::ORDER_POINT[4..]);
+        }
+        Signature::from_bytes(&signature)
+    }
+}
+
+impl<C, D, R> DigestSigner<D, Signature<C>> for SigningKey<'_, C, R>
+where
+    C: PukccCurve,
+    D: Digest,
+    R: CryptoRng,
+    FieldBytesSize<C>: ModulusSize,
+    SignatureSize<C>: ArrayLength<u8>,
+{
+    fn try_sign_digest(&self, digest: D) -> Result<Signature<C>, signature::Error> {
+        self.sign_prehash(&digest.finalize())
+    }
+}
+
+impl<C, R> Signer<Signature<C>> for SigningKey<'_, C, R>
+where
+    C: PukccCurve + DigestPrimitive,
+    R: CryptoRng,
+    FieldBytesSize<C>: ModulusSize,
+    SignatureSize<C>: ArrayLength<u8>,
+{
+    fn try_sign(&self, msg: &[u8]) -> Result<Signature<C>, signature::Error> {
+        self.try_sign_digest(C::Digest::new_with_prefix(msg))
+    }
+}
+
+/// An error type representing failure modes of key construction
+#[allow(missing_docs)]
+#[derive(Debug)]
+pub enum KeyFailure {
+    /// Malformed SEC1 encoding or the identity point
+    InvalidEncoding,
+    /// Compressed and compact points are not supported
+    UnsupportedEncoding,
+    /// Secret scalar is zero or not smaller than the curve order
+    ScalarOutOfRange,
+    PublicKeyValidationFailure(PublicKeyValidationFailure),
+    PointMultiplicationFailure(PointMultiplicationFailure),
+    KeyGenerationFailure(KeyGenerationFailure),
+}
+
+impl From<PublicKeyValidationFailure> for KeyFailure {
+    fn from(f: PublicKeyValidationFailure) -> Self {
+        KeyFailure::PublicKeyValidationFailure(f)
+    }
+}
+
+impl From<PointMultiplicationFailure> for KeyFailure {
+    fn from(f: PointMultiplicationFailure) -> Self {
+        KeyFailure::PointMultiplicationFailure(f)
+    }
+}
+
+impl From<KeyGenerationFailure> for KeyFailure {
+    fn from(f: KeyGenerationFailure) -> Self {
+        KeyFailure::KeyGenerationFailure(f)
+    }
+}
+
+/// Replace a big endian `s` with `n - s` when `s > n / 2`
+fn normalize_s(s: &mut [u8], order: &[u8]) {
+    let mut negated = [0; curves::MAX_MOD_LENGTH as usize];
+    let negated = &mut negated[..s.len()];
+    let mut borrow = 0;
+    for ((target, &n), &s) in negated.iter_mut().zip(order).zip(s.iter()).rev() {
+        let difference = i16::from(n) - i16::from(s) - borrow;
+        *target = difference as u8;
+        borrow = i16::from(difference < 0);
+    }
+    if &*negated < s {
+        s.copy_from_slice(negated);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn high_s_is_negated() {
+        let order = [0x00, 0xff, 0xf1];
+        let mut s = [0x00, 0xff, 0x00];
+        normalize_s(&mut s, &order);
+        assert_eq!(s, [0x00, 0x00, 0xf1]);
+        normalize_s(&mut s, &order);
+        assert_eq!(s, [0x00, 0x00, 0xf1]);
+    }
+}
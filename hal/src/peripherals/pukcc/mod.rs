@@ -12,6 +12,18 @@
 //! definitions. [`Pukcc`] wraps this low-level access API and exposes it in a
 //! safe manner.
 //!
+//! Over GF(p), [`Pukcc`] offers ECDSA signing and verification, ECDH key
+//! agreement, public key derivation and validation, and key pair generation
+//! for any [`Curve`]. Parameter sets for the NIST P-256, P-384 and P-521,
+//! secp256k1 and Brainpool P256r1, P384r1 and P512r1 curves are provided in
+//! [`curves`].
+//!
+//! With the `ecdsa` feature, [`ecdsa`] exposes ECDSA through the RustCrypto
+//! `signature` traits.
+//!
+//! [`rsa`] provides PKCS#1 v1.5 and PSS signatures and OAEP encryption on top
+//! of [`Pukcc::modular_exponentiation`].
+//!
 //! ## WARNING!
 //! This module has not been evaluated for correctness nor suitability for any
 //! use-case. Subtle implementation details may have catastrophic implications
@@ -20,6 +32,9 @@
 #![allow(clippy::just_underscores_and_digits)]
 pub mod c_abi;
 pub mod curves;
+#[cfg(feature = "ecdsa")]
+pub mod ecdsa;
+pub mod rsa;
 
 use core::iter::{once, repeat_n};
 
@@ -476,6 +491,350 @@ impl Pukcc {
         }
     }
 
+    /// Service checking that a public key is a valid point of a curve.
+    ///
+    /// GF(p) service. GF(2^n) variant is not implemented -- use low-level API.
+    ///
+    /// Input parameters:
+    /// - `public_key`: `&[u8]` of length `2 * `[`Curve::MOD_LENGTH`]
+    ///     - Affine `X || Y` coordinates, big endian
+    ///
+    /// Return value:
+    /// - `Result::Ok`
+    ///     - Both coordinates are smaller than the modulus and the point
+    ///       satisfies the curve equation
+    /// - `Result::Err`
+    ///     - Possible failure scenarios are encapsulated in a
+    ///       [`PublicKeyValidationFailure`] enum type
+    ///
+    /// In case of a point outside of the curve the returned error type will be
+    /// [`PublicKeyValidationFailure::ServiceFailure`]`(`
+    /// [`Warning`][`PukclReturnCode::Warning`]`(`
+    /// [`PointIsNotOnCurve`][`PukclReturnCodeWarning::PointIsNotOnCurve`]`))`
+    ///
+    /// All curves in [`curves`] have a cofactor of 1, so a point on the curve
+    /// is also in the prime order subgroup.
+    pub fn zp_ec_validate_public_key<C: Curve>(
+        &self,
+        public_key: &[u8],
+    ) -> Result<(), PublicKeyValidationFailure> {
+        C::verify_curve().map_err(PublicKeyValidationFailure::InvalidCurve)?;
+
+        let mod_length = usize::from(C::MOD_LENGTH);
+        if public_key.len() != 2 * mod_length {
+            return Err(PublicKeyValidationFailure::WrongInputParameterLength {
+                faulty_slice: "public_key",
+                expected_length: 2 * mod_length,
+                actual_length: public_key.len(),
+            });
+        }
+        let (x, y) = public_key.split_at(mod_length);
+        let modulus = &C::MODULO_P[4..];
+        if x >= modulus || y >= modulus {
+            return Err(PublicKeyValidationFailure::CoordinateOutOfRange);
+        }
+
+        let (modulo_p, cns, a_curve, b_curve, point, workspace, mut __);
+        let mut crypto_ram = unsafe { c_abi::CryptoRam::new() };
+        copy_to_cryptoram! {
+            crypto_ram,
+            (modulo_p, C::MODULO_P.iter().cloned().rev()),
+            (cns, C::CNS.iter().cloned().rev()),
+            (a_curve, C::A_CURVE.iter().cloned().rev()),
+            (b_curve, C::B_CURVE.iter().cloned().rev()),
+            // Point layout:
+            //   [ X coordinate: (little endian) ][ 0_u32 ]..
+            (point, x.iter().cloned().rev()),
+            (__, repeat_n(0, 4)),
+            // ..[ Y coordinate: (little endian) ][ 0_u32 ]..
+            (__, y.iter().cloned().rev()),
+            (__, repeat_n(0, 4)),
+            // ..[ Z coordinate: (little endian) ][ 0_u32 ] == 1
+            (__, once(1).chain(repeat_n(0, mod_length - 1))),
+            (__, repeat_n(0, 4)),
+            // Workspace is just marked with a zero length iterator just to get its address.
+            // As it is placed at the end, idea is that algorithm will use whatever amount
+            // of memory it needs
+            (workspace, 0..0)
+        };
+        let mut pukcl_params = c_abi::PukclParams::default();
+        unsafe {
+            let service_params = &mut pukcl_params.params.ZpEcPointIsOnCurve;
+            service_params.nu1ModBase = modulo_p.pukcc_base();
+            service_params.nu1CnsBase = cns.pukcc_base();
+            service_params.u2ModLength = C::MOD_LENGTH;
+            service_params.nu1AParam = a_curve.pukcc_base();
+            service_params.nu1BParam = b_curve.pukcc_base();
+            service_params.nu1PointBase = point.pukcc_base();
+            service_params.nu1Workspace = workspace.pukcc_base();
+        }
+
+        unsafe { c_abi::ZpEcPointIsOnCurve::call(&mut pukcl_params) };
+
+        match pukcl_params.header.u2Status.into() {
+            PukclReturnCode::Ok => Ok(()),
+            error_code => Err(PublicKeyValidationFailure::ServiceFailure(error_code)),
+        }
+    }
+
+    /// Service deriving a public key from a private key.
+    ///
+    /// GF(p) service. GF(2^n) variant is not implemented -- use low-level API.
+    ///
+    /// Input parameters:
+    /// - `private_key`: `&[u8]` of length [`Curve::SCALAR_LENGTH`]
+    ///     - Big endian scalar in range `[1, n - 1]`, where `n` is
+    ///       [`Curve::ORDER_POINT`]
+    ///
+    /// Output parameters:
+    /// - `public_key`: `&mut [u8]` of length `2 * `[`Curve::MOD_LENGTH`]
+    ///     - Affine `X || Y` coordinates of `private_key * G`, big endian
+    ///
+    /// Return value:
+    /// - `Result::Ok`
+    ///     - Public key was derived successfully
+    /// - `Result::Err`
+    ///     - Possible failure scenarios are encapsulated in a
+    ///       [`PointMultiplicationFailure`] enum type
+    pub fn zp_ec_derive_public_key<C: Curve>(
+        &self,
+        public_key: &mut [u8],
+        private_key: &[u8],
+    ) -> Result<(), PointMultiplicationFailure> {
+        self.zp_ec_point_multiply::<C>(
+            public_key,
+            &C::BASE_POINT_A_X[4..],
+            &C::BASE_POINT_A_Y[4..],
+            private_key,
+        )
+    }
+
+    /// Service computing an ECDH shared secret.
+    ///
+    /// GF(p) service. GF(2^n) variant is not implemented -- use low-level API.
+    ///
+    /// The peer public key is validated with
+    /// [`Pukcc::zp_ec_validate_public_key`] before use.
+    ///
+    /// Input parameters:
+    /// - `private_key`: `&[u8]` of length [`Curve::SCALAR_LENGTH`]
+    ///     - Own private key, big endian scalar in range `[1, n - 1]`
+    /// - `peer_public_key`: `&[u8]` of length `2 * `[`Curve::MOD_LENGTH`]
+    ///     - Affine `X || Y` coordinates of the other party's public key, big
+    ///       endian
+    ///
+    /// Output parameters:
+    /// - `shared_secret`: `&mut [u8]` of length [`Curve::MOD_LENGTH`]
+    ///     - X coordinate of `private_key * peer_public_key`, big endian. It
+    ///       should be passed through a key derivation function before use.
+    ///
+    /// Return value:
+    /// - `Result::Ok`
+    ///     - Shared secret was computed successfully
+    /// - `Result::Err`
+    ///     - Possible failure scenarios are encapsulated in a [`EcdhFailure`]
+    ///       enum type
+    pub fn zp_ecdh_shared_secret<C: Curve>(
+        &self,
+        shared_secret: &mut [u8],
+        private_key: &[u8],
+        peer_public_key: &[u8],
+    ) -> Result<(), EcdhFailure> {
+        if C::MOD_LENGTH > curves::MAX_MOD_LENGTH {
+            return Err(EcdhFailure::ModulusTooLarge);
+        }
+        let mod_length = usize::from(C::MOD_LENGTH);
+        if shared_secret.len() != mod_length {
+            return Err(EcdhFailure::WrongInputParameterLength {
+                faulty_slice: "shared_secret",
+                expected_length: mod_length,
+                actual_length: shared_secret.len(),
+            });
+        }
+        self.zp_ec_validate_public_key::<C>(peer_public_key)?;
+
+        let (peer_x, peer_y) = peer_public_key.split_at(mod_length);
+        let mut point = [0; 2 * curves::MAX_MOD_LENGTH as usize];
+        let point = &mut point[..2 * mod_length];
+        let result = self.zp_ec_point_multiply::<C>(point, peer_x, peer_y, private_key);
+        shared_secret.copy_from_slice(&point[..mod_length]);
+        point.fill(0);
+        result.map_err(Into::into)
+    }
+
+    /// Service generating an EC key pair.
+    ///
+    /// GF(p) service. GF(2^n) variant is not implemented -- use low-level API.
+    ///
+    /// The private key is drawn from `entropy_source` (typically
+    /// [`Trng`](crate::trng::Trng)) by rejection sampling until it falls in
+    /// range `[1, n - 1]`, and the public key is derived from it.
+    ///
+    /// Output parameters:
+    /// - `private_key`: `&mut [u8]` of length [`Curve::SCALAR_LENGTH`]
+    ///     - Big endian private scalar
+    /// - `public_key`: `&mut [u8]` of length `2 * `[`Curve::MOD_LENGTH`]
+    ///     - Affine `X || Y` coordinates, big endian
+    ///
+    /// Return value:
+    /// - `Result::Ok`
+    ///     - Key pair was generated successfully
+    /// - `Result::Err`
+    ///     - Possible failure scenarios are encapsulated in a
+    ///       [`KeyGenerationFailure`] enum type
+    pub fn zp_ec_generate_key_pair<C: Curve>(
+        &self,
+        private_key: &mut [u8],
+        public_key: &mut [u8],
+        entropy_source: &mut impl CryptoRng,
+    ) -> Result<(), KeyGenerationFailure> {
+        C::verify_curve().map_err(KeyGenerationFailure::InvalidCurve)?;
+
+        if private_key.len() != C::SCALAR_LENGTH.into() {
+            return Err(KeyGenerationFailure::WrongInputParameterLength {
+                faulty_slice: "private_key",
+                expected_length: C::SCALAR_LENGTH.into(),
+                actual_length: private_key.len(),
+            });
+        }
+
+        let order = &C::ORDER_POINT[4..];
+        let mut found = false;
+        for _ in 0..KEY_GENERATION_ATTEMPTS {
+            entropy_source.fill_bytes(private_key);
+            mask_to_bit_length(private_key, order);
+            if scalar_in_range(private_key, order) {
+                found = true;
+                break;
+            }
+        }
+        if !found {
+            private_key.fill(0);
+            return Err(KeyGenerationFailure::EntropySourceFailure);
+        }
+
+        self.zp_ec_derive_public_key::<C>(public_key, private_key)
+            .inspect_err(|_| private_key.fill(0))
+            .map_err(Into::into)
+    }
+
+    /// Multiply an affine point by a scalar, writing the affine result as
+    /// `X || Y`
+    fn zp_ec_point_multiply<C: Curve>(
+        &self,
+        result: &mut [u8],
+        point_x: &[u8],
+        point_y: &[u8],
+        scalar: &[u8],
+    ) -> Result<(), PointMultiplicationFailure> {
+        C::verify_curve().map_err(PointMultiplicationFailure::InvalidCurve)?;
+
+        let mod_length = usize::from(C::MOD_LENGTH);
+        if result.len() != 2 * mod_length {
+            return Err(PointMultiplicationFailure::WrongInputParameterLength {
+                faulty_slice: "public_key",
+                expected_length: 2 * mod_length,
+                actual_length: result.len(),
+            });
+        }
+        if scalar.len() != C::SCALAR_LENGTH.into() {
+            return Err(PointMultiplicationFailure::WrongInputParameterLength {
+                faulty_slice: "private_key",
+                expected_length: C::SCALAR_LENGTH.into(),
+                actual_length: scalar.len(),
+            });
+        }
+        if !scalar_in_range(scalar, &C::ORDER_POINT[4..]) {
+            return Err(PointMultiplicationFailure::InvalidPrivateKey);
+        }
+
+        let (
+            modulo_p,
+            cns,
+            a_curve,
+            point_x_cr,
+            point_y_cr,
+            point_z_cr,
+            scalar_cr,
+            workspace,
+            mut __,
+        );
+        let mut crypto_ram = unsafe { c_abi::CryptoRam::new() };
+        copy_to_cryptoram! {
+            crypto_ram,
+            (modulo_p, C::MODULO_P.iter().cloned().rev()),
+            (cns, C::CNS.iter().cloned().rev()),
+            (a_curve, C::A_CURVE.iter().cloned().rev()),
+            // Point is passed in projective coordinates with Z == 1
+            // Point layout:
+            //   [ X coordinate: (little endian) ][ 0_u32 ]..
+            (point_x_cr, point_x.iter().cloned().rev()),
+            (__, repeat_n(0, 4)),
+            // ..[ Y coordinate: (little endian) ][ 0_u32 ]..
+            (point_y_cr, point_y.iter().cloned().rev()),
+            (__, repeat_n(0, 4)),
+            // ..[ Z coordinate: (little endian) ][ 0_u32 ] == 1
+            (point_z_cr, once(1).chain(repeat_n(0, mod_length - 1))),
+            (__, repeat_n(0, 4)),
+            (scalar_cr, scalar.iter().cloned().rev()),
+            (__, repeat_n(0, 4)),
+            // Workspace is just marked with a zero length iterator just to get its address.
+            // As it is placed at the end, idea is that algorithm will use whatever amount
+            // of memory it needs
+            (workspace, 0..0)
+        };
+        let mut pukcl_params = c_abi::PukclParams::default();
+        unsafe {
+            let service_params = &mut pukcl_params.params.ZpEccMulFast;
+            service_params.nu1PointBase = point_x_cr.pukcc_base();
+            service_params.nu1ModBase = modulo_p.pukcc_base();
+            service_params.nu1CnsBase = cns.pukcc_base();
+            service_params.nu1KBase = scalar_cr.pukcc_base();
+            service_params.nu1ABase = a_curve.pukcc_base();
+            service_params.nu1Workspace = workspace.pukcc_base();
+            service_params.u2ModLength = C::MOD_LENGTH;
+            service_params.u2KLength = C::SCALAR_LENGTH;
+        }
+
+        unsafe { c_abi::ZpEccMulFast::call(&mut pukcl_params) };
+
+        match pukcl_params.header.u2Status.into() {
+            PukclReturnCode::Ok => {}
+            error_code => return Err(PointMultiplicationFailure::ServiceFailure(error_code)),
+        };
+
+        // Product lands at the input point location, in projective coordinates
+        if point_z_cr.iter().all(|&el| el == 0) {
+            return Err(PointMultiplicationFailure::PointAtInfinity);
+        }
+
+        let mut pukcl_params = c_abi::PukclParams::default();
+        unsafe {
+            let service_params = &mut pukcl_params.params.ZpEcConvProjToAffine;
+            service_params.nu1ModBase = modulo_p.pukcc_base();
+            service_params.nu1CnsBase = cns.pukcc_base();
+            service_params.u2ModLength = C::MOD_LENGTH;
+            service_params.nu1PointABase = point_x_cr.pukcc_base();
+            service_params.nu1Workspace = workspace.pukcc_base();
+        }
+
+        unsafe { c_abi::ZpEcConvProjToAffine::call(&mut pukcl_params) };
+
+        match pukcl_params.header.u2Status.into() {
+            PukclReturnCode::Ok => {}
+            error_code => return Err(PointMultiplicationFailure::ServiceFailure(error_code)),
+        };
+
+        // Copying the affine point back from the CryptoRAM while ignoring irrelevant
+        // padding.
+        result
+            .iter_mut()
+            .zip(point_x_cr.iter().rev().chain(point_y_cr.iter().rev()))
+            .for_each(|(target_iter, source_iter)| *target_iter = *source_iter);
+
+        Ok(())
+    }
+
     /// Service performing a modular exponentiation.
     ///
     /// ```text
@@ -571,6 +930,77 @@ impl Pukcc {
         mode: ExpModMode,
         window_size: ExpModWindowSize,
         buffer: &'a mut [u8],
+    ) -> Result<&'a [u8], ExpModFailure> {
+        self.exp_mod(input, exponent, modulus, None, mode, window_size, buffer)
+    }
+
+    /// Service performing a modular exponentiation with a precomputed
+    /// reduction constant.
+    ///
+    /// Behaves exactly like [`Pukcc::modular_exponentiation`] but skips the
+    /// `CNS` calculation on every call. This is worthwhile whenever the same
+    /// `modulus` is used repeatedly, e.g. an RSA key.
+    ///
+    /// Additional input parameters:
+    /// - `cns`: `&[u8]`
+    ///     - Requirements:
+    ///         - `len(cns) == len(modulus) + 5`
+    ///     - Reduction constant of `modulus`, as returned by
+    ///       [`Pukcc::zp_calculate_cns`]
+    /// - `buffer`: `&'a mut [u8]`
+    ///     - Requirements:
+    ///         - `len(buffer) >= len(modulus)`
+    ///     - Buffer used for a return value.
+    #[allow(clippy::too_many_arguments)]
+    pub fn modular_exponentiation_with_cns<'a>(
+        &self,
+        input: &[u8],
+        exponent: &[u8],
+        modulus: &[u8],
+        cns: &[u8],
+        mode: ExpModMode,
+        window_size: ExpModWindowSize,
+        buffer: &'a mut [u8],
+    ) -> Result<&'a [u8], ExpModFailure> {
+        if cns.len() != modulus.len() + 5 {
+            return Err(ExpModFailure::WrongInputParameterLength {
+                faulty_slice: "cns",
+                actual_length: cns.len(),
+                expected_length: ExpectedLengthError::Exactly(modulus.len() + 5),
+            });
+        }
+        if buffer.len() < modulus.len() {
+            return Err(ExpModFailure::WrongInputParameterLength {
+                faulty_slice: "buffer",
+                actual_length: buffer.len(),
+                expected_length: ExpectedLengthError::AtLeast(modulus.len()),
+            });
+        }
+        self.exp_mod(
+            input,
+            exponent,
+            modulus,
+            Some(cns),
+            mode,
+            window_size,
+            buffer,
+        )
+    }
+
+    /// Common implementation of [`Pukcc::modular_exponentiation`] and
+    /// [`Pukcc::modular_exponentiation_with_cns`]
+    ///
+    /// `CNS` is calculated into `buffer` when not provided.
+    #[allow(clippy::too_many_arguments)]
+    fn exp_mod<'a>(
+        &self,
+        input: &[u8],
+        exponent: &[u8],
+        modulus: &[u8],
+        cns: Option<&[u8]>,
+        mode: ExpModMode,
+        window_size: ExpModWindowSize,
+        buffer: &'a mut [u8],
     ) -> Result<&'a [u8], ExpModFailure> {
         const PUKCL_EXPMOD_EXPINPUKCCRAM: u16 = 0x02;
 
@@ -612,7 +1042,10 @@ impl Pukcc {
 
         let (modulus_cr, cns_cr, output, workspace, exponent_cr, mut __);
 
-        let cns = self.zp_calculate_cns(buffer, modulus)?;
+        let cns = match cns {
+            Some(cns) => cns,
+            None => self.zp_calculate_cns(buffer, modulus)?,
+        };
         let padding_for_cns = padding_for_len(cns.len());
         // Sanity check in case someone changes `zp_calculate_cns` implementation
         assert!(cns.len() + padding_for_cns == modulus.len() + 8);
@@ -689,8 +1122,27 @@ impl Pukcc {
         Ok(&buffer[..modulus.len()])
     }
 
-    /// Service producing a reduction constant value
-    fn zp_calculate_cns<'a>(
+    /// Service producing a reduction constant value.
+    ///
+    /// GF(p) service. `CNS` depends on `modulus` only, so it can be computed
+    /// once and reused with [`Pukcc::modular_exponentiation_with_cns`].
+    ///
+    /// Input parameters:
+    /// - `buffer`: `&'a mut [u8]`
+    ///     - Requirements:
+    ///         - `len(buffer) >= len(modulus) + 5`
+    /// - `modulus`: `&[u8]`
+    ///     - Requirements:
+    ///         - `len(modulus) % 4`
+    ///
+    /// Return value:
+    /// - `Result::Ok(&'a [u8])`
+    ///     - Length: `len(modulus) + 5`
+    ///     - Big endian reduction constant
+    /// - `Result::Err`
+    ///     - Possible failure scenarios are encapsulated in a
+    ///       [`CalculateCnsFailure`] enum type
+    pub fn zp_calculate_cns<'a>(
         &self,
         buffer: &'a mut [u8],
         modulus: &[u8],
@@ -787,6 +1239,73 @@ pub enum EcdsaSignatureVerificationFailure {
     ServiceFailure(PukclReturnCode),
 }
 
+/// An error type representing failure modes for a
+/// [`Pukcc::zp_ec_validate_public_key`] service
+#[allow(missing_docs)]
+#[derive(Debug)]
+pub enum PublicKeyValidationFailure {
+    WrongInputParameterLength {
+        faulty_slice: &'static str,
+        expected_length: usize,
+        actual_length: usize,
+    },
+    InvalidCurve(curves::CurveVerificationFailure),
+    /// A coordinate is not smaller than the curve modulus
+    CoordinateOutOfRange,
+    ServiceFailure(PukclReturnCode),
+}
+
+/// An error type representing failure modes for a
+/// [`Pukcc::zp_ec_derive_public_key`] service
+#[allow(missing_docs)]
+#[derive(Debug)]
+pub enum PointMultiplicationFailure {
+    WrongInputParameterLength {
+        faulty_slice: &'static str,
+        expected_length: usize,
+        actual_length: usize,
+    },
+    InvalidCurve(curves::CurveVerificationFailure),
+    /// Private key is zero or not smaller than the order of the curve
+    InvalidPrivateKey,
+    /// The product is the point at infinity
+    PointAtInfinity,
+    ServiceFailure(PukclReturnCode),
+}
+
+/// An error type representing failure modes for a
+/// [`Pukcc::zp_ecdh_shared_secret`] service
+#[allow(missing_docs)]
+#[derive(Debug)]
+pub enum EcdhFailure {
+    WrongInputParameterLength {
+        faulty_slice: &'static str,
+        expected_length: usize,
+        actual_length: usize,
+    },
+    /// [`Curve::MOD_LENGTH`] exceeds [`curves::MAX_MOD_LENGTH`]
+    ModulusTooLarge,
+    InvalidPublicKey(PublicKeyValidationFailure),
+    PointMultiplicationFailure(PointMultiplicationFailure),
+}
+
+/// An error type representing failure modes for a
+/// [`Pukcc::zp_ec_generate_key_pair`] service
+#[allow(missing_docs)]
+#[derive(Debug)]
+pub enum KeyGenerationFailure {
+    WrongInputParameterLength {
+        faulty_slice: &'static str,
+        expected_length: usize,
+        actual_length: usize,
+    },
+    InvalidCurve(curves::CurveVerificationFailure),
+    /// The entropy source did not produce a valid private key within
+    /// [`KEY_GENERATION_ATTEMPTS`] attempts
+    EntropySourceFailure,
+    PointMultiplicationFailure(PointMultiplicationFailure),
+}
+
 /// An error type specifying an expected length of a slice in question
 #[allow(missing_docs)]
 #[derive(Debug)]
@@ -869,7 +1388,7 @@ pub enum ExpModFailure {
 }
 
 /// An error type representing failure modes for a
-/// `Pukcc::zp_calculate_cns` service
+/// [`Pukcc::zp_calculate_cns`] service
 #[allow(missing_docs)]
 #[derive(Debug)]
 pub enum CalculateCnsFailure {
@@ -891,6 +1410,24 @@ impl From<CalculateCnsFailure> for ExpModFailure {
     }
 }
 
+impl From<PublicKeyValidationFailure> for EcdhFailure {
+    fn from(f: PublicKeyValidationFailure) -> Self {
+        EcdhFailure::InvalidPublicKey(f)
+    }
+}
+
+impl From<PointMultiplicationFailure> for EcdhFailure {
+    fn from(f: PointMultiplicationFailure) -> Self {
+        EcdhFailure::PointMultiplicationFailure(f)
+    }
+}
+
+impl From<PointMultiplicationFailure> for KeyGenerationFailure {
+    fn from(f: PointMultiplicationFailure) -> Self {
+        KeyGenerationFailure::PointMultiplicationFailure(f)
+    }
+}
+
 // PukclReturnCode <-> c_abi::PukclReturnCode
 impl core::convert::From<c_abi::PukclReturnCode> for PukclReturnCode {
     fn from(v: c_abi::PukclReturnCode) -> Self {
@@ -1027,6 +1564,30 @@ pub enum PukclReturnCodeSevere {
     WrongService,
 }
 
+/// Number of random candidates [`Pukcc::zp_ec_generate_key_pair`] draws before
+/// giving up
+pub const KEY_GENERATION_ATTEMPTS: usize = 64;
+
+/// Whether a big endian scalar lies in `[1, order - 1]`
+///
+/// `scalar` and `order` must have the same length.
+fn scalar_in_range(scalar: &[u8], order: &[u8]) -> bool {
+    scalar.iter().any(|&byte| byte != 0) && scalar < order
+}
+
+/// Clear all bits of a big endian `value` above the most significant set bit of
+/// `reference`
+fn mask_to_bit_length(value: &mut [u8], reference: &[u8]) {
+    for (byte, &limit) in value.iter_mut().zip(reference) {
+        if limit == 0 {
+            *byte = 0;
+        } else {
+            *byte &= u8::MAX >> limit.leading_zeros();
+            break;
+        }
+    }
+}
+
 fn padding_for_len(len: usize) -> usize {
     const ALIGNMENT: usize = 4;
     if len % ALIGNMENT != 0 {
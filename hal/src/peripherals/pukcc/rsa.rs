@@ -0,0 +1,1092 @@
+//! # RSA
+//!
+//! RSA public and private key operations on top of
+//! [`Pukcc::modular_exponentiation_with_cns`].
+//!
+//! Keys borrow their components as big endian byte slices, e.g. straight out
+//! of flash. Every modulus needs a reduction constant (`CNS`). It is either
+//! computed on-chip once when a key is constructed ([`RsaPublicKey::new`],
+//! [`RsaPrivateKey::new`]) or supplied precomputed
+//! ([`RsaPublicKey::with_cns`], [`RsaPrivateKey::with_cns`]), for instance
+//! stored next to a key in a firmware image. [`RsaPublicKey::cns`] returns the
+//! value to be stored.
+//!
+//! Supported schemes (RFC 8017):
+//! - PKCS#1 v1.5 signatures ([`RsaPublicKey::verify_pkcs1v15`],
+//!   [`RsaPrivateKey::sign_pkcs1v15`])
+//! - PSS signatures with MGF1 and a salt as long as the digest
+//!   ([`RsaPublicKey::verify_pss`], [`RsaPrivateKey::sign_pss`])
+//! - OAEP encryption with MGF1 ([`RsaPublicKey::encrypt_oaep`],
+//!   [`RsaPrivateKey::decrypt_oaep`])
+//!
+//! Private key operations use the Chinese Remainder Theorem: two half-sized
+//! exponentiations are done by PUKCC and recombined in software. Every result
+//! is checked against the public key before being released to guard against
+//! fault attacks.
+//!
+//! [`Pkcs1v15VerifyingKey`], [`Pkcs1v15SigningKey`] and [`PssVerifyingKey`]
+//! implement the RustCrypto [`signature`] traits for a fixed [`Digest`].
+//! Signatures are represented by [`Signature`], sized by the modulus length
+//! in bytes.
+//!
+//! ```ignore
+//! # use atsamd_hal::pukcc::{Pukcc, rsa::*};
+//! # use sha2::Sha256;
+//! # fn f(pukcc: &Pukcc, modulus: &[u8], signature: &Signature<256>, firmware: &[u8]) {
+//! let mut cns = [0; 256 + 5];
+//! let key = RsaPublicKey::new(pukcc, modulus, &[0x01, 0x00, 0x01], &mut cns).unwrap();
+//! let verifier = Pkcs1v15VerifyingKey::<Sha256>::new(key);
+//! verifier.verify(firmware, signature).unwrap();
+//! # }
+//! ```
+//!
+//! PSS signing needs a source of randomness and is therefore only available
+//! through [`RsaPrivateKey::sign_pss`].
+
+use core::marker::PhantomData;
+
+use digest::const_oid::AssociatedOid;
+use digest::{Digest, Output};
+use rand_core::CryptoRng;
+
+use super::{
+    CalculateCnsFailure, ExpModFailure, ExpModMode, ExpModWindowSize, ExpectedLengthError, Pukcc,
+    padding_for_len,
+};
+
+pub use digest;
+pub use signature::{self, DigestSigner, DigestVerifier, SignatureEncoding, Signer, Verifier};
+
+/// Largest supported modulus length in bytes (RSA4096)
+pub const MAX_MODULUS_LENGTH: usize = 512;
+
+/// Largest supported prime length in bytes
+pub const MAX_PRIME_LENGTH: usize = MAX_MODULUS_LENGTH / 2;
+
+/// Smallest modulus length in bytes accepted by PUKCC
+const MIN_MODULUS_LENGTH: usize = 12;
+
+/// CryptoRAM size, used to pick the largest exponentiation window that fits
+const CRYPTO_RAM_LENGTH: usize = 0x1000;
+
+/// Length of the zero prefix of `M'` in EMSA-PSS
+const PSS_PREFIX_LENGTH: usize = 8;
+
+/// RSA public key
+pub struct RsaPublicKey<'a> {
+    pukcc: &'a Pukcc,
+    modulus: &'a [u8],
+    exponent: &'a [u8],
+    cns: &'a [u8],
+}
+
+impl<'a> RsaPublicKey<'a> {
+    /// Create a public key, computing the `CNS` of `modulus` on-chip.
+    ///
+    /// Input parameters:
+    /// - `modulus`: `&[u8]`
+    ///     - Requirements:
+    ///         - `len(modulus) % 4`
+    ///         - `12 <= len(modulus) <= `[`MAX_MODULUS_LENGTH`]
+    ///         - `modulus[0] != 0`
+    /// - `exponent`: `&[u8]`
+    ///     - Requirements:
+    ///         - `0 < len(exponent) <= len(modulus)`
+    /// - `cns_buffer`: `&'a mut [u8]`
+    ///     - Requirements:
+    ///         - `len(cns_buffer) >= len(modulus) + 5`
+    pub fn new(
+        pukcc: &'a Pukcc,
+        modulus: &'a [u8],
+        exponent: &'a [u8],
+        cns_buffer: &'a mut [u8],
+    ) -> Result<Self, RsaFailure> {
+        validate_public_modulus(modulus)?;
+        validate_exponent("exponent", exponent, modulus)?;
+        let cns = pukcc.zp_calculate_cns(cns_buffer, modulus)?;
+        Ok(Self {
+            pukcc,
+            modulus,
+            exponent,
+            cns,
+        })
+    }
+
+    /// Create a public key from a precomputed `CNS`.
+    ///
+    /// Same requirements as [`RsaPublicKey::new`] apply; `cns` must be
+    /// `len(modulus) + 5` bytes long.
+    pub fn with_cns(
+        pukcc: &'a Pukcc,
+        modulus: &'a [u8],
+        exponent: &'a [u8],
+        cns: &'a [u8],
+    ) -> Result<Self, RsaFailure> {
+        validate_public_modulus(modulus)?;
+        validate_exponent("exponent", exponent, modulus)?;
+        validate_cns("cns", cns, modulus)?;
+        Ok(Self {
+            pukcc,
+            modulus,
+            exponent,
+            cns,
+        })
+    }
+
+    /// Big endian modulus
+    pub fn modulus(&self) -> &[u8] {
+        self.modulus
+    }
+
+    /// Big endian public exponent
+    pub fn exponent(&self) -> &[u8] {
+        self.exponent
+    }
+
+    /// Big endian reduction constant of the modulus
+    pub fn cns(&self) -> &[u8] {
+        self.cns
+    }
+
+    /// Modulus length in bytes; also the length of signatures and
+    /// ciphertexts
+    pub fn size(&self) -> usize {
+        self.modulus.len()
+    }
+
+    /// Verify a PKCS#1 v1.5 signature of a `hashed` message.
+    ///
+    /// `hashed` is the output of `D`; the `DigestInfo` is derived from the
+    /// digest OID.
+    pub fn verify_pkcs1v15<D: Digest + AssociatedOid>(
+        &self,
+        hashed: &[u8],
+        signature: &[u8],
+    ) -> Result<(), RsaFailure> {
+        let k = self.size();
+        let mut expected = [0; MAX_MODULUS_LENGTH];
+        emsa_pkcs1v15_encode::<D>(hashed, &mut expected[..k])?;
+        let mut em = [0; MAX_MODULUS_LENGTH];
+        self.public_operation(signature, &mut em[..k])?;
+        if ct_eq(&em[..k], &expected[..k]) {
+            Ok(())
+        } else {
+            Err(RsaFailure::InvalidSignature)
+        }
+    }
+
+    /// Verify a PSS signature of a `hashed` message.
+    ///
+    /// MGF1 with `D` is used as a mask generation function. Salt length is
+    /// recovered from the signature.
+    pub fn verify_pss<D: Digest>(&self, hashed: &[u8], signature: &[u8]) -> Result<(), RsaFailure> {
+        let k = self.size();
+        let mut em = [0; MAX_MODULUS_LENGTH];
+        self.public_operation(signature, &mut em[..k])?;
+        let em_bits = bit_length(self.modulus) - 1;
+        let em_len = em_bits.div_ceil(8);
+        // `em_len` is `k - 1` when the modulus bit length is `8 * x + 1`
+        let (leading, em) = em[..k].split_at_mut(k - em_len);
+        if leading.iter().any(|&byte| byte != 0) {
+            return Err(RsaFailure::InvalidSignature);
+        }
+        emsa_pss_verify::<D>(hashed, em, em_bits)
+    }
+
+    /// Encrypt `message` using OAEP with `D` and an optional `label`.
+    ///
+    /// `ciphertext` must be [`RsaPublicKey::size`] bytes long and `message` at
+    /// most `size - 2 * len(D) - 2` bytes long.
+    pub fn encrypt_oaep<D: Digest>(
+        &self,
+        entropy_source: &mut impl CryptoRng,
+        message: &[u8],
+        label: &[u8],
+        ciphertext: &mut [u8],
+    ) -> Result<(), RsaFailure> {
+        let k = self.size();
+        let h_len = <D as Digest>::output_size();
+        if k < 2 * h_len + 2 || message.len() > k - 2 * h_len - 2 {
+            return Err(RsaFailure::MessageTooLong);
+        }
+        let mut em = [0; MAX_MODULUS_LENGTH];
+        let em = &mut em[..k];
+        let (seed, db) = em[1..].split_at_mut(h_len);
+        entropy_source.fill_bytes(seed);
+        // DB = lHash || PS || 0x01 || M
+        db[..h_len].copy_from_slice(&D::digest(label));
+        let message_offset = db.len() - message.len();
+        db[message_offset - 1] = 0x01;
+        db[message_offset..].copy_from_slice(message);
+        mgf1_xor::<D>(db, seed);
+        mgf1_xor::<D>(seed, db);
+        self.public_operation(em, ciphertext)
+    }
+
+    /// `output = input ^ e mod n`
+    fn public_operation(&self, input: &[u8], output: &mut [u8]) -> Result<(), RsaFailure> {
+        validate_representative("input", input, self.modulus)?;
+        validate_length("output", output, self.size())?;
+        self.pukcc.modular_exponentiation_with_cns(
+            input,
+            self.exponent,
+            self.modulus,
+            self.cns,
+            ExpModMode::Fast,
+            window_size(self.modulus.len(), self.exponent.len()),
+            output,
+        )?;
+        Ok(())
+    }
+}
+
+/// Components of an RSA private key, named as in PKCS#1 `RSAPrivateKey`
+///
+/// All values are big endian. Both primes must satisfy the modulus
+/// requirements of [`Pukcc::modular_exponentiation`] and be at most
+/// [`MAX_PRIME_LENGTH`] bytes long.
+pub struct RsaPrivateKeyComponents<'a> {
+    /// `n = p * q`
+    pub modulus: &'a [u8],
+    /// `e`
+    pub public_exponent: &'a [u8],
+    /// `p`
+    pub prime_1: &'a [u8],
+    /// `q`
+    pub prime_2: &'a [u8],
+    /// `d mod (p - 1)`
+    pub exponent_1: &'a [u8],
+    /// `d mod (q - 1)`
+    pub exponent_2: &'a [u8],
+    /// `q^(-1) mod p`
+    pub coefficient: &'a [u8],
+}
+
+/// RSA private key in CRT form
+pub struct RsaPrivateKey<'a> {
+    pukcc: &'a Pukcc,
+    components: RsaPrivateKeyComponents<'a>,
+    cns_1: &'a [u8],
+    cns_2: &'a [u8],
+    cns_n: &'a [u8],
+}
+
+impl<'a> RsaPrivateKey<'a> {
+    /// Create a private key, computing the `CNS` of both primes and of the
+    /// modulus on-chip.
+    ///
+    /// `cns_buffer` must be at least `len(p) + len(q) + len(n) + 15` bytes
+    /// long.
+    pub fn new(
+        pukcc: &'a Pukcc,
+        components: RsaPrivateKeyComponents<'a>,
+        cns_buffer: &'a mut [u8],
+    ) -> Result<Self, RsaFailure> {
+        validate_private_components(&components)?;
+        let cns_1_length = components.prime_1.len() + 5;
+        let cns_2_length = components.prime_2.len() + 5;
+        let cns_n_length = components.modulus.len() + 5;
+        validate_length_at_least(
+            "cns_buffer",
+            cns_buffer,
+            cns_1_length + cns_2_length + cns_n_length,
+        )?;
+        let (cns_1_buffer, cns_buffer) = cns_buffer.split_at_mut(cns_1_length);
+        let (cns_2_buffer, cns_n_buffer) = cns_buffer.split_at_mut(cns_2_length);
+        let cns_1 = pukcc.zp_calculate_cns(cns_1_buffer, components.prime_1)?;
+        let cns_2 = pukcc.zp_calculate_cns(cns_2_buffer, components.prime_2)?;
+        let cns_n = pukcc.zp_calculate_cns(cns_n_buffer, components.modulus)?;
+        Ok(Self {
+            pukcc,
+            components,
+            cns_1,
+            cns_2,
+            cns_n,
+        })
+    }
+
+    /// Create a private key from precomputed `CNS` values of `p` (`cns_1`),
+    /// `q` (`cns_2`) and `n` (`cns_n`).
+    pub fn with_cns(
+        pukcc: &'a Pukcc,
+        components: RsaPrivateKeyComponents<'a>,
+        cns_1: &'a [u8],
+        cns_2: &'a [u8],
+        cns_n: &'a [u8],
+    ) -> Result<Self, RsaFailure> {
+        validate_private_components(&components)?;
+        validate_cns("cns_1", cns_1, components.prime_1)?;
+        validate_cns("cns_2", cns_2, components.prime_2)?;
+        validate_cns("cns_n", cns_n, components.modulus)?;
+        Ok(Self {
+            pukcc,
+            components,
+            cns_1,
+            cns_2,
+            cns_n,
+        })
+    }
+
+    /// Key components
+    pub fn components(&self) -> &RsaPrivateKeyComponents<'a> {
+        &self.components
+    }
+
+    /// Big endian reduction constants of `p`, `q` and `n`
+    pub fn cns(&self) -> (&[u8], &[u8], &[u8]) {
+        (self.cns_1, self.cns_2, self.cns_n)
+    }
+
+    /// Modulus length in bytes; also the length of signatures and
+    /// ciphertexts
+    pub fn size(&self) -> usize {
+        self.components.modulus.len()
+    }
+
+    /// Produce a PKCS#1 v1.5 signature of a `hashed` message
+    pub fn sign_pkcs1v15<D: Digest + AssociatedOid>(
+        &self,
+        hashed: &[u8],
+        signature: &mut [u8],
+    ) -> Result<(), RsaFailure> {
+        let k = self.size();
+        let mut em = [0; MAX_MODULUS_LENGTH];
+        emsa_pkcs1v15_encode::<D>(hashed, &mut em[..k])?;
+        self.private_operation(&em[..k], signature)
+    }
+
+    /// Produce a PSS signature of a `hashed` message with a salt as long as
+    /// the output of `D`
+    pub fn sign_pss<D: Digest>(
+        &self,
+        entropy_source: &mut impl CryptoRng,
+        hashed: &[u8],
+        signature: &mut [u8],
+    ) -> Result<(), RsaFailure> {
+        let k = self.size();
+        let h_len = <D as Digest>::output_size();
+        validate_length("hashed", hashed, h_len)?;
+        let em_bits = bit_length(self.components.modulus) - 1;
+        let em_len = em_bits.div_ceil(8);
+        if em_len < 2 * h_len + 2 {
+            return Err(RsaFailure::MessageTooLong);
+        }
+        let mut em = [0; MAX_MODULUS_LENGTH];
+        let (db, tail) = em[k - em_len..k].split_at_mut(em_len - h_len - 1);
+        // DB = PS || 0x01 || salt
+        let salt_offset = db.len() - h_len;
+        db[salt_offset - 1] = 0x01;
+        entropy_source.fill_bytes(&mut db[salt_offset..]);
+        let h = D::new()
+            .chain_update([0; PSS_PREFIX_LENGTH])
+            .chain_update(hashed)
+            .chain_update(&db[salt_offset..])
+            .finalize();
+        tail[..h_len].copy_from_slice(&h);
+        tail[h_len] = 0xbc;
+        mgf1_xor::<D>(db, &h);
+        db[0] &= u8::MAX >> (8 * em_len - em_bits);
+        self.private_operation(&em[..k], signature)
+    }
+
+    /// Decrypt an OAEP `ciphertext` with `D` and an optional `label`.
+    ///
+    /// The message is copied into `message`; a subslice of its exact length is
+    /// returned.
+    pub fn decrypt_oaep<'m, D: Digest>(
+        &self,
+        ciphertext: &[u8],
+        label: &[u8],
+        message: &'m mut [u8],
+    ) -> Result<&'m [u8], RsaFailure> {
+        let k = self.size();
+        let h_len = <D as Digest>::output_size();
+        if k < 2 * h_len + 2 {
+            return Err(RsaFailure::DecryptionFailure);
+        }
+        let mut em = [0; MAX_MODULUS_LENGTH];
+        self.private_operation(ciphertext, &mut em[..k])?;
+        let (y, rest) = em[..k].split_at_mut(1);
+        let (seed, db) = rest.split_at_mut(h_len);
+        mgf1_xor::<D>(seed, db);
+        mgf1_xor::<D>(db, seed);
+
+        // Padding is checked without data dependent branches to not leak which
+        // check failed (Manger's attack)
+        let (l_hash, ps_and_message) = db.split_at(h_len);
+        let mut valid = ct_mask(ct_eq(l_hash, &D::digest(label))) & ct_is_zero(y[0]);
+        let mut looking_for_separator = u8::MAX;
+        let mut separator_index = 0;
+        for (index, &byte) in ps_and_message.iter().enumerate() {
+            let is_separator = looking_for_separator & ct_is_zero(byte ^ 0x01);
+            separator_index |= index & usize::from(is_separator & 1).wrapping_neg();
+            valid &= !(looking_for_separator & !is_separator & !ct_is_zero(byte));
+            looking_for_separator &= !is_separator;
+        }
+        valid &= !looking_for_separator;
+        if valid == 0 {
+            return Err(RsaFailure::DecryptionFailure);
+        }
+
+        let decrypted = &ps_and_message[separator_index + 1..];
+        validate_length_at_least("message", message, decrypted.len())?;
+        message[..decrypted.len()].copy_from_slice(decrypted);
+        Ok(&message[..decrypted.len()])
+    }
+
+    /// `output = input ^ d mod n`, computed with CRT
+    fn private_operation(&self, input: &[u8], output: &mut [u8]) -> Result<(), RsaFailure> {
+        let RsaPrivateKeyComponents {
+            modulus,
+            public_exponent,
+            prime_1: p,
+            prime_2: q,
+            exponent_1,
+            exponent_2,
+            coefficient,
+        } = self.components;
+        validate_representative("input", input, modulus)?;
+        validate_length("output", output, modulus.len())?;
+
+        let mut reduced = [0; MAX_PRIME_LENGTH];
+        let mut m_1 = [0; MAX_PRIME_LENGTH];
+        let mut m_2 = [0; MAX_PRIME_LENGTH];
+        let reduced_p = &mut reduced[..p.len()];
+
+        // m_1 = (c mod p) ^ dP mod p
+        reduce(input, p, reduced_p);
+        self.pukcc.modular_exponentiation_with_cns(
+            reduced_p,
+            exponent_1,
+            p,
+            self.cns_1,
+            ExpModMode::Regular,
+            window_size(p.len(), exponent_1.len()),
+            &mut m_1,
+        )?;
+        // m_2 = (c mod q) ^ dQ mod q
+        let reduced_q = &mut reduced[..q.len()];
+        reduce(input, q, reduced_q);
+        self.pukcc.modular_exponentiation_with_cns(
+            reduced_q,
+            exponent_2,
+            q,
+            self.cns_2,
+            ExpModMode::Regular,
+            window_size(q.len(), exponent_2.len()),
+            &mut m_2,
+        )?;
+        let m_1 = &mut m_1[..p.len()];
+        let m_2 = &m_2[..q.len()];
+
+        // h = qInv * (m_1 - m_2) mod p
+        let reduced_p = &mut reduced[..p.len()];
+        reduce(m_2, p, reduced_p);
+        mod_sub(m_1, reduced_p, p);
+        mod_mul(coefficient, m_1, p, reduced_p);
+        // m = m_2 + h * q
+        mul_add(reduced_p, q, m_2, output);
+
+        // Fault attack countermeasure: a glitched half exponentiation would
+        // otherwise leak a factor of `n`
+        let mut check = [0; MAX_MODULUS_LENGTH];
+        let check = self.pukcc.modular_exponentiation_with_cns(
+            output,
+            public_exponent,
+            modulus,
+            self.cns_n,
+            ExpModMode::Fast,
+            window_size(modulus.len(), public_exponent.len()),
+            &mut check,
+        )?;
+        if !ct_eq(check, input) {
+            output.fill(0);
+            return Err(RsaFailure::FaultDetected);
+        }
+        Ok(())
+    }
+}
+
+/// RSA signature, `N` being the modulus length in bytes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature<const N: usize>(pub [u8; N]);
+
+impl<const N: usize> AsRef<[u8]> for Signature<N> {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for Signature<N> {
+    fn from(bytes: [u8; N]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl<const N: usize> From<Signature<N>> for [u8; N] {
+    fn from(signature: Signature<N>) -> Self {
+        signature.0
+    }
+}
+
+impl<const N: usize> TryFrom<&[u8]> for Signature<N> {
+    type Error = signature::Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        bytes
+            .try_into()
+            .map(Self)
+            .map_err(|_| signature::Error::new())
+    }
+}
+
+impl<const N: usize> SignatureEncoding for Signature<N> {
+    type Repr = [u8; N];
+}
+
+/// PKCS#1 v1.5 signature verifier for a digest `D`
+pub struct Pkcs1v15VerifyingKey<'a, D> {
+    key: RsaPublicKey<'a>,
+    _digest: PhantomData<D>,
+}
+
+impl<'a, D: Digest + AssociatedOid> Pkcs1v15VerifyingKey<'a, D> {
+    /// Constructor
+    pub fn new(key: RsaPublicKey<'a>) -> Self {
+        Self {
+            key,
+            _digest: PhantomData,
+        }
+    }
+
+    /// Underlying public key
+    pub fn key(&self) -> &RsaPublicKey<'a> {
+        &self.key
+    }
+}
+
+impl<D: Digest + AssociatedOid, const N: usize> Verifier<Signature<N>>
+    for Pkcs1v15VerifyingKey<'_, D>
+{
+    fn verify(&self, msg: &[u8], signature: &Signature<N>) -> Result<(), signature::Error> {
+        self.verify_digest(D::new_with_prefix(msg), signature)
+    }
+}
+
+impl<D: Digest + AssociatedOid, const N: usize> DigestVerifier<D, Signature<N>>
+    for Pkcs1v15VerifyingKey<'_, D>
+{
+    fn verify_digest(&self, digest: D, signature: &Signature<N>) -> Result<(), signature::Error> {
+        self.key
+            .verify_pkcs1v15::<D>(&digest.finalize(), &signature.0)
+            .map_err(|_| signature::Error::new())
+    }
+}
+
+/// PKCS#1 v1.5 signer for a digest `D`
+pub struct Pkcs1v15SigningKey<'a, D> {
+    key: RsaPrivateKey<'a>,
+    _digest: PhantomData<D>,
+}
+
+impl<'a, D: Digest + AssociatedOid> Pkcs1v15SigningKey<'a, D> {
+    /// Constructor
+    pub fn new(key: RsaPrivateKey<'a>) -> Self {
+        Self {
+            key,
+            _digest: PhantomData,
+        }
+    }
+
+    /// Underlying private key
+    pub fn key(&self) -> &RsaPrivateKey<'a> {
+        &self.key
+    }
+}
+
+impl<D: Digest + AssociatedOid, const N: usize> Signer<Signature<N>> for Pkcs1v15SigningKey<'_, D> {
+    fn try_sign(&self, msg: &[u8]) -> Result<Signature<N>, signature::Error> {
+        self.try_sign_digest(D::new_with_prefix(msg))
+    }
+}
+
+impl<D: Digest + AssociatedOid, const N: usize> DigestSigner<D, Signature<N>>
+    for Pkcs1v15SigningKey<'_, D>
+{
+    fn try_sign_digest(&self, digest: D) -> Result<Signature<N>, signature::Error> {
+        let mut signature = [0; N];
+        self.key
+            .sign_pkcs1v15::<D>(&digest.finalize(), &mut signature)
+            .map_err(|_| signature::Error::new())?;
+        Ok(Signature(signature))
+    }
+}
+
+/// PSS signature verifier for a digest `D`
+pub struct PssVerifyingKey<'a, D> {
+    key: RsaPublicKey<'a>,
+    _digest: PhantomData<D>,
+}
+
+impl<'a, D: Digest> PssVerifyingKey<'a, D> {
+    /// Constructor
+    pub fn new(key: RsaPublicKey<'a>) -> Self {
+        Self {
+            key,
+            _digest: PhantomData,
+        }
+    }
+
+    /// Underlying public key
+    pub fn key(&self) -> &RsaPublicKey<'a> {
+        &self.key
+    }
+}
+
+impl<D: Digest, const N: usize> Verifier<Signature<N>> for PssVerifyingKey<'_, D> {
+    fn verify(&self, msg: &[u8], signature: &Signature<N>) -> Result<(), signature::Error> {
+        self.verify_digest(D::new_with_prefix(msg), signature)
+    }
+}
+
+impl<D: Digest, const N: usize> DigestVerifier<D, Signature<N>> for PssVerifyingKey<'_, D> {
+    fn verify_digest(&self, digest: D, signature: &Signature<N>) -> Result<(), signature::Error> {
+        self.key
+            .verify_pss::<D>(&digest.finalize(), &signature.0)
+            .map_err(|_| signature::Error::new())
+    }
+}
+
+/// An error type representing failure modes of RSA operations
+#[allow(missing_docs)]
+#[derive(Debug)]
+pub enum RsaFailure {
+    WrongInputParameterLength {
+        faulty_slice: &'static str,
+        expected_length: ExpectedLengthError,
+        actual_length: usize,
+    },
+    /// Should be 4-aligned
+    WrongInputParameterAlignment {
+        faulty_slice: &'static str,
+    },
+    /// Modulus has a leading zero byte
+    NotMinimallyEncoded {
+        faulty_slice: &'static str,
+    },
+    /// Input is not smaller than the modulus
+    InputOutOfRange {
+        faulty_slice: &'static str,
+    },
+    /// Message does not fit into the encoding for this key size and digest
+    MessageTooLong,
+    /// Signature does not match the message
+    InvalidSignature,
+    /// Ciphertext is not a valid encoding; deliberately carries no detail
+    DecryptionFailure,
+    /// Private key operation result did not match the public key
+    FaultDetected,
+    CalculateCnsFailure(CalculateCnsFailure),
+    ExpModFailure(ExpModFailure),
+}
+
+impl From<CalculateCnsFailure> for RsaFailure {
+    fn from(f: CalculateCnsFailure) -> Self {
+        RsaFailure::CalculateCnsFailure(f)
+    }
+}
+
+impl From<ExpModFailure> for RsaFailure {
+    fn from(f: ExpModFailure) -> Self {
+        RsaFailure::ExpModFailure(f)
+    }
+}
+
+fn validate_length(
+    faulty_slice: &'static str,
+    slice: &[u8],
+    expected_length: usize,
+) -> Result<(), RsaFailure> {
+    if slice.len() != expected_length {
+        return Err(RsaFailure::WrongInputParameterLength {
+            faulty_slice,
+            expected_length: ExpectedLengthError::Exactly(expected_length),
+            actual_length: slice.len(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_length_at_least(
+    faulty_slice: &'static str,
+    slice: &[u8],
+    expected_length: usize,
+) -> Result<(), RsaFailure> {
+    if slice.len() < expected_length {
+        return Err(RsaFailure::WrongInputParameterLength {
+            faulty_slice,
+            expected_length: ExpectedLengthError::AtLeast(expected_length),
+            actual_length: slice.len(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_modulus(
+    faulty_slice: &'static str,
+    modulus: &[u8],
+    max_length: usize,
+) -> Result<(), RsaFailure> {
+    if modulus.len() % 4 != 0 {
+        return Err(RsaFailure::WrongInputParameterAlignment { faulty_slice });
+    }
+    validate_length_at_least(faulty_slice, modulus, MIN_MODULUS_LENGTH)?;
+    if modulus.len() > max_length {
+        return Err(RsaFailure::WrongInputParameterLength {
+            faulty_slice,
+            expected_length: ExpectedLengthError::AtMost(max_length),
+            actual_length: modulus.len(),
+        });
+    }
+    Ok(())
+}
+
+/// `n` additionally must not be zero padded as signatures and ciphertexts
+/// are as long as it is
+fn validate_public_modulus(modulus: &[u8]) -> Result<(), RsaFailure> {
+    validate_modulus("modulus", modulus, MAX_MODULUS_LENGTH)?;
+    if modulus[0] == 0 {
+        return Err(RsaFailure::NotMinimallyEncoded {
+            faulty_slice: "modulus",
+        });
+    }
+    Ok(())
+}
+
+fn validate_exponent(
+    faulty_slice: &'static str,
+    exponent: &[u8],
+    modulus: &[u8],
+) -> Result<(), RsaFailure> {
+    validate_length_at_least(faulty_slice, exponent, 1)?;
+    if exponent.len() > modulus.len() {
+        return Err(RsaFailure::WrongInputParameterLength {
+            faulty_slice,
+            expected_length: ExpectedLengthError::AtMost(modulus.len()),
+            actual_length: exponent.len(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_cns(faulty_slice: &'static str, cns: &[u8], modulus: &[u8]) -> Result<(), RsaFailure> {
+    validate_length(faulty_slice, cns, modulus.len() + 5)
+}
+
+fn validate_private_components(components: &RsaPrivateKeyComponents) -> Result<(), RsaFailure> {
+    validate_public_modulus(components.modulus)?;
+    validate_exponent(
+        "public_exponent",
+        components.public_exponent,
+        components.modulus,
+    )?;
+    validate_modulus("prime_1", components.prime_1, MAX_PRIME_LENGTH)?;
+    validate_modulus("prime_2", components.prime_2, MAX_PRIME_LENGTH)?;
+    validate_exponent("exponent_1", components.exponent_1, components.prime_1)?;
+    validate_exponent("exponent_2", components.exponent_2, components.prime_2)?;
+    validate_exponent("coefficient", components.coefficient, components.prime_1)
+}
+
+/// Input must be exactly as long as the modulus and smaller than it
+fn validate_representative(
+    faulty_slice: &'static str,
+    input: &[u8],
+    modulus: &[u8],
+) -> Result<(), RsaFailure> {
+    validate_length(faulty_slice, input, modulus.len())?;
+    if input >= modulus {
+        return Err(RsaFailure::InputOutOfRange { faulty_slice });
+    }
+    Ok(())
+}
+
+/// Largest [`ExpModWindowSize`] whose workspace still fits into CryptoRAM
+/// (see the data layout of [`Pukcc::modular_exponentiation`])
+fn window_size(modulus_length: usize, exponent_length: usize) -> ExpModWindowSize {
+    let parameters = (modulus_length + 4)
+        + (modulus_length + 8)
+        + (modulus_length + 16)
+        + (exponent_length + 4 + padding_for_len(exponent_length));
+    let fits =
+        |multiplier: usize| parameters + multiplier * (modulus_length + 4) + 8 <= CRYPTO_RAM_LENGTH;
+    if fits(10) {
+        ExpModWindowSize::Four
+    } else if fits(6) {
+        ExpModWindowSize::Three
+    } else if fits(4) {
+        ExpModWindowSize::Two
+    } else {
+        ExpModWindowSize::One
+    }
+}
+
+/// EMSA-PKCS1-v1_5: `0x00 || 0x01 || PS || 0x00 || DigestInfo`
+fn emsa_pkcs1v15_encode<D: Digest + AssociatedOid>(
+    hashed: &[u8],
+    em: &mut [u8],
+) -> Result<(), RsaFailure> {
+    let h_len = <D as Digest>::output_size();
+    validate_length("hashed", hashed, h_len)?;
+    let oid = D::OID;
+    let oid = oid.as_bytes();
+    // DER: SEQUENCE { SEQUENCE { OID, NULL }, OCTET STRING }
+    let algorithm_length = oid.len() + 4;
+    let digest_info_length = algorithm_length + h_len + 4;
+    let t_len = digest_info_length + 2;
+    // At least 8 bytes of PS are mandatory
+    if em.len() < t_len + 11 || digest_info_length > 0x7f {
+        return Err(RsaFailure::MessageTooLong);
+    }
+    let ps_end = em.len() - t_len - 1;
+    em[0] = 0x00;
+    em[1] = 0x01;
+    em[2..ps_end].fill(0xff);
+    em[ps_end] = 0x00;
+    let t = &mut em[ps_end + 1..];
+    t[..4].copy_from_slice(&[0x30, digest_info_length as u8, 0x30, algorithm_length as u8]);
+    t[4..6].copy_from_slice(&[0x06, oid.len() as u8]);
+    t[6..6 + oid.len()].copy_from_slice(oid);
+    let t = &mut t[6 + oid.len()..];
+    t[..4].copy_from_slice(&[0x05, 0x00, 0x04, h_len as u8]);
+    t[4..].copy_from_slice(hashed);
+    Ok(())
+}
+
+/// EMSA-PSS-VERIFY with MGF1 and salt length recovery
+fn emsa_pss_verify<D: Digest>(
+    hashed: &[u8],
+    em: &mut [u8],
+    em_bits: usize,
+) -> Result<(), RsaFailure> {
+    let h_len = <D as Digest>::output_size();
+    validate_length("hashed", hashed, h_len)?;
+    let em_len = em.len();
+    if em_len < h_len + 2 || em[em_len - 1] != 0xbc {
+        return Err(RsaFailure::InvalidSignature);
+    }
+    let (db, tail) = em.split_at_mut(em_len - h_len - 1);
+    let h = &tail[..h_len];
+    let top_bits_mask = u8::MAX >> (8 * em_len - em_bits);
+    if db[0] & !top_bits_mask != 0 {
+        return Err(RsaFailure::InvalidSignature);
+    }
+    mgf1_xor::<D>(db, h);
+    db[0] &= top_bits_mask;
+    let Some(separator) = db.iter().position(|&byte| byte != 0) else {
+        return Err(RsaFailure::InvalidSignature);
+    };
+    if db[separator] != 0x01 {
+        return Err(RsaFailure::InvalidSignature);
+    }
+    let salt = &db[separator + 1..];
+    let expected: Output<D> = D::new()
+        .chain_update([0; PSS_PREFIX_LENGTH])
+        .chain_update(hashed)
+        .chain_update(salt)
+        .finalize();
+    if ct_eq(h, &expected) {
+        Ok(())
+    } else {
+        Err(RsaFailure::InvalidSignature)
+    }
+}
+
+/// XOR `output` with MGF1 of `seed`
+fn mgf1_xor<D: Digest>(output: &mut [u8], seed: &[u8]) {
+    for (counter, chunk) in output.chunks_mut(<D as Digest>::output_size()).enumerate() {
+        let mask = D::new()
+            .chain_update(seed)
+            .chain_update((counter as u32).to_be_bytes())
+            .finalize();
+        chunk
+            .iter_mut()
+            .zip(mask)
+            .for_each(|(byte, mask)| *byte ^= mask);
+    }
+}
+
+/// Number of significant bits of a big endian value
+fn bit_length(value: &[u8]) -> usize {
+    match value.iter().position(|&byte| byte != 0) {
+        Some(index) => (value.len() - index) * 8 - value[index].leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+/// Constant time slice equality
+fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// `0xff` if `value` is true, `0x00` otherwise
+fn ct_mask(value: bool) -> u8 {
+    u8::from(value).wrapping_neg()
+}
+
+/// `0xff` if `value` is zero, `0x00` otherwise
+fn ct_is_zero(value: u8) -> u8 {
+    // Top bit of `value - 1` is set only if `value` was zero
+    ((u16::from(value).wrapping_sub(1) >> 8) as u8 & 1).wrapping_neg()
+}
+
+// Software arithmetic on big endian values used for CRT recombination. All of
+// it operates on equally long slices and avoids data dependent branches.
+
+/// Subtract `modulus` from `value` if `overflow` is set or `value >= modulus`
+fn conditional_subtract(value: &mut [u8], modulus: &[u8], overflow: u8) {
+    let mut borrow = 0;
+    for (&a, &b) in value.iter().rev().zip(modulus.iter().rev()) {
+        borrow = (i16::from(a) - i16::from(b) - borrow < 0) as i16;
+    }
+    let mask = (overflow | (borrow as u8 ^ 1)).wrapping_neg();
+    let mut borrow = 0;
+    for (a, &b) in value.iter_mut().rev().zip(modulus.iter().rev()) {
+        let difference = i16::from(*a) - i16::from(b & mask) - borrow;
+        *a = difference as u8;
+        borrow = (difference < 0) as i16;
+    }
+}
+
+/// `value = (2 * value + bit) mod modulus` for `value < modulus`
+fn mod_shift_in(value: &mut [u8], bit: u8, modulus: &[u8]) {
+    let mut carry = bit;
+    for byte in value.iter_mut().rev() {
+        let top = *byte >> 7;
+        *byte = (*byte << 1) | carry;
+        carry = top;
+    }
+    conditional_subtract(value, modulus, carry);
+}
+
+/// `value = (value + (addend & mask)) mod modulus` for `value, addend <
+/// modulus`
+fn mod_add_masked(value: &mut [u8], addend: &[u8], mask: u8, modulus: &[u8]) {
+    let mut carry = 0;
+    for (a, &b) in value.iter_mut().rev().zip(addend.iter().rev()) {
+        let sum = u16::from(*a) + u16::from(b & mask) + carry;
+        *a = sum as u8;
+        carry = sum >> 8;
+    }
+    conditional_subtract(value, modulus, carry as u8);
+}
+
+/// `value = (value - subtrahend) mod modulus` for `value, subtrahend < modulus`
+fn mod_sub(value: &mut [u8], subtrahend: &[u8], modulus: &[u8]) {
+    let mut borrow = 0;
+    for (a, &b) in value.iter_mut().rev().zip(subtrahend.iter().rev()) {
+        let difference = i16::from(*a) - i16::from(b) - borrow;
+        *a = difference as u8;
+        borrow = (difference < 0) as i16;
+    }
+    let mask = (borrow as u8).wrapping_neg();
+    let mut carry = 0;
+    for (a, &b) in value.iter_mut().rev().zip(modulus.iter().rev()) {
+        let sum = u16::from(*a) + u16::from(b & mask) + carry;
+        *a = sum as u8;
+        carry = sum >> 8;
+    }
+}
+
+/// `result = value mod modulus` for a `value` of any length
+fn reduce(value: &[u8], modulus: &[u8], result: &mut [u8]) {
+    result.fill(0);
+    for &byte in value {
+        for shift in (0..8).rev() {
+            mod_shift_in(result, (byte >> shift) & 1, modulus);
+        }
+    }
+}
+
+/// `result = a * b mod modulus` for `b < modulus`
+fn mod_mul(a: &[u8], b: &[u8], modulus: &[u8], result: &mut [u8]) {
+    result.fill(0);
+    for &byte in a {
+        for shift in (0..8).rev() {
+            mod_shift_in(result, 0, modulus);
+            mod_add_masked(result, b, ((byte >> shift) & 1).wrapping_neg(), modulus);
+        }
+    }
+}
+
+/// `result = addend + a * b`, the result being known to fit into `result`
+fn mul_add(a: &[u8], b: &[u8], addend: &[u8], result: &mut [u8]) {
+    let length = result.len();
+    result.fill(0);
+    result[length - addend.len()..].copy_from_slice(addend);
+    for (i, &x) in a.iter().rev().enumerate() {
+        let mut carry = 0;
+        for position in i..length {
+            let y = position
+                .checked_sub(i)
+                .and_then(|j| b.len().checked_sub(j + 1))
+                .map_or(0, |j| b[j]);
+            let byte = &mut result[length - 1 - position];
+            let sum = u32::from(*byte) + u32::from(x) * u32::from(y) + carry;
+            *byte = sum as u8;
+            carry = sum >> 8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_u64(value: u64) -> [u8; 8] {
+        value.to_be_bytes()
+    }
+
+    fn to_u64(value: &[u8]) -> u64 {
+        value
+            .iter()
+            .fold(0, |acc, &byte| (acc << 8) | u64::from(byte))
+    }
+
+    #[test]
+    fn crt_arithmetic() {
+        let modulus = from_u64(0xffff_fffb);
+        let value = from_u64(0x1234_5678_9abc_def0);
+        let mut result = [0; 8];
+
+        reduce(&value, &modulus, &mut result);
+        assert_eq!(to_u64(&result), 0x1234_5678_9abc_def0 % 0xffff_fffb);
+
+        let b = from_u64(0xdead_beef);
+        mod_mul(&value, &b, &modulus, &mut result);
+        let expected = (0x1234_5678_9abc_def0_u128 % 0xffff_fffb) * 0xdead_beef % 0xffff_fffb;
+        assert_eq!(u128::from(to_u64(&result)), expected);
+
+        let mut difference = from_u64(5);
+        mod_sub(&mut difference, &from_u64(7), &modulus);
+        assert_eq!(to_u64(&difference), 0xffff_fffb - 2);
+
+        let mut product = [0; 8];
+        mul_add(
+            &from_u64(0xffff_ffff),
+            &from_u64(0xffff_fffb),
+            &from_u64(9),
+            &mut product,
+        );
+        assert_eq!(to_u64(&product), 0xffff_ffff * 0xffff_fffb + 9);
+    }
+
+    #[test]
+    fn constant_time_helpers() {
+        assert_eq!(ct_is_zero(0), 0xff);
+        assert_eq!(ct_is_zero(1), 0x00);
+        assert_eq!(ct_is_zero(0x80), 0x00);
+        assert!(ct_eq(&[1, 2], &[1, 2]));
+        assert!(!ct_eq(&[1, 2], &[1, 3]));
+        assert_eq!(bit_length(&[0, 0, 0x01, 0xff]), 9);
+    }
+}
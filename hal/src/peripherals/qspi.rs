@@ -4,6 +4,9 @@ use crate::{
     pac::{self, Mclk},
 };
 use core::marker::PhantomData;
+use embedded_storage::nor_flash::{
+    ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash, check_erase, check_read, check_write,
+};
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -381,6 +384,149 @@ impl<MODE> Qspi<MODE> {
     }
 }
 
+/// Size of a program page of the QSPI flash
+const FLASH_PAGE_SIZE: u32 = 256;
+/// Size of a sector of the QSPI flash, the smallest erasable unit
+const FLASH_SECTOR_SIZE: u32 = 4096;
+/// Size of a block of the QSPI flash
+const FLASH_BLOCK_SIZE: u32 = 65536;
+
+/// QSPI flash chip used as NOR flash storage
+///
+/// Implements the [`embedded_storage`] NOR flash traits (and the
+/// `embedded-storage-async` ones when the `async` feature is enabled) on top
+/// of a [`Qspi`] in [`OneShot`] mode, so crates such as `sequential-storage`
+/// or `ekv` can use the external flash.
+///
+/// The flash is assumed to follow the common 25-series command set, with 256
+/// byte program pages, 4 KiB sectors and 64 KiB blocks, and to have its quad
+/// mode already enabled, as [`Qspi::read_memory`] and [`Qspi::write_memory`]
+/// require.
+pub struct QspiFlash {
+    qspi: Qspi<OneShot>,
+    capacity: u32,
+}
+
+impl QspiFlash {
+    /// Wrap a [`Qspi`] driving a flash chip of `capacity` bytes
+    pub fn new(qspi: Qspi<OneShot>, capacity: u32) -> Self {
+        Self { qspi, capacity }
+    }
+
+    /// Return the underlying [`Qspi`]
+    pub fn free(self) -> Qspi<OneShot> {
+        self.qspi
+    }
+
+    /// Wait for the write in progress bit of the status register to clear
+    fn wait_ready(&self) {
+        let mut status = [0u8; 1];
+        loop {
+            // `ReadStatus` is a read command, so this cannot fail
+            let _ = self.qspi.read_command(Command::ReadStatus, &mut status);
+            if status[0] & 0x01 == 0 {
+                break;
+            }
+        }
+    }
+
+    /// Erase a single sector or block at `address`, then wait for completion
+    fn erase_unit(&mut self, command: Command, address: u32) {
+        // Both are commands accepted by their respective functions
+        let _ = self.qspi.run_command(Command::WriteEnable);
+        let _ = self.qspi.erase_command(command, address);
+        self.wait_ready();
+    }
+}
+
+impl ErrorType for QspiFlash {
+    type Error = NorFlashErrorKind;
+}
+
+impl ReadNorFlash for QspiFlash {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        check_read(self, offset, bytes.len())?;
+        self.wait_ready();
+        self.qspi.read_memory(offset, bytes);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity as usize
+    }
+}
+
+impl NorFlash for QspiFlash {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = FLASH_SECTOR_SIZE as usize;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        check_erase(self, from, to)?;
+        let mut address = from;
+        while address < to {
+            // Use the larger block erase whenever a whole aligned block is
+            // covered, as it is much faster than erasing its sectors
+            if address % FLASH_BLOCK_SIZE == 0 && to - address >= FLASH_BLOCK_SIZE {
+                self.erase_unit(Command::EraseBlock, address);
+                address += FLASH_BLOCK_SIZE;
+            } else {
+                self.erase_unit(Command::EraseSector, address);
+                address += FLASH_SECTOR_SIZE;
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        check_write(self, offset, bytes.len())?;
+        let mut address = offset;
+        let mut bytes = bytes;
+        while !bytes.is_empty() {
+            // A page program wraps around within the page, so writes must be
+            // split at page boundaries
+            let room = (FLASH_PAGE_SIZE - address % FLASH_PAGE_SIZE) as usize;
+            let (chunk, rest) = bytes.split_at(room.min(bytes.len()));
+            self.wait_ready();
+            // `WriteEnable` is accepted by `run_command`
+            let _ = self.qspi.run_command(Command::WriteEnable);
+            self.qspi.write_memory(address, chunk);
+            address += chunk.len() as u32;
+            bytes = rest;
+        }
+        self.wait_ready();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_storage_async::nor_flash::ReadNorFlash for QspiFlash {
+    const READ_SIZE: usize = <Self as ReadNorFlash>::READ_SIZE;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        ReadNorFlash::read(self, offset, bytes)
+    }
+
+    fn capacity(&self) -> usize {
+        ReadNorFlash::capacity(self)
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_storage_async::nor_flash::NorFlash for QspiFlash {
+    const WRITE_SIZE: usize = <Self as NorFlash>::WRITE_SIZE;
+    const ERASE_SIZE: usize = <Self as NorFlash>::ERASE_SIZE;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        NorFlash::erase(self, from, to)
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        NorFlash::write(self, offset, bytes)
+    }
+}
+
 #[derive(Default, Debug, Copy, Clone)]
 struct TransferMode {
     quad_width: bool,
@@ -1,6 +1,29 @@
+//! # TRNG - True Random Number Generator
+//!
+//! [`Trng`] reads 32-bit words from the TRNG, busy-waiting for each one, and
+//! implements [`RngCore`] and [`CryptoRng`]. [`CheckedTrng`] additionally
+//! runs continuous health tests on the output and reports failures through
+//! [`TryRngCore`]. With the `async` feature, [`Trng::into_future`] returns a
+//! [`TrngFuture`] which waits for the `TRNG` interrupt instead, and is
+//! health tested the same way.
+//!
+//! ## Health tests
+//!
+//! The health tests follow the repetition count and adaptive proportion tests
+//! of NIST SP 800-90B, applied to the 32-bit output words with an assumed
+//! min-entropy of 16 bits per word and a false positive probability below
+//! 2^-20. The first 1024 words after creation or a reset are tested and
+//! discarded as start-up test. A failure is sticky: every following request
+//! returns the same [`HealthError`] until the tests are reset.
+//!
+//! The TRNG output is already post-processed by the hardware, so these tests
+//! only catch gross failures such as a stuck generator.
+
+use core::fmt;
+
 use crate::pac::{self, Mclk};
 
-use rand_core::{CryptoRng, RngCore};
+use rand_core::{CryptoRng, RngCore, TryCryptoRng, TryRngCore};
 
 use crate::ehal_02::blocking::rng::Read;
 
@@ -69,3 +92,356 @@ impl Read for Trng {
         Ok(())
     }
 }
+
+/// Number of words tested and discarded before any output
+const STARTUP_SAMPLES: u16 = 1024;
+
+/// Number of identical consecutive words failing the repetition count test
+const REPETITION_CUTOFF: u8 = 3;
+
+/// Window size of the adaptive proportion test
+const PROPORTION_WINDOW: u16 = 512;
+
+/// Number of occurrences of the first word of a window failing the adaptive
+/// proportion test
+const PROPORTION_CUTOFF: u16 = 4;
+
+/// A failed TRNG health test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HealthError {
+    /// The same word was generated too many times in a row
+    RepetitionCount,
+    /// A word occurred too often within the adaptive proportion window
+    AdaptiveProportion,
+}
+
+impl fmt::Display for HealthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RepetitionCount => f.write_str("TRNG repetition count test failed"),
+            Self::AdaptiveProportion => f.write_str("TRNG adaptive proportion test failed"),
+        }
+    }
+}
+
+/// State of the continuous health tests
+struct HealthTests {
+    failure: Option<HealthError>,
+    startup: u16,
+    last: u32,
+    repetitions: u8,
+    reference: u32,
+    occurrences: u16,
+    window: u16,
+}
+
+impl HealthTests {
+    const fn new() -> Self {
+        Self {
+            failure: None,
+            startup: STARTUP_SAMPLES,
+            last: 0,
+            repetitions: 0,
+            reference: 0,
+            occurrences: 0,
+            window: 0,
+        }
+    }
+
+    /// Run the tests on a new word
+    ///
+    /// Returns `Ok(false)` while the word has to be discarded as part of the
+    /// start-up test.
+    fn check(&mut self, word: u32) -> Result<bool, HealthError> {
+        if let Some(failure) = self.failure {
+            return Err(failure);
+        }
+
+        if self.repetitions > 0 && word == self.last {
+            self.repetitions += 1;
+            if self.repetitions >= REPETITION_CUTOFF {
+                return Err(*self.failure.insert(HealthError::RepetitionCount));
+            }
+        } else {
+            self.last = word;
+            self.repetitions = 1;
+        }
+
+        if self.window == 0 {
+            self.reference = word;
+            self.occurrences = 1;
+        } else if word == self.reference {
+            self.occurrences += 1;
+            if self.occurrences >= PROPORTION_CUTOFF {
+                return Err(*self.failure.insert(HealthError::AdaptiveProportion));
+            }
+        }
+        self.window = (self.window + 1) % PROPORTION_WINDOW;
+
+        if self.startup > 0 {
+            self.startup -= 1;
+            Ok(false)
+        } else {
+            Ok(true)
+        }
+    }
+}
+
+/// Health tested TRNG
+///
+/// Implements [`TryRngCore`] and [`TryCryptoRng`], returning a
+/// [`HealthError`] once a health test failed. Wrap it in
+/// [`rand_core::UnwrapErr`] where an infallible [`CryptoRng`] is required.
+pub struct CheckedTrng {
+    trng: Trng,
+    tests: HealthTests,
+}
+
+impl CheckedTrng {
+    /// Start health testing the output of `trng`
+    ///
+    /// The start-up test runs on the first request.
+    pub fn new(trng: Trng) -> Self {
+        Self {
+            trng,
+            tests: HealthTests::new(),
+        }
+    }
+
+    /// Returns the health test failure, if any
+    pub fn health_failure(&self) -> Option<HealthError> {
+        self.tests.failure
+    }
+
+    /// Clear a health test failure and restart the tests, including the
+    /// start-up test
+    pub fn reset_health_tests(&mut self) {
+        self.tests = HealthTests::new();
+    }
+
+    /// Releases the [`Trng`]
+    pub fn free(self) -> Trng {
+        self.trng
+    }
+}
+
+impl TryRngCore for CheckedTrng {
+    type Error = HealthError;
+
+    fn try_next_u32(&mut self) -> Result<u32, Self::Error> {
+        loop {
+            let word = self.trng.random_u32();
+            if self.tests.check(word)? {
+                return Ok(word);
+            }
+        }
+    }
+
+    fn try_next_u64(&mut self) -> Result<u64, Self::Error> {
+        let lower_half = self.try_next_u32()? as u64;
+        let upper_half = self.try_next_u32()? as u64;
+        Ok((upper_half << 32) | lower_half)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Self::Error> {
+        for chunk in dest.chunks_mut(4) {
+            chunk.copy_from_slice(&self.try_next_u32()?.to_le_bytes()[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
+
+impl TryCryptoRng for CheckedTrng {}
+
+/// Interrupt handler for the `TRNG` interrupt, used by [`TrngFuture`]
+#[cfg(feature = "async")]
+pub struct InterruptHandler {
+    _private: (),
+}
+
+#[cfg(feature = "async")]
+impl crate::typelevel::Sealed for InterruptHandler {}
+
+#[cfg(feature = "async")]
+static TRNG_WAKER: embassy_sync::waitqueue::AtomicWaker =
+    embassy_sync::waitqueue::AtomicWaker::new();
+
+#[cfg(feature = "async")]
+impl crate::async_hal::interrupts::Handler<crate::async_hal::interrupts::TRNG>
+    for InterruptHandler
+{
+    unsafe fn on_interrupt() {
+        let trng = unsafe { &*pac::Trng::ptr() };
+        if trng.intenset().read().datardy().bit_is_set()
+            && trng.intflag().read().datardy().bit_is_set()
+        {
+            // DATARDY is cleared by reading DATA, which is left to the task
+            trng.intenclr().write(|w| w.datardy().set_bit());
+            TRNG_WAKER.wake();
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl Trng {
+    /// Turn a [`Trng`] into a health tested [`TrngFuture`]
+    ///
+    /// The `TRNG` interrupt has to be bound to [`InterruptHandler`]. It is
+    /// enabled in the NVIC by this method.
+    pub fn into_future<I>(self, _interrupts: I) -> TrngFuture
+    where
+        I: crate::async_hal::interrupts::Binding<
+                crate::async_hal::interrupts::TRNG,
+                InterruptHandler,
+            >,
+    {
+        use crate::async_hal::interrupts::{Interrupt, TRNG};
+
+        TRNG::unpend();
+        unsafe { TRNG::enable() };
+        TrngFuture {
+            trng: self,
+            tests: HealthTests::new(),
+        }
+    }
+}
+
+/// `async` and health tested version of [`Trng`]
+///
+/// Create this struct by calling [`Trng::into_future`].
+#[cfg(feature = "async")]
+pub struct TrngFuture {
+    trng: Trng,
+    tests: HealthTests,
+}
+
+#[cfg(feature = "async")]
+impl TrngFuture {
+    /// Wait for the next word from the TRNG
+    async fn read_word(&mut self) -> u32 {
+        use core::task::Poll;
+
+        let trng = &self.trng.0;
+        core::future::poll_fn(|cx| {
+            if trng.intflag().read().datardy().bit_is_set() {
+                return Poll::Ready(trng.data().read().bits());
+            }
+            TRNG_WAKER.register(cx.waker());
+            trng.intenset().write(|w| w.datardy().set_bit());
+            if trng.intflag().read().datardy().bit_is_set() {
+                trng.intenclr().write(|w| w.datardy().set_bit());
+                Poll::Ready(trng.data().read().bits())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Generate a health tested random `u32`
+    pub async fn next_u32(&mut self) -> Result<u32, HealthError> {
+        loop {
+            let word = self.read_word().await;
+            if self.tests.check(word)? {
+                return Ok(word);
+            }
+        }
+    }
+
+    /// Generate a health tested random `u64`
+    pub async fn next_u64(&mut self) -> Result<u64, HealthError> {
+        let lower_half = self.next_u32().await? as u64;
+        let upper_half = self.next_u32().await? as u64;
+        Ok((upper_half << 32) | lower_half)
+    }
+
+    /// Fill `dest` with health tested random bytes
+    pub async fn fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), HealthError> {
+        for chunk in dest.chunks_mut(4) {
+            chunk.copy_from_slice(&self.next_u32().await?.to_le_bytes()[..chunk.len()]);
+        }
+        Ok(())
+    }
+
+    /// Returns the health test failure, if any
+    pub fn health_failure(&self) -> Option<HealthError> {
+        self.tests.failure
+    }
+
+    /// Clear a health test failure and restart the tests, including the
+    /// start-up test
+    pub fn reset_health_tests(&mut self) {
+        self.tests = HealthTests::new();
+    }
+
+    /// Releases the [`Trng`]
+    pub fn free(self) -> Trng {
+        self.trng.0.intenclr().write(|w| w.datardy().set_bit());
+        self.trng
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `words` through the tests, returning the first failure
+    fn run(tests: &mut HealthTests, words: impl Iterator<Item = u32>) -> Result<(), HealthError> {
+        for word in words {
+            tests.check(word)?;
+        }
+        Ok(())
+    }
+
+    /// Distinct words spread over the whole range
+    fn distinct(n: u32) -> impl Iterator<Item = u32> {
+        (0..n).map(|i| i.wrapping_mul(0x9e37_79b9))
+    }
+
+    #[test]
+    fn startup_samples_are_discarded() {
+        let mut tests = HealthTests::new();
+        let mut words = distinct(STARTUP_SAMPLES as u32 + 1);
+        for word in words.by_ref().take(STARTUP_SAMPLES as usize) {
+            assert_eq!(tests.check(word), Ok(false));
+        }
+        assert_eq!(tests.check(words.next().unwrap()), Ok(true));
+    }
+
+    #[test]
+    fn repetition_count_fails_and_latches() {
+        let mut tests = HealthTests::new();
+        assert_eq!(tests.check(7), Ok(false));
+        assert_eq!(tests.check(7), Ok(false));
+        assert_eq!(tests.check(7), Err(HealthError::RepetitionCount));
+        assert_eq!(tests.check(8), Err(HealthError::RepetitionCount));
+        assert_eq!(tests.failure, Some(HealthError::RepetitionCount));
+    }
+
+    #[test]
+    fn adaptive_proportion_fails_within_window() {
+        let mut tests = HealthTests::new();
+        let words = distinct(PROPORTION_WINDOW as u32)
+            .enumerate()
+            .map(|(i, word)| if i % 100 == 0 { 0 } else { word | 1 });
+        assert_eq!(run(&mut tests, words), Err(HealthError::AdaptiveProportion));
+    }
+
+    #[test]
+    fn adaptive_proportion_restarts_each_window() {
+        let mut tests = HealthTests::new();
+        let window = PROPORTION_WINDOW as usize;
+        // The first word of each window occurs 3 times, one less than the cutoff
+        let words = distinct(4 * PROPORTION_WINDOW as u32)
+            .enumerate()
+            .map(|(i, word)| {
+                if i % window % 200 == 0 {
+                    (i / window) as u32 * 2
+                } else {
+                    word | 1
+                }
+            });
+        assert_eq!(run(&mut tests, words), Ok(()));
+    }
+}
@@ -0,0 +1,862 @@
+//! Async USB device driver
+//!
+//! This module provides an implementation of the [`embassy_usb_driver`]
+//! traits, so the USB peripheral can be used with the `embassy-usb` device
+//! stack. All operations are interrupt-driven: futures register a waker and
+//! enable the relevant interrupt, and the [`InterruptHandler`] wakes them up.
+//!
+//! The USB interrupt(s) must be bound to the [`InterruptHandler`] with
+//! [`bind_interrupts!`](crate::bind_interrupts) (SAMD11/SAMD21) or
+//! [`bind_multiple_interrupts!`](crate::bind_multiple_interrupts) (SAMx5x):
+//!
+//! ```no_run
+//! use atsamd_hal::usb::async_api::{Driver, InterruptHandler};
+//!
+//! // SAMD11/SAMD21
+//! atsamd_hal::bind_interrupts!(struct Irqs {
+//!     USB => InterruptHandler;
+//! });
+//!
+//! // SAMx5x
+//! atsamd_hal::bind_multiple_interrupts!(struct Irqs {
+//!     USB: [USB_OTHER, USB_SOF_HSOF, USB_TRCPT0, USB_TRCPT1] => InterruptHandler;
+//! });
+//!
+//! let driver = Driver::new(&usb_clock, &mut mclk, pins.pa24, pins.pa25, peripherals.usb, Irqs);
+//! let mut builder = embassy_usb::Builder::new(driver, config, /* ... */);
+//! ```
+//!
+//! Each endpoint is given one allocation of
+//! [`ALLOC_SIZE_MAX_PER_EP`](super::ALLOC_SIZE_MAX_PER_EP) bytes from the
+//! same buffer used by [`UsbBus`](super::UsbBus), so [`Driver`] and
+//! [`UsbBus`](super::UsbBus) cannot both be created in the same application.
+//! Packets are transferred one at a time, with no multi-packet transfers.
+
+use super::devicedesc::{Descriptors, DeviceDescBank};
+use super::{ALLOC_SIZE_MAX_PER_EP, BufferAllocator, DmPad, DpPad};
+use crate::async_hal::interrupts::{Binding, Handler, InterruptSource, USB};
+use crate::calibration::{usb_transn_cal, usb_transp_cal, usb_trim_cal};
+use crate::clock;
+use crate::gpio::{AnyPin, PA24, PA25};
+use crate::pac::Usb;
+use crate::pac::usb::Device;
+use crate::util::BitIter;
+use atsamd_hal_macros::hal_cfg;
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+use critical_section::Mutex;
+use embassy_sync::waitqueue::AtomicWaker;
+use embassy_usb_driver::{
+    Direction, EndpointAddress, EndpointAllocError, EndpointError, EndpointInfo, EndpointType,
+    Event, Unsupported,
+};
+
+#[hal_cfg(any("usb-d11", "usb-d21"))]
+use crate::pac::Pm;
+
+#[hal_cfg("usb-d5x")]
+use crate::pac::Mclk;
+
+#[hal_cfg(any("usb-d11", "usb-d21"))]
+use crate::pac::usb::device as ep_regs;
+
+#[hal_cfg("usb-d5x")]
+use crate::pac::usb::device::device_endpoint as ep_regs;
+
+/// Number of endpoints per direction, including the control endpoint
+const NUM_ENDPOINTS: usize = 8;
+
+// EPINTFLAG bits
+const TRCPT0: u8 = 1 << 0;
+const TRCPT1: u8 = 1 << 1;
+const TRFAIL0: u8 = 1 << 2;
+const TRFAIL1: u8 = 1 << 3;
+const RXSTP: u8 = 1 << 4;
+const STALL0: u8 = 1 << 5;
+const STALL1: u8 = 1 << 6;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NEW_WAKER: AtomicWaker = AtomicWaker::new();
+static BUS_WAKER: AtomicWaker = NEW_WAKER;
+static EP_IN_WAKERS: [AtomicWaker; NUM_ENDPOINTS] = [NEW_WAKER; NUM_ENDPOINTS];
+static EP_OUT_WAKERS: [AtomicWaker; NUM_ENDPOINTS] = [NEW_WAKER; NUM_ENDPOINTS];
+
+/// The endpoint descriptor table is read by the USB peripheral through DMA,
+/// so it lives in a static and never moves once its address is written to
+/// DESCADD.
+static DESCRIPTORS: Mutex<RefCell<Option<Descriptors>>> = Mutex::new(RefCell::new(None));
+
+#[inline]
+fn usb() -> &'static Device {
+    unsafe { (*Usb::ptr()).device() }
+}
+
+/// Runs `f` on the descriptor bank of `ep`.
+fn with_bank<R>(ep: EndpointAddress, f: impl FnOnce(&mut DeviceDescBank) -> R) -> R {
+    critical_section::with(|cs| {
+        let mut desc = DESCRIPTORS.borrow_ref_mut(cs);
+        let desc = desc.as_mut().expect("USB descriptors not initialized");
+        let bank = if ep.is_in() { 1 } else { 0 };
+        f(desc.bank(ep.index(), bank))
+    })
+}
+
+fn waker(ep: EndpointAddress) -> &'static AtomicWaker {
+    match ep.direction() {
+        Direction::In => &EP_IN_WAKERS[ep.index()],
+        Direction::Out => &EP_OUT_WAKERS[ep.index()],
+    }
+}
+
+/// Registers of a single device endpoint
+struct EpRegs {
+    index: usize,
+}
+
+#[hal_cfg(any("usb-d11", "usb-d21"))]
+impl EpRegs {
+    fn epcfg(&self) -> &'static ep_regs::Epcfg {
+        usb().epcfg(self.index)
+    }
+
+    fn epstatus(&self) -> &'static ep_regs::Epstatus {
+        usb().epstatus(self.index)
+    }
+
+    fn epstatusset(&self) -> &'static ep_regs::Epstatusset {
+        usb().epstatusset(self.index)
+    }
+
+    fn epstatusclr(&self) -> &'static ep_regs::Epstatusclr {
+        usb().epstatusclr(self.index)
+    }
+
+    fn epintflag(&self) -> &'static ep_regs::Epintflag {
+        usb().epintflag(self.index)
+    }
+
+    fn epintenset(&self) -> &'static ep_regs::Epintenset {
+        usb().epintenset(self.index)
+    }
+
+    fn epintenclr(&self) -> &'static ep_regs::Epintenclr {
+        usb().epintenclr(self.index)
+    }
+}
+
+#[hal_cfg("usb-d5x")]
+impl EpRegs {
+    fn epcfg(&self) -> &'static ep_regs::Epcfg {
+        usb().device_endpoint(self.index).epcfg()
+    }
+
+    fn epstatus(&self) -> &'static ep_regs::Epstatus {
+        usb().device_endpoint(self.index).epstatus()
+    }
+
+    fn epstatusset(&self) -> &'static ep_regs::Epstatusset {
+        usb().device_endpoint(self.index).epstatusset()
+    }
+
+    fn epstatusclr(&self) -> &'static ep_regs::Epstatusclr {
+        usb().device_endpoint(self.index).epstatusclr()
+    }
+
+    fn epintflag(&self) -> &'static ep_regs::Epintflag {
+        usb().device_endpoint(self.index).epintflag()
+    }
+
+    fn epintenset(&self) -> &'static ep_regs::Epintenset {
+        usb().device_endpoint(self.index).epintenset()
+    }
+
+    fn epintenclr(&self) -> &'static ep_regs::Epintenclr {
+        usb().device_endpoint(self.index).epintenclr()
+    }
+}
+
+impl EpRegs {
+    fn new(index: usize) -> Self {
+        Self { index }
+    }
+
+    /// Returns `true` if the bank of `dir` is configured, which is the case
+    /// between [`Bus::endpoint_set_enabled`] and the next bus reset.
+    fn is_enabled(&self, dir: Direction) -> bool {
+        let epcfg = self.epcfg().read();
+        match dir {
+            Direction::Out => epcfg.eptype0().bits() != 0,
+            Direction::In => epcfg.eptype1().bits() != 0,
+        }
+    }
+}
+
+/// Value of the EPTYPE fields of EPCFG for each endpoint type
+fn eptype_bits(ep_type: EndpointType) -> u8 {
+    match ep_type {
+        EndpointType::Control => 1,
+        EndpointType::Isochronous => 2,
+        EndpointType::Bulk => 3,
+        EndpointType::Interrupt => 4,
+    }
+}
+
+/// Configuration of an allocated endpoint bank
+#[derive(Clone, Copy)]
+struct EpConfig {
+    ep_type: EndpointType,
+    max_packet_size: u16,
+    buffer: *mut u8,
+}
+
+// The buffer is only ever accessed by the endpoint owning it
+unsafe impl Send for EpConfig {}
+
+/// Interrupt handler for the async USB driver
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl crate::typelevel::Sealed for InterruptHandler {}
+
+impl Handler<USB> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let usb = usb();
+
+        // Disable interrupts, but don't clear the flags. The futures will take
+        // care of clearing flags and re-enabling interrupts when woken.
+        let pending = usb.intflag().read().bits() & usb.intenset().read().bits();
+        if pending != 0 {
+            usb.intenclr().write(|w| unsafe { w.bits(pending) });
+            BUS_WAKER.wake();
+        }
+
+        for index in BitIter(usb.epintsmry().read().bits() as u32) {
+            let index = index as usize;
+            let regs = EpRegs::new(index);
+            let pending = regs.epintflag().read().bits() & regs.epintenset().read().bits();
+            regs.epintenclr().write(|w| unsafe { w.bits(pending) });
+
+            if pending & (TRCPT0 | TRFAIL0 | RXSTP | STALL0) != 0 {
+                EP_OUT_WAKERS[index].wake();
+            }
+            if pending & (TRCPT1 | TRFAIL1 | STALL1) != 0 {
+                EP_IN_WAKERS[index].wake();
+            }
+        }
+    }
+}
+
+/// Async USB device driver
+///
+/// Endpoints are allocated by the `embassy-usb` builder, after which
+/// [`start`](embassy_usb_driver::Driver::start) splits the driver into a
+/// [`Bus`] and a [`ControlPipe`].
+pub struct Driver {
+    dm_pad: DmPad,
+    dp_pad: DpPad,
+    buffers: BufferAllocator,
+    ep_in: [Option<EpConfig>; NUM_ENDPOINTS],
+    ep_out: [Option<EpConfig>; NUM_ENDPOINTS],
+}
+
+impl Driver {
+    /// Creates the driver, enabling the bus clock of the USB peripheral.
+    ///
+    /// The peripheral itself is enabled by
+    /// [`Bus::enable`](embassy_usb_driver::Bus::enable).
+    #[hal_cfg(any("usb-d11", "usb-d21"))]
+    pub fn new<I>(
+        _clock: &clock::UsbClock,
+        pm: &mut Pm,
+        dm_pad: impl AnyPin<Id = PA24>,
+        dp_pad: impl AnyPin<Id = PA25>,
+        _usb: Usb,
+        _irqs: I,
+    ) -> Self
+    where
+        I: Binding<USB, InterruptHandler>,
+    {
+        pm.apbbmask().modify(|_, w| w.usb_().set_bit());
+        Self::init(dm_pad, dp_pad)
+    }
+
+    /// Creates the driver, enabling the bus clocks of the USB peripheral.
+    ///
+    /// The peripheral itself is enabled by
+    /// [`Bus::enable`](embassy_usb_driver::Bus::enable).
+    #[hal_cfg("usb-d5x")]
+    pub fn new<I>(
+        _clock: &clock::UsbClock,
+        mclk: &mut Mclk,
+        dm_pad: impl AnyPin<Id = PA24>,
+        dp_pad: impl AnyPin<Id = PA25>,
+        _usb: Usb,
+        _irqs: I,
+    ) -> Self
+    where
+        I: Binding<USB, InterruptHandler>,
+    {
+        mclk.ahbmask().modify(|_, w| w.usb_().set_bit());
+        mclk.apbbmask().modify(|_, w| w.usb_().set_bit());
+        Self::init(dm_pad, dp_pad)
+    }
+
+    fn init(dm_pad: impl AnyPin<Id = PA24>, dp_pad: impl AnyPin<Id = PA25>) -> Self {
+        critical_section::with(|cs| {
+            DESCRIPTORS.borrow_ref_mut(cs).replace(Descriptors::new());
+        });
+
+        USB::unpend();
+        unsafe { USB::enable() };
+
+        Self {
+            dm_pad: dm_pad.into().into_mode(),
+            dp_pad: dp_pad.into().into_mode(),
+            buffers: BufferAllocator::default(),
+            ep_in: [None; NUM_ENDPOINTS],
+            ep_out: [None; NUM_ENDPOINTS],
+        }
+    }
+
+    fn alloc_endpoint<D>(
+        &mut self,
+        dir: Direction,
+        ep_type: EndpointType,
+        max_packet_size: u16,
+        interval_ms: u8,
+    ) -> Result<Endpoint<D>, EndpointAllocError> {
+        if max_packet_size as usize > ALLOC_SIZE_MAX_PER_EP {
+            return Err(EndpointAllocError);
+        }
+
+        let table = match dir {
+            Direction::In => &mut self.ep_in,
+            Direction::Out => &mut self.ep_out,
+        };
+        // Endpoint 0 is reserved for the control pipe
+        let index = (1..NUM_ENDPOINTS)
+            .find(|&i| table[i].is_none())
+            .ok_or(EndpointAllocError)?;
+        let buffer = self
+            .buffers
+            .allocate_buffer()
+            .map_err(|_| EndpointAllocError)?;
+
+        table[index] = Some(EpConfig {
+            ep_type,
+            max_packet_size,
+            buffer,
+        });
+
+        Ok(Endpoint {
+            info: EndpointInfo {
+                addr: EndpointAddress::from_parts(index, dir),
+                ep_type,
+                max_packet_size,
+                interval_ms,
+            },
+            buffer,
+            _dir: PhantomData,
+        })
+    }
+
+    fn control_endpoint<D>(
+        &mut self,
+        dir: Direction,
+        max_packet_size: u16,
+    ) -> (EpConfig, Endpoint<D>) {
+        let buffer = self
+            .buffers
+            .allocate_buffer()
+            .expect("No buffer left for the control endpoint");
+        let config = EpConfig {
+            ep_type: EndpointType::Control,
+            max_packet_size,
+            buffer,
+        };
+        let endpoint = Endpoint {
+            info: EndpointInfo {
+                addr: EndpointAddress::from_parts(0, dir),
+                ep_type: EndpointType::Control,
+                max_packet_size,
+                interval_ms: 0,
+            },
+            buffer,
+            _dir: PhantomData,
+        };
+        (config, endpoint)
+    }
+}
+
+impl<'a> embassy_usb_driver::Driver<'a> for Driver {
+    type EndpointOut = Endpoint<Out>;
+    type EndpointIn = Endpoint<In>;
+    type ControlPipe = ControlPipe;
+    type Bus = Bus;
+
+    fn alloc_endpoint_out(
+        &mut self,
+        ep_type: EndpointType,
+        max_packet_size: u16,
+        interval_ms: u8,
+    ) -> Result<Self::EndpointOut, EndpointAllocError> {
+        self.alloc_endpoint(Direction::Out, ep_type, max_packet_size, interval_ms)
+    }
+
+    fn alloc_endpoint_in(
+        &mut self,
+        ep_type: EndpointType,
+        max_packet_size: u16,
+        interval_ms: u8,
+    ) -> Result<Self::EndpointIn, EndpointAllocError> {
+        self.alloc_endpoint(Direction::In, ep_type, max_packet_size, interval_ms)
+    }
+
+    fn start(mut self, control_max_packet_size: u16) -> (Self::Bus, Self::ControlPipe) {
+        let (out_config, ep_out) = self.control_endpoint(Direction::Out, control_max_packet_size);
+        let (in_config, ep_in) = self.control_endpoint(Direction::In, control_max_packet_size);
+        self.ep_out[0] = Some(out_config);
+        self.ep_in[0] = Some(in_config);
+
+        let bus = Bus {
+            _dm_pad: self.dm_pad,
+            _dp_pad: self.dp_pad,
+            ep_in: self.ep_in,
+            ep_out: self.ep_out,
+            power_detected: false,
+        };
+        let control = ControlPipe {
+            max_packet_size: control_max_packet_size,
+            ep_in,
+            ep_out,
+        };
+        (bus, control)
+    }
+}
+
+/// Bus of the async USB driver, returned by
+/// [`start`](embassy_usb_driver::Driver::start)
+pub struct Bus {
+    _dm_pad: DmPad,
+    _dp_pad: DpPad,
+    ep_in: [Option<EpConfig>; NUM_ENDPOINTS],
+    ep_out: [Option<EpConfig>; NUM_ENDPOINTS],
+    power_detected: bool,
+}
+
+impl Bus {
+    fn config(&self, ep: EndpointAddress) -> Option<EpConfig> {
+        match ep.direction() {
+            Direction::In => self.ep_in.get(ep.index()).copied().flatten(),
+            Direction::Out => self.ep_out.get(ep.index()).copied().flatten(),
+        }
+    }
+
+    fn set_enabled(&self, ep: EndpointAddress, enabled: bool) {
+        let Some(config) = self.config(ep) else {
+            return;
+        };
+        let regs = EpRegs::new(ep.index());
+
+        let eptype = if enabled {
+            with_bank(ep, |bank| {
+                bank.set_address(config.buffer);
+                bank.set_endpoint_size(config.max_packet_size);
+                bank.set_multi_packet_size(0);
+                bank.set_byte_count(0);
+            });
+            eptype_bits(config.ep_type)
+        } else {
+            0
+        };
+
+        match ep.direction() {
+            Direction::Out => {
+                regs.epcfg()
+                    .modify(|_, w| unsafe { w.eptype0().bits(eptype) });
+                // An empty bank 0 accepts the next OUT packet
+                regs.epstatusclr().write(|w| {
+                    w.bk0rdy().set_bit();
+                    w.stallrq0().set_bit();
+                    w.dtglout().set_bit()
+                });
+            }
+            Direction::In => {
+                regs.epcfg()
+                    .modify(|_, w| unsafe { w.eptype1().bits(eptype) });
+                // An empty bank 1 NAKs IN tokens until data is written
+                regs.epstatusclr().write(|w| {
+                    w.bk1rdy().set_bit();
+                    w.stallrq1().set_bit();
+                    w.dtglin().set_bit()
+                });
+            }
+        }
+
+        waker(ep).wake();
+    }
+
+    /// Configures the control endpoint, which is disabled by a bus reset.
+    fn configure_control(&self) {
+        self.set_enabled(EndpointAddress::from_parts(0, Direction::Out), true);
+        self.set_enabled(EndpointAddress::from_parts(0, Direction::In), true);
+    }
+}
+
+impl embassy_usb_driver::Bus for Bus {
+    async fn enable(&mut self) {
+        let usb = usb();
+        usb.ctrla().modify(|_, w| w.swrst().set_bit());
+        while usb.syncbusy().read().swrst().bit_is_set() {}
+
+        let addr = critical_section::with(|cs| {
+            DESCRIPTORS
+                .borrow_ref(cs)
+                .as_ref()
+                .map(|desc| desc.address())
+                .unwrap_or(0)
+        });
+        usb.descadd().write(|w| unsafe { w.descadd().bits(addr) });
+        usb.padcal().modify(|_, w| unsafe {
+            w.transn().bits(usb_transn_cal());
+            w.transp().bits(usb_transp_cal());
+            w.trim().bits(usb_trim_cal())
+        });
+
+        usb.qosctrl().modify(|_, w| unsafe {
+            w.dqos().bits(0b11);
+            w.cqos().bits(0b11)
+        });
+
+        usb.ctrla().modify(|_, w| {
+            w.mode().device();
+            w.runstdby().set_bit()
+        });
+        // full speed
+        usb.ctrlb().modify(|_, w| w.spdconf().fs());
+
+        usb.ctrla().modify(|_, w| w.enable().set_bit());
+        while usb.syncbusy().read().enable().bit_is_set() {}
+
+        // Clear pending.
+        usb.intflag()
+            .write(|w| unsafe { w.bits(usb.intflag().read().bits()) });
+
+        // Configure the control endpoint before we attach, as hosts may
+        // enumerate before attempting a USB protocol reset.
+        self.configure_control();
+
+        usb.ctrlb().modify(|_, w| w.detach().clear_bit());
+    }
+
+    async fn disable(&mut self) {
+        let usb = usb();
+        usb.ctrlb().modify(|_, w| w.detach().set_bit());
+        usb.ctrla().modify(|_, w| w.enable().clear_bit());
+        while usb.syncbusy().read().enable().bit_is_set() {}
+    }
+
+    async fn poll(&mut self) -> Event {
+        // There is no VBUS detection, so the device is assumed to be powered
+        if !self.power_detected {
+            self.power_detected = true;
+            return Event::PowerDetected;
+        }
+
+        poll_fn(|cx| {
+            BUS_WAKER.register(cx.waker());
+            let usb = usb();
+            let flags = usb.intflag().read();
+
+            if flags.eorst().bit_is_set() {
+                usb.intflag().write(|w| w.eorst().set_bit());
+                // A bus reset disables all endpoints but the control endpoint
+                for index in 1..NUM_ENDPOINTS {
+                    EpRegs::new(index).epcfg().write(|w| unsafe { w.bits(0) });
+                    EP_IN_WAKERS[index].wake();
+                    EP_OUT_WAKERS[index].wake();
+                }
+                self.configure_control();
+                return Poll::Ready(Event::Reset);
+            }
+
+            if flags.suspend().bit_is_set() {
+                usb.intflag().write(|w| {
+                    w.suspend().set_bit();
+                    w.wakeup().set_bit()
+                });
+                return Poll::Ready(Event::Suspend);
+            }
+
+            if flags.wakeup().bit_is_set() || flags.eorsm().bit_is_set() {
+                usb.intflag().write(|w| {
+                    w.wakeup().set_bit();
+                    w.eorsm().set_bit()
+                });
+                return Poll::Ready(Event::Resume);
+            }
+
+            usb.intenset().write(|w| {
+                w.eorst().set_bit();
+                w.suspend().set_bit();
+                w.wakeup().set_bit();
+                w.eorsm().set_bit()
+            });
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn endpoint_set_enabled(&mut self, ep_addr: EndpointAddress, enabled: bool) {
+        self.set_enabled(ep_addr, enabled);
+    }
+
+    fn endpoint_set_stalled(&mut self, ep_addr: EndpointAddress, stalled: bool) {
+        let regs = EpRegs::new(ep_addr.index());
+        match (ep_addr.direction(), stalled) {
+            (Direction::Out, true) => regs.epstatusset().write(|w| w.stallrq0().set_bit()),
+            (Direction::In, true) => regs.epstatusset().write(|w| w.stallrq1().set_bit()),
+            // Clearing a halt also resets the data toggle
+            (Direction::Out, false) => regs.epstatusclr().write(|w| {
+                w.stallrq0().set_bit();
+                w.dtglout().set_bit()
+            }),
+            (Direction::In, false) => regs.epstatusclr().write(|w| {
+                w.stallrq1().set_bit();
+                w.dtglin().set_bit()
+            }),
+        };
+    }
+
+    fn endpoint_is_stalled(&mut self, ep_addr: EndpointAddress) -> bool {
+        let status = EpRegs::new(ep_addr.index()).epstatus().read();
+        match ep_addr.direction() {
+            Direction::Out => status.stallrq0().bit_is_set(),
+            Direction::In => status.stallrq1().bit_is_set(),
+        }
+    }
+
+    async fn remote_wakeup(&mut self) -> Result<(), Unsupported> {
+        usb().ctrlb().modify(|_, w| w.uprsm().set_bit());
+        Ok(())
+    }
+}
+
+/// Marker type for IN endpoints
+pub enum In {}
+
+/// Marker type for OUT endpoints
+pub enum Out {}
+
+/// Endpoint of the async USB driver
+pub struct Endpoint<D> {
+    info: EndpointInfo,
+    buffer: *mut u8,
+    _dir: PhantomData<D>,
+}
+
+// The buffer is only ever accessed by the endpoint owning it
+unsafe impl<D> Send for Endpoint<D> {}
+
+impl<D> Endpoint<D> {
+    fn regs(&self) -> EpRegs {
+        EpRegs::new(self.info.addr.index())
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.regs().is_enabled(self.info.addr.direction())
+    }
+}
+
+impl<D> embassy_usb_driver::Endpoint for Endpoint<D> {
+    fn info(&self) -> &EndpointInfo {
+        &self.info
+    }
+
+    async fn wait_enabled(&mut self) {
+        poll_fn(|cx| {
+            waker(self.info.addr).register(cx.waker());
+            if self.is_enabled() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+impl embassy_usb_driver::EndpointOut for Endpoint<Out> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, EndpointError> {
+        let index = self.info.addr.index();
+        let regs = self.regs();
+
+        poll_fn(|cx| {
+            EP_OUT_WAKERS[index].register(cx.waker());
+            if !self.is_enabled() {
+                return Poll::Ready(Err(EndpointError::Disabled));
+            }
+            if regs.epstatus().read().bk0rdy().bit_is_set() {
+                return Poll::Ready(Ok(()));
+            }
+            regs.epintenset().write(|w| w.trcpt0().set_bit());
+            Poll::Pending
+        })
+        .await?;
+
+        let len = with_bank(self.info.addr, |bank| bank.get_byte_count()) as usize;
+        let result = if len > buf.len() {
+            Err(EndpointError::BufferOverflow)
+        } else {
+            unsafe { core::ptr::copy_nonoverlapping(self.buffer, buf.as_mut_ptr(), len) };
+            Ok(len)
+        };
+
+        // Hand the bank back to the peripheral for the next packet
+        regs.epintflag().write(|w| {
+            w.trcpt0().set_bit();
+            w.trfail0().set_bit()
+        });
+        regs.epstatusclr().write(|w| w.bk0rdy().set_bit());
+        result
+    }
+}
+
+impl embassy_usb_driver::EndpointIn for Endpoint<In> {
+    async fn write(&mut self, buf: &[u8]) -> Result<(), EndpointError> {
+        if buf.len() > self.info.max_packet_size as usize {
+            return Err(EndpointError::BufferOverflow);
+        }
+        let index = self.info.addr.index();
+        let regs = self.regs();
+
+        // Wait for a previous, possibly cancelled, write to complete
+        poll_fn(|cx| {
+            EP_IN_WAKERS[index].register(cx.waker());
+            if !self.is_enabled() {
+                return Poll::Ready(Err(EndpointError::Disabled));
+            }
+            if regs.epstatus().read().bk1rdy().bit_is_clear() {
+                return Poll::Ready(Ok(()));
+            }
+            regs.epintenset().write(|w| w.trcpt1().set_bit());
+            Poll::Pending
+        })
+        .await?;
+
+        unsafe { core::ptr::copy_nonoverlapping(buf.as_ptr(), self.buffer, buf.len()) };
+        with_bank(self.info.addr, |bank| {
+            bank.set_byte_count(buf.len() as u16);
+            bank.set_multi_packet_size(0);
+        });
+        regs.epintflag().write(|w| {
+            w.trcpt1().set_bit();
+            w.trfail1().set_bit()
+        });
+        regs.epstatusset().write(|w| w.bk1rdy().set_bit());
+
+        poll_fn(|cx| {
+            EP_IN_WAKERS[index].register(cx.waker());
+            if !self.is_enabled() {
+                return Poll::Ready(Err(EndpointError::Disabled));
+            }
+            if regs.epintflag().read().trcpt1().bit_is_set() {
+                regs.epintflag().write(|w| w.trcpt1().set_bit());
+                return Poll::Ready(Ok(()));
+            }
+            regs.epintenset().write(|w| w.trcpt1().set_bit());
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+/// Control pipe of the async USB driver, returned by
+/// [`start`](embassy_usb_driver::Driver::start)
+pub struct ControlPipe {
+    max_packet_size: u16,
+    ep_in: Endpoint<In>,
+    ep_out: Endpoint<Out>,
+}
+
+impl embassy_usb_driver::ControlPipe for ControlPipe {
+    fn max_packet_size(&self) -> usize {
+        self.max_packet_size as usize
+    }
+
+    async fn setup(&mut self) -> [u8; 8] {
+        let regs = EpRegs::new(0);
+
+        poll_fn(|cx| {
+            EP_OUT_WAKERS[0].register(cx.waker());
+            if regs.epintflag().read().rxstp().bit_is_set() {
+                return Poll::Ready(());
+            }
+            regs.epintenset().write(|w| w.rxstp().set_bit());
+            Poll::Pending
+        })
+        .await;
+
+        let mut setup = [0; 8];
+        unsafe { core::ptr::copy_nonoverlapping(self.ep_out.buffer, setup.as_mut_ptr(), 8) };
+
+        // A SETUP packet ends any previous transfer and clears a STALL
+        regs.epintflag().write(|w| {
+            w.rxstp().set_bit();
+            w.trcpt0().set_bit();
+            w.trfail0().set_bit();
+            w.trcpt1().set_bit();
+            w.trfail1().set_bit()
+        });
+        regs.epstatusclr().write(|w| {
+            w.stallrq0().set_bit();
+            w.stallrq1().set_bit();
+            w.bk1rdy().set_bit();
+            w.bk0rdy().set_bit()
+        });
+        setup
+    }
+
+    async fn data_out(
+        &mut self,
+        buf: &mut [u8],
+        _first: bool,
+        _last: bool,
+    ) -> Result<usize, EndpointError> {
+        embassy_usb_driver::EndpointOut::read(&mut self.ep_out, buf).await
+    }
+
+    async fn data_in(
+        &mut self,
+        data: &[u8],
+        _first: bool,
+        _last: bool,
+    ) -> Result<(), EndpointError> {
+        // Bank 0 was emptied by `setup`, so the OUT ZLP of the status stage
+        // is acknowledged by the peripheral.
+        embassy_usb_driver::EndpointIn::write(&mut self.ep_in, data).await
+    }
+
+    async fn accept(&mut self) {
+        // Status stage: send an IN ZLP
+        let _ = embassy_usb_driver::EndpointIn::write(&mut self.ep_in, &[]).await;
+    }
+
+    async fn reject(&mut self) {
+        EpRegs::new(0).epstatusset().write(|w| {
+            w.stallrq0().set_bit();
+            w.stallrq1().set_bit()
+        });
+    }
+
+    async fn accept_set_address(&mut self, addr: u8) {
+        // The address only takes effect once the status stage completes
+        self.accept().await;
+        usb()
+            .dadd()
+            .write(|w| unsafe { w.dadd().bits(addr).adden().set_bit() });
+    }
+}
@@ -5,7 +5,7 @@
 // people doing that should be familiar with the USB standard. http://ww1.microchip.com/downloads/en/DeviceDoc/60001507E.pdf
 // http://ww1.microchip.com/downloads/en/AppNotes/Atmel-42261-SAM-D21-USB_Application-Note_AT06475.pdf
 
-use super::Descriptors;
+use super::{Descriptors, LinkState, LinkStateCallback, LpmMode};
 use crate::calibration::{usb_transn_cal, usb_transp_cal, usb_trim_cal};
 use crate::clock;
 use crate::gpio::{AlternateG, AnyPin, PA24, PA25, Pin};
@@ -14,7 +14,7 @@ use crate::pac::{Pm, Usb};
 use crate::usb::buffer::*;
 use crate::usb::devicedesc::DeviceDescBank;
 use atsamd_hal_macros::{hal_cfg, hal_macro_helper};
-use core::cell::{Ref, RefCell, RefMut};
+use core::cell::{Cell, Ref, RefCell, RefMut};
 use core::marker::PhantomData;
 use critical_section::{Mutex, with as disable_interrupts};
 use usb_device::bus::PollResult;
@@ -145,6 +145,11 @@ struct Inner {
     _dp_pad: Pin<PA25, AlternateG>,
     endpoints: RefCell<AllEndpoints>,
     buffers: RefCell<BufferAllocator>,
+    link_state: Cell<LinkState>,
+    /// Whether the host has reset the device since it was enabled
+    attached: Cell<bool>,
+    link_state_callback: Option<LinkStateCallback>,
+    lpm_mode: LpmMode,
 }
 
 pub struct UsbBus {
@@ -449,6 +454,10 @@ impl UsbBus {
             desc,
             buffers: RefCell::new(BufferAllocator::default()),
             endpoints: RefCell::new(AllEndpoints::new()),
+            link_state: Cell::new(LinkState::Active),
+            attached: Cell::new(false),
+            link_state_callback: None,
+            lpm_mode: LpmMode::Disabled,
         };
 
         Self {
@@ -520,6 +529,7 @@ impl Inner {
         });
         // full speed
         usb.ctrlb().modify(|_, w| w.spdconf().fs());
+        self.apply_lpm_mode();
 
         usb.ctrla().modify(|_, w| w.enable().set_bit());
         while usb.syncbusy().read().enable().bit_is_set() {}
@@ -527,7 +537,15 @@ impl Inner {
         // Clear pending.
         usb.intflag()
             .write(|w| unsafe { w.bits(usb.intflag().read().bits()) });
-        usb.intenset().write(|w| w.eorst().set_bit());
+        usb.intenset().write(|w| {
+            w.eorst().set_bit();
+            w.suspend().set_bit();
+            w.wakeup().set_bit();
+            w.eorsm().set_bit();
+            w.lpmsusp().set_bit()
+        });
+        self.link_state.set(LinkState::Active);
+        self.attached.set(false);
 
         // Configure the endpoints before we attach, as hosts may enumerate
         // before attempting a USB protocol reset.
@@ -617,9 +635,38 @@ impl Inner {
         self.flush_eps(FlushConfigMode::ProtocolReset);
     }
 
-    fn suspend(&self) {}
+    /// Returns the link state callback and the state to notify it of.
+    fn link_state_notification(&self) -> Option<(LinkStateCallback, LinkState)> {
+        self.link_state_callback
+            .map(|callback| (callback, self.link_state.get()))
+    }
+
+    fn apply_lpm_mode(&self) {
+        self.usb().ctrlb().modify(|_, w| match self.lpm_mode {
+            LpmMode::Disabled => w.lpmhdsk().no(),
+            LpmMode::Ack => w.lpmhdsk().ack(),
+            LpmMode::Nyet => w.lpmhdsk().nyet(),
+        });
+    }
+
+    fn set_lpm_mode(&mut self, mode: LpmMode) {
+        self.lpm_mode = mode;
+        self.apply_lpm_mode();
+    }
 
-    fn resume(&self) {}
+    fn remote_wakeup(&self) -> UsbResult<()> {
+        match self.link_state.get() {
+            LinkState::Active
+            | LinkState::Sleep {
+                remote_wakeup: false,
+                ..
+            } => Err(UsbError::InvalidState),
+            _ => {
+                self.usb().ctrlb().modify(|_, w| w.uprsm().set_bit());
+                Ok(())
+            }
+        }
+    }
 
     fn alloc_ep(
         &mut self,
@@ -686,13 +733,57 @@ impl Inner {
     fn poll(&self) -> PollResult {
         let intflags = self.usb().intflag().read();
         if intflags.eorst().bit() {
+            // A bus reset also ends a suspend. Report the resume first, so the
+            // application restores its clocks before handling the reset.
+            if self.link_state.get() != LinkState::Active {
+                self.link_state.set(LinkState::Active);
+                return PollResult::Resume;
+            }
             // end of reset interrupt
             self.usb().intflag().write(|w| w.eorst().set_bit());
+            self.attached.set(true);
             return PollResult::Reset;
         }
-        // As the suspend & wakup interrupts/states cannot distinguish between
-        // unconnected & unsuspended, we do not handle them to avoid spurious
-        // transitions.
+
+        // Link state flags are cleared even when ignored, so they don't keep
+        // the interrupt pending.
+        let suspend = intflags.suspend().bit();
+        let lpm_suspend = intflags.lpmsusp().bit();
+        let resume = intflags.wakeup().bit() || intflags.eorsm().bit();
+        if suspend || lpm_suspend || resume {
+            self.usb().intflag().write(|w| {
+                w.suspend().set_bit();
+                w.lpmsusp().set_bit();
+                w.wakeup().set_bit();
+                w.eorsm().set_bit()
+            });
+        }
+        match self.link_state.get() {
+            LinkState::Active if lpm_suspend => {
+                // The LPM token is stored in the extended register of the
+                // endpoint 0 OUT bank
+                let mut desc = self.desc.borrow_mut();
+                let bank = desc.bank(0, 0);
+                self.link_state.set(LinkState::Sleep {
+                    besl: bank.besl(),
+                    remote_wakeup: bank.remote_wake(),
+                });
+                return PollResult::Suspend;
+            }
+            // The suspend interrupt is also raised while detached, so it is
+            // only reported once the host has reset the device.
+            LinkState::Active if suspend && self.attached.get() => {
+                self.link_state.set(LinkState::Suspend);
+                return PollResult::Suspend;
+            }
+            // Bus activity also raises the wakeup interrupt while active
+            LinkState::Active => {}
+            _ if resume => {
+                self.link_state.set(LinkState::Active);
+                return PollResult::Resume;
+            }
+            _ => {}
+        }
 
         let mut ep_out = 0;
         let mut ep_in_complete = 0;
@@ -825,6 +916,46 @@ impl UsbBus {
         disable_interrupts(|cs| self.inner.borrow(cs).borrow_mut().check_sof_interrupt())
     }
 
+    /// Returns the current link power state
+    pub fn link_state(&self) -> LinkState {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow().link_state.get())
+    }
+
+    /// Sets the callback notified when the link enters or leaves a low-power
+    /// state. See [`LinkState`].
+    pub fn set_link_state_callback(&self, callback: Option<LinkStateCallback>) {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow_mut().link_state_callback = callback)
+    }
+
+    /// Selects the handshake to LPM transactions, which enables L1 sleep.
+    /// LPM is disabled by default.
+    pub fn set_lpm_mode(&self, mode: LpmMode) {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow_mut().set_lpm_mode(mode))
+    }
+
+    /// Signals a remote wakeup to the host.
+    ///
+    /// From L2 suspend, the host must have enabled remote wakeup, which is
+    /// reported by `UsbDevice::remote_wakeup_enabled`, and the device must
+    /// have been suspended for at least 5 ms. From L1 sleep, the LPM token
+    /// must have allowed it. The generic clock of the USB peripheral must be
+    /// running.
+    ///
+    /// Returns [`UsbError::InvalidState`] if the link is active, or if the LPM
+    /// token did not allow remote wakeup.
+    pub fn remote_wakeup(&self) -> UsbResult<()> {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow().remote_wakeup())
+    }
+
+    /// Calls the link state callback, outside of the critical section.
+    fn notify_link_state(&self) {
+        let notification =
+            disable_interrupts(|cs| self.inner.borrow(cs).borrow().link_state_notification());
+        if let Some((callback, state)) = notification {
+            callback(state);
+        }
+    }
+
     /// Configures the Multi-Packet-Rx feature of the USB peripheral.
     ///
     /// This allows for the USB Peripheral to ACK multiple incomming packets in hardware, and then
@@ -882,11 +1013,11 @@ impl usb_device::bus::UsbBus for UsbBus {
     }
 
     fn suspend(&self) {
-        disable_interrupts(|cs| self.inner.borrow(cs).borrow().suspend())
+        self.notify_link_state();
     }
 
     fn resume(&self) {
-        disable_interrupts(|cs| self.inner.borrow(cs).borrow().resume())
+        self.notify_link_state();
     }
 
     fn alloc_ep(
@@ -138,12 +138,10 @@ impl DeviceDescBank {
     }
 
     /// best effort service latency
-    #[allow(unused)]
     pub fn besl(&self) -> u8 {
         self.extreg.besl() as u8
     }
 
-    #[allow(unused)]
     pub fn remote_wake(&self) -> bool {
         self.extreg.remote_wake()
     }
@@ -5,6 +5,8 @@ use crate::gpio::{
     AlternateG,
 };
 
+use atsamd_hal_macros::hal_module;
+
 pub use usb_device;
 
 mod buffer;
@@ -12,9 +14,20 @@ mod bus;
 pub use self::buffer::*;
 pub use self::bus::UsbBus;
 
+#[path = "../power.rs"]
+mod power;
+pub use self::power::*;
+
 mod devicedesc;
 use self::devicedesc::Descriptors;
 
+#[cfg(feature = "async")]
+#[path = "../async_api.rs"]
+pub mod async_api;
+
+#[hal_module("usb-d21" => "../host/mod.rs")]
+pub mod host {}
+
 /// Emit SOF at 1Khz on this pin when configured as function G
 pub type SofPad = Pin<PA23, AlternateG>;
 
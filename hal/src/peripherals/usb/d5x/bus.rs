@@ -5,16 +5,21 @@
 // people doing that should be familiar with the USB standard. http://ww1.microchip.com/downloads/en/DeviceDoc/60001507E.pdf
 // http://ww1.microchip.com/downloads/en/AppNotes/Atmel-42261-SAM-D21-USB_Application-Note_AT06475.pdf
 
-use super::Descriptors;
+use super::{Descriptors, LinkState, LinkStateCallback, LpmMode};
 use crate::calibration::{usb_transn_cal, usb_transp_cal, usb_trim_cal};
 use crate::clock;
+use crate::clock::v2::ahb::AhbClk;
+use crate::clock::v2::apb::ApbClk;
+use crate::clock::v2::pclk::{Pclk, PclkSourceId};
+use crate::clock::v2::types::Usb as UsbClk;
 use crate::gpio::{AlternateH, AnyPin, PA24, PA25, Pin};
 use crate::pac;
 use crate::pac::usb::Device;
 use crate::pac::{Mclk, Usb};
+use crate::typelevel::NoneT;
 use crate::usb::buffer::*;
 use crate::usb::devicedesc::DeviceDescBank;
-use core::cell::{Ref, RefCell, RefMut};
+use core::cell::{Cell, Ref, RefCell, RefMut};
 use core::marker::PhantomData;
 use critical_section::{Mutex, with as disable_interrupts};
 use usb_device::bus::PollResult;
@@ -145,10 +150,41 @@ struct Inner {
     _dp_pad: Pin<PA25, AlternateH>,
     endpoints: RefCell<AllEndpoints>,
     buffers: RefCell<BufferAllocator>,
+    link_state: Cell<LinkState>,
+    /// Whether the host has reset the device since it was enabled
+    attached: Cell<bool>,
+    link_state_callback: Option<LinkStateCallback>,
+    lpm_mode: LpmMode,
 }
 
-pub struct UsbBus {
+/// USB device bus
+///
+/// `C` holds the peripheral and the clocks when the bus is created with
+/// [`UsbBus::new_v2`], and is [`NoneT`] otherwise.
+pub struct UsbBus<C = NoneT> {
     inner: Mutex<RefCell<Inner>>,
+    clocks: C,
+}
+
+/// Peripheral and [`clock::v2`](crate::clock::v2) clocks owned by a
+/// [`UsbBus`] created with [`UsbBus::new_v2`]
+pub struct V2Clocks<S: PclkSourceId> {
+    usb: Usb,
+    pclk: Pclk<UsbClk, S>,
+    apb_clk: ApbClk<UsbClk>,
+    ahb_clk: AhbClk<UsbClk>,
+}
+
+// SAFETY: The fields are only accessed through `UsbBus::free_v2`, which takes
+// the bus by value, so sharing a reference to them across threads is harmless.
+unsafe impl<S: PclkSourceId> Sync for V2Clocks<S> {}
+
+/// Error returned by [`UsbBus::new_v2`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClockError {
+    /// The [`Pclk`] frequency is not 48 MHz within 0.25%
+    InvalidFrequency,
 }
 
 struct Bank<'a, T> {
@@ -484,6 +520,93 @@ impl UsbBus {
         mclk.ahbmask().modify(|_, w| w.usb_().set_bit());
         mclk.apbbmask().modify(|_, w| w.usb_().set_bit());
 
+        Self::init(dm_pad, dp_pad, NoneT)
+    }
+}
+
+impl<S: PclkSourceId> UsbBus<V2Clocks<S>> {
+    /// Create the bus from [`clock::v2`](crate::clock::v2) clocks
+    ///
+    /// Full-speed USB requires a 48 MHz [`Pclk`], within 0.25%. Without a
+    /// crystal, this is achieved by sourcing its [`Gclk`] from the [`Dfll`]
+    /// in USB clock recovery mode, see
+    /// [`EnabledDfll::into_usb_recovery`].
+    ///
+    /// The bus takes ownership of the [`Pclk`] and of the bus clocks, so that
+    /// they stay enabled for as long as the bus is in use. Release them with
+    /// [`UsbBus::free_v2`].
+    ///
+    /// Returns [`ClockError::InvalidFrequency`], along with all the
+    /// arguments, if the [`Pclk`] frequency is not 48 MHz.
+    ///
+    /// [`Gclk`]: crate::clock::v2::gclk::Gclk
+    /// [`Dfll`]: crate::clock::v2::dfll::Dfll
+    /// [`EnabledDfll::into_usb_recovery`]: crate::clock::v2::dfll::EnabledDfll::into_usb_recovery
+    #[allow(clippy::type_complexity)]
+    pub fn new_v2<DM, DP>(
+        pclk: Pclk<UsbClk, S>,
+        apb_clk: ApbClk<UsbClk>,
+        ahb_clk: AhbClk<UsbClk>,
+        dm_pad: DM,
+        dp_pad: DP,
+        usb: Usb,
+    ) -> Result<
+        Self,
+        (
+            ClockError,
+            Pclk<UsbClk, S>,
+            ApbClk<UsbClk>,
+            AhbClk<UsbClk>,
+            DM,
+            DP,
+            Usb,
+        ),
+    >
+    where
+        DM: AnyPin<Id = PA24>,
+        DP: AnyPin<Id = PA25>,
+    {
+        const MIN: u32 = 47_880_000;
+        const MAX: u32 = 48_120_000;
+        let freq = pclk.freq().to_Hz();
+        if !(MIN..=MAX).contains(&freq) {
+            return Err((
+                ClockError::InvalidFrequency,
+                pclk,
+                apb_clk,
+                ahb_clk,
+                dm_pad,
+                dp_pad,
+                usb,
+            ));
+        }
+
+        let clocks = V2Clocks {
+            usb,
+            pclk,
+            apb_clk,
+            ahb_clk,
+        };
+        Ok(Self::init(dm_pad, dp_pad, clocks))
+    }
+
+    /// Disable the bus and release the peripheral and its clocks
+    pub fn free_v2(self) -> (Usb, Pclk<UsbClk, S>, ApbClk<UsbClk>, AhbClk<UsbClk>) {
+        let usb = self.clocks.usb.device();
+        usb.ctrla().modify(|_, w| w.enable().clear_bit());
+        while usb.syncbusy().read().enable().bit_is_set() {}
+        let V2Clocks {
+            usb,
+            pclk,
+            apb_clk,
+            ahb_clk,
+        } = self.clocks;
+        (usb, pclk, apb_clk, ahb_clk)
+    }
+}
+
+impl<C> UsbBus<C> {
+    fn init(dm_pad: impl AnyPin<Id = PA24>, dp_pad: impl AnyPin<Id = PA25>, clocks: C) -> Self {
         let desc = RefCell::new(Descriptors::new());
 
         let inner = Inner {
@@ -492,10 +615,15 @@ impl UsbBus {
             desc,
             buffers: RefCell::new(BufferAllocator::default()),
             endpoints: RefCell::new(AllEndpoints::new()),
+            link_state: Cell::new(LinkState::Active),
+            attached: Cell::new(false),
+            link_state_callback: None,
+            lpm_mode: LpmMode::Disabled,
         };
 
         Self {
             inner: Mutex::new(RefCell::new(inner)),
+            clocks,
         }
     }
 }
@@ -548,6 +676,7 @@ impl Inner {
         });
         // full speed
         usb.ctrlb().modify(|_, w| w.spdconf().fs());
+        self.apply_lpm_mode();
 
         usb.ctrla().modify(|_, w| w.enable().set_bit());
         while usb.syncbusy().read().enable().bit_is_set() {}
@@ -555,7 +684,15 @@ impl Inner {
         // Clear pending.
         usb.intflag()
             .write(|w| unsafe { w.bits(usb.intflag().read().bits()) });
-        usb.intenset().write(|w| w.eorst().set_bit());
+        usb.intenset().write(|w| {
+            w.eorst().set_bit();
+            w.suspend().set_bit();
+            w.wakeup().set_bit();
+            w.eorsm().set_bit();
+            w.lpmsusp().set_bit()
+        });
+        self.link_state.set(LinkState::Active);
+        self.attached.set(false);
 
         // Configure the endpoints before we attach, as hosts may enumerate
         // before attempting a USB protocol reset.
@@ -645,8 +782,38 @@ impl Inner {
         self.flush_eps(FlushConfigMode::ProtocolReset);
     }
 
-    fn suspend(&self) {}
-    fn resume(&self) {}
+    /// Returns the link state callback and the state to notify it of.
+    fn link_state_notification(&self) -> Option<(LinkStateCallback, LinkState)> {
+        self.link_state_callback
+            .map(|callback| (callback, self.link_state.get()))
+    }
+
+    fn apply_lpm_mode(&self) {
+        self.usb().ctrlb().modify(|_, w| match self.lpm_mode {
+            LpmMode::Disabled => w.lpmhdsk().no(),
+            LpmMode::Ack => w.lpmhdsk().ack(),
+            LpmMode::Nyet => w.lpmhdsk().nyet(),
+        });
+    }
+
+    fn set_lpm_mode(&mut self, mode: LpmMode) {
+        self.lpm_mode = mode;
+        self.apply_lpm_mode();
+    }
+
+    fn remote_wakeup(&self) -> UsbResult<()> {
+        match self.link_state.get() {
+            LinkState::Active
+            | LinkState::Sleep {
+                remote_wakeup: false,
+                ..
+            } => Err(UsbError::InvalidState),
+            _ => {
+                self.usb().ctrlb().modify(|_, w| w.uprsm().set_bit());
+                Ok(())
+            }
+        }
+    }
 
     fn alloc_ep(
         &mut self,
@@ -713,13 +880,57 @@ impl Inner {
     fn poll(&self) -> PollResult {
         let intflags = self.usb().intflag().read();
         if intflags.eorst().bit() {
+            // A bus reset also ends a suspend. Report the resume first, so the
+            // application restores its clocks before handling the reset.
+            if self.link_state.get() != LinkState::Active {
+                self.link_state.set(LinkState::Active);
+                return PollResult::Resume;
+            }
             // end of reset interrupt
             self.usb().intflag().write(|w| w.eorst().set_bit());
+            self.attached.set(true);
             return PollResult::Reset;
         }
-        // As the suspend & wakup interrupts/states cannot distinguish between
-        // unconnected & unsuspended, we do not handle them to avoid spurious
-        // transitions.
+
+        // Link state flags are cleared even when ignored, so they don't keep
+        // the interrupt pending.
+        let suspend = intflags.suspend().bit();
+        let lpm_suspend = intflags.lpmsusp().bit();
+        let resume = intflags.wakeup().bit() || intflags.eorsm().bit();
+        if suspend || lpm_suspend || resume {
+            self.usb().intflag().write(|w| {
+                w.suspend().set_bit();
+                w.lpmsusp().set_bit();
+                w.wakeup().set_bit();
+                w.eorsm().set_bit()
+            });
+        }
+        match self.link_state.get() {
+            LinkState::Active if lpm_suspend => {
+                // The LPM token is stored in the extended register of the
+                // endpoint 0 OUT bank
+                let mut desc = self.desc.borrow_mut();
+                let bank = desc.bank(0, 0);
+                self.link_state.set(LinkState::Sleep {
+                    besl: bank.besl(),
+                    remote_wakeup: bank.remote_wake(),
+                });
+                return PollResult::Suspend;
+            }
+            // The suspend interrupt is also raised while detached, so it is
+            // only reported once the host has reset the device.
+            LinkState::Active if suspend && self.attached.get() => {
+                self.link_state.set(LinkState::Suspend);
+                return PollResult::Suspend;
+            }
+            // Bus activity also raises the wakeup interrupt while active
+            LinkState::Active => {}
+            _ if resume => {
+                self.link_state.set(LinkState::Active);
+                return PollResult::Resume;
+            }
+            _ => {}
+        }
 
         let mut ep_out = 0;
         let mut ep_in_complete = 0;
@@ -836,7 +1047,7 @@ impl Inner {
     }
 }
 
-impl UsbBus {
+impl<C> UsbBus<C> {
     /// Enables the Start Of Frame (SOF) interrupt
     pub fn enable_sof_interrupt(&self) {
         disable_interrupts(|cs| self.inner.borrow(cs).borrow_mut().sof_interrupt(true))
@@ -852,6 +1063,46 @@ impl UsbBus {
         disable_interrupts(|cs| self.inner.borrow(cs).borrow_mut().check_sof_interrupt())
     }
 
+    /// Returns the current link power state
+    pub fn link_state(&self) -> LinkState {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow().link_state.get())
+    }
+
+    /// Sets the callback notified when the link enters or leaves a low-power
+    /// state. See [`LinkState`].
+    pub fn set_link_state_callback(&self, callback: Option<LinkStateCallback>) {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow_mut().link_state_callback = callback)
+    }
+
+    /// Selects the handshake to LPM transactions, which enables L1 sleep.
+    /// LPM is disabled by default.
+    pub fn set_lpm_mode(&self, mode: LpmMode) {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow_mut().set_lpm_mode(mode))
+    }
+
+    /// Signals a remote wakeup to the host.
+    ///
+    /// From L2 suspend, the host must have enabled remote wakeup, which is
+    /// reported by `UsbDevice::remote_wakeup_enabled`, and the device must
+    /// have been suspended for at least 5 ms. From L1 sleep, the LPM token
+    /// must have allowed it. The generic clock of the USB peripheral must be
+    /// running.
+    ///
+    /// Returns [`UsbError::InvalidState`] if the link is active, or if the LPM
+    /// token did not allow remote wakeup.
+    pub fn remote_wakeup(&self) -> UsbResult<()> {
+        disable_interrupts(|cs| self.inner.borrow(cs).borrow().remote_wakeup())
+    }
+
+    /// Calls the link state callback, outside of the critical section.
+    fn notify_link_state(&self) {
+        let notification =
+            disable_interrupts(|cs| self.inner.borrow(cs).borrow().link_state_notification());
+        if let Some((callback, state)) = notification {
+            callback(state);
+        }
+    }
+
     /// Configures the Multi-Packet-Rx feature of the USB peripheral.
     ///
     /// This allows for the USB Peripheral to ACK multiple incomming packets in hardware, and then
@@ -899,7 +1150,7 @@ impl UsbBus {
     }
 }
 
-impl usb_device::bus::UsbBus for UsbBus {
+impl<C: Sync> usb_device::bus::UsbBus for UsbBus<C> {
     fn enable(&mut self) {
         disable_interrupts(|cs| self.inner.borrow(cs).borrow_mut().enable())
     }
@@ -909,11 +1160,11 @@ impl usb_device::bus::UsbBus for UsbBus {
     }
 
     fn suspend(&self) {
-        disable_interrupts(|cs| self.inner.borrow(cs).borrow().suspend())
+        self.notify_link_state();
     }
 
     fn resume(&self) {
-        disable_interrupts(|cs| self.inner.borrow(cs).borrow().resume())
+        self.notify_link_state();
     }
 
     fn alloc_ep(
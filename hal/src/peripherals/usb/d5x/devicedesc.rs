@@ -138,13 +138,11 @@ impl DeviceDescBank {
         self.extreg.link_state() as u8
     }
 
-    #[allow(unused)]
     /// best effort service latency
     pub fn besl(&self) -> u8 {
         self.extreg.besl() as u8
     }
 
-    #[allow(unused)]
     pub fn remote_wake(&self) -> bool {
         self.extreg.remote_wake()
     }
@@ -5,16 +5,29 @@ use crate::gpio::{
     AlternateH,
 };
 
+use atsamd_hal_macros::hal_module;
+
 pub use usb_device;
 
 mod buffer;
 mod bus;
 pub use self::buffer::*;
-pub use self::bus::UsbBus;
+pub use self::bus::{ClockError, UsbBus, V2Clocks};
+
+#[path = "../power.rs"]
+mod power;
+pub use self::power::*;
 
 mod devicedesc;
 use self::devicedesc::Descriptors;
 
+#[cfg(feature = "async")]
+#[path = "../async_api.rs"]
+pub mod async_api;
+
+#[hal_module("usb-d5x" => "../host/mod.rs")]
+pub mod host {}
+
 /// Default SOF pad
 pub type SofPad = Pin<PA23, AlternateH>;
 
@@ -0,0 +1,873 @@
+// Pipe handling follows the USB host chapter of the SAM D21 and SAM D5x/E5x
+// datasheets ("Host Operations"). A pipe is frozen (PSTATUS.PFREEZE) while it
+// is being configured and unfrozen to start a transaction; the peripheral
+// freezes it again on STALL or pipe error.
+
+use super::pipedesc::{NUM_PIPES, PipeDescBank, PipeDescriptors, descriptors, pipe_buffers};
+use super::{HostError, HostEvent, SetupPacket, Speed, VbusControl};
+use crate::calibration::{usb_transn_cal, usb_transp_cal, usb_trim_cal};
+use crate::clock;
+use crate::gpio::{AnyPin, PA24, PA25};
+use crate::pac::Usb;
+use crate::pac::usb::Host;
+use crate::usb::{ALLOC_SIZE_MAX_PER_EP, DmPad, DpPad};
+use atsamd_hal_macros::hal_cfg;
+use usb_device::UsbDirection;
+use usb_device::endpoint::EndpointAddress;
+
+#[hal_cfg("usb-d21")]
+use crate::pac::Pm;
+#[hal_cfg("usb-d21")]
+use crate::pac::usb::host as pipe_regs;
+
+#[hal_cfg("usb-d5x")]
+use crate::pac::Mclk;
+#[hal_cfg("usb-d5x")]
+use crate::pac::usb::host::host_pipe as pipe_regs;
+
+/// Maximum number of consecutive errors before a pipe is frozen
+const PIPE_MAX_ERRORS: u8 = 3;
+
+/// Default transfer timeout, in frames (milliseconds)
+const DEFAULT_TIMEOUT: u16 = 1000;
+
+/// Reset recovery time granted to a device after a bus reset, in frames
+const RESET_RECOVERY: u16 = 20;
+
+/// Transfer type of a host pipe, as written to the PCFG.PTYPE field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PipeType {
+    Control = 1,
+    Bulk = 3,
+    Interrupt = 4,
+}
+
+/// Token used by a pipe transaction, as written to the PCFG.PTOKEN field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Setup = 0,
+    In = 1,
+    Out = 2,
+}
+
+/// Handle to an allocated host pipe
+///
+/// Pipes are allocated with [`UsbHost::alloc_pipe`] and returned with
+/// [`UsbHost::free_pipe`]. All pipes are released when the device is
+/// disconnected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Pipe {
+    index: u8,
+}
+
+impl Pipe {
+    /// Index of the hardware pipe
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+/// PipeInfo tracks the configuration of one hardware pipe.
+#[derive(Default, Clone, Copy)]
+struct PipeInfo {
+    ptype: Option<PipeType>,
+    direction: Option<UsbDirection>,
+    max_packet_size: u16,
+    buffer: usize,
+    armed: bool,
+}
+
+/// Register block of a single pipe
+struct PipeRegs<'a> {
+    host: &'a Host,
+    index: usize,
+}
+
+#[hal_cfg("usb-d21")]
+impl PipeRegs<'_> {
+    #[inline]
+    fn pcfg(&self) -> &pipe_regs::Pcfg {
+        self.host.pcfg(self.index)
+    }
+
+    #[inline]
+    fn binterval(&self) -> &pipe_regs::Binterval {
+        self.host.binterval(self.index)
+    }
+
+    #[inline]
+    fn pstatus(&self) -> &pipe_regs::Pstatus {
+        self.host.pstatus(self.index)
+    }
+
+    #[inline]
+    fn pstatusset(&self) -> &pipe_regs::Pstatusset {
+        self.host.pstatusset(self.index)
+    }
+
+    #[inline]
+    fn pstatusclr(&self) -> &pipe_regs::Pstatusclr {
+        self.host.pstatusclr(self.index)
+    }
+
+    #[inline]
+    fn pintflag(&self) -> &pipe_regs::Pintflag {
+        self.host.pintflag(self.index)
+    }
+}
+
+#[hal_cfg("usb-d5x")]
+impl PipeRegs<'_> {
+    #[inline]
+    fn pcfg(&self) -> &pipe_regs::Pcfg {
+        self.host.host_pipe(self.index).pcfg()
+    }
+
+    #[inline]
+    fn binterval(&self) -> &pipe_regs::Binterval {
+        self.host.host_pipe(self.index).binterval()
+    }
+
+    #[inline]
+    fn pstatus(&self) -> &pipe_regs::Pstatus {
+        self.host.host_pipe(self.index).pstatus()
+    }
+
+    #[inline]
+    fn pstatusset(&self) -> &pipe_regs::Pstatusset {
+        self.host.host_pipe(self.index).pstatusset()
+    }
+
+    #[inline]
+    fn pstatusclr(&self) -> &pipe_regs::Pstatusclr {
+        self.host.host_pipe(self.index).pstatusclr()
+    }
+
+    #[inline]
+    fn pintflag(&self) -> &pipe_regs::Pintflag {
+        self.host.host_pipe(self.index).pintflag()
+    }
+}
+
+impl PipeRegs<'_> {
+    #[inline]
+    fn freeze(&self) {
+        self.pstatusset().write(|w| w.pfreeze().set_bit());
+    }
+
+    #[inline]
+    fn unfreeze(&self) {
+        self.pstatusclr().write(|w| w.pfreeze().set_bit());
+    }
+
+    /// Selects the data toggle of the next transaction (DATA0 or DATA1)
+    #[inline]
+    fn set_data_toggle(&self, toggle: bool) {
+        if toggle {
+            self.pstatusset().write(|w| w.dtgl().set_bit());
+        } else {
+            self.pstatusclr().write(|w| w.dtgl().set_bit());
+        }
+    }
+
+    #[inline]
+    fn set_token(&self, token: Token) {
+        self.pcfg()
+            .modify(|_, w| unsafe { w.ptoken().bits(token as u8) });
+    }
+
+    /// Clears all pipe interrupt flags by writing them to 1
+    #[inline]
+    fn clear_flags(&self) {
+        self.pintflag().write(|w| {
+            w.trcpt0().set_bit();
+            w.trcpt1().set_bit();
+            w.trfail().set_bit();
+            w.perr().set_bit();
+            w.txstp().set_bit();
+            w.stall().set_bit()
+        });
+    }
+
+    /// Checks the pipe interrupt flags of an ongoing transaction.
+    ///
+    /// Returns `Ok(true)` once the transaction has completed.
+    fn check_complete(&self, token: Token) -> Result<bool, HostError> {
+        let flags = self.pintflag().read();
+        if flags.stall().bit() {
+            self.pintflag().write(|w| w.stall().set_bit());
+            self.freeze();
+            Err(HostError::Stall)
+        } else if flags.perr().bit() {
+            self.pintflag().write(|w| w.perr().set_bit());
+            self.freeze();
+            Err(HostError::PipeError)
+        } else if flags.trfail().bit() {
+            self.pintflag().write(|w| w.trfail().set_bit());
+            self.freeze();
+            Err(HostError::TransferFailed)
+        } else if token == Token::Setup {
+            Ok(flags.txstp().bit())
+        } else {
+            Ok(flags.trcpt0().bit())
+        }
+    }
+}
+
+/// USB host driver
+///
+/// See the [module-level documentation](super) for an example.
+pub struct UsbHost<V: VbusControl> {
+    _dm_pad: DmPad,
+    _dp_pad: DpPad,
+    vbus: V,
+    usb: Usb,
+    desc: &'static mut PipeDescriptors,
+    pipes: [PipeInfo; NUM_PIPES],
+    speed: Option<Speed>,
+    timeout: u16,
+    next_address: u8,
+}
+
+impl<V: VbusControl> UsbHost<V> {
+    /// Create a new USB host driver.
+    ///
+    /// The USB peripheral is switched to host mode, VBUS is powered through
+    /// `vbus` and the root port starts waiting for a device to connect.
+    ///
+    /// The USB peripheral must be clocked at 48 MHz. As the host generates
+    /// the bus timing, the clock must be accurate to 0.25%; the DFLL must
+    /// therefore run in closed loop from a crystal reference, and *not* in
+    /// USB clock recovery mode.
+    #[hal_cfg("usb-d21")]
+    pub fn new(
+        _clock: &clock::UsbClock,
+        pm: &mut Pm,
+        dm_pad: impl AnyPin<Id = PA24>,
+        dp_pad: impl AnyPin<Id = PA25>,
+        vbus: V,
+        usb: Usb,
+    ) -> Self {
+        pm.apbbmask().modify(|_, w| w.usb_().set_bit());
+        Self::init(dm_pad, dp_pad, vbus, usb)
+    }
+
+    /// Create a new USB host driver.
+    ///
+    /// The USB peripheral is switched to host mode, VBUS is powered through
+    /// `vbus` and the root port starts waiting for a device to connect.
+    ///
+    /// The USB peripheral must be clocked at 48 MHz. As the host generates
+    /// the bus timing, the clock must be accurate to 0.25%; the DFLL must
+    /// therefore run in closed loop from a crystal reference, and *not* in
+    /// USB clock recovery mode.
+    #[hal_cfg("usb-d5x")]
+    pub fn new(
+        _clock: &clock::UsbClock,
+        mclk: &mut Mclk,
+        dm_pad: impl AnyPin<Id = PA24>,
+        dp_pad: impl AnyPin<Id = PA25>,
+        vbus: V,
+        usb: Usb,
+    ) -> Self {
+        mclk.ahbmask().modify(|_, w| w.usb_().set_bit());
+        mclk.apbbmask().modify(|_, w| w.usb_().set_bit());
+        Self::init(dm_pad, dp_pad, vbus, usb)
+    }
+
+    fn init(
+        dm_pad: impl AnyPin<Id = PA24>,
+        dp_pad: impl AnyPin<Id = PA25>,
+        vbus: V,
+        usb: Usb,
+    ) -> Self {
+        let mut pipes = [PipeInfo::default(); NUM_PIPES];
+        for (pipe, buffer) in pipes.iter_mut().zip(pipe_buffers(&usb)) {
+            pipe.buffer = buffer;
+        }
+
+        let mut host = Self {
+            _dm_pad: dm_pad.into().into_mode(),
+            _dp_pad: dp_pad.into().into_mode(),
+            vbus,
+            // SAFETY: `init` runs once per `Usb` peripheral taken by value,
+            // and `free` drops the table along with giving the peripheral back.
+            desc: unsafe { descriptors(&usb) },
+            usb,
+            pipes,
+            speed: None,
+            timeout: DEFAULT_TIMEOUT,
+            next_address: 1,
+        };
+        host.enable();
+        host
+    }
+
+    #[inline]
+    fn usb(&self) -> &Host {
+        self.usb.host()
+    }
+
+    #[inline]
+    fn pipe_regs(&self, index: usize) -> PipeRegs<'_> {
+        PipeRegs {
+            host: self.usb(),
+            index,
+        }
+    }
+
+    fn enable(&mut self) {
+        let usb = self.usb();
+        usb.ctrla().modify(|_, w| w.swrst().set_bit());
+        while usb.syncbusy().read().swrst().bit_is_set() {}
+
+        usb.descadd()
+            .write(|w| unsafe { w.descadd().bits(self.desc.address()) });
+        usb.padcal().modify(|_, w| unsafe {
+            w.transn().bits(usb_transn_cal());
+            w.transp().bits(usb_transp_cal());
+            w.trim().bits(usb_trim_cal())
+        });
+        usb.qosctrl().modify(|_, w| unsafe {
+            w.dqos().bits(0b11);
+            w.cqos().bits(0b11)
+        });
+        usb.ctrla().modify(|_, w| {
+            w.mode().host();
+            w.runstdby().set_bit()
+        });
+        usb.ctrlb().modify(|_, w| w.spdconf().normal());
+
+        usb.ctrla().modify(|_, w| w.enable().set_bit());
+        while usb.syncbusy().read().enable().bit_is_set() {}
+
+        // Clear pending.
+        usb.intflag()
+            .write(|w| unsafe { w.bits(usb.intflag().read().bits()) });
+
+        self.set_vbus(true);
+    }
+
+    /// Switches VBUS on or off.
+    ///
+    /// Switching VBUS off disconnects the attached device.
+    pub fn set_vbus(&mut self, enabled: bool) {
+        self.vbus.set_vbus(enabled);
+        // The host state machine only runs while VBUSOK is set
+        self.usb().ctrlb().modify(|_, w| w.vbusok().bit(enabled));
+    }
+
+    /// Sets the timeout of blocking transfers, in milliseconds.
+    ///
+    /// Devices may NAK a transaction indefinitely; the peripheral then keeps
+    /// retrying until the timeout elapses and [`HostError::Timeout`] is
+    /// returned. Defaults to 1000 ms.
+    pub fn set_timeout(&mut self, ms: u16) {
+        self.timeout = ms;
+    }
+
+    /// Polls the root port for connection changes and VBUS faults.
+    ///
+    /// This should be called periodically, or from the USB interrupt
+    /// handler. On disconnection, all pipes are released.
+    pub fn poll(&mut self) -> HostEvent {
+        if self.vbus.fault() {
+            self.set_vbus(false);
+            self.disconnect();
+            return HostEvent::VbusFault;
+        }
+
+        let flags = self.usb().intflag().read();
+        if flags.ddisc().bit() {
+            self.usb()
+                .intflag()
+                .write(|w| w.ddisc().set_bit().dconn().set_bit());
+            self.disconnect();
+            HostEvent::Disconnected
+        } else if flags.dconn().bit() {
+            self.usb().intflag().write(|w| w.dconn().set_bit());
+            let speed = self.read_speed();
+            self.speed = Some(speed);
+            HostEvent::Connected(speed)
+        } else {
+            HostEvent::None
+        }
+    }
+
+    /// Returns the speed of the connected device, if any.
+    pub fn speed(&self) -> Option<Speed> {
+        self.speed
+    }
+
+    fn read_speed(&self) -> Speed {
+        match self.usb().status().read().speed().bits() {
+            1 => Speed::Low,
+            _ => Speed::Full,
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.speed = None;
+        self.next_address = 1;
+        self.usb().ctrlb().modify(|_, w| w.sofe().clear_bit());
+        for index in 0..NUM_PIPES {
+            self.release_pipe(index);
+        }
+    }
+
+    /// Resets the device on the root port.
+    ///
+    /// A bus reset is required before a newly connected device can be
+    /// enumerated. Start-of-frame generation is enabled, and the device is
+    /// given the reset recovery time of the USB specification before this
+    /// returns the detected device speed.
+    pub fn reset_port(&mut self) -> Result<Speed, HostError> {
+        if self.speed.is_none() {
+            return Err(HostError::NotConnected);
+        }
+
+        let usb = self.usb();
+        usb.intflag().write(|w| w.rst().set_bit());
+        usb.ctrlb().modify(|_, w| w.busreset().set_bit());
+        while usb.intflag().read().rst().bit_is_clear() {}
+        usb.intflag().write(|w| w.rst().set_bit());
+        usb.ctrlb().modify(|_, w| w.sofe().set_bit());
+
+        self.wait_frames(RESET_RECOVERY)?;
+
+        let speed = self.read_speed();
+        self.speed = Some(speed);
+        Ok(speed)
+    }
+
+    /// Busy-waits for the given number of frames (milliseconds).
+    ///
+    /// Start-of-frame generation must be running, i.e. the port must have
+    /// been reset with [`reset_port`](Self::reset_port).
+    pub fn wait_frames(&mut self, frames: u16) -> Result<(), HostError> {
+        let mut elapsed = 0;
+        while elapsed < frames {
+            self.check_connected()?;
+            if self.tick() {
+                elapsed += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks, and clears if set, the host start-of-frame flag.
+    #[inline]
+    fn tick(&self) -> bool {
+        if self.usb().intflag().read().hsof().bit() {
+            self.usb().intflag().write(|w| w.hsof().set_bit());
+            true
+        } else {
+            false
+        }
+    }
+
+    #[inline]
+    fn check_connected(&self) -> Result<(), HostError> {
+        if self.speed.is_none() || self.usb().intflag().read().ddisc().bit() {
+            Err(HostError::NotConnected)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Allocates the next free USB device address.
+    pub(super) fn alloc_address(&mut self) -> Result<u8, HostError> {
+        if self.next_address > 127 {
+            return Err(HostError::Unsupported);
+        }
+        let address = self.next_address;
+        self.next_address += 1;
+        Ok(address)
+    }
+
+    /// Allocates and configures a pipe to a device endpoint.
+    ///
+    /// `interval` is the polling interval of interrupt pipes, in frames, and
+    /// is ignored for other pipe types. The direction of `ep` is ignored for
+    /// control pipes.
+    pub fn alloc_pipe(
+        &mut self,
+        device_address: u8,
+        ep: EndpointAddress,
+        ptype: PipeType,
+        max_packet_size: u16,
+        interval: u8,
+    ) -> Result<Pipe, HostError> {
+        if max_packet_size == 0 || max_packet_size as usize > ALLOC_SIZE_MAX_PER_EP {
+            return Err(HostError::Unsupported);
+        }
+
+        let index = self
+            .pipes
+            .iter()
+            .position(|p| p.ptype.is_none())
+            .ok_or(HostError::NoPipes)?;
+
+        let info = &mut self.pipes[index];
+        info.ptype = Some(ptype);
+        info.direction = match ptype {
+            PipeType::Control => None,
+            _ => Some(ep.direction()),
+        };
+        info.max_packet_size = max_packet_size;
+        info.armed = false;
+
+        let buffer = info.buffer as *mut u8;
+        let bank = self.desc.bank(index, 0);
+        bank.set_address(buffer);
+        bank.set_pipe_size(max_packet_size);
+        bank.set_byte_count(0);
+        bank.set_multi_packet_size(0);
+        bank.set_device_address(device_address);
+        bank.set_endpoint_number(ep.index() as u8);
+        bank.set_max_errors(PIPE_MAX_ERRORS);
+        bank.clear_pipe_errors();
+
+        let regs = self.pipe_regs(index);
+        regs.freeze();
+        regs.pcfg()
+            .write(|w| unsafe { w.ptype().bits(ptype as u8) });
+        regs.binterval().write(|w| unsafe {
+            w.bitinterval().bits(if ptype == PipeType::Interrupt {
+                interval
+            } else {
+                0
+            })
+        });
+        regs.set_data_toggle(false);
+        regs.clear_flags();
+
+        Ok(Pipe { index: index as u8 })
+    }
+
+    /// Releases a pipe.
+    pub fn free_pipe(&mut self, pipe: Pipe) {
+        self.release_pipe(pipe.index());
+    }
+
+    fn release_pipe(&mut self, index: usize) {
+        let regs = self.pipe_regs(index);
+        regs.freeze();
+        regs.pcfg().write(|w| unsafe { w.ptype().bits(0) });
+        regs.clear_flags();
+        let info = &mut self.pipes[index];
+        info.ptype = None;
+        info.armed = false;
+    }
+
+    /// Changes the device address and maximum packet size of a pipe.
+    ///
+    /// This is used during enumeration, once the device has been assigned an
+    /// address and its default control pipe size is known.
+    pub fn reconfigure_pipe(
+        &mut self,
+        pipe: Pipe,
+        device_address: u8,
+        max_packet_size: u16,
+    ) -> Result<(), HostError> {
+        if max_packet_size == 0 || max_packet_size as usize > ALLOC_SIZE_MAX_PER_EP {
+            return Err(HostError::Unsupported);
+        }
+        self.info(pipe)?;
+        self.pipes[pipe.index()].max_packet_size = max_packet_size;
+        let bank = self.desc.bank(pipe.index(), 0);
+        bank.set_pipe_size(max_packet_size);
+        bank.set_device_address(device_address);
+        Ok(())
+    }
+
+    /// Resets the data toggle of a pipe to DATA0.
+    ///
+    /// This must be done after clearing an endpoint halt on the device.
+    pub fn reset_data_toggle(&mut self, pipe: Pipe) -> Result<(), HostError> {
+        self.info(pipe)?;
+        self.pipe_regs(pipe.index()).set_data_toggle(false);
+        Ok(())
+    }
+
+    fn info(&self, pipe: Pipe) -> Result<PipeInfo, HostError> {
+        let info = self.pipes[pipe.index()];
+        match info.ptype {
+            Some(_) => Ok(info),
+            None => Err(HostError::InvalidPipe),
+        }
+    }
+
+    #[inline]
+    fn bank(&mut self, pipe: Pipe) -> &mut PipeDescBank {
+        self.desc.bank(pipe.index(), 0)
+    }
+
+    /// Runs a single transaction on a pipe and waits for its completion.
+    ///
+    /// For OUT and SETUP transactions, the data must already be in the pipe
+    /// buffer. Returns the number of bytes transferred.
+    fn transaction(&mut self, pipe: Pipe, token: Token, len: usize) -> Result<usize, HostError> {
+        self.check_connected()?;
+
+        let info = self.info(pipe)?;
+        {
+            let bank = self.bank(pipe);
+            bank.clear_pipe_errors();
+            match token {
+                Token::In => {
+                    bank.set_byte_count(0);
+                    bank.set_multi_packet_size(info.max_packet_size);
+                }
+                Token::Out | Token::Setup => {
+                    bank.set_byte_count(len as u16);
+                    bank.set_multi_packet_size(0);
+                }
+            }
+        }
+
+        let regs = self.pipe_regs(pipe.index());
+        regs.freeze();
+        regs.set_token(token);
+        regs.clear_flags();
+        match token {
+            Token::In => regs.pstatusclr().write(|w| w.bk0rdy().set_bit()),
+            Token::Out | Token::Setup => regs.pstatusset().write(|w| w.bk0rdy().set_bit()),
+        }
+        regs.unfreeze();
+
+        let mut elapsed = 0;
+        loop {
+            match self.pipe_regs(pipe.index()).check_complete(token) {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(e) => return Err(e),
+            }
+            if let Err(e) = self.check_connected() {
+                self.pipe_regs(pipe.index()).freeze();
+                return Err(e);
+            }
+            if self.tick() {
+                elapsed += 1;
+                if elapsed >= self.timeout {
+                    self.pipe_regs(pipe.index()).freeze();
+                    return Err(HostError::Timeout);
+                }
+            }
+        }
+
+        let regs = self.pipe_regs(pipe.index());
+        regs.freeze();
+        regs.clear_flags();
+
+        match token {
+            Token::In => Ok(self.bank(pipe).get_byte_count() as usize),
+            Token::Out | Token::Setup => Ok(len),
+        }
+    }
+
+    /// Copies `data` into the pipe buffer.
+    fn load(&mut self, pipe: Pipe, data: &[u8]) {
+        let addr = self.pipes[pipe.index()].buffer as *mut u8;
+        unsafe {
+            data.as_ptr().copy_to_nonoverlapping(addr, data.len());
+        }
+    }
+
+    /// Copies `len` bytes out of the pipe buffer.
+    fn unload(&mut self, pipe: Pipe, buf: &mut [u8], len: usize) -> Result<(), HostError> {
+        if len > buf.len() {
+            return Err(HostError::BufferOverflow);
+        }
+        let addr = self.pipes[pipe.index()].buffer as *const u8;
+        unsafe {
+            addr.copy_to_nonoverlapping(buf.as_mut_ptr(), len);
+        }
+        Ok(())
+    }
+
+    /// Reads packets from an IN pipe until `buf` is full or a short packet
+    /// is received. Returns the number of bytes read.
+    fn read_packets(&mut self, pipe: Pipe, buf: &mut [u8]) -> Result<usize, HostError> {
+        let max_packet_size = self.info(pipe)?.max_packet_size as usize;
+        let mut total = 0;
+        loop {
+            let len = self.transaction(pipe, Token::In, 0)?;
+            self.unload(pipe, &mut buf[total..], len)?;
+            total += len;
+            if len < max_packet_size || total == buf.len() {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Writes `data` to an OUT pipe, split into maximum-size packets.
+    fn write_packets(&mut self, pipe: Pipe, data: &[u8]) -> Result<(), HostError> {
+        let max_packet_size = self.info(pipe)?.max_packet_size as usize;
+        for chunk in data.chunks(max_packet_size) {
+            self.load(pipe, chunk);
+            self.transaction(pipe, Token::Out, chunk.len())?;
+        }
+        Ok(())
+    }
+
+    fn setup_stage(&mut self, pipe: Pipe, setup: &SetupPacket) -> Result<(), HostError> {
+        if self.info(pipe)?.ptype != Some(PipeType::Control) {
+            return Err(HostError::InvalidPipe);
+        }
+        self.load(pipe, &setup.to_bytes());
+        self.pipe_regs(pipe.index()).set_data_toggle(false);
+        self.transaction(pipe, Token::Setup, 8)?;
+        Ok(())
+    }
+
+    /// Performs a control transfer with an IN data stage.
+    ///
+    /// Returns the number of bytes received in `buf`.
+    pub fn control_in(
+        &mut self,
+        pipe: Pipe,
+        setup: &SetupPacket,
+        buf: &mut [u8],
+    ) -> Result<usize, HostError> {
+        let len = (setup.length as usize).min(buf.len());
+        self.setup_stage(pipe, setup)?;
+
+        // Data stage
+        self.pipe_regs(pipe.index()).set_data_toggle(true);
+        let received = if len > 0 {
+            self.read_packets(pipe, &mut buf[..len])?
+        } else {
+            0
+        };
+
+        // Status stage
+        self.pipe_regs(pipe.index()).set_data_toggle(true);
+        self.transaction(pipe, Token::Out, 0)?;
+        Ok(received)
+    }
+
+    /// Performs a control transfer with an optional OUT data stage.
+    pub fn control_out(
+        &mut self,
+        pipe: Pipe,
+        setup: &SetupPacket,
+        data: &[u8],
+    ) -> Result<(), HostError> {
+        if data.len() != setup.length as usize {
+            return Err(HostError::BufferOverflow);
+        }
+        self.setup_stage(pipe, setup)?;
+
+        // Data stage
+        self.pipe_regs(pipe.index()).set_data_toggle(true);
+        self.write_packets(pipe, data)?;
+
+        // Status stage
+        self.pipe_regs(pipe.index()).set_data_toggle(true);
+        self.transaction(pipe, Token::In, 0)?;
+        Ok(())
+    }
+
+    fn check_direction(
+        &self,
+        pipe: Pipe,
+        ptype: PipeType,
+        direction: UsbDirection,
+    ) -> Result<(), HostError> {
+        let info = self.info(pipe)?;
+        if info.ptype != Some(ptype) || info.direction != Some(direction) {
+            return Err(HostError::InvalidPipe);
+        }
+        Ok(())
+    }
+
+    /// Reads from a bulk IN pipe until `buf` is full or a short packet is
+    /// received. Returns the number of bytes read.
+    pub fn bulk_in(&mut self, pipe: Pipe, buf: &mut [u8]) -> Result<usize, HostError> {
+        self.check_direction(pipe, PipeType::Bulk, UsbDirection::In)?;
+        self.read_packets(pipe, buf)
+    }
+
+    /// Writes `data` to a bulk OUT pipe.
+    ///
+    /// No zero-length packet is appended when `data` is a multiple of the
+    /// pipe's packet size.
+    pub fn bulk_out(&mut self, pipe: Pipe, data: &[u8]) -> Result<(), HostError> {
+        self.check_direction(pipe, PipeType::Bulk, UsbDirection::Out)?;
+        self.write_packets(pipe, data)
+    }
+
+    /// Polls an interrupt IN pipe.
+    ///
+    /// The first call arms the pipe; the peripheral then polls the device at
+    /// the pipe's interval. Returns [`nb::Error::WouldBlock`] until the
+    /// device answers with data, which is then copied into `buf`.
+    pub fn interrupt_in(&mut self, pipe: Pipe, buf: &mut [u8]) -> nb::Result<usize, HostError> {
+        self.check_direction(pipe, PipeType::Interrupt, UsbDirection::In)?;
+        self.check_connected()?;
+
+        let regs = self.pipe_regs(pipe.index());
+        if !self.pipes[pipe.index()].armed {
+            regs.freeze();
+            regs.set_token(Token::In);
+            regs.clear_flags();
+            regs.pstatusclr().write(|w| w.bk0rdy().set_bit());
+            let max_packet_size = self.pipes[pipe.index()].max_packet_size;
+            let bank = self.bank(pipe);
+            bank.clear_pipe_errors();
+            bank.set_byte_count(0);
+            bank.set_multi_packet_size(max_packet_size);
+            self.pipe_regs(pipe.index()).unfreeze();
+            self.pipes[pipe.index()].armed = true;
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let complete = regs.check_complete(Token::In).inspect_err(|_| {
+            self.pipes[pipe.index()].armed = false;
+        })?;
+        if !complete {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let regs = self.pipe_regs(pipe.index());
+        regs.freeze();
+        regs.clear_flags();
+        self.pipes[pipe.index()].armed = false;
+
+        let len = self.bank(pipe).get_byte_count() as usize;
+        self.unload(pipe, buf, len)?;
+        Ok(len)
+    }
+
+    /// Writes `data` to an interrupt OUT pipe.
+    pub fn interrupt_out(&mut self, pipe: Pipe, data: &[u8]) -> Result<(), HostError> {
+        self.check_direction(pipe, PipeType::Interrupt, UsbDirection::Out)?;
+        self.write_packets(pipe, data)
+    }
+
+    /// Returns whether a pipe is currently frozen.
+    pub fn is_frozen(&self, pipe: Pipe) -> bool {
+        self.pipe_regs(pipe.index())
+            .pstatus()
+            .read()
+            .pfreeze()
+            .bit()
+    }
+
+    /// Disables the host, switches VBUS off and returns the resources.
+    ///
+    /// The pipe descriptor table is released as well, so a new host driver
+    /// can be created from the returned peripheral.
+    pub fn free(mut self) -> (DmPad, DpPad, V, Usb) {
+        self.disconnect();
+        self.set_vbus(false);
+        let usb = self.usb();
+        usb.ctrla().modify(|_, w| w.enable().clear_bit());
+        while usb.syncbusy().read().enable().bit_is_set() {}
+        (self._dm_pad, self._dp_pad, self.vbus, self.usb)
+    }
+}
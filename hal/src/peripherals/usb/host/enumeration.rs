@@ -0,0 +1,426 @@
+//! Standard requests and descriptor parsing used to enumerate devices
+
+use super::{HostError, Pipe, PipeType, Speed, UsbHost, VbusControl};
+use usb_device::UsbDirection;
+use usb_device::endpoint::EndpointAddress;
+
+/// Descriptor types
+const DESC_DEVICE: u8 = 1;
+const DESC_CONFIGURATION: u8 = 2;
+const DESC_INTERFACE: u8 = 4;
+const DESC_ENDPOINT: u8 = 5;
+
+/// Standard request codes
+const REQ_CLEAR_FEATURE: u8 = 1;
+const REQ_SET_ADDRESS: u8 = 5;
+const REQ_GET_DESCRIPTOR: u8 = 6;
+const REQ_SET_CONFIGURATION: u8 = 9;
+
+/// ENDPOINT_HALT feature selector
+const FEATURE_ENDPOINT_HALT: u16 = 0;
+
+/// Time granted to the device to apply SET_ADDRESS, in frames
+const SET_ADDRESS_RECOVERY: u16 = 2;
+
+/// Interface class codes recognised by [`UsbHost::enumerate`]
+const CLASS_HID: u8 = 0x03;
+const CLASS_MASS_STORAGE: u8 = 0x08;
+const HID_SUBCLASS_BOOT: u8 = 0x01;
+const HID_PROTOCOL_KEYBOARD: u8 = 0x01;
+const MSC_SUBCLASS_SCSI: u8 = 0x06;
+const MSC_PROTOCOL_BBB: u8 = 0x50;
+
+/// USB control request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SetupPacket {
+    /// bmRequestType
+    pub request_type: u8,
+    /// bRequest
+    pub request: u8,
+    /// wValue
+    pub value: u16,
+    /// wIndex
+    pub index: u16,
+    /// wLength
+    pub length: u16,
+}
+
+impl SetupPacket {
+    /// Standard GET_DESCRIPTOR request to the device
+    pub const fn get_descriptor(desc_type: u8, desc_index: u8, length: u16) -> Self {
+        Self {
+            request_type: 0x80,
+            request: REQ_GET_DESCRIPTOR,
+            value: (desc_type as u16) << 8 | desc_index as u16,
+            index: 0,
+            length,
+        }
+    }
+
+    /// Standard SET_ADDRESS request
+    pub const fn set_address(address: u8) -> Self {
+        Self {
+            request_type: 0x00,
+            request: REQ_SET_ADDRESS,
+            value: address as u16,
+            index: 0,
+            length: 0,
+        }
+    }
+
+    /// Standard SET_CONFIGURATION request
+    pub const fn set_configuration(value: u8) -> Self {
+        Self {
+            request_type: 0x00,
+            request: REQ_SET_CONFIGURATION,
+            value: value as u16,
+            index: 0,
+            length: 0,
+        }
+    }
+
+    /// Standard CLEAR_FEATURE(ENDPOINT_HALT) request
+    pub fn clear_halt(ep: EndpointAddress) -> Self {
+        Self {
+            request_type: 0x02,
+            request: REQ_CLEAR_FEATURE,
+            value: FEATURE_ENDPOINT_HALT,
+            index: u8::from(ep) as u16,
+            length: 0,
+        }
+    }
+
+    /// Class-specific request addressed to an interface
+    pub const fn class_interface(
+        direction: UsbDirection,
+        request: u8,
+        value: u16,
+        interface: u8,
+        length: u16,
+    ) -> Self {
+        Self {
+            request_type: direction as u8 | 0x21,
+            request,
+            value,
+            index: interface as u16,
+            length,
+        }
+    }
+
+    /// Serializes the request as sent in the SETUP stage
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let value = self.value.to_le_bytes();
+        let index = self.index.to_le_bytes();
+        let length = self.length.to_le_bytes();
+        [
+            self.request_type,
+            self.request,
+            value[0],
+            value[1],
+            index[0],
+            index[1],
+            length[0],
+            length[1],
+        ]
+    }
+}
+
+/// Standard device descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeviceDescriptor {
+    pub usb_version: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub protocol: u8,
+    pub max_packet_size0: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub device_version: u16,
+    pub manufacturer: u8,
+    pub product: u8,
+    pub serial_number: u8,
+    pub num_configurations: u8,
+}
+
+impl DeviceDescriptor {
+    /// Size of the device descriptor, in bytes
+    pub const SIZE: usize = 18;
+
+    /// Parses a device descriptor
+    pub fn parse(buf: &[u8]) -> Result<Self, HostError> {
+        if buf.len() < Self::SIZE || buf[0] as usize != Self::SIZE || buf[1] != DESC_DEVICE {
+            return Err(HostError::InvalidDescriptor);
+        }
+        let word = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+        Ok(Self {
+            usb_version: word(2),
+            class: buf[4],
+            subclass: buf[5],
+            protocol: buf[6],
+            max_packet_size0: buf[7],
+            vendor_id: word(8),
+            product_id: word(10),
+            device_version: word(12),
+            manufacturer: buf[14],
+            product: buf[15],
+            serial_number: buf[16],
+            num_configurations: buf[17],
+        })
+    }
+}
+
+/// Endpoint of a device interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EndpointInfo {
+    /// bEndpointAddress
+    pub address: u8,
+    /// Transfer type, from bmAttributes
+    pub transfer_type: u8,
+    /// wMaxPacketSize
+    pub max_packet_size: u16,
+    /// bInterval
+    pub interval: u8,
+}
+
+impl EndpointInfo {
+    /// Endpoint address
+    pub fn address(&self) -> EndpointAddress {
+        EndpointAddress::from(self.address)
+    }
+
+    fn is(&self, ptype: PipeType, direction: UsbDirection) -> bool {
+        self.transfer_type == ptype as u8 - 1 && self.address().direction() == direction
+    }
+}
+
+/// Device function supported by the class drivers of this module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Function {
+    /// HID boot keyboard, see [`Keyboard`](super::Keyboard)
+    Keyboard { interface: u8, ep_in: EndpointInfo },
+    /// SCSI bulk-only mass storage, see [`MassStorage`](super::MassStorage)
+    MassStorage {
+        interface: u8,
+        ep_in: EndpointInfo,
+        ep_out: EndpointInfo,
+    },
+}
+
+/// An enumerated and configured device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Device {
+    /// Assigned device address
+    pub address: u8,
+    /// Device speed
+    pub speed: Speed,
+    /// Device descriptor
+    pub descriptor: DeviceDescriptor,
+    /// bConfigurationValue of the selected configuration
+    pub configuration: u8,
+    /// Default control pipe of the device
+    pub control: Pipe,
+    /// First supported function found in the configuration, if any
+    pub function: Option<Function>,
+}
+
+/// Interface being parsed by [`find_function`]
+struct InterfaceParser {
+    number: u8,
+    class: u8,
+    subclass: u8,
+    protocol: u8,
+    ep_in: Option<EndpointInfo>,
+    ep_out: Option<EndpointInfo>,
+}
+
+impl InterfaceParser {
+    fn function(&self) -> Option<Function> {
+        match (self.class, self.subclass, self.protocol) {
+            (CLASS_HID, HID_SUBCLASS_BOOT, HID_PROTOCOL_KEYBOARD) => {
+                let ep_in = self.ep_in?;
+                ep_in
+                    .is(PipeType::Interrupt, UsbDirection::In)
+                    .then_some(Function::Keyboard {
+                        interface: self.number,
+                        ep_in,
+                    })
+            }
+            (CLASS_MASS_STORAGE, MSC_SUBCLASS_SCSI, MSC_PROTOCOL_BBB) => {
+                let (ep_in, ep_out) = (self.ep_in?, self.ep_out?);
+                (ep_in.is(PipeType::Bulk, UsbDirection::In)
+                    && ep_out.is(PipeType::Bulk, UsbDirection::Out))
+                .then_some(Function::MassStorage {
+                    interface: self.number,
+                    ep_in,
+                    ep_out,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Walks a complete configuration descriptor and returns the first
+/// supported function. Alternate settings other than 0 are ignored.
+fn find_function(config: &[u8]) -> Result<Option<Function>, HostError> {
+    let mut current: Option<InterfaceParser> = None;
+    let mut offset = 0;
+    while offset + 2 <= config.len() {
+        let len = config[offset] as usize;
+        if len < 2 || offset + len > config.len() {
+            return Err(HostError::InvalidDescriptor);
+        }
+        let desc = &config[offset..offset + len];
+        match desc[1] {
+            DESC_INTERFACE if len >= 9 => {
+                if let Some(function) = current.take().and_then(|i| i.function()) {
+                    return Ok(Some(function));
+                }
+                if desc[3] == 0 {
+                    current = Some(InterfaceParser {
+                        number: desc[2],
+                        class: desc[5],
+                        subclass: desc[6],
+                        protocol: desc[7],
+                        ep_in: None,
+                        ep_out: None,
+                    });
+                }
+            }
+            DESC_ENDPOINT if len >= 7 => {
+                if let Some(interface) = current.as_mut() {
+                    let ep = EndpointInfo {
+                        address: desc[2],
+                        transfer_type: desc[3] & 0x03,
+                        max_packet_size: u16::from_le_bytes([desc[4], desc[5]]) & 0x07ff,
+                        interval: desc[6],
+                    };
+                    let slot = match ep.address().direction() {
+                        UsbDirection::In => &mut interface.ep_in,
+                        UsbDirection::Out => &mut interface.ep_out,
+                    };
+                    slot.get_or_insert(ep);
+                }
+            }
+            _ => {}
+        }
+        offset += len;
+    }
+    Ok(current.and_then(|i| i.function()))
+}
+
+impl<V: VbusControl> UsbHost<V> {
+    /// Enumerates the device attached to the root port.
+    ///
+    /// The port must have been reset with [`reset_port`](Self::reset_port).
+    /// The device is assigned an address and its first configuration is
+    /// selected. `config_buf` receives the complete configuration descriptor
+    /// and must be large enough to hold it.
+    ///
+    /// On error, the default control pipe is released.
+    pub fn enumerate(&mut self, config_buf: &mut [u8]) -> Result<Device, HostError> {
+        let speed = self.speed().ok_or(HostError::NotConnected)?;
+        let control = self.alloc_pipe(
+            0,
+            EndpointAddress::from_parts(0, UsbDirection::Out),
+            PipeType::Control,
+            8,
+            0,
+        )?;
+        self.enumerate_with(control, speed, config_buf)
+            .inspect_err(|_| self.free_pipe(control))
+    }
+
+    fn enumerate_with(
+        &mut self,
+        control: Pipe,
+        speed: Speed,
+        config_buf: &mut [u8],
+    ) -> Result<Device, HostError> {
+        let mut buf = [0; DeviceDescriptor::SIZE];
+
+        // Only the first 8 bytes may be read before the size of the default
+        // control pipe is known
+        let setup = SetupPacket::get_descriptor(DESC_DEVICE, 0, 8);
+        if self.control_in(control, &setup, &mut buf)? < 8 {
+            return Err(HostError::InvalidDescriptor);
+        }
+        let max_packet_size0 = match buf[7] {
+            size @ (8 | 16 | 32 | 64) => size as u16,
+            _ => return Err(HostError::InvalidDescriptor),
+        };
+        self.reconfigure_pipe(control, 0, max_packet_size0)?;
+
+        let address = self.alloc_address()?;
+        self.control_out(control, &SetupPacket::set_address(address), &[])?;
+        self.wait_frames(SET_ADDRESS_RECOVERY)?;
+        self.reconfigure_pipe(control, address, max_packet_size0)?;
+
+        let setup = SetupPacket::get_descriptor(DESC_DEVICE, 0, DeviceDescriptor::SIZE as u16);
+        let len = self.control_in(control, &setup, &mut buf)?;
+        let descriptor = DeviceDescriptor::parse(&buf[..len])?;
+
+        // Read the configuration header to learn the total length
+        if config_buf.len() < 9 {
+            return Err(HostError::BufferOverflow);
+        }
+        let setup = SetupPacket::get_descriptor(DESC_CONFIGURATION, 0, 9);
+        let len = self.control_in(control, &setup, &mut config_buf[..9])?;
+        if len < 9 || config_buf[1] != DESC_CONFIGURATION {
+            return Err(HostError::InvalidDescriptor);
+        }
+        let total = u16::from_le_bytes([config_buf[2], config_buf[3]]);
+        let configuration = config_buf[5];
+        if total as usize > config_buf.len() {
+            return Err(HostError::BufferOverflow);
+        }
+
+        let setup = SetupPacket::get_descriptor(DESC_CONFIGURATION, 0, total);
+        let len = self.control_in(control, &setup, config_buf)?;
+        let function = find_function(&config_buf[9.min(len)..len])?;
+
+        self.control_out(control, &SetupPacket::set_configuration(configuration), &[])?;
+
+        Ok(Device {
+            address,
+            speed,
+            descriptor,
+            configuration,
+            control,
+            function,
+        })
+    }
+
+    /// Allocates a pipe to one of the endpoints of an enumerated device.
+    pub fn alloc_endpoint_pipe(
+        &mut self,
+        device: &Device,
+        ep: &EndpointInfo,
+        ptype: PipeType,
+    ) -> Result<Pipe, HostError> {
+        self.alloc_pipe(
+            device.address,
+            ep.address(),
+            ptype,
+            ep.max_packet_size,
+            ep.interval,
+        )
+    }
+
+    /// Clears a halted endpoint on the device and resets the data toggle of
+    /// the corresponding pipe.
+    pub fn clear_halt(
+        &mut self,
+        device: &Device,
+        ep: EndpointAddress,
+        pipe: Pipe,
+    ) -> Result<(), HostError> {
+        self.control_out(device.control, &SetupPacket::clear_halt(ep), &[])?;
+        self.reset_data_toggle(pipe)
+    }
+}
@@ -0,0 +1,124 @@
+//! HID boot protocol keyboard driver
+
+use super::{Device, Function, HostError, Pipe, PipeType, SetupPacket, UsbHost, VbusControl};
+use usb_device::UsbDirection;
+
+/// HID class request codes
+const HID_SET_REPORT: u8 = 0x09;
+const HID_SET_IDLE: u8 = 0x0a;
+const HID_SET_PROTOCOL: u8 = 0x0b;
+
+/// Output report type, in the high byte of wValue of SET_REPORT
+const HID_REPORT_OUTPUT: u16 = 0x02;
+
+/// Boot protocol selector of SET_PROTOCOL
+const HID_PROTOCOL_BOOT: u16 = 0;
+
+/// Keyboard input report in the boot protocol format
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct KeyboardReport {
+    /// Modifier keys bitmap (left ctrl, shift, alt, GUI, then right ones)
+    pub modifiers: u8,
+    /// Usage IDs of up to 6 pressed keys; unused slots are 0
+    pub keys: [u8; 6],
+}
+
+impl KeyboardReport {
+    /// Returns whether the key with the given usage ID is pressed.
+    pub fn is_pressed(&self, key: u8) -> bool {
+        key != 0 && self.keys.contains(&key)
+    }
+
+    /// Returns whether the keyboard reported a rollover error, i.e. too
+    /// many keys are pressed at once.
+    pub fn is_rollover(&self) -> bool {
+        self.keys.iter().all(|&k| k == 0x01)
+    }
+}
+
+/// HID keyboard using the boot protocol
+///
+/// The keyboard must have been enumerated with [`UsbHost::enumerate`] and
+/// reported as [`Function::Keyboard`].
+pub struct Keyboard {
+    device: Device,
+    interface: u8,
+    pipe: Pipe,
+}
+
+impl Keyboard {
+    /// Switches the keyboard to the boot protocol and allocates its
+    /// interrupt pipe.
+    pub fn new<V: VbusControl>(host: &mut UsbHost<V>, device: &Device) -> Result<Self, HostError> {
+        let Some(Function::Keyboard { interface, ep_in }) = device.function else {
+            return Err(HostError::Unsupported);
+        };
+
+        let setup = SetupPacket::class_interface(
+            UsbDirection::Out,
+            HID_SET_PROTOCOL,
+            HID_PROTOCOL_BOOT,
+            interface,
+            0,
+        );
+        host.control_out(device.control, &setup, &[])?;
+
+        // Only report on changes. SET_IDLE is optional for boot keyboards,
+        // so a STALL is not an error.
+        let setup = SetupPacket::class_interface(UsbDirection::Out, HID_SET_IDLE, 0, interface, 0);
+        match host.control_out(device.control, &setup, &[]) {
+            Ok(()) | Err(HostError::Stall) => {}
+            Err(e) => return Err(e),
+        }
+
+        let pipe = host.alloc_endpoint_pipe(device, &ep_in, PipeType::Interrupt)?;
+        Ok(Self {
+            device: *device,
+            interface,
+            pipe,
+        })
+    }
+
+    /// Polls the keyboard for a new input report.
+    ///
+    /// Returns [`nb::Error::WouldBlock`] until the keyboard sends a report.
+    pub fn poll<V: VbusControl>(
+        &mut self,
+        host: &mut UsbHost<V>,
+    ) -> nb::Result<KeyboardReport, HostError> {
+        let mut buf = [0; 8];
+        let len = host.interrupt_in(self.pipe, &mut buf)?;
+        if len < 8 {
+            return Err(nb::Error::Other(HostError::InvalidDescriptor));
+        }
+        let mut keys = [0; 6];
+        keys.copy_from_slice(&buf[2..8]);
+        Ok(KeyboardReport {
+            modifiers: buf[0],
+            keys,
+        })
+    }
+
+    /// Sets the keyboard LEDs (bit 0: Num Lock, 1: Caps Lock, 2: Scroll
+    /// Lock, 3: Compose, 4: Kana).
+    pub fn set_leds<V: VbusControl>(
+        &mut self,
+        host: &mut UsbHost<V>,
+        leds: u8,
+    ) -> Result<(), HostError> {
+        let setup = SetupPacket::class_interface(
+            UsbDirection::Out,
+            HID_SET_REPORT,
+            HID_REPORT_OUTPUT << 8,
+            self.interface,
+            1,
+        );
+        host.control_out(self.device.control, &setup, &[leds])
+    }
+
+    /// Releases the interrupt pipe.
+    pub fn free<V: VbusControl>(self, host: &mut UsbHost<V>) {
+        host.free_pipe(self.pipe);
+    }
+}
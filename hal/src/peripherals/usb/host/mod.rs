@@ -0,0 +1,167 @@
+//! USB Host support
+//!
+//! The USB peripheral of the SAMD21 and SAMx5x can operate as a full- or
+//! low-speed host with a single root port. [`UsbHost`] provides:
+//!
+//! * Root port connection and speed detection with [`UsbHost::poll`], and
+//!   bus reset with [`UsbHost::reset_port`]
+//! * VBUS switching through a [`VbusControl`] implementation, typically an
+//!   output pin driving the enable input of a power switch
+//! * Allocation of the eight hardware [`Pipe`]s
+//! * Blocking control, bulk and interrupt OUT transfers, and non-blocking
+//!   interrupt IN transfers
+//!
+//! On top of this, a minimal enumeration layer assigns an address to the
+//! attached device, selects its first configuration and looks for a function
+//! supported by the class drivers of this module:
+//!
+//! * [`Keyboard`]: HID keyboards, using the boot protocol
+//! * [`MassStorage`]: USB mass storage devices using the SCSI command set
+//!   over the bulk-only transport
+//!
+//! Hubs are not supported, so a single device can be attached at a time.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use atsamd_hal::usb::host::{Function, HostEvent, Keyboard, UsbHost};
+//!
+//! let mut host = UsbHost::new(&usb_clock, &mut mclk, pins.pa24, pins.pa25, vbus_enable, usb);
+//! let mut config_buf = [0; 256];
+//!
+//! loop {
+//!     if let HostEvent::Connected(_) = host.poll() {
+//!         host.reset_port().unwrap();
+//!         let device = host.enumerate(&mut config_buf).unwrap();
+//!         if let Some(Function::Keyboard { .. }) = device.function {
+//!             let mut keyboard = Keyboard::new(&mut host, &device).unwrap();
+//!             loop {
+//!                 if let Ok(report) = keyboard.poll(&mut host) {
+//!                     // Handle key presses
+//!                 }
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! # Buffers
+//!
+//! Each pipe is given a buffer of [`ALLOC_SIZE_MAX_PER_EP`](super::ALLOC_SIZE_MAX_PER_EP)
+//! bytes, the same size as the endpoint buffers of [`UsbBus`](super::UsbBus),
+//! so the `usb-buffer-*` features also bound the maximum packet size of
+//! pipes. The buffers and the pipe descriptor table belong to the owner of the
+//! `USB` peripheral: [`UsbHost::free`] gives the peripheral back, after which
+//! a new [`UsbHost`] can be created from it.
+
+use crate::gpio::pin::{InputConfig, OutputConfig};
+use crate::gpio::{Input, Output, Pin, PinId};
+
+mod bus;
+mod enumeration;
+mod keyboard;
+mod msc;
+mod pipedesc;
+
+pub use bus::{Pipe, PipeType, UsbHost};
+pub use enumeration::*;
+pub use keyboard::*;
+pub use msc::*;
+pub use pipedesc::NUM_PIPES;
+
+/// Speed of the device attached to the root port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Speed {
+    Full,
+    Low,
+}
+
+/// Root port events returned by [`UsbHost::poll`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HostEvent {
+    /// Nothing happened
+    None,
+    /// A device was connected; it must be reset before enumeration
+    Connected(Speed),
+    /// The device was disconnected and all pipes were released
+    Disconnected,
+    /// The VBUS power switch reported a fault and VBUS was switched off
+    VbusFault,
+}
+
+/// Errors returned by USB host operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HostError {
+    /// No device is connected to the root port
+    NotConnected,
+    /// The device answered with a STALL handshake
+    Stall,
+    /// The pipe was frozen after too many consecutive transaction errors
+    PipeError,
+    /// The transfer failed
+    TransferFailed,
+    /// The device did not complete the transfer in time
+    Timeout,
+    /// All pipes are in use
+    NoPipes,
+    /// The pipe is not allocated, or has the wrong type or direction
+    InvalidPipe,
+    /// The received data does not fit in the provided buffer
+    BufferOverflow,
+    /// The device returned a malformed descriptor or response
+    InvalidDescriptor,
+    /// The device or request is not supported
+    Unsupported,
+    /// A class command completed with a failure status
+    CommandFailed,
+}
+
+/// Control of the VBUS supply of the root port
+pub trait VbusControl {
+    /// Switches VBUS on or off.
+    fn set_vbus(&mut self, enabled: bool);
+
+    /// Returns `true` if the power switch reports a fault, such as an
+    /// over-current condition.
+    fn fault(&mut self) -> bool {
+        false
+    }
+}
+
+/// VBUS is supplied externally and cannot be switched
+pub struct NoVbus;
+
+impl VbusControl for NoVbus {
+    fn set_vbus(&mut self, _enabled: bool) {}
+}
+
+/// Active-high VBUS enable pin
+impl<I: PinId, C: OutputConfig> VbusControl for Pin<I, Output<C>> {
+    fn set_vbus(&mut self, enabled: bool) {
+        if enabled {
+            self._set_high();
+        } else {
+            self._set_low();
+        }
+    }
+}
+
+/// Active-high VBUS enable pin and active-low fault input, as found on most
+/// USB power switches
+pub struct VbusPins<E: PinId, EC: OutputConfig, F: PinId, FC: InputConfig> {
+    pub enable: Pin<E, Output<EC>>,
+    pub fault: Pin<F, Input<FC>>,
+}
+
+impl<E: PinId, EC: OutputConfig, F: PinId, FC: InputConfig> VbusControl for VbusPins<E, EC, F, FC> {
+    fn set_vbus(&mut self, enabled: bool) {
+        self.enable.set_vbus(enabled);
+    }
+
+    fn fault(&mut self) -> bool {
+        self.fault._is_low()
+    }
+}
@@ -0,0 +1,313 @@
+//! Mass storage driver, using SCSI commands over the bulk-only transport
+
+use super::{Device, Function, HostError, Pipe, PipeType, SetupPacket, UsbHost, VbusControl};
+use usb_device::UsbDirection;
+use usb_device::endpoint::EndpointAddress;
+
+/// Bulk-only transport class request codes
+const MSC_GET_MAX_LUN: u8 = 0xfe;
+
+/// Command block wrapper
+const CBW_SIGNATURE: u32 = 0x4342_5355;
+const CBW_SIZE: usize = 31;
+
+/// Command status wrapper
+const CSW_SIGNATURE: u32 = 0x5342_5355;
+const CSW_SIZE: usize = 13;
+
+/// SCSI operation codes
+const SCSI_TEST_UNIT_READY: u8 = 0x00;
+const SCSI_REQUEST_SENSE: u8 = 0x03;
+const SCSI_INQUIRY: u8 = 0x12;
+const SCSI_READ_CAPACITY_10: u8 = 0x25;
+const SCSI_READ_10: u8 = 0x28;
+const SCSI_WRITE_10: u8 = 0x2a;
+
+/// Data phase of a bulk-only transport command
+enum DataPhase<'a> {
+    None,
+    In(&'a mut [u8]),
+    Out(&'a [u8]),
+}
+
+impl DataPhase<'_> {
+    fn len(&self) -> usize {
+        match self {
+            DataPhase::None => 0,
+            DataPhase::In(buf) => buf.len(),
+            DataPhase::Out(buf) => buf.len(),
+        }
+    }
+}
+
+/// Sense data returned by REQUEST SENSE
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sense {
+    pub key: u8,
+    pub asc: u8,
+    pub ascq: u8,
+}
+
+/// Capacity returned by READ CAPACITY (10)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Capacity {
+    /// Number of blocks
+    pub block_count: u32,
+    /// Size of a block, in bytes
+    pub block_size: u32,
+}
+
+/// USB mass storage device
+///
+/// The device must have been enumerated with [`UsbHost::enumerate`] and
+/// reported as [`Function::MassStorage`]. Block transfers larger than the
+/// pipe buffers are split into packets by the host driver.
+pub struct MassStorage {
+    device: Device,
+    ep_in: EndpointAddress,
+    ep_out: EndpointAddress,
+    pipe_in: Pipe,
+    pipe_out: Pipe,
+    max_lun: u8,
+    tag: u32,
+}
+
+impl MassStorage {
+    /// Allocates the bulk pipes of the device and reads its number of
+    /// logical units.
+    pub fn new<V: VbusControl>(host: &mut UsbHost<V>, device: &Device) -> Result<Self, HostError> {
+        let Some(Function::MassStorage {
+            interface,
+            ep_in,
+            ep_out,
+        }) = device.function
+        else {
+            return Err(HostError::Unsupported);
+        };
+
+        // Devices with a single LUN may STALL GET_MAX_LUN
+        let setup =
+            SetupPacket::class_interface(UsbDirection::In, MSC_GET_MAX_LUN, 0, interface, 1);
+        let mut max_lun = [0];
+        match host.control_in(device.control, &setup, &mut max_lun) {
+            Ok(_) => {}
+            Err(HostError::Stall) => max_lun[0] = 0,
+            Err(e) => return Err(e),
+        }
+
+        let pipe_in = host.alloc_endpoint_pipe(device, &ep_in, PipeType::Bulk)?;
+        let pipe_out = host
+            .alloc_endpoint_pipe(device, &ep_out, PipeType::Bulk)
+            .inspect_err(|_| host.free_pipe(pipe_in))?;
+
+        Ok(Self {
+            device: *device,
+            ep_in: ep_in.address(),
+            ep_out: ep_out.address(),
+            pipe_in,
+            pipe_out,
+            max_lun: max_lun[0],
+            tag: 0,
+        })
+    }
+
+    /// Highest logical unit number of the device
+    pub fn max_lun(&self) -> u8 {
+        self.max_lun
+    }
+
+    /// Runs a command through the bulk-only transport.
+    ///
+    /// Returns the number of bytes transferred in the data phase.
+    fn command<V: VbusControl>(
+        &mut self,
+        host: &mut UsbHost<V>,
+        lun: u8,
+        cb: &[u8],
+        mut data: DataPhase<'_>,
+    ) -> Result<usize, HostError> {
+        if lun > self.max_lun {
+            return Err(HostError::Unsupported);
+        }
+
+        self.tag = self.tag.wrapping_add(1);
+        let len = data.len();
+        let mut cbw = [0; CBW_SIZE];
+        cbw[0..4].copy_from_slice(&CBW_SIGNATURE.to_le_bytes());
+        cbw[4..8].copy_from_slice(&self.tag.to_le_bytes());
+        cbw[8..12].copy_from_slice(&(len as u32).to_le_bytes());
+        cbw[12] = if matches!(data, DataPhase::In(_)) {
+            0x80
+        } else {
+            0
+        };
+        cbw[13] = lun;
+        cbw[14] = cb.len() as u8;
+        cbw[15..15 + cb.len()].copy_from_slice(cb);
+        host.bulk_out(self.pipe_out, &cbw)?;
+
+        // A STALL ends the data phase early; the status is still read after
+        // clearing the halt.
+        let transferred = match &mut data {
+            DataPhase::None => Ok(0),
+            DataPhase::In(buf) => host.bulk_in(self.pipe_in, buf),
+            DataPhase::Out(buf) => host.bulk_out(self.pipe_out, buf).map(|_| buf.len()),
+        };
+        let transferred = match transferred {
+            Ok(n) => n,
+            Err(HostError::Stall) => {
+                let (ep, pipe) = match data {
+                    DataPhase::Out(_) => (self.ep_out, self.pipe_out),
+                    _ => (self.ep_in, self.pipe_in),
+                };
+                host.clear_halt(&self.device, ep, pipe)?;
+                0
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut csw = [0; CSW_SIZE];
+        let received = match host.bulk_in(self.pipe_in, &mut csw) {
+            Err(HostError::Stall) => {
+                host.clear_halt(&self.device, self.ep_in, self.pipe_in)?;
+                host.bulk_in(self.pipe_in, &mut csw)?
+            }
+            result => result?,
+        };
+
+        let signature = u32::from_le_bytes([csw[0], csw[1], csw[2], csw[3]]);
+        let tag = u32::from_le_bytes([csw[4], csw[5], csw[6], csw[7]]);
+        if received != CSW_SIZE || signature != CSW_SIGNATURE || tag != self.tag {
+            return Err(HostError::InvalidDescriptor);
+        }
+        match csw[12] {
+            0 => Ok(transferred),
+            _ => Err(HostError::CommandFailed),
+        }
+    }
+
+    /// Checks whether the logical unit is ready.
+    ///
+    /// Returns [`HostError::CommandFailed`] if it is not, for instance
+    /// while a card reader has no medium inserted. The reason can then be
+    /// retrieved with [`request_sense`](Self::request_sense).
+    pub fn test_unit_ready<V: VbusControl>(
+        &mut self,
+        host: &mut UsbHost<V>,
+        lun: u8,
+    ) -> Result<(), HostError> {
+        let cb = [SCSI_TEST_UNIT_READY, 0, 0, 0, 0, 0];
+        self.command(host, lun, &cb, DataPhase::None)?;
+        Ok(())
+    }
+
+    /// Retrieves the sense data of the last failed command.
+    pub fn request_sense<V: VbusControl>(
+        &mut self,
+        host: &mut UsbHost<V>,
+        lun: u8,
+    ) -> Result<Sense, HostError> {
+        let mut buf = [0; 18];
+        let cb = [SCSI_REQUEST_SENSE, 0, 0, 0, buf.len() as u8, 0];
+        let len = self.command(host, lun, &cb, DataPhase::In(&mut buf))?;
+        if len < 14 {
+            return Err(HostError::InvalidDescriptor);
+        }
+        Ok(Sense {
+            key: buf[2] & 0x0f,
+            asc: buf[12],
+            ascq: buf[13],
+        })
+    }
+
+    /// Reads the standard INQUIRY data of the logical unit into `buf`.
+    ///
+    /// Returns the number of bytes read; 36 bytes hold the standard data.
+    pub fn inquiry<V: VbusControl>(
+        &mut self,
+        host: &mut UsbHost<V>,
+        lun: u8,
+        buf: &mut [u8],
+    ) -> Result<usize, HostError> {
+        let len = buf.len().min(u8::MAX as usize);
+        let cb = [SCSI_INQUIRY, 0, 0, 0, len as u8, 0];
+        self.command(host, lun, &cb, DataPhase::In(&mut buf[..len]))
+    }
+
+    /// Reads the capacity of the logical unit.
+    pub fn read_capacity<V: VbusControl>(
+        &mut self,
+        host: &mut UsbHost<V>,
+        lun: u8,
+    ) -> Result<Capacity, HostError> {
+        let mut buf = [0; 8];
+        let cb = [SCSI_READ_CAPACITY_10, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        if self.command(host, lun, &cb, DataPhase::In(&mut buf))? < 8 {
+            return Err(HostError::InvalidDescriptor);
+        }
+        let last_block = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
+        Ok(Capacity {
+            block_count: last_block.wrapping_add(1),
+            block_size: u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]),
+        })
+    }
+
+    /// Builds a READ (10) or WRITE (10) command block.
+    fn rw10(opcode: u8, lba: u32, blocks: usize) -> Result<[u8; 10], HostError> {
+        let blocks = u16::try_from(blocks).map_err(|_| HostError::Unsupported)?;
+        let lba = lba.to_be_bytes();
+        let blocks = blocks.to_be_bytes();
+        Ok([
+            opcode, 0, lba[0], lba[1], lba[2], lba[3], 0, blocks[0], blocks[1], 0,
+        ])
+    }
+
+    /// Reads consecutive blocks starting at `lba`.
+    ///
+    /// The length of `buf` must be a multiple of `block_size`.
+    pub fn read_blocks<V: VbusControl>(
+        &mut self,
+        host: &mut UsbHost<V>,
+        lun: u8,
+        lba: u32,
+        block_size: u32,
+        buf: &mut [u8],
+    ) -> Result<(), HostError> {
+        if block_size == 0 || buf.len() % block_size as usize != 0 {
+            return Err(HostError::BufferOverflow);
+        }
+        let cb = Self::rw10(SCSI_READ_10, lba, buf.len() / block_size as usize)?;
+        let len = buf.len();
+        if self.command(host, lun, &cb, DataPhase::In(buf))? != len {
+            return Err(HostError::TransferFailed);
+        }
+        Ok(())
+    }
+
+    /// Writes consecutive blocks starting at `lba`.
+    ///
+    /// The length of `data` must be a multiple of `block_size`.
+    pub fn write_blocks<V: VbusControl>(
+        &mut self,
+        host: &mut UsbHost<V>,
+        lun: u8,
+        lba: u32,
+        block_size: u32,
+        data: &[u8],
+    ) -> Result<(), HostError> {
+        if block_size == 0 || data.len() % block_size as usize != 0 {
+            return Err(HostError::BufferOverflow);
+        }
+        let cb = Self::rw10(SCSI_WRITE_10, lba, data.len() / block_size as usize)?;
+        self.command(host, lun, &cb, DataPhase::Out(data))?;
+        Ok(())
+    }
+
+    /// Releases the bulk pipes.
+    pub fn free<V: VbusControl>(self, host: &mut UsbHost<V>) {
+        host.free_pipe(self.pipe_in);
+        host.free_pipe(self.pipe_out);
+    }
+}
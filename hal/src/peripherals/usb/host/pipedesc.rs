@@ -0,0 +1,246 @@
+use bitfield::bitfield;
+use core::fmt::{Debug, Error as FmtError, Formatter};
+use core::mem::{self, MaybeUninit};
+use core::ptr::null_mut;
+
+use crate::pac::Usb;
+use crate::usb::ALLOC_SIZE_MAX_PER_EP;
+
+type FmtResult = Result<(), FmtError>;
+
+/// Number of host pipes provided by the USB peripheral
+pub const NUM_PIPES: usize = 8;
+
+bitfield! {
+    struct PckSize(u32);
+    impl Debug;
+    pub byte_count, set_byte_count: 13, 0;
+    pub multi_packet_size, set_multi_packet_size: 27, 14;
+    pub size, set_size: 30, 28;
+    pub auto_zlp, set_auto_zlp : 31;
+}
+
+bitfield! {
+    struct ExtReg(u16);
+    impl Debug;
+    pub subpid, set_subpid: 3, 0;
+    pub variable, set_variable: 14, 4;
+}
+
+bitfield! {
+    struct StatusBk(u8);
+    impl Debug;
+    pub crc_error, set_crc_error: 0;
+    pub error_flow, set_error_flow: 1;
+}
+
+bitfield! {
+    struct CtrlPipe(u16);
+    impl Debug;
+    pub pdaddr, set_pdaddr: 6, 0;
+    pub pepnum, set_pepnum: 11, 8;
+    pub permax, set_permax: 15, 12;
+}
+
+bitfield! {
+    struct StatusPipe(u16);
+    impl Debug;
+    pub dtgler, set_dtgler: 0;
+    pub dapider, set_dapider: 1;
+    pub pider, set_pider: 2;
+    pub touter, set_touter: 3;
+    pub crc16er, set_crc16er: 4;
+    pub ercnt, set_ercnt: 7, 5;
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct PipeDescBank {
+    /// pipe data buffer, must be 32-bit aligned
+    addr: *mut u8,
+    pcksize: PckSize,
+    extreg: ExtReg,
+    status_bk: StatusBk,
+    _reserved0: u8,
+    ctrl_pipe: CtrlPipe,
+    status_pipe: StatusPipe,
+}
+
+impl PipeDescBank {
+    fn new() -> Self {
+        debug_assert_eq!(16, mem::size_of::<PipeDescBank>());
+        Self {
+            addr: null_mut(),
+            pcksize: PckSize(0),
+            extreg: ExtReg(0),
+            status_bk: StatusBk(0),
+            _reserved0: 0,
+            ctrl_pipe: CtrlPipe(0),
+            status_pipe: StatusPipe(0),
+        }
+    }
+
+    /// These bits contains the maximum packet size of the pipe.
+    ///
+    /// The maximum packet size is encoded in 3 bits; this method takes any u16
+    /// below 1024B and rounds up to the lowest pipe size value which will
+    /// accommodate `size`.  Panics if a `size` > 1023 is supplied.
+    pub fn set_pipe_size(&mut self, size: u16) {
+        let size = match size {
+            1..=8 => 0u32,
+            9..=16 => 1,
+            17..=32 => 2,
+            33..=64 => 3,
+            65..=128 => 4,
+            129..=256 => 5,
+            257..=512 => 6,
+            513..=1023 => 7,
+            _ => unreachable!(),
+        };
+        self.pcksize.set_size(size);
+    }
+
+    /// For IN pipes, MULTI_PACKET_SIZE holds the total number of bytes
+    /// expected. Writing zero limits the transfer to a single packet.
+    pub fn set_multi_packet_size(&mut self, size: u16) {
+        self.pcksize.set_multi_packet_size(size.into());
+    }
+
+    /// For OUT or SETUP pipes, BYTE_COUNT holds the number of bytes to be
+    /// sent in the next transaction.
+    /// For IN pipes, BYTE_COUNT holds the number of bytes received upon the
+    /// last IN transaction.
+    pub fn set_byte_count(&mut self, size: u16) {
+        self.pcksize.set_byte_count(size.into());
+    }
+
+    /// For OUT or SETUP pipes, BYTE_COUNT holds the number of bytes to be
+    /// sent in the next transaction.
+    /// For IN pipes, BYTE_COUNT holds the number of bytes received upon the
+    /// last IN transaction.
+    pub fn get_byte_count(&self) -> u16 {
+        self.pcksize.byte_count() as u16
+    }
+
+    /// Sets the address of the device this pipe talks to. Only valid for
+    /// bank 0.
+    pub fn set_device_address(&mut self, address: u8) {
+        self.ctrl_pipe.set_pdaddr(address.into());
+    }
+
+    /// Sets the endpoint number this pipe talks to. Only valid for bank 0.
+    pub fn set_endpoint_number(&mut self, ep: u8) {
+        self.ctrl_pipe.set_pepnum(ep.into());
+    }
+
+    /// Sets the maximum number of consecutive errors before the pipe is
+    /// frozen and PINTFLAG.PERR is raised. Only valid for bank 0.
+    pub fn set_max_errors(&mut self, count: u8) {
+        self.ctrl_pipe.set_permax(count.min(15).into());
+    }
+
+    /// Clears the error status of the pipe, including the error counter.
+    pub fn clear_pipe_errors(&mut self) {
+        self.status_pipe = StatusPipe(0);
+        self.status_bk = StatusBk(0);
+    }
+
+    pub fn set_address(&mut self, address: *mut u8) {
+        self.addr = address;
+    }
+}
+
+#[derive(Debug)]
+#[repr(C)]
+pub struct PipeDescriptor {
+    bank: [PipeDescBank; 2],
+}
+
+impl PipeDescriptor {
+    fn new() -> Self {
+        debug_assert_eq!(32, mem::size_of::<PipeDescriptor>());
+        Self {
+            bank: [PipeDescBank::new(), PipeDescBank::new()],
+        }
+    }
+}
+
+pub struct PipeDescriptors {
+    desc: [PipeDescriptor; NUM_PIPES],
+}
+
+impl Debug for PipeDescriptors {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+        for pipe in 0..NUM_PIPES {
+            write!(fmt, "\npipe{}: {:?}", pipe, self.desc[pipe])?;
+        }
+        Ok(())
+    }
+}
+
+impl PipeDescriptors {
+    fn new() -> Self {
+        Self {
+            desc: [
+                PipeDescriptor::new(),
+                PipeDescriptor::new(),
+                PipeDescriptor::new(),
+                PipeDescriptor::new(),
+                PipeDescriptor::new(),
+                PipeDescriptor::new(),
+                PipeDescriptor::new(),
+                PipeDescriptor::new(),
+            ],
+        }
+    }
+
+    pub fn address(&self) -> u32 {
+        &self.desc as *const _ as u32
+    }
+
+    pub fn bank(&mut self, idx: usize, bank: usize) -> &mut PipeDescBank {
+        &mut self.desc[idx].bank[bank]
+    }
+}
+
+/// Storage of the pipe descriptor table
+///
+/// The table is read by the USB peripheral through DMA, so it must never move
+/// once its address is written to DESCADD.
+static mut DESCRIPTORS: MaybeUninit<PipeDescriptors> = MaybeUninit::uninit();
+
+/// Takes the pipe descriptor table, resetting it to its initial state.
+///
+/// # Safety
+///
+/// The returned reference must be the only one to the table. Only the owner
+/// of the [`Usb`] peripheral may call this, at most once while it holds the
+/// peripheral, and the reference must be dropped before the peripheral is
+/// given back.
+pub(super) unsafe fn descriptors(_usb: &Usb) -> &'static mut PipeDescriptors {
+    let table = (&raw mut DESCRIPTORS).cast::<PipeDescriptors>();
+    // SAFETY: The caller guarantees that no other reference to the table
+    // exists, and the table is initialized before the reference is created.
+    unsafe {
+        table.write(PipeDescriptors::new());
+        &mut *table
+    }
+}
+
+/// Packet buffers of the pipes, aligned to 4 bytes
+#[repr(C, align(4))]
+struct PipeBuffers([[u8; ALLOC_SIZE_MAX_PER_EP]; NUM_PIPES]);
+
+static mut PIPE_BUFFERS: PipeBuffers = PipeBuffers([[0; ALLOC_SIZE_MAX_PER_EP]; NUM_PIPES]);
+
+/// Returns the addresses of the packet buffers, one per pipe.
+///
+/// Like the descriptor table, the buffers belong to the owner of the [`Usb`]
+/// peripheral.
+pub(super) fn pipe_buffers(_usb: &Usb) -> [usize; NUM_PIPES] {
+    let buffers = &raw mut PIPE_BUFFERS;
+    // SAFETY: Only the addresses are taken here; see `descriptors`.
+    core::array::from_fn(|pipe| unsafe { (&raw mut (*buffers).0[pipe]) as usize })
+}
+
+unsafe impl Send for PipeDescBank {}
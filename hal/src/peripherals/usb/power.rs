@@ -0,0 +1,57 @@
+//! USB device link power management
+
+/// Link power state of the USB device
+///
+/// The device reports two low-power link states to `usb-device`, both as
+/// [`PollResult::Suspend`](usb_device::bus::PollResult::Suspend):
+///
+/// * L2 suspend, entered after 3 ms of bus inactivity. A bus-powered device
+///   must then draw no more than 2.5 mA from VBUS.
+/// * L1 sleep, entered when the host sends an LPM transaction that the device
+///   acknowledges. L1 is only used if enabled with
+///   [`UsbBus::set_lpm_mode`](super::UsbBus::set_lpm_mode). The host only
+///   issues LPM transactions to devices with a `bcdUSB` of 0x0201 which
+///   advertise LPM in the USB 2.0 extension capability of their BOS
+///   descriptor.
+///
+/// The application is notified of link state changes through a
+/// [`LinkStateCallback`], which is the place to scale down clocks or prepare
+/// for standby. The callback is called from `UsbDevice::poll`, which usually
+/// runs in the USB interrupt handler, so it should not itself wait for an
+/// interrupt; sleep from the main loop instead.
+///
+/// While suspended, a device the host has allowed to do so (see
+/// `UsbDevice::remote_wakeup_enabled`) can wake the host up with
+/// [`UsbBus::remote_wakeup`](super::UsbBus::remote_wakeup).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LinkState {
+    /// L0: the link is active
+    Active,
+    /// L1: sleep entered through an LPM transaction
+    Sleep {
+        /// Best effort service latency requested by the host, encoded as in
+        /// the LPM token
+        besl: u8,
+        /// Whether the host allows the device to wake it up
+        remote_wakeup: bool,
+    },
+    /// L2: suspend entered after 3 ms of bus inactivity
+    Suspend,
+}
+
+/// Handshake of the device to LPM transactions
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LpmMode {
+    /// LPM is not supported, and LPM transactions are not answered
+    #[default]
+    Disabled,
+    /// LPM transactions are acknowledged, and the device enters L1 sleep
+    Ack,
+    /// LPM transactions are answered with NYET, and the device stays active
+    Nyet,
+}
+
+/// Callback notified of link state changes, see [`LinkState`]
+pub type LinkStateCallback = fn(LinkState);
@@ -1,11 +1,57 @@
+//! # Watchdog timer
+//!
+//! The [`Watchdog`] resets the device if it is not fed within the configured
+//! period, counted in cycles of its clock. On SAMD11/SAMD21 it is clocked by
+//! `GCLK_WDT`. On SAMx5x it is clocked by the 1.024 kHz output of the
+//! OSCULP32K oscillator, so a period of [`WatchdogTimeout::Cycles1K`] lasts
+//! one second.
+//!
+//! ## Window mode
+//!
+//! A [`Watchdog<Window>`] additionally resets the device if it is fed too
+//! early, within the closed window that starts each period. See
+//! [`Watchdog::into_window`].
+//!
+//! ## Early warning
+//!
+//! The early warning interrupt fires a configurable time before the watchdog
+//! resets the device, e.g. to save diagnostics. Enable it with
+//! [`Watchdog::enable_early_warning`], and call [`on_early_warning`] from the
+//! `WDT` interrupt handler:
+//!
+//! ```no_run
+//! # use atsamd_hal::pac::interrupt;
+//! # use atsamd_hal::watchdog;
+//! #[interrupt]
+//! fn WDT() {
+//!     watchdog::on_early_warning();
+//! }
+//! ```
+//!
+//! ## Always-on mode
+//!
+//! [`Watchdog::into_always_on`] locks the watchdog configuration until the
+//! next power-on reset. The returned [`AlwaysOnWatchdog`] can only be fed.
+use core::cell::Cell;
+use core::marker::PhantomData;
+
+use atsamd_hal_macros::{hal_cfg, hal_macro_helper};
+use critical_section::Mutex;
+
 use crate::ehal_02::watchdog;
 use crate::pac::Wdt;
-use atsamd_hal_macros::hal_macro_helper;
+use crate::typelevel::{NoneT, Sealed};
+
+#[hal_cfg("wdt-d5x")]
+use crate::clock::v2::{Source, apb::ApbClk, osculp32k::OscUlp1kId, types};
+#[hal_cfg("wdt-d5x")]
+use crate::typelevel::{Decrement, Increment};
 
 /// WatchdogTimeout enumerates usable values for configuring
 /// the timeout of the watchdog peripheral.
 #[repr(u8)]
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum WatchdogTimeout {
     Cycles8 = 0,
     Cycles16,
@@ -21,79 +67,335 @@ pub enum WatchdogTimeout {
     Cycles16K,
 }
 
-pub struct Watchdog {
+impl From<WatchdogTimeout> for u8 {
+    #[inline]
+    fn from(timeout: WatchdogTimeout) -> u8 {
+        timeout as u8
+    }
+}
+
+//==============================================================================
+// Modes
+//==============================================================================
+
+/// Type-level enum for the watchdog mode
+pub trait WatchdogMode: Sealed {
+    /// Value of the window enable bit
+    const WEN: bool;
+}
+
+/// Normal mode, the watchdog can be fed at any time within the period
+pub enum Normal {}
+
+impl Sealed for Normal {}
+impl WatchdogMode for Normal {
+    const WEN: bool = false;
+}
+
+/// Window mode, feeding the watchdog within the closed window resets the
+/// device
+pub enum Window {}
+
+impl Sealed for Window {}
+impl WatchdogMode for Window {
+    const WEN: bool = true;
+}
+
+//==============================================================================
+// Early warning
+//==============================================================================
+
+/// Function called by [`on_early_warning`]
+type EarlyWarningHook = Option<fn()>;
+
+static EARLY_WARNING_HOOK: Mutex<Cell<EarlyWarningHook>> = Mutex::new(Cell::new(None));
+
+/// Handles the watchdog early warning interrupt.
+///
+/// Call this from the `WDT` interrupt handler. If the early warning flag is
+/// set, it is cleared and the hook registered with
+/// [`Watchdog::enable_early_warning`] is called. The hook runs in interrupt
+/// context, and the watchdog resets the device once the period expires unless
+/// it is fed.
+pub fn on_early_warning() {
+    // Safety: Only the write-one-to-clear INTFLAG register is accessed, which
+    // does not interfere with the owner of the `Wdt`.
+    let wdt = unsafe { &*Wdt::ptr() };
+    if wdt.intflag().read().ew().bit_is_set() {
+        wdt.intflag().write(|w| w.ew().set_bit());
+        if let Some(hook) = critical_section::with(|cs| EARLY_WARNING_HOOK.borrow(cs).get()) {
+            hook();
+        }
+    }
+}
+
+//==============================================================================
+// Register helpers
+//==============================================================================
+
+#[hal_macro_helper]
+fn sync(wdt: &Wdt) {
+    #[hal_cfg(any("wdt-d11", "wdt-d21"))]
+    while wdt.status().read().syncbusy().bit_is_set() {}
+    #[hal_cfg("wdt-d5x")]
+    while wdt.syncbusy().read().bits() != 0 {}
+}
+
+#[hal_macro_helper]
+fn is_enabled(wdt: &Wdt) -> bool {
+    #[hal_cfg(any("wdt-d11", "wdt-d21"))]
+    return wdt.ctrl().read().enable().bit_is_set();
+    #[hal_cfg("wdt-d5x")]
+    return wdt.ctrla().read().enable().bit_is_set();
+}
+
+#[hal_macro_helper]
+fn set_control(wdt: &Wdt, enable: bool, wen: bool) {
+    sync(wdt);
+    #[hal_cfg(any("wdt-d11", "wdt-d21"))]
+    wdt.ctrl()
+        .modify(|_, w| w.enable().bit(enable).wen().bit(wen));
+    #[hal_cfg("wdt-d5x")]
+    wdt.ctrla()
+        .modify(|_, w| w.enable().bit(enable).wen().bit(wen));
+    sync(wdt);
+}
+
+fn feed(wdt: &Wdt) {
+    wdt.clear().write(|w| unsafe { w.clear().bits(0xA5) });
+}
+
+//==============================================================================
+// Watchdog
+//==============================================================================
+
+/// Watchdog timer in [`Normal`] or [`Window`] mode
+///
+/// `C` is the APB clock of the WDT when the watchdog was created with
+/// `Watchdog::new_v2`, and [`NoneT`] otherwise.
+pub struct Watchdog<M: WatchdogMode = Normal, C = NoneT> {
     wdt: Wdt,
+    apb_clk: C,
+    mode: PhantomData<M>,
 }
 
 impl Watchdog {
     pub fn new(wdt: Wdt) -> Self {
-        Self { wdt }
+        Self {
+            wdt,
+            apb_clk: NoneT,
+            mode: PhantomData,
+        }
     }
 }
 
-impl watchdog::Watchdog for Watchdog {
+#[hal_cfg("wdt-d5x")]
+impl Watchdog<Normal, ApbClk<types::Wdt>> {
+    /// Creates the watchdog using the `clock::v2` API.
+    ///
+    /// The WDT is clocked by the 1.024 kHz output of the OSCULP32K
+    /// oscillator. This will [`Increment`] the consumer count of the
+    /// [`EnabledOscUlp1k`](crate::clock::v2::osculp32k::EnabledOscUlp1k).
+    #[inline]
+    pub fn new_v2<S>(wdt: Wdt, apb_clk: ApbClk<types::Wdt>, osc: S) -> (Self, S::Inc)
+    where
+        S: Source<Id = OscUlp1kId> + Increment,
+    {
+        let watchdog = Self {
+            wdt,
+            apb_clk,
+            mode: PhantomData,
+        };
+        (watchdog, osc.inc())
+    }
+}
+
+impl<C> Watchdog<Normal, C> {
+    /// Switches to window mode.
+    ///
+    /// Each period starts with a closed window of `window` cycles, followed
+    /// by the open window of the configured period. Feeding the watchdog
+    /// within the closed window resets the device. A running watchdog is
+    /// briefly stopped to change the configuration.
+    pub fn into_window(self, window: WatchdogTimeout) -> Watchdog<Window, C> {
+        let mut watchdog = self.into_mode::<Window>();
+        watchdog.reconfigure(|wdt| {
+            wdt.config()
+                .modify(|_, w| unsafe { w.window().bits(window.into()) });
+        });
+        watchdog
+    }
+}
+
+impl<C> Watchdog<Window, C> {
+    /// Switches back to normal mode.
+    pub fn into_normal(self) -> Watchdog<Normal, C> {
+        let mut watchdog = self.into_mode::<Normal>();
+        watchdog.reconfigure(|_| {});
+        watchdog
+    }
+}
+
+impl<M: WatchdogMode, C> Watchdog<M, C> {
+    fn into_mode<N: WatchdogMode>(self) -> Watchdog<N, C> {
+        Watchdog {
+            wdt: self.wdt,
+            apb_clk: self.apb_clk,
+            mode: PhantomData,
+        }
+    }
+
+    /// Runs `f` with the watchdog disabled, for enable-protected registers,
+    /// then restores the enable state and applies the window mode of `M`.
+    fn reconfigure(&mut self, f: impl FnOnce(&Wdt)) {
+        let enabled = is_enabled(&self.wdt);
+        if enabled {
+            set_control(&self.wdt, false, M::WEN);
+        }
+        f(&self.wdt);
+        set_control(&self.wdt, enabled, M::WEN);
+    }
+
+    /// Feeds the watchdog, restarting the period.
+    #[inline]
+    pub fn feed(&mut self) {
+        feed(&self.wdt);
+    }
+
+    /// Enables the early warning interrupt, `offset` cycles after the start
+    /// of the period (of the open window in window mode).
+    ///
+    /// `hook` is called by [`on_early_warning`], which has to be called from
+    /// the `WDT` interrupt handler. This only sets the WDT configuration; it
+    /// does not configure the interrupt controller. A running watchdog is
+    /// briefly stopped to change the offset.
+    pub fn enable_early_warning(&mut self, offset: WatchdogTimeout, hook: Option<fn()>) {
+        critical_section::with(|cs| EARLY_WARNING_HOOK.borrow(cs).set(hook));
+        self.reconfigure(|wdt| {
+            wdt.ewctrl()
+                .write(|w| unsafe { w.ewoffset().bits(offset.into()) });
+        });
+        self.wdt.intflag().write(|w| w.ew().set_bit());
+        self.wdt.intenset().write(|w| w.ew().set_bit());
+    }
+
+    /// Disables the early warning interrupt and removes its hook.
+    pub fn disable_early_warning(&mut self) {
+        self.wdt.intenclr().write(|w| w.ew().set_bit());
+        critical_section::with(|cs| EARLY_WARNING_HOOK.borrow(cs).set(None));
+    }
+
+    /// Starts the watchdog with the given period and locks its configuration
+    /// until the next power-on reset.
+    ///
+    /// The watchdog cannot be stopped or reconfigured afterwards, not even by
+    /// a system reset other than power-on reset. The early warning
+    /// configuration is kept.
+    #[hal_macro_helper]
+    pub fn into_always_on(mut self, period: WatchdogTimeout) -> AlwaysOnWatchdog<M, C> {
+        self.reconfigure(|wdt| {
+            wdt.config()
+                .modify(|_, w| unsafe { w.per().bits(period.into()) });
+        });
+        sync(&self.wdt);
+        #[hal_cfg(any("wdt-d11", "wdt-d21"))]
+        self.wdt
+            .ctrl()
+            .modify(|_, w| w.wen().bit(M::WEN).alwayson().set_bit());
+        #[hal_cfg("wdt-d5x")]
+        self.wdt
+            .ctrla()
+            .modify(|_, w| w.wen().bit(M::WEN).alwayson().set_bit());
+        sync(&self.wdt);
+        AlwaysOnWatchdog { watchdog: self }
+    }
+}
+
+impl<M: WatchdogMode> Watchdog<M, NoneT> {
+    /// Releases the WDT resource
+    #[inline]
+    pub fn free(self) -> Wdt {
+        self.wdt
+    }
+}
+
+#[hal_cfg("wdt-d5x")]
+impl<M: WatchdogMode> Watchdog<M, ApbClk<types::Wdt>> {
+    /// Releases the WDT resource and its clocks.
+    ///
+    /// This will [`Decrement`] the consumer count of the
+    /// [`EnabledOscUlp1k`](crate::clock::v2::osculp32k::EnabledOscUlp1k).
+    #[inline]
+    pub fn free_v2<S>(self, osc: S) -> (Wdt, ApbClk<types::Wdt>, S::Dec)
+    where
+        S: Source<Id = OscUlp1kId> + Decrement,
+    {
+        (self.wdt, self.apb_clk, osc.dec())
+    }
+}
+
+impl<M: WatchdogMode, C> watchdog::Watchdog for Watchdog<M, C> {
     /// Feeds an existing watchdog to ensure the processor isn't reset.
     /// Sometimes commonly referred to as "kicking" or "refreshing".
     fn feed(&mut self) {
-        self.wdt.clear().write(|w| unsafe { w.clear().bits(0xA5) });
+        feed(&self.wdt);
     }
 }
 
 /// Disables a running watchdog timer so the processor won't be reset.
-impl watchdog::WatchdogDisable for Watchdog {
-    #[hal_macro_helper]
+impl<M: WatchdogMode, C> watchdog::WatchdogDisable for Watchdog<M, C> {
     fn disable(&mut self) {
-        #[hal_cfg(any("wdt-d11", "wdt-d21"))]
-        {
-            // Disable the watchdog timer.
-            self.wdt.ctrl().write(|w| w.enable().clear_bit());
-            // Wait for watchdog timer to be disabled.
-            while self.wdt.status().read().syncbusy().bit_is_set() {}
-        }
-        #[hal_cfg("wdt-d5x")]
-        {
-            // Disable the watchdog timer.
-            self.wdt.ctrla().write(|w| w.enable().clear_bit());
-            // Wait for watchdog timer to be disabled.
-            while self.wdt.syncbusy().read().enable().bit_is_set() {}
-        }
+        // Disable the watchdog timer and wait for it to be disabled.
+        set_control(&self.wdt, false, M::WEN);
     }
 }
 
-impl watchdog::WatchdogEnable for Watchdog {
+impl<M: WatchdogMode, C> watchdog::WatchdogEnable for Watchdog<M, C> {
     type Time = u8;
 
     /// Enables a watchdog timer to reset the processor if software is frozen
     /// or stalled. Pass [`WatchdogTimeout`] as the period.
-    /// 
+    ///
     /// As WDT is driven by a 1024Hz clock, the time until timeout can be calculated
     /// as `(1 second/1024)*period`
-    /// 
+    ///
     /// EG:
     /// `Timeout of 2048 cycles = (1/1024)*2048 = 2 seconds`
-    #[hal_macro_helper]
     fn start<T>(&mut self, period: T)
     where
         T: Into<Self::Time>,
     {
-        // Write the timeout configuration.
-        self.wdt
-            .config()
-            .write(|w| unsafe { w.per().bits(period.into()) });
-        #[hal_cfg(any("wdt-d11", "wdt-d21"))]
-        {
-            // Enable the watchdog timer.
-            self.wdt.ctrl().write(|w| w.enable().set_bit());
-            // Wait for watchdog timer to be enabled.
-            while self.wdt.status().read().syncbusy().bit_is_set() {}
-        }
+        // Write the timeout configuration, keeping the window.
+        let period = period.into();
+        self.reconfigure(|wdt| {
+            wdt.config().modify(|_, w| unsafe { w.per().bits(period) });
+        });
+        // Enable the watchdog timer and wait for it to be enabled.
+        set_control(&self.wdt, true, M::WEN);
+    }
+}
 
-        #[hal_cfg("wdt-d5x")]
-        {
-            // Enable the watchdog timer.
-            self.wdt.ctrla().write(|w| w.enable().set_bit());
-            // Wait for watchdog timer to be enabled.
-            while self.wdt.syncbusy().read().enable().bit_is_set() {}
-        }
+//==============================================================================
+// AlwaysOnWatchdog
+//==============================================================================
+
+/// Watchdog locked in always-on mode until the next power-on reset
+///
+/// Created by [`Watchdog::into_always_on`].
+pub struct AlwaysOnWatchdog<M: WatchdogMode = Normal, C = NoneT> {
+    watchdog: Watchdog<M, C>,
+}
+
+impl<M: WatchdogMode, C> AlwaysOnWatchdog<M, C> {
+    /// Feeds the watchdog, restarting the period.
+    #[inline]
+    pub fn feed(&mut self) {
+        feed(&self.watchdog.wdt);
+    }
+}
+
+impl<M: WatchdogMode, C> watchdog::Watchdog for AlwaysOnWatchdog<M, C> {
+    fn feed(&mut self) {
+        feed(&self.watchdog.wdt);
     }
 }
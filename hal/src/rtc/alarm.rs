@@ -0,0 +1,219 @@
+//! Calendar alarms of the RTC in clock mode.
+//!
+//! An alarm compares the fields of the `CLOCK` register with the alarm
+//! time. [`AlarmMatch`] selects which fields take part in the comparison, so
+//! that an alarm can repeat every minute, hour, day, month or year. A daily
+//! alarm at 06:30:00 for example is set with
+//! [`AlarmMatch::HoursMinutesSeconds`]:
+//!
+//! ```no_run
+//! # use atsamd_hal::rtc::{Rtc, ClockMode, Datetime};
+//! # use atsamd_hal::rtc::alarm::{Alarm, AlarmMatch};
+//! # fn example(rtc: &mut Rtc<ClockMode>) {
+//! let time = Datetime {
+//!     hours: 6,
+//!     minutes: 30,
+//!     ..Default::default()
+//! };
+//! rtc.set_alarm(Alarm::Alarm0, time, AlarmMatch::HoursMinutesSeconds);
+//! # }
+//! ```
+//!
+//! The SAMx5x has two alarms, the SAMD11/SAMD21 a single one.
+
+use atsamd_hal_macros::{hal_cfg, hal_macro_helper};
+
+use super::modes::{
+    RtcMode as _,
+    mode2::{Alarm0, RtcMode2},
+};
+use super::{ClockMode, Datetime, Rtc};
+
+#[hal_cfg("rtc-d5x")]
+use super::modes::mode2::Alarm1;
+
+/// RTC alarm
+#[hal_macro_helper]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Alarm {
+    /// Alarm 0
+    Alarm0,
+    /// Alarm 1
+    #[hal_cfg("rtc-d5x")]
+    Alarm1,
+}
+
+impl Alarm {
+    #[inline]
+    #[hal_macro_helper]
+    fn index(self) -> usize {
+        match self {
+            Alarm::Alarm0 => 0,
+            #[hal_cfg("rtc-d5x")]
+            Alarm::Alarm1 => 1,
+        }
+    }
+}
+
+/// Fields of the clock compared with the alarm time
+///
+/// Fields not taken into account repeat the alarm, e.g.
+/// [`AlarmMatch::MinutesSeconds`] triggers once per hour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AlarmMatch {
+    /// Alarm disabled
+    Off,
+    /// Match seconds only
+    Seconds,
+    /// Match minutes and seconds
+    MinutesSeconds,
+    /// Match hours, minutes and seconds
+    HoursMinutesSeconds,
+    /// Match day, hours, minutes and seconds
+    DayTime,
+    /// Match month, day, hours, minutes and seconds
+    MonthDayTime,
+    /// Match all fields
+    Full,
+}
+
+/// Writes the SEL field of a MASK register.
+macro_rules! write_mask {
+    ($w:ident, $matching:expr) => {
+        match $matching {
+            AlarmMatch::Off => $w.sel().off(),
+            AlarmMatch::Seconds => $w.sel().ss(),
+            AlarmMatch::MinutesSeconds => $w.sel().mmss(),
+            AlarmMatch::HoursMinutesSeconds => $w.sel().hhmmss(),
+            AlarmMatch::DayTime => $w.sel().ddhhmmss(),
+            AlarmMatch::MonthDayTime => $w.sel().mmddhhmmss(),
+            AlarmMatch::Full => $w.sel().yymmddhhmmss(),
+        }
+    };
+}
+
+impl Rtc<ClockMode> {
+    #[inline]
+    #[hal_macro_helper]
+    fn set_alarm_match(&mut self, alarm: Alarm, matching: AlarmMatch) {
+        // SYNC: Write
+        RtcMode2::sync(&self.rtc);
+        #[hal_cfg(any("rtc-d11", "rtc-d21"))]
+        self.rtc
+            .mode2()
+            .mask(alarm.index())
+            .write(|w| write_mask!(w, matching));
+        #[hal_cfg("rtc-d5x")]
+        match alarm {
+            Alarm::Alarm0 => self.rtc.mode2().mask0().write(|w| write_mask!(w, matching)),
+            Alarm::Alarm1 => self.rtc.mode2().mask1().write(|w| write_mask!(w, matching)),
+        };
+    }
+
+    /// Sets an alarm, triggering when the fields of the clock selected by
+    /// `matching` equal those of `time`.
+    ///
+    /// This also clears the alarm flag.
+    pub fn set_alarm(&mut self, alarm: Alarm, time: Datetime, matching: AlarmMatch) {
+        RtcMode2::set_compare(&self.rtc, alarm.index(), time);
+        self.set_alarm_match(alarm, matching);
+        self.clear_alarm(alarm);
+    }
+
+    /// Disables an alarm.
+    pub fn disable_alarm(&mut self, alarm: Alarm) {
+        self.set_alarm_match(alarm, AlarmMatch::Off);
+    }
+
+    /// Returns the time an alarm is set to.
+    #[inline]
+    #[hal_macro_helper]
+    pub fn alarm_time(&self, alarm: Alarm) -> Datetime {
+        // SYNC: Write (we just read though)
+        #[hal_cfg(any("rtc-d11", "rtc-d21"))]
+        return self.rtc.mode2().alarm(alarm.index()).read().into();
+        #[hal_cfg("rtc-d5x")]
+        match alarm {
+            Alarm::Alarm0 => self.rtc.mode2().alarm0().read().into(),
+            Alarm::Alarm1 => self.rtc.mode2().alarm1().read().into(),
+        }
+    }
+
+    /// Returns `true` if an alarm triggered since its flag was last cleared.
+    #[inline]
+    #[hal_macro_helper]
+    pub fn alarm_triggered(&self, alarm: Alarm) -> bool {
+        match alarm {
+            Alarm::Alarm0 => RtcMode2::check_interrupt_flag::<Alarm0>(&self.rtc),
+            #[hal_cfg("rtc-d5x")]
+            Alarm::Alarm1 => RtcMode2::check_interrupt_flag::<Alarm1>(&self.rtc),
+        }
+    }
+
+    /// Clears the flag of an alarm.
+    #[inline]
+    #[hal_macro_helper]
+    pub fn clear_alarm(&mut self, alarm: Alarm) {
+        match alarm {
+            Alarm::Alarm0 => RtcMode2::clear_interrupt_flag::<Alarm0>(&self.rtc),
+            #[hal_cfg("rtc-d5x")]
+            Alarm::Alarm1 => RtcMode2::clear_interrupt_flag::<Alarm1>(&self.rtc),
+        }
+    }
+
+    /// Enables the interrupt of an alarm.
+    ///
+    /// This only sets the RTC configuration; it does not configure the
+    /// interrupt controller or define an interrupt handler.
+    #[inline]
+    #[hal_macro_helper]
+    pub fn enable_alarm_interrupt(&mut self, alarm: Alarm) {
+        match alarm {
+            Alarm::Alarm0 => RtcMode2::enable_interrupt::<Alarm0>(&self.rtc),
+            #[hal_cfg("rtc-d5x")]
+            Alarm::Alarm1 => RtcMode2::enable_interrupt::<Alarm1>(&self.rtc),
+        }
+    }
+
+    /// Disables the interrupt of an alarm.
+    #[inline]
+    #[hal_macro_helper]
+    pub fn disable_alarm_interrupt(&mut self, alarm: Alarm) {
+        match alarm {
+            Alarm::Alarm0 => RtcMode2::disable_interrupt::<Alarm0>(&self.rtc),
+            #[hal_cfg("rtc-d5x")]
+            Alarm::Alarm1 => RtcMode2::disable_interrupt::<Alarm1>(&self.rtc),
+        }
+    }
+
+    /// Waits for an alarm to trigger, then clears its flag.
+    ///
+    /// Returns immediately if the alarm already triggered since its flag was
+    /// last cleared. The `RTC` interrupt has to be bound to
+    /// [`InterruptHandler`](super::InterruptHandler). It is enabled in the
+    /// NVIC by this method.
+    #[cfg(feature = "async")]
+    #[hal_macro_helper]
+    pub async fn wait_alarm<I>(&mut self, alarm: Alarm, _irqs: I)
+    where
+        I: crate::async_hal::interrupts::Binding<
+                crate::async_hal::interrupts::RTC,
+                super::InterruptHandler,
+            >,
+    {
+        use crate::async_hal::interrupts::{Interrupt, RTC};
+
+        // Bit of the alarm in INTENSET/INTENCLR/INTFLAG
+        #[hal_cfg(any("rtc-d11", "rtc-d21"))]
+        let mask = 1 << alarm.index();
+        #[hal_cfg("rtc-d5x")]
+        let mask = 1 << (8 + alarm.index());
+
+        RTC::unpend();
+        unsafe { RTC::enable() };
+        super::async_api::wait_until(&self.rtc, mask, || self.alarm_triggered(alarm)).await;
+        self.clear_alarm(alarm);
+    }
+}
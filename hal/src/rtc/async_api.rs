@@ -0,0 +1,67 @@
+//! Async support for the RTC interrupt.
+//!
+//! The [`InterruptHandler`] masks every RTC interrupt that fired and wakes the
+//! waiting task, which then checks and clears the flags it is interested in.
+//! This keeps the handler independent of the RTC mode.
+
+use atsamd_hal_macros::hal_cfg;
+use core::{future::poll_fn, task::Poll};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use crate::async_hal::interrupts::{Handler, RTC};
+use crate::pac;
+use crate::typelevel::Sealed;
+
+/// Raw value of the INTENSET/INTENCLR/INTFLAG registers
+#[hal_cfg(any("rtc-d11", "rtc-d21"))]
+pub(super) type IntBits = u8;
+
+/// Raw value of the INTENSET/INTENCLR/INTFLAG registers
+#[hal_cfg("rtc-d5x")]
+pub(super) type IntBits = u16;
+
+static RTC_WAKER: AtomicWaker = AtomicWaker::new();
+
+/// Interrupt handler for the `RTC` interrupt, used by the async RTC methods.
+pub struct InterruptHandler {
+    _private: (),
+}
+
+impl Sealed for InterruptHandler {}
+
+impl Handler<RTC> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let rtc = unsafe { &*pac::Rtc::ptr() };
+        // NOTE: These registers have the same layout in all modes.
+        // SYNC: None
+        let pending = rtc.mode0().intenset().read().bits() & rtc.mode0().intflag().read().bits();
+        if pending != 0 {
+            // SYNC: None
+            rtc.mode0().intenclr().write(|w| unsafe { w.bits(pending) });
+            RTC_WAKER.wake();
+        }
+    }
+}
+
+/// Waits until `done` returns `true`, enabling the interrupts in `mask` while
+/// pending.
+///
+/// The `RTC` interrupt has to be enabled in the NVIC.
+pub(super) async fn wait_until(rtc: &pac::Rtc, mask: IntBits, mut done: impl FnMut() -> bool) {
+    poll_fn(|cx| {
+        if done() {
+            return Poll::Ready(());
+        }
+        RTC_WAKER.register(cx.waker());
+        // SYNC: None
+        rtc.mode0().intenset().write(|w| unsafe { w.bits(mask) });
+        if done() {
+            // SYNC: None
+            rtc.mode0().intenclr().write(|w| unsafe { w.bits(mask) });
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
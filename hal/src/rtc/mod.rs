@@ -1,5 +1,5 @@
 //! Real-time clock/counter
-use atsamd_hal_macros::hal_cfg;
+use atsamd_hal_macros::{hal_cfg, hal_module};
 use fugit::ExtU32;
 
 use crate::ehal;
@@ -21,6 +21,16 @@ use embedded_sdmmc::{TimeSource, Timestamp};
 
 mod modes;
 
+#[cfg(feature = "async")]
+mod async_api;
+#[cfg(feature = "async")]
+pub use async_api::InterruptHandler;
+
+pub mod alarm;
+pub mod periodic;
+#[hal_module("rtc-d5x")]
+pub mod tamper {}
+
 #[cfg(feature = "rtic")]
 pub mod rtic;
 
@@ -67,6 +77,34 @@ impl From<Datetime> for Timestamp {
     }
 }
 
+/// Error returned by [`Rtc::set_frequency_correction`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The requested correction exceeds the range of FREQCORR
+    CorrectionOutOfRange,
+}
+
+/// Encodes a correction in parts per billion into the FREQCORR VALUE and SIGN
+/// fields.
+///
+/// SIGN=0 is a positive correction, which slows the RTC down, so speeding it
+/// up (positive `ppb`) sets SIGN.
+fn encode_frequency_correction(ppb: i32) -> Result<(u8, bool), Error> {
+    let steps = (i64::from(ppb).abs() * (1 << 20) + 500_000_000) / 1_000_000_000;
+    if steps > 127 {
+        return Err(Error::CorrectionOutOfRange);
+    }
+    Ok((steps as u8, ppb > 0))
+}
+
+/// Decodes the FREQCORR VALUE and SIGN fields into a correction in parts per
+/// billion, the inverse of [`encode_frequency_correction`].
+fn decode_frequency_correction(value: u8, sign: bool) -> i32 {
+    let ppb = ((i64::from(value) * 1_000_000_000) >> 20) as i32;
+    if sign { ppb } else { -ppb }
+}
+
 /// Represents the RTC peripheral for either clock/calendar or timer mode.
 pub struct Rtc<Mode: RtcMode> {
     rtc: pac::Rtc,
@@ -107,6 +145,17 @@ impl<Mode: RtcMode> Rtc<Mode> {
         Rtc::create(self.rtc, self.rtc_clock_freq)
     }
 
+    /// Runs `f` with the RTC disabled, for enable-protected registers.
+    fn with_disabled(&mut self, f: impl FnOnce(&pac::Rtc)) {
+        // NOTE: CTRLA.ENABLE is the same in all modes.
+        RtcMode0::disable(&self.rtc);
+        // SYNC: Write
+        RtcMode0::sync(&self.rtc);
+        f(&self.rtc);
+        RtcMode0::enable(&self.rtc);
+        RtcMode0::sync(&self.rtc);
+    }
+
     /// Reconfigures the RTC for 32-bit counter mode with no prescaler (default
     /// state after reset) and the counter initialized to zero and started.
     pub fn into_count32_mode(self) -> Rtc<Count32Mode> {
@@ -121,6 +170,38 @@ impl<Mode: RtcMode> Rtc<Mode> {
         self.into_mode()
     }
 
+    /// Corrects the frequency of the RTC clock by `ppb` parts per billion.
+    ///
+    /// A positive value speeds up the RTC, compensating a crystal running
+    /// too slow. The correction is applied in steps of about 0.954 ppm, up to
+    /// ±121 ppm, by adding or removing prescaler cycles. The value is rounded
+    /// to the nearest step.
+    pub fn set_frequency_correction(&mut self, ppb: i32) -> Result<(), Error> {
+        let (value, sign) = encode_frequency_correction(ppb)?;
+
+        // NOTE: This register and field are the same in all modes.
+        // SYNC: Write
+        RtcMode0::sync(&self.rtc);
+        self.rtc
+            .mode0()
+            .freqcorr()
+            .write(|w| unsafe { w.value().bits(value).sign().bit(sign) });
+        RtcMode0::sync(&self.rtc);
+        Ok(())
+    }
+
+    /// Returns the frequency correction of the RTC clock, in parts per
+    /// billion.
+    ///
+    /// This is the value applied by the hardware, which is rounded to steps
+    /// of about 0.954 ppm.
+    pub fn frequency_correction(&self) -> i32 {
+        // NOTE: This register and field are the same in all modes.
+        // SYNC: None
+        let freqcorr = self.rtc.mode0().freqcorr().read();
+        decode_frequency_correction(freqcorr.value().bits(), freqcorr.sign().bit_is_set())
+    }
+
     /// Releases the RTC resource
     pub fn free(self) -> pac::Rtc {
         self.rtc
@@ -344,3 +425,31 @@ impl TimerParams {
         TimerParams { divider, cycles }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frequency_correction_encoding() {
+        // Speeding up sets SIGN, slowing down clears it
+        assert_eq!(encode_frequency_correction(2_000), Ok((2, true)));
+        assert_eq!(encode_frequency_correction(-2_000), Ok((2, false)));
+        assert_eq!(encode_frequency_correction(0), Ok((0, false)));
+        assert_eq!(encode_frequency_correction(121_000), Ok((127, true)));
+        assert_eq!(
+            encode_frequency_correction(-122_000),
+            Err(Error::CorrectionOutOfRange)
+        );
+
+        assert_eq!(decode_frequency_correction(2, true), 1_907);
+        assert_eq!(decode_frequency_correction(2, false), -1_907);
+        for ppb in [-100_000, -954, 954, 100_000] {
+            let (value, sign) = encode_frequency_correction(ppb).unwrap();
+            assert_eq!(
+                decode_frequency_correction(value, sign).signum(),
+                ppb.signum()
+            );
+        }
+    }
+}
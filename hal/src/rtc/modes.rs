@@ -509,9 +509,9 @@ pub mod mode1 {
 pub mod mode2 {
     use super::*;
 
-    // These actually aren't needed for anything right now
-    //create_rtc_interrupt!(mode2, Alarm0, alarm0);
-    //create_rtc_interrupt!(mode2, Alarm1, alarm1);
+    create_rtc_interrupt!(mode2, Alarm0, alarm0);
+    #[hal_cfg("rtc-d5x")]
+    create_rtc_interrupt!(mode2, Alarm1, alarm1);
 
     /// Datetime represents an RTC clock/calendar value.
     #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
@@ -549,6 +549,8 @@ pub mod mode2 {
     from_reg_datetime!(alarm0);
     #[hal_cfg("rtc-d5x")]
     from_reg_datetime!(alarm1);
+    #[hal_cfg("rtc-d5x")]
+    from_reg_datetime!(timestamp);
 
     /// Macro to write to the clock or alarm registers.
     macro_rules! write_datetime {
@@ -0,0 +1,143 @@
+//! Periodic events and interrupts of the RTC.
+//!
+//! The RTC prescaler taps `PER0`..`PER7` toggle at fixed fractions of the
+//! RTC input clock, independently of the RTC mode and prescaler setting. With
+//! the usual 1.024 kHz RTC clock, `PER0` runs at 128 Hz and `PER7` at 1 Hz.
+//!
+//! All devices can output the taps as events. The SAMx5x can additionally
+//! raise an interrupt for each of them.
+
+use atsamd_hal_macros::{hal_cfg, hal_macro_helper};
+
+use super::{Rtc, RtcMode};
+
+/// Prescaler tap `PERn`, running at `f_CLK_RTC / 2^(n + 3)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Periodic {
+    /// 128 Hz with a 1.024 kHz RTC clock
+    Per0 = 0,
+    /// 64 Hz with a 1.024 kHz RTC clock
+    Per1,
+    /// 32 Hz with a 1.024 kHz RTC clock
+    Per2,
+    /// 16 Hz with a 1.024 kHz RTC clock
+    Per3,
+    /// 8 Hz with a 1.024 kHz RTC clock
+    Per4,
+    /// 4 Hz with a 1.024 kHz RTC clock
+    Per5,
+    /// 2 Hz with a 1.024 kHz RTC clock
+    Per6,
+    /// 1 Hz with a 1.024 kHz RTC clock
+    Per7,
+}
+
+impl Periodic {
+    /// Bit of the tap in EVCTRL and INTENSET/INTENCLR/INTFLAG
+    #[inline]
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl<Mode: RtcMode> Rtc<Mode> {
+    /// Enables or disables the periodic event output of a prescaler tap.
+    ///
+    /// EVCTRL can only be written while the RTC is disabled, so this briefly
+    /// stops the RTC.
+    #[hal_macro_helper]
+    pub fn set_periodic_event(&mut self, periodic: Periodic, enable: bool) {
+        #[hal_cfg(any("rtc-d11", "rtc-d21"))]
+        let mask = periodic.mask() as u16;
+        #[hal_cfg("rtc-d5x")]
+        let mask = periodic.mask() as u32;
+        self.with_disabled(|rtc| {
+            // NOTE: This register and field are the same in all modes.
+            // SYNC: None (enable-protected)
+            rtc.mode0().evctrl().modify(|r, w| unsafe {
+                if enable {
+                    w.bits(r.bits() | mask)
+                } else {
+                    w.bits(r.bits() & !mask)
+                }
+            });
+        });
+    }
+
+    /// Enables the periodic interrupt of a prescaler tap.
+    ///
+    /// This only sets the RTC configuration; it does not configure the
+    /// interrupt controller or define an interrupt handler.
+    #[hal_cfg("rtc-d5x")]
+    #[inline]
+    pub fn enable_periodic_interrupt(&mut self, periodic: Periodic) {
+        // NOTE: This register and field are the same in all modes.
+        // SYNC: None
+        self.rtc
+            .mode0()
+            .intenset()
+            .write(|w| unsafe { w.bits(periodic.mask().into()) });
+    }
+
+    /// Disables the periodic interrupt of a prescaler tap.
+    #[hal_cfg("rtc-d5x")]
+    #[inline]
+    pub fn disable_periodic_interrupt(&mut self, periodic: Periodic) {
+        // NOTE: This register and field are the same in all modes.
+        // SYNC: None
+        self.rtc
+            .mode0()
+            .intenclr()
+            .write(|w| unsafe { w.bits(periodic.mask().into()) });
+    }
+
+    /// Returns `true` if a prescaler tap ticked since its flag was last
+    /// cleared.
+    #[hal_cfg("rtc-d5x")]
+    #[inline]
+    pub fn periodic_triggered(&self, periodic: Periodic) -> bool {
+        // NOTE: This register and field are the same in all modes.
+        // SYNC: None
+        self.rtc.mode0().intflag().read().bits() & u16::from(periodic.mask()) != 0
+    }
+
+    /// Clears the periodic interrupt flag of a prescaler tap.
+    #[hal_cfg("rtc-d5x")]
+    #[inline]
+    pub fn clear_periodic(&mut self, periodic: Periodic) {
+        // NOTE: This register and field are the same in all modes.
+        // SYNC: None
+        self.rtc
+            .mode0()
+            .intflag()
+            .write(|w| unsafe { w.bits(periodic.mask().into()) });
+    }
+
+    /// Waits for the next tick of a prescaler tap.
+    ///
+    /// The `RTC` interrupt has to be bound to
+    /// [`InterruptHandler`](super::InterruptHandler). It is enabled in the
+    /// NVIC by this method.
+    #[hal_cfg("rtc-d5x")]
+    #[cfg(feature = "async")]
+    pub async fn wait_periodic<I>(&mut self, periodic: Periodic, _irqs: I)
+    where
+        I: crate::async_hal::interrupts::Binding<
+                crate::async_hal::interrupts::RTC,
+                super::InterruptHandler,
+            >,
+    {
+        use crate::async_hal::interrupts::{Interrupt, RTC};
+
+        RTC::unpend();
+        unsafe { RTC::enable() };
+        self.clear_periodic(periodic);
+        super::async_api::wait_until(&self.rtc, periodic.mask().into(), || {
+            self.periodic_triggered(periodic)
+        })
+        .await;
+        self.clear_periodic(periodic);
+    }
+}
@@ -0,0 +1,418 @@
+//! Tamper detection and backup registers of the SAMx5x RTC.
+//!
+//! The RTC monitors up to five tamper inputs (`IN0`..`IN4`) on the backup
+//! I/O pins. A tamper condition sets the tamper flag, can wake the device,
+//! and can capture the current time in the `TIMESTAMP` register. With
+//! active layer protection, the RTC drives a pseudo-random pattern on `OUT`
+//! which has to be looped back to the inputs, so that cutting or shorting
+//! the trace is detected as well.
+//!
+//! The RTC also holds the general purpose registers `GP0`..`GP3` and the
+//! backup registers `BKUP0`..`BKUP7`, which keep their content in backup
+//! sleep mode. Both can be wiped by hardware on tamper detection, see
+//! [`Rtc::set_tamper_wipe`].
+//!
+//! Tamper inputs are claimed by passing the pin to
+//! [`Rtc::enable_tamper_input`], which returns a [`TamperInput`] to give the
+//! pin back through [`Rtc::disable_tamper_input`].
+//!
+//! Most of the tamper configuration registers can only be written while the
+//! RTC is disabled. The configuration methods therefore briefly stop the
+//! RTC, which may delay the counter by a few RTC clock cycles.
+
+use atsamd_hal_macros::hal_cfg;
+
+use super::modes::{RtcMode as _, mode0::RtcMode0};
+use super::{ClockMode, Count32Mode, Datetime, Rtc, RtcMode};
+use crate::gpio::{Input, InputConfig, Pin, PinId};
+
+#[hal_cfg("pa02")]
+use crate::gpio::PA02;
+#[hal_cfg("pb00")]
+use crate::gpio::PB00;
+#[hal_cfg("pb02")]
+use crate::gpio::PB02;
+#[hal_cfg("pc00")]
+use crate::gpio::PC00;
+#[hal_cfg("pc01")]
+use crate::gpio::PC01;
+#[hal_cfg("pb01")]
+use crate::gpio::{PB01, PushPullOutput};
+
+pub use crate::pac::rtc::mode0::ctrlb::{
+    Actfselect as ActiveLayerFrequency, Debfselect as DebounceFrequency,
+};
+
+/// Number of backup registers (`BKUP0`..`BKUP7`)
+pub const BACKUP_REGISTERS: usize = 8;
+
+/// Number of general purpose registers (`GP0`..`GP3`)
+pub const GENERAL_PURPOSE_REGISTERS: usize = 4;
+
+/// Bit of the tamper interrupt in INTENSET/INTENCLR/INTFLAG
+#[cfg(feature = "async")]
+const TAMPER_INTERRUPT: super::async_api::IntBits = 1 << 14;
+
+/// [`PinId`] of a pin usable as RTC tamper input
+pub trait TamperPinId: PinId {
+    /// Number of the tamper input (`INn`)
+    const NUM: u8;
+}
+
+macro_rules! tamper_pin {
+    ($pin:ident, $num:literal) => {
+        impl TamperPinId for $pin {
+            const NUM: u8 = $num;
+        }
+    };
+}
+
+#[hal_cfg("pb00")]
+tamper_pin!(PB00, 0);
+#[hal_cfg("pb02")]
+tamper_pin!(PB02, 1);
+#[hal_cfg("pa02")]
+tamper_pin!(PA02, 2);
+#[hal_cfg("pc00")]
+tamper_pin!(PC00, 3);
+#[hal_cfg("pc01")]
+tamper_pin!(PC01, 4);
+
+/// Action taken on a tamper condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TamperAction {
+    /// Set the tamper flag and wake the device
+    Wake,
+    /// Set the tamper flag and capture the time in `TIMESTAMP`
+    Capture,
+}
+
+/// Edge of the tamper input which is detected as tamper condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TamperEdge {
+    /// Falling edge
+    Falling,
+    /// Rising edge
+    Rising,
+}
+
+/// Configuration of a tamper input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TamperConfig {
+    /// Action taken on a tamper condition
+    pub action: TamperAction,
+    /// Detected edge
+    pub edge: TamperEdge,
+    /// Debounce the input, see [`Rtc::set_tamper_debounce`]
+    pub debounce: bool,
+}
+
+/// Tamper input claimed by the RTC
+///
+/// Created by [`Rtc::enable_tamper_input`] or
+/// [`Rtc::enable_active_layer_input`].
+pub struct TamperInput<I: TamperPinId, C: InputConfig> {
+    pin: Pin<I, Input<C>>,
+}
+
+/// Active layer output (`OUT`) driven by the RTC
+///
+/// Created by [`Rtc::enable_active_layer`].
+#[hal_cfg("pb01")]
+pub struct ActiveLayer {
+    pin: Pin<PB01, PushPullOutput>,
+}
+
+/// Tamper inputs which detected a tamper condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TamperStatus {
+    /// Bitmask of the inputs, bit `n` representing `INn`
+    pub inputs: u8,
+    /// A tamper event was received from the event system
+    pub event: bool,
+}
+
+impl TamperStatus {
+    /// Returns `true` if any tamper condition was detected
+    #[inline]
+    pub fn detected(&self) -> bool {
+        self.inputs != 0 || self.event
+    }
+
+    /// Returns `true` if tamper input `n` detected a tamper condition
+    #[inline]
+    pub fn input(&self, n: u8) -> bool {
+        self.inputs & (1 << n) != 0
+    }
+}
+
+impl<Mode: RtcMode> Rtc<Mode> {
+    /// Updates the TAMPCTRL fields of tamper input `num`.
+    fn set_tamper_input(&mut self, num: u8, action: u32, edge: TamperEdge, debounce: bool) {
+        let mask = (0b11 << (2 * num)) | (1 << (16 + num)) | (1 << (24 + num));
+        let mut value = action << (2 * num);
+        if edge == TamperEdge::Rising {
+            value |= 1 << (16 + num);
+        }
+        if debounce {
+            value |= 1 << (24 + num);
+        }
+        self.with_disabled(|rtc| {
+            // SYNC: None (enable-protected)
+            rtc.mode0()
+                .tampctrl()
+                .modify(|r, w| unsafe { w.bits((r.bits() & !mask) | value) });
+        });
+    }
+
+    /// Claims `pin` as tamper input and enables tamper detection on it.
+    pub fn enable_tamper_input<I: TamperPinId, C: InputConfig>(
+        &mut self,
+        pin: Pin<I, Input<C>>,
+        config: TamperConfig,
+    ) -> TamperInput<I, C> {
+        let action = match config.action {
+            TamperAction::Wake => 1,
+            TamperAction::Capture => 2,
+        };
+        self.set_tamper_input(I::NUM, action, config.edge, config.debounce);
+        TamperInput { pin }
+    }
+
+    /// Disables tamper detection on an input and returns its pin.
+    pub fn disable_tamper_input<I: TamperPinId, C: InputConfig>(
+        &mut self,
+        input: TamperInput<I, C>,
+    ) -> Pin<I, Input<C>> {
+        self.set_tamper_input(I::NUM, 0, TamperEdge::Falling, false);
+        input.pin
+    }
+
+    /// Starts driving the active layer pattern on `OUT`.
+    #[hal_cfg("pb01")]
+    pub fn enable_active_layer(
+        &mut self,
+        pin: Pin<PB01, PushPullOutput>,
+        frequency: ActiveLayerFrequency,
+    ) -> ActiveLayer {
+        self.with_disabled(|rtc| {
+            // SYNC: None (enable-protected)
+            rtc.mode0()
+                .ctrlb()
+                .modify(|_, w| w.actf().variant(frequency).rtcout().set_bit());
+        });
+        ActiveLayer { pin }
+    }
+
+    /// Stops driving the active layer pattern and returns the `OUT` pin.
+    #[hal_cfg("pb01")]
+    pub fn disable_active_layer(&mut self, layer: ActiveLayer) -> Pin<PB01, PushPullOutput> {
+        self.with_disabled(|rtc| {
+            // SYNC: None (enable-protected)
+            rtc.mode0().ctrlb().modify(|_, w| w.rtcout().clear_bit());
+        });
+        layer.pin
+    }
+
+    /// Claims `pin` as active layer input, comparing it to the pattern driven
+    /// on `OUT`.
+    ///
+    /// A mismatch sets the tamper flag and captures the time in `TIMESTAMP`.
+    #[hal_cfg("pb01")]
+    pub fn enable_active_layer_input<I: TamperPinId, C: InputConfig>(
+        &mut self,
+        pin: Pin<I, Input<C>>,
+        _layer: &ActiveLayer,
+        debounce: bool,
+    ) -> TamperInput<I, C> {
+        self.set_tamper_input(I::NUM, 3, TamperEdge::Falling, debounce);
+        TamperInput { pin }
+    }
+
+    /// Configures the debouncer used by tamper inputs with
+    /// [`TamperConfig::debounce`] set.
+    ///
+    /// - `frequency` is the sampling frequency, as division of the RTC clock
+    /// - `majority` requires 2 out of 3 samples to agree instead of 3 equal
+    ///   samples
+    /// - `asynchronous` detects the first edge without waiting for the
+    ///   debouncer, which still filters the following ones
+    pub fn set_tamper_debounce(
+        &mut self,
+        frequency: DebounceFrequency,
+        majority: bool,
+        asynchronous: bool,
+    ) {
+        self.with_disabled(|rtc| {
+            // SYNC: None (enable-protected)
+            rtc.mode0().ctrlb().modify(|_, w| {
+                w.debf()
+                    .variant(frequency)
+                    .debmaj()
+                    .bit(majority)
+                    .debasync()
+                    .bit(asynchronous)
+            });
+        });
+    }
+
+    /// Selects which registers are wiped by hardware on a tamper condition.
+    ///
+    /// - `general_purpose` resets `GP0`..`GP3`
+    /// - `backup` resets `BKUP0`..`BKUP7`
+    pub fn set_tamper_wipe(&mut self, general_purpose: bool, backup: bool) {
+        self.with_disabled(|rtc| {
+            // SYNC: None (enable-protected)
+            rtc.mode0()
+                .ctrla()
+                .modify(|_, w| w.gptrst().bit(general_purpose).bktrst().bit(backup));
+        });
+    }
+
+    /// Returns the tamper inputs which detected a tamper condition since the
+    /// last [`Rtc::clear_tamper`].
+    #[inline]
+    pub fn tamper_status(&self) -> TamperStatus {
+        // SYNC: None
+        let tampid = self.rtc.mode0().tampid().read();
+        TamperStatus {
+            inputs: (tampid.bits() & 0x1f) as u8,
+            event: tampid.tampevt().bit_is_set(),
+        }
+    }
+
+    /// Clears the tamper status and the tamper interrupt flag.
+    #[inline]
+    pub fn clear_tamper(&mut self) {
+        // SYNC: None
+        self.rtc
+            .mode0()
+            .tampid()
+            .write(|w| unsafe { w.bits(0x8000_001f) });
+        // SYNC: None
+        self.rtc.mode0().intflag().write(|w| w.tamper().set_bit());
+    }
+
+    /// Waits for a tamper condition, then clears and returns the tamper
+    /// status.
+    ///
+    /// The `RTC` interrupt has to be bound to
+    /// [`InterruptHandler`](super::InterruptHandler). It is enabled in the
+    /// NVIC by this method.
+    #[cfg(feature = "async")]
+    pub async fn wait_tamper<I>(&mut self, _irqs: I) -> TamperStatus
+    where
+        I: crate::async_hal::interrupts::Binding<
+                crate::async_hal::interrupts::RTC,
+                super::InterruptHandler,
+            >,
+    {
+        use crate::async_hal::interrupts::{Interrupt, RTC};
+
+        RTC::unpend();
+        unsafe { RTC::enable() };
+        let rtc = &self.rtc;
+        // SYNC: None
+        super::async_api::wait_until(rtc, TAMPER_INTERRUPT, || {
+            rtc.mode0().intflag().read().tamper().bit_is_set()
+        })
+        .await;
+        let status = self.tamper_status();
+        self.clear_tamper();
+        status
+    }
+
+    /// Reads backup register `BKUPn`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is not below [`BACKUP_REGISTERS`].
+    #[inline]
+    pub fn backup_register(&self, n: usize) -> u32 {
+        assert!(n < BACKUP_REGISTERS, "invalid backup register");
+        // SYNC: None
+        self.rtc.mode0().bkup(n).read().bits()
+    }
+
+    /// Writes backup register `BKUPn`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is not below [`BACKUP_REGISTERS`].
+    #[inline]
+    pub fn set_backup_register(&mut self, n: usize, value: u32) {
+        assert!(n < BACKUP_REGISTERS, "invalid backup register");
+        // SYNC: None
+        self.rtc.mode0().bkup(n).write(|w| unsafe { w.bits(value) });
+    }
+
+    /// Enables the general purpose registers.
+    ///
+    /// `GP0`/`GP1` (`CTRLB.GP0EN`) and `GP2`/`GP3` (`CTRLB.GP2EN`) share
+    /// their storage with compare and alarm registers, whose function is
+    /// disabled while the general purpose registers are enabled. See the
+    /// datasheet for the mapping in each mode.
+    pub fn enable_general_purpose_registers(&mut self, gp01: bool, gp23: bool) {
+        self.with_disabled(|rtc| {
+            // SYNC: None (enable-protected)
+            rtc.mode0()
+                .ctrlb()
+                .modify(|_, w| w.gp0en().bit(gp01).gp2en().bit(gp23));
+        });
+    }
+
+    /// Reads general purpose register `GPn`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is not below [`GENERAL_PURPOSE_REGISTERS`].
+    #[inline]
+    pub fn general_purpose_register(&self, n: usize) -> u32 {
+        assert!(
+            n < GENERAL_PURPOSE_REGISTERS,
+            "invalid general purpose register"
+        );
+        // SYNC: Write (we just read though)
+        self.rtc.mode0().gp(n).read().bits()
+    }
+
+    /// Writes general purpose register `GPn`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is not below [`GENERAL_PURPOSE_REGISTERS`].
+    #[inline]
+    pub fn set_general_purpose_register(&mut self, n: usize, value: u32) {
+        assert!(
+            n < GENERAL_PURPOSE_REGISTERS,
+            "invalid general purpose register"
+        );
+        // SYNC: Write
+        RtcMode0::sync(&self.rtc);
+        self.rtc.mode0().gp(n).write(|w| unsafe { w.bits(value) });
+    }
+}
+
+impl Rtc<ClockMode> {
+    /// Returns the time captured by the last tamper condition with
+    /// [`TamperAction::Capture`] or an active layer mismatch.
+    #[inline]
+    pub fn tamper_timestamp(&self) -> Datetime {
+        // SYNC: None
+        self.rtc.mode2().timestamp().read().into()
+    }
+}
+
+impl Rtc<Count32Mode> {
+    /// Returns the count captured by the last tamper condition with
+    /// [`TamperAction::Capture`] or an active layer mismatch.
+    #[inline]
+    pub fn tamper_timestamp(&self) -> u32 {
+        // SYNC: None
+        self.rtc.mode0().timestamp().read().count().bits()
+    }
+}
@@ -233,6 +233,122 @@
 //! note](https://www.silabs.com/documents/public/application-notes/an0059.0-uart-flow-control.pdf)
 //! provides more information about UART hardware flow control.
 //!
+//! # RS485 (SAMx5x only)
+//!
+//! SAMx5x SERCOMs can automatically drive the driver-enable input of an RS485
+//! transceiver. Specify the transmit-enable pad with [`Pads::te`] instead of
+//! [`Pads::rts`], leaving `CTS` empty. `TE` is always on `Pad2`. The resulting
+//! set of [`Pads`] implements [`Rs485Pads`], and the [`Config`] can then set
+//! how many bit periods `TE` stays asserted after the last stop bit, using
+//! [`Config::rs485_guard_time`].
+//!
+//! ```
+//! use atsamd_hal::sercom::{Sercom0, uart};
+//! use atsamd_hal::time::U32Ext;
+//!
+//! let pads = uart::Pads::<Sercom0>::default()
+//!     .rx(pins.pa07)
+//!     .tx(pins.pa04)
+//!     .te(pins.pa06);
+//! let uart = uart::Config::new(&mclk, sercom, pads, freq)
+//!     .baud(19200.Hz(), uart::BaudMode::Fractional(uart::Oversampling::Bits16))
+//!     .rs485_guard_time(2)
+//!     .enable();
+//! ```
+//!
+//! `TE` is controlled entirely in hardware: it is asserted as soon as the
+//! first start bit is sent, and stays asserted until the last stop bit plus
+//! the guard time. No GPIO toggling is needed. The `async` write methods and
+//! the blocking [`embedded_io::Write`] implementation with a DMA channel wait
+//! for the `TXC` flag before returning, so the frame is complete by the time
+//! the write returns, and it is safe to wait for a reply right away. A
+//! [`Transfer`] started with [`send_with_dma`] completes as soon as the last
+//! word has been handed to the SERCOM, before it is sent; after
+//! [`wait`](crate::dmac::Transfer::wait) returns, call `flush` on the
+//! [`Uart`] to wait for the end of the frame. Do the same when writing word
+//! by word through the blocking traits.
+//!
+//! SAMD11 and SAMD21 chips do not have RS485 support.
+//!
+//! # LIN
+//!
+//! The UART can act as a LIN slave on all chips, and as a LIN master on SAMx5x
+//! chips. Select the mode with [`Config::lin_mode`]. A LIN master generates
+//! the break and sync fields in hardware; their timing is set with
+//! [`Config::lin_break_length`] and [`Config::lin_header_delay`]. A LIN slave
+//! detects break fields, and automatically adjusts its baud rate from the sync
+//! field.
+//!
+//! A [`Duplex`] [`Uart`] with an [`EightBit`] character size provides a
+//! frame-level API, in blocking and `async` versions:
+//!
+//! * [`lin_send_header`](Uart::lin_send_header) transmits a header (master)
+//! * [`lin_read_header`](Uart::lin_read_header) waits for a header, and
+//!   returns the frame identifier (slave)
+//! * [`lin_write_response`](Uart::lin_write_response) and
+//!   [`lin_read_response`](Uart::lin_read_response) transfer the response and
+//!   its checksum
+//!
+//! ```
+//! use atsamd_hal::sercom::uart::{ChecksumModel, LinMode};
+//!
+//! let mut lin = uart::Config::new(&mclk, sercom, pads, freq)
+//!     .baud(19200.Hz(), BaudMode::Fractional(Oversampling::Bits16))
+//!     .lin_mode(LinMode::Slave)
+//!     .enable();
+//!
+//! let id = lin.lin_read_header()?;
+//! if id == 0x10 {
+//!     lin.lin_write_response(id, &[0x01, 0x02], ChecksumModel::Enhanced)?;
+//! }
+//! ```
+//!
+//! LIN transceivers read back every bit sent on the bus, so the frame-level
+//! API checks every transmitted byte against its echo. Errors are reported as
+//! a [`LinError`]. A break received in the middle of a frame is reported as
+//! [`LinError::Break`], and an inconsistent sync field as
+//! [`Error::InconsistentSyncField`]. The underlying `RXBRK` flag and `ISF`
+//! status flag can also be read directly through [`Uart::read_flags`] and
+//! [`Uart::read_status`].
+//!
+//! # ISO 7816 (SAMx5x only)
+//!
+//! SAMx5x SERCOMs can drive the IO line of a smart card. The IO line is both
+//! transmitted and received on `Pad0`, so the [`Pads`] must only specify a
+//! `TX` pad (see [`Iso7816Pads`]). The card clock is provided by a GCLK output
+//! pin ([`GclkOut`](crate::clock::v2::gclk::GclkOut)), from which the bit rate
+//! is derived with [`Config::iso7816_clock`]. The card reset line is a regular
+//! GPIO output.
+//!
+//! ```
+//! use atsamd_hal::sercom::uart::{Iso7816Protocol, Pads};
+//!
+//! let (gclk2, card_clock) = gclk2.enable_gclk_out(pins.pa16);
+//! let pads = Pads::<Sercom0>::default().tx(pins.pa04);
+//! let mut card = uart::Config::new(&mclk, sercom, pads, freq)
+//!     .iso7816_clock(&card_clock, 372, 1)
+//!     .iso7816_protocol(Iso7816Protocol::T0)
+//!     .max_iterations(3)
+//!     .enable_iso7816();
+//!
+//! reset.set_high().unwrap();
+//! let atr = card.read_atr()?;
+//! let mut response = [0; 256];
+//! let resp = card.t0_command(&[0x00, 0xa4, 0x04, 0x00, 0x00], &[], &mut response)?;
+//! ```
+//!
+//! [`Config::enable_iso7816`] returns a [`Uart`] with the [`Iso7816`]
+//! capability, which can be used to transfer raw characters, to receive the
+//! answer to reset with [`read_atr`](Uart::read_atr), to send T=0 commands
+//! with [`t0_command`](Uart::t0_command), or to transfer T=1 blocks with
+//! [`t1_write_block`](Uart::t1_write_block) and
+//! [`t1_read_block`](Uart::t1_read_block). When the card fails to receive a
+//! character after the maximum number of repetitions set with
+//! [`Config::max_iterations`], [`Error::MaxIterations`] is reported.
+//!
+//! Only the direct convention is supported, and the T=1 epilogue is always an
+//! LRC.
+//!
 //! # Splitting
 //!
 //! A `Uart<C, Duplex>` can be split into its [`RxDuplex`] and [`TxDuplex`]
@@ -323,7 +439,6 @@
 //! # Non-supported advanced features
 //!
 //! * Synchronous mode (USART) is not supported
-//! * LIN mode is not supported (SAMx5x)
 //! * 32-bit extension mode is not supported (SAMx5x). If you need to transfer
 //!   slices, consider using the DMA methods instead. The <span class="stab
 //!   portability" title="Available on crate feature `dma`
@@ -545,6 +660,14 @@ pub use flags::*;
 mod config;
 pub use config::*;
 
+mod lin;
+pub use lin::*;
+
+#[hal_module("sercom0-d5x")]
+mod iso7816 {}
+#[hal_cfg("sercom0-d5x")]
+pub use iso7816::*;
+
 pub mod impl_ehal;
 
 #[cfg(feature = "async")]
@@ -79,7 +79,7 @@ where
     C: ValidConfig,
     D: Capability,
 {
-    uart: Uart<C, D, R, T>,
+    pub(super) uart: Uart<C, D, R, T>,
 }
 
 /// Convenience type for a [`UartFuture`] with RX and TX capabilities
@@ -175,7 +175,7 @@ where
     S: Sercom,
 {
     #[inline]
-    async fn wait_flags(&mut self, flags_to_wait: Flags) {
+    pub(super) async fn wait_flags(&mut self, flags_to_wait: Flags) {
         let flags_to_wait = flags_to_wait & Flags::from_bits_retain(D::FLAG_MASK);
 
         core::future::poll_fn(|cx| {
@@ -321,11 +321,17 @@ where
 {
     /// Write the specified number of [`Word`](crate::sercom::uart::Word)s from
     /// a buffer to the UART, word by word.
+    ///
+    /// In RS485 mode, this returns once the last word has been completely
+    /// transmitted.
     #[inline]
     pub async fn write(&mut self, buffer: &[C::Word]) {
         for word in buffer {
             self.write_word(*word).await;
         }
+        if self.uart.config.as_ref().registers.get_rs485() {
+            self.wait_flags(Flags::TXC).await;
+        }
     }
 }
 
@@ -1,11 +1,13 @@
 //! UART [`Config`] definition and implementation\
 
-use atsamd_hal_macros::hal_cfg;
+use atsamd_hal_macros::{hal_cfg, hal_macro_helper};
 
 use super::{
     BaudMode, BitOrder, Capability, CharSize, CharSizeEnum, DataReg, DynCharSize, EightBit,
-    FixedCharSize, Parity, Registers, StopBits, Uart, ValidConfig, ValidPads,
+    FixedCharSize, LinMode, Parity, Registers, StopBits, Uart, ValidConfig, ValidPads,
 };
+#[hal_cfg("sercom0-d5x")]
+use super::{BreakLength, HeaderDelay, Rs485Pads, iso7816::Iso7816Frame};
 use crate::{
     pac,
     sercom::Sercom,
@@ -39,6 +41,7 @@ use num_traits::{AsPrimitive, PrimInt};
 ///
 /// [`enable`]: Config::enable
 /// [`Pads`]: super::Pads
+#[hal_macro_helper]
 pub struct Config<P, C = EightBit>
 where
     P: ValidPads,
@@ -48,6 +51,9 @@ where
     pads: P,
     chsize: PhantomData<C>,
     freq: Hertz,
+    /// Frame format to restore when leaving ISO 7816 mode
+    #[hal_cfg("sercom0-d5x")]
+    pub(super) iso7816_frame: Option<Iso7816Frame>,
 }
 
 /// Clock type needed to create a new [`Config`]. [`Pm`](pac::Pm) for thumbv6m
@@ -83,6 +89,7 @@ impl<P: ValidPads> Config<P> {
     }
 
     /// Create a new [`Config`] in the default configuration
+    #[hal_macro_helper]
     #[inline]
     fn default(sercom: P::Sercom, pads: P, freq: impl Into<Hertz>) -> Self {
         let mut registers = Registers::new(sercom);
@@ -98,6 +105,8 @@ impl<P: ValidPads> Config<P> {
             pads,
             chsize: PhantomData,
             freq: freq.into(),
+            #[hal_cfg("sercom0-d5x")]
+            iso7816_frame: None,
         }
     }
 }
@@ -108,6 +117,7 @@ where
     C: CharSize,
 {
     /// Change the [`Config`] [`CharSize`]
+    #[hal_macro_helper]
     #[inline]
     fn change<C2>(self) -> Config<P, C2>
     where
@@ -118,6 +128,8 @@ where
             pads: self.pads,
             chsize: PhantomData,
             freq: self.freq,
+            #[hal_cfg("sercom0-d5x")]
+            iso7816_frame: self.iso7816_frame,
         }
     }
 
@@ -261,6 +273,82 @@ where
         self.registers.get_collision_detection()
     }
 
+    /// Change the LIN mode (builder pattern version)
+    ///
+    /// In [`LinMode::Slave`], the UART detects break fields and adjusts its
+    /// baud rate from the sync field that follows. LIN master frames have no
+    /// parity bit, so selecting [`LinMode::Master`] disables parity.
+    #[inline]
+    pub fn lin_mode(mut self, mode: LinMode) -> Self {
+        self.set_lin_mode(mode);
+        self
+    }
+
+    /// Change the LIN mode (setter version)
+    ///
+    /// In [`LinMode::Slave`], the UART detects break fields and adjusts its
+    /// baud rate from the sync field that follows. LIN master frames have no
+    /// parity bit, so selecting [`LinMode::Master`] disables parity.
+    #[inline]
+    pub fn set_lin_mode(&mut self, mode: LinMode) {
+        self.registers.set_lin_mode(mode);
+    }
+
+    /// Get the current LIN mode
+    #[inline]
+    pub fn get_lin_mode(&self) -> LinMode {
+        self.registers.get_lin_mode()
+    }
+
+    /// Change the length of the break field sent in LIN master mode (builder
+    /// pattern version)
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub fn lin_break_length(mut self, length: BreakLength) -> Self {
+        self.set_lin_break_length(length);
+        self
+    }
+
+    /// Change the length of the break field sent in LIN master mode (setter
+    /// version)
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub fn set_lin_break_length(&mut self, length: BreakLength) {
+        self.registers.set_break_length(length);
+    }
+
+    /// Get the current length of the break field sent in LIN master mode
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub fn get_lin_break_length(&self) -> BreakLength {
+        self.registers.get_break_length()
+    }
+
+    /// Change the delays between the fields of a header sent in LIN master
+    /// mode (builder pattern version)
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub fn lin_header_delay(mut self, delay: HeaderDelay) -> Self {
+        self.set_lin_header_delay(delay);
+        self
+    }
+
+    /// Change the delays between the fields of a header sent in LIN master
+    /// mode (setter version)
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub fn set_lin_header_delay(&mut self, delay: HeaderDelay) {
+        self.registers.set_header_delay(delay);
+    }
+
+    /// Get the current delays between the fields of a header sent in LIN
+    /// master mode
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub fn get_lin_header_delay(&self) -> HeaderDelay {
+        self.registers.get_header_delay()
+    }
+
     /// Set the baud rate (builder pattern version)
     ///
     /// This function will calculate the best BAUD register setting based on the
@@ -391,6 +479,42 @@ impl<P: ValidPads> Config<P, DynCharSize> {
     }
 }
 
+#[hal_cfg("sercom0-d5x")]
+impl<P, C> Config<P, C>
+where
+    P: Rs485Pads,
+    C: CharSize,
+{
+    /// Set the RS485 guard time (builder pattern version)
+    ///
+    /// The `TE` pad is asserted by hardware as soon as a transmission starts,
+    /// and remains asserted for `bits` bit periods after the last stop bit
+    /// has been sent. The guard time ranges from 0 to 7 bit periods; larger
+    /// values saturate.
+    #[inline]
+    pub fn rs485_guard_time(mut self, bits: u8) -> Self {
+        self.set_rs485_guard_time(bits);
+        self
+    }
+
+    /// Set the RS485 guard time (setter version)
+    ///
+    /// The `TE` pad is asserted by hardware as soon as a transmission starts,
+    /// and remains asserted for `bits` bit periods after the last stop bit
+    /// has been sent. The guard time ranges from 0 to 7 bit periods; larger
+    /// values saturate.
+    #[inline]
+    pub fn set_rs485_guard_time(&mut self, bits: u8) {
+        self.registers.set_guard_time(bits);
+    }
+
+    /// Get the current RS485 guard time, in bit periods
+    #[inline]
+    pub fn get_rs485_guard_time(&self) -> u8 {
+        self.registers.get_guard_time()
+    }
+}
+
 impl<P, C> Config<P, C>
 where
     P: ValidPads,
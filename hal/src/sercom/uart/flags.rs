@@ -1,5 +1,6 @@
 //! Flag definitions
 
+use atsamd_hal_macros::{hal_cfg, hal_macro_helper};
 use bitflags::bitflags;
 
 //=============================================================================
@@ -56,17 +57,23 @@ const BUFOVF: u16 = 0x04;
 const CTS: u16 = 0x08;
 const ISF: u16 = 0x10;
 const COLL: u16 = 0x20;
+#[hal_cfg("sercom0-d5x")]
+const ITER: u16 = 0x80;
 
 /// Status flags available for RX transactions
 pub const RX_STATUS_MASK: u16 = PERR | FERR | BUFOVF | ISF | COLL;
 /// Status flags available for Duplex transactions
 pub const DUPLEX_STATUS_MASK: u16 = RX_STATUS_MASK;
+/// Status flags available for ISO 7816 transactions
+#[hal_cfg("sercom0-d5x")]
+pub const ISO7816_STATUS_MASK: u16 = DUPLEX_STATUS_MASK | ITER;
 
+#[hal_macro_helper]
 bitflags! {
     /// Status flags for UART Rx transactions
     ///
     /// The available status flags are `PERR`, `FERR`, `BUFOVF`,
-    /// `CTS`, `ISF` and `COLL`.
+    /// `CTS`, `ISF`, `COLL` and `ITER` (SAMx5x only).
     /// The binary format of the underlying bits exactly matches
     /// the STATUS bits.
     pub struct Status: u16 {
@@ -76,6 +83,8 @@ bitflags! {
         const CTS = CTS;
         const ISF = ISF;
         const COLL = COLL;
+        #[hal_cfg("sercom0-d5x")]
+        const ITER = ITER;
     }
 }
 
@@ -91,9 +100,18 @@ impl Status {
     /// * `BUFOVF` - Buffer overflow
     /// * `ISF` - Inconsistent SYNC field
     /// * `COLL` - Collision
+    /// * `ITER` - Maximum number of ISO 7816 repetitions reached (SAMx5x only)
+    #[hal_macro_helper]
     #[inline]
     pub fn check_bus_error(self) -> Result<(), Error> {
         use Error::*;
+        // ITER comes with a parity error on the last repetition, so check it
+        // first
+        #[hal_cfg("sercom0-d5x")]
+        if self.contains(Status::ITER) {
+            return Err(MaxIterations);
+        }
+
         if self.contains(Status::PERR) {
             Err(ParityError)
         } else if self.contains(Status::FERR) {
@@ -115,6 +133,7 @@ impl Status {
 //=============================================================================
 
 /// Errors available for UART transactions
+#[hal_macro_helper]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
@@ -128,12 +147,16 @@ pub enum Error {
     InconsistentSyncField,
     /// Detected a collision
     CollisionDetected,
+    /// Reached the maximum number of ISO 7816 repetitions
+    #[hal_cfg("sercom0-d5x")]
+    MaxIterations,
     /// DMA error
     #[cfg(feature = "dma")]
     Dma(crate::dmac::Error),
 }
 
 impl From<Error> for Status {
+    #[hal_macro_helper]
     #[inline]
     fn from(err: Error) -> Self {
         use Error::*;
@@ -143,6 +166,8 @@ impl From<Error> for Status {
             Overflow => Status::BUFOVF,
             InconsistentSyncField => Status::ISF,
             CollisionDetected => Status::COLL,
+            #[hal_cfg("sercom0-d5x")]
+            MaxIterations => Status::ITER,
             #[cfg(feature = "dma")]
             Dma(_) => unimplemented!(),
         }
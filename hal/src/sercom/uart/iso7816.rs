@@ -0,0 +1,591 @@
+//! ISO 7816 smart card support
+//!
+//! See the [module-level](crate::sercom::uart#iso-7816-samx5x-only)
+//! documentation for more details.
+
+use embedded_hal_nb::serial::{Read, Write};
+
+use super::{
+    BaudMode, BitOrder, Capability, Config, DUPLEX_FLAG_MASK, EightBit, Error, Flags,
+    ISO7816_STATUS_MASK, Oversampling, Pads, Parity, Receive, SingleOwner, SpecificConfig, Status,
+    StopBits, Transmit, Tx, Uart, ValidConfig, ValidPads,
+};
+use crate::{
+    clock::v2::gclk::{GclkIo, GclkOut},
+    sercom::{Sercom, ShareIoSet, pad::SomePad},
+    time::Hertz,
+    typelevel::{NoneT, Sealed},
+};
+use core::marker::PhantomData;
+
+//=============================================================================
+// Iso7816 capability
+//=============================================================================
+
+/// Marker type representing a UART driving the IO line of a smart card
+///
+/// The IO line is half-duplex: the UART can both transmit and receive, but
+/// never at the same time.
+pub enum Iso7816 {}
+impl Sealed for Iso7816 {}
+impl Capability for Iso7816 {
+    const FLAG_MASK: u8 = DUPLEX_FLAG_MASK;
+    const STATUS_MASK: u16 = ISO7816_STATUS_MASK;
+    const RXEN: bool = true;
+    const TXEN: bool = true;
+}
+impl Receive for Iso7816 {}
+impl Transmit for Iso7816 {}
+impl SingleOwner for Iso7816 {}
+
+//=============================================================================
+// Iso7816Pads
+//=============================================================================
+
+/// Marker trait for sets of [`Pads`] usable in ISO 7816 mode
+///
+/// In ISO 7816 mode, the smart card IO line is both transmitted and received
+/// on `Pad0`. A valid set of [`Pads`] therefore only specifies a `TX` pad,
+/// which is always `Pad0`.
+pub trait Iso7816Pads: ValidPads<Capability = Tx> {}
+
+impl<S, IO> Iso7816Pads for Pads<S, NoneT, IO>
+where
+    S: Sercom,
+    IO: SomePad,
+    (NoneT, IO, NoneT, NoneT): ShareIoSet,
+    Self: ValidPads<Capability = Tx>,
+{
+}
+
+//=============================================================================
+// Protocol
+//=============================================================================
+
+/// ISO 7816 transmission protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Iso7816Protocol {
+    /// Character-oriented protocol. Characters received with a parity error
+    /// are NACKed, and NACKed characters are repeated.
+    T0,
+    /// Block-oriented protocol, without character repetition
+    T1,
+}
+
+//=============================================================================
+// Config
+//=============================================================================
+
+/// Frame format configured before entering ISO 7816 mode
+#[derive(Clone, Copy)]
+pub(super) struct Iso7816Frame {
+    form: u8,
+    parity: Parity,
+    bit_order: BitOrder,
+}
+
+impl<P> Config<P, EightBit>
+where
+    P: Iso7816Pads,
+{
+    /// Set the bit rate from the card clock (builder pattern version)
+    ///
+    /// The elementary time unit (ETU) of the card is `f / d` card clock
+    /// cycles, where `f` and `d` are the clock rate conversion and baud rate
+    /// adjustment factors negotiated with the card. Their default values are
+    /// `f = 372` and `d = 1`. The card clock is provided by a [`GclkOut`]
+    /// pin, which must be kept alive while the card is in use.
+    #[inline]
+    pub fn iso7816_clock<I: GclkIo>(mut self, clock: &GclkOut<I>, f: u16, d: u16) -> Self {
+        self.set_iso7816_clock(clock, f, d);
+        self
+    }
+
+    /// Set the bit rate from the card clock (setter version)
+    ///
+    /// The elementary time unit (ETU) of the card is `f / d` card clock
+    /// cycles, where `f` and `d` are the clock rate conversion and baud rate
+    /// adjustment factors negotiated with the card. Their default values are
+    /// `f = 372` and `d = 1`. The card clock is provided by a [`GclkOut`]
+    /// pin, which must be kept alive while the card is in use.
+    #[inline]
+    pub fn set_iso7816_clock<I: GclkIo>(&mut self, clock: &GclkOut<I>, f: u16, d: u16) {
+        let baud = clock.freq().to_Hz() as u64 * d as u64 / f.max(1) as u64;
+        self.set_baud(
+            Hertz::from_raw(baud as u32),
+            BaudMode::Fractional(Oversampling::Bits16),
+        );
+    }
+
+    /// Change the transmission protocol (builder pattern version)
+    ///
+    /// [`Iso7816Protocol::T0`] uses two stop bits (12 ETU characters) and
+    /// NACKs characters received with a parity error.
+    /// [`Iso7816Protocol::T1`] uses one stop bit (11 ETU characters) and
+    /// inhibits NACKs.
+    #[inline]
+    pub fn iso7816_protocol(mut self, protocol: Iso7816Protocol) -> Self {
+        self.set_iso7816_protocol(protocol);
+        self
+    }
+
+    /// Change the transmission protocol (setter version)
+    ///
+    /// [`Iso7816Protocol::T0`] uses two stop bits (12 ETU characters) and
+    /// NACKs characters received with a parity error.
+    /// [`Iso7816Protocol::T1`] uses one stop bit (11 ETU characters) and
+    /// inhibits NACKs.
+    #[inline]
+    pub fn set_iso7816_protocol(&mut self, protocol: Iso7816Protocol) {
+        match protocol {
+            Iso7816Protocol::T0 => {
+                self.set_stop_bits(StopBits::TwoBits);
+                self.set_inhibit_nack(false);
+            }
+            Iso7816Protocol::T1 => {
+                self.set_stop_bits(StopBits::OneBit);
+                self.set_inhibit_nack(true);
+            }
+        }
+    }
+
+    /// Get the current transmission protocol
+    #[inline]
+    pub fn get_iso7816_protocol(&self) -> Iso7816Protocol {
+        match self.get_stop_bits() {
+            StopBits::TwoBits => Iso7816Protocol::T0,
+            StopBits::OneBit => Iso7816Protocol::T1,
+        }
+    }
+
+    /// Inhibit the NACK response to parity errors (builder pattern version)
+    ///
+    /// When set, characters received with a parity error are not NACKed, and
+    /// are not repeated by the card.
+    #[inline]
+    pub fn inhibit_nack(mut self, inhibit: bool) -> Self {
+        self.set_inhibit_nack(inhibit);
+        self
+    }
+
+    /// Inhibit the NACK response to parity errors (setter version)
+    ///
+    /// When set, characters received with a parity error are not NACKed, and
+    /// are not repeated by the card.
+    #[inline]
+    pub fn set_inhibit_nack(&mut self, inhibit: bool) {
+        self.registers.set_inhibit_nack(inhibit);
+    }
+
+    /// Get the current inhibit NACK setting
+    #[inline]
+    pub fn get_inhibit_nack(&self) -> bool {
+        self.registers.get_inhibit_nack()
+    }
+
+    /// Disable successive NACKs (builder pattern version)
+    ///
+    /// When set, a character received with a parity error is accepted without
+    /// a NACK once the maximum number of repetitions has been reached.
+    #[inline]
+    pub fn disable_successive_nack(mut self, disable: bool) -> Self {
+        self.set_disable_successive_nack(disable);
+        self
+    }
+
+    /// Disable successive NACKs (setter version)
+    ///
+    /// When set, a character received with a parity error is accepted without
+    /// a NACK once the maximum number of repetitions has been reached.
+    #[inline]
+    pub fn set_disable_successive_nack(&mut self, disable: bool) {
+        self.registers.set_disable_successive_nack(disable);
+    }
+
+    /// Get the current disable successive NACK setting
+    #[inline]
+    pub fn get_disable_successive_nack(&self) -> bool {
+        self.registers.get_disable_successive_nack()
+    }
+
+    /// Set the maximum number of repetitions of a character (builder pattern
+    /// version)
+    ///
+    /// Once a character has been repeated this many times, the
+    /// [`Error::MaxIterations`] error is reported. The maximum number of
+    /// repetitions ranges from 0 to 7; larger values saturate.
+    #[inline]
+    pub fn max_iterations(mut self, iterations: u8) -> Self {
+        self.set_max_iterations(iterations);
+        self
+    }
+
+    /// Set the maximum number of repetitions of a character (setter version)
+    ///
+    /// Once a character has been repeated this many times, the
+    /// [`Error::MaxIterations`] error is reported. The maximum number of
+    /// repetitions ranges from 0 to 7; larger values saturate.
+    #[inline]
+    pub fn set_max_iterations(&mut self, iterations: u8) {
+        self.registers.set_max_iterations(iterations);
+    }
+
+    /// Get the current maximum number of repetitions
+    #[inline]
+    pub fn get_max_iterations(&self) -> u8 {
+        self.registers.get_max_iterations()
+    }
+
+    /// Enable the UART peripheral in ISO 7816 mode and return a [`Uart`]
+    /// struct with the [`Iso7816`] capability.
+    ///
+    /// The frame format is set to 8 data bits with even parity, LSB first,
+    /// which corresponds to the direct convention.
+    #[inline]
+    pub fn enable_iso7816(mut self) -> Uart<Self, Iso7816> {
+        self.iso7816_frame = Some(Iso7816Frame {
+            form: self.registers.get_form(),
+            parity: self.get_parity(),
+            bit_order: self.get_bit_order(),
+        });
+        self.set_parity(Parity::Even);
+        self.set_bit_order(BitOrder::LsbFirst);
+        self.registers.set_form(0x7);
+        self.registers.enable(Iso7816::RXEN, Iso7816::TXEN);
+        Uart {
+            config: self,
+            capability: PhantomData,
+            rx_channel: NoneT,
+            tx_channel: NoneT,
+        }
+    }
+}
+
+//=============================================================================
+// Errors and frames
+//=============================================================================
+
+/// Errors available for ISO 7816 transactions
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Iso7816Error {
+    /// The UART reported an error. Reaching the maximum number of character
+    /// repetitions is reported as [`Error::MaxIterations`].
+    Uart(Error),
+    /// The answer to reset is malformed, uses the inverse convention, or has
+    /// an invalid check byte
+    Atr,
+    /// The card sent an unexpected T=0 procedure byte
+    Procedure,
+    /// The epilogue of a received T=1 block does not match its contents
+    Edc,
+    /// The provided buffer is too small
+    BufferTooSmall,
+}
+
+impl From<Error> for Iso7816Error {
+    #[inline]
+    fn from(err: Error) -> Self {
+        Iso7816Error::Uart(err)
+    }
+}
+
+/// Maximum length of an answer to reset
+pub const MAX_ATR_LEN: usize = 33;
+
+/// Answer to reset sent by a smart card
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Atr {
+    bytes: [u8; MAX_ATR_LEN],
+    len: usize,
+    historical: (usize, usize),
+}
+
+impl Atr {
+    /// Return the raw bytes of the answer to reset, starting with `TS`
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// Return the historical bytes of the answer to reset
+    #[inline]
+    pub fn historical_bytes(&self) -> &[u8] {
+        &self.bytes[self.historical.0..self.historical.1]
+    }
+}
+
+/// Status words and length of the response to a T=0 command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct T0Response {
+    /// Status words `SW1` and `SW2`
+    pub status: u16,
+    /// Number of response bytes received
+    pub len: usize,
+}
+
+/// A T=1 block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct T1Block<'a> {
+    /// Node address byte
+    pub nad: u8,
+    /// Protocol control byte
+    pub pcb: u8,
+    /// Information field, up to 254 bytes
+    pub inf: &'a [u8],
+}
+
+/// Compute the longitudinal redundancy check of a T=1 block
+#[inline]
+fn lrc(prologue: &[u8], inf: &[u8]) -> u8 {
+    prologue.iter().chain(inf).fold(0, |lrc, byte| lrc ^ byte)
+}
+
+//=============================================================================
+// Uart
+//=============================================================================
+
+impl<C> Uart<C, Iso7816>
+where
+    C: ValidConfig,
+{
+    /// Disable the UART peripheral, leave ISO 7816 mode, and return the
+    /// underlying [`Config`]
+    ///
+    /// The frame format, parity and bit order configured before
+    /// [`Config::enable_iso7816`] are restored.
+    #[inline]
+    pub fn disable(self) -> C {
+        let mut config = self.config;
+        let specific = config.as_mut();
+        specific.registers.disable();
+        if let Some(frame) = specific.iso7816_frame.take() {
+            specific.registers.set_form(frame.form);
+            specific.set_parity(frame.parity);
+            specific.set_bit_order(frame.bit_order);
+        }
+        config
+    }
+
+    /// Reconfigure the UART.
+    ///
+    /// Calling this method will temporarily disable the SERCOM peripheral, as
+    /// some registers are enable-protected. This may interrupt any ongoing
+    /// transactions.
+    #[inline]
+    pub fn reconfigure<U>(&mut self, update: U)
+    where
+        U: FnOnce(&mut SpecificConfig<C>),
+    {
+        self._reconfigure(update);
+    }
+}
+
+impl<P> Uart<Config<P, EightBit>, Iso7816>
+where
+    P: Iso7816Pads,
+{
+    /// Clear the error status flags, including `ITER`
+    #[inline]
+    fn clear_iso7816_errors(&mut self) {
+        self.clear_status(Status::PERR | Status::FERR | Status::BUFOVF | Status::ITER);
+        self.clear_flags(Flags::ERROR);
+    }
+
+    /// Receive a single character
+    #[inline]
+    fn iso7816_read(&mut self) -> Result<u8, Error> {
+        nb::block!(Read::read(self)).inspect_err(|_| self.clear_iso7816_errors())
+    }
+
+    /// Transmit characters, and wait until the last one has been accepted by
+    /// the card
+    #[inline]
+    fn iso7816_write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        for &byte in bytes {
+            nb::block!(Write::write(self, byte))?;
+        }
+        nb::block!(Write::flush(self))?;
+
+        let result = self.read_status().check_bus_error();
+        // The IO line is shared between the transmitter and the receiver, so
+        // discard anything received while transmitting.
+        self.flush_rx_buffer();
+        self.clear_iso7816_errors();
+        result
+    }
+
+    /// Receive the answer to reset of a card
+    ///
+    /// This method must be called right after releasing the reset line of the
+    /// card. It blocks until the whole answer to reset has been received.
+    /// Only the direct convention (`TS = 0x3B`) is supported.
+    #[inline]
+    pub fn read_atr(&mut self) -> Result<Atr, Iso7816Error> {
+        let mut atr = Atr {
+            bytes: [0; MAX_ATR_LEN],
+            len: 0,
+            historical: (0, 0),
+        };
+
+        let push = |uart: &mut Self, atr: &mut Atr| -> Result<u8, Iso7816Error> {
+            if atr.len == MAX_ATR_LEN {
+                return Err(Iso7816Error::Atr);
+            }
+            let byte = uart.iso7816_read()?;
+            atr.bytes[atr.len] = byte;
+            atr.len += 1;
+            Ok(byte)
+        };
+
+        if push(self, &mut atr)? != 0x3b {
+            return Err(Iso7816Error::Atr);
+        }
+
+        let t0 = push(self, &mut atr)?;
+        let historical = (t0 & 0x0f) as usize;
+        let mut indicator = t0 >> 4;
+        let mut check_byte = false;
+        loop {
+            // TAi, TBi and TCi
+            for bit in 0..3 {
+                if indicator & (1 << bit) != 0 {
+                    push(self, &mut atr)?;
+                }
+            }
+            // TDi
+            if indicator & 0x8 == 0 {
+                break;
+            }
+            let td = push(self, &mut atr)?;
+            check_byte |= td & 0x0f != 0;
+            indicator = td >> 4;
+        }
+
+        let start = atr.len;
+        for _ in 0..historical {
+            push(self, &mut atr)?;
+        }
+        atr.historical = (start, atr.len);
+
+        // TCK is only present if a protocol other than T=0 is indicated
+        if check_byte {
+            push(self, &mut atr)?;
+            if atr.bytes[1..atr.len].iter().fold(0, |x, byte| x ^ byte) != 0 {
+                return Err(Iso7816Error::Atr);
+            }
+        }
+
+        Ok(atr)
+    }
+
+    /// Send a T=0 command, and return the status words
+    ///
+    /// `header` contains the `CLA`, `INS`, `P1`, `P2` and `P3` bytes of the
+    /// command. If `data` is not empty, it is sent to the card after the
+    /// header, and `P3` should be its length. Otherwise, up to `P3` bytes of
+    /// response are received into `response` (256 bytes if `P3` is 0).
+    ///
+    /// Procedure bytes sent by the card are handled transparently.
+    #[inline]
+    pub fn t0_command(
+        &mut self,
+        header: &[u8; 5],
+        data: &[u8],
+        response: &mut [u8],
+    ) -> Result<T0Response, Iso7816Error> {
+        let ins = header[1];
+        let expected = match header[4] {
+            _ if response.is_empty() => 0,
+            0 => 256,
+            p3 => p3 as usize,
+        };
+        if data.is_empty() && response.len() < expected {
+            return Err(Iso7816Error::BufferTooSmall);
+        }
+
+        self.iso7816_write(header)?;
+
+        let mut len = 0;
+        loop {
+            let procedure = self.iso7816_read()?;
+            match procedure {
+                // NULL byte: the card needs more time
+                0x60 => continue,
+                // SW1, followed by SW2
+                sw1 if sw1 & 0xf0 == 0x60 || sw1 & 0xf0 == 0x90 => {
+                    let sw2 = self.iso7816_read()?;
+                    return Ok(T0Response {
+                        status: u16::from_be_bytes([sw1, sw2]),
+                        len,
+                    });
+                }
+                // ACK: transfer all remaining bytes, or the next byte only
+                ack if ack == ins || ack == !ins => {
+                    let count = if ack == ins { usize::MAX } else { 1 };
+                    if data.is_empty() {
+                        let end = expected.min(len.saturating_add(count));
+                        if len == end {
+                            return Err(Iso7816Error::Procedure);
+                        }
+                        for byte in &mut response[len..end] {
+                            *byte = self.iso7816_read()?;
+                        }
+                        len = end;
+                    } else {
+                        let end = data.len().min(len.saturating_add(count));
+                        if len == end {
+                            return Err(Iso7816Error::Procedure);
+                        }
+                        self.iso7816_write(&data[len..end])?;
+                        len = end;
+                    }
+                }
+                _ => return Err(Iso7816Error::Procedure),
+            }
+        }
+    }
+
+    /// Send a T=1 block, followed by its LRC epilogue
+    #[inline]
+    pub fn t1_write_block(&mut self, block: &T1Block<'_>) -> Result<(), Iso7816Error> {
+        if block.inf.len() > 254 {
+            return Err(Iso7816Error::BufferTooSmall);
+        }
+        let prologue = [block.nad, block.pcb, block.inf.len() as u8];
+        let epilogue = [lrc(&prologue, block.inf)];
+        self.iso7816_write(&prologue)?;
+        self.iso7816_write(block.inf)?;
+        self.iso7816_write(&epilogue)?;
+        Ok(())
+    }
+
+    /// Receive a T=1 block, and check its LRC epilogue
+    ///
+    /// The information field is stored in `buf`, which must be large enough
+    /// to hold it.
+    #[inline]
+    pub fn t1_read_block<'a>(&mut self, buf: &'a mut [u8]) -> Result<T1Block<'a>, Iso7816Error> {
+        let mut prologue = [0; 3];
+        for byte in &mut prologue {
+            *byte = self.iso7816_read()?;
+        }
+        let len = prologue[2] as usize;
+        let inf = buf.get_mut(..len).ok_or(Iso7816Error::BufferTooSmall)?;
+        for byte in inf.iter_mut() {
+            *byte = self.iso7816_read()?;
+        }
+        if self.iso7816_read()? != lrc(&prologue, inf) {
+            return Err(Iso7816Error::Edc);
+        }
+        Ok(T1Block {
+            nad: prologue[0],
+            pcb: prologue[1],
+            inf,
+        })
+    }
+}
@@ -0,0 +1,409 @@
+//! LIN bus support
+//!
+//! See the [module-level](crate::sercom::uart#lin) documentation for more
+//! details.
+
+use atsamd_hal_macros::{hal_cfg, hal_macro_helper};
+
+use super::{Config, Duplex, EightBit, Error, Flags, Status, Uart, ValidPads};
+
+//=============================================================================
+// LinMode
+//=============================================================================
+
+/// LIN operating mode of a UART
+#[hal_macro_helper]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LinMode {
+    /// LIN disabled, regular UART frames
+    Disabled,
+    /// LIN slave: break detection and auto-baud on the sync field
+    Slave,
+    /// LIN master: break and sync fields generated by hardware
+    #[hal_cfg("sercom0-d5x")]
+    Master,
+}
+
+/// Length of the break field sent by a LIN master
+#[hal_cfg("sercom0-d5x")]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BreakLength {
+    /// 13 bit times
+    Bits13 = 0,
+    /// 17 bit times
+    Bits17 = 1,
+    /// 21 bit times
+    Bits21 = 2,
+    /// 26 bit times
+    Bits26 = 3,
+}
+
+/// Delays inserted between the fields of a header sent by a LIN master
+#[hal_cfg("sercom0-d5x")]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HeaderDelay {
+    /// 1 bit time between break and sync, 1 bit time between sync and
+    /// identifier
+    Bits1 = 0,
+    /// 4 bit times between break and sync, 4 bit times between sync and
+    /// identifier
+    Bits4 = 1,
+    /// 8 bit times between break and sync, 4 bit times between sync and
+    /// identifier
+    Bits8 = 2,
+    /// 14 bit times between break and sync, 4 bit times between sync and
+    /// identifier
+    Bits14 = 3,
+}
+
+/// Checksum model of a LIN frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChecksumModel {
+    /// Classic checksum (LIN 1.x), computed over the data bytes only
+    Classic,
+    /// Enhanced checksum (LIN 2.x), computed over the protected identifier
+    /// and the data bytes
+    Enhanced,
+}
+
+//=============================================================================
+// LinError
+//=============================================================================
+
+/// Errors available for LIN frame transactions
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LinError {
+    /// The UART reported an error. An inconsistent sync field detected by
+    /// the auto-baud logic is reported as
+    /// [`Error::InconsistentSyncField`].
+    Bus(Error),
+    /// A break field was received in the middle of a frame
+    Break,
+    /// The sync field read back from the bus was not `0x55`
+    Sync,
+    /// The parity bits of the received protected identifier are invalid
+    IdentifierParity,
+    /// The received checksum does not match the frame contents
+    Checksum,
+    /// A byte read back from the bus differs from the transmitted byte
+    Bit,
+}
+
+impl From<Error> for LinError {
+    #[inline]
+    fn from(err: Error) -> Self {
+        LinError::Bus(err)
+    }
+}
+
+//=============================================================================
+// Frame helpers
+//=============================================================================
+
+/// LIN sync field value
+#[hal_cfg("sercom0-d5x")]
+const SYNC: u8 = 0x55;
+
+/// Compute the protected identifier of a 6-bit frame identifier
+#[inline]
+pub const fn protected_id(id: u8) -> u8 {
+    let id = id & 0x3f;
+    let p0 = (id ^ (id >> 1) ^ (id >> 2) ^ (id >> 4)) & 1;
+    let p1 = !((id >> 1) ^ (id >> 3) ^ (id >> 4) ^ (id >> 5)) & 1;
+    id | (p0 << 6) | (p1 << 7)
+}
+
+/// Compute the checksum of a LIN frame
+#[inline]
+pub fn checksum(model: ChecksumModel, id: u8, data: &[u8]) -> u8 {
+    let init = match model {
+        ChecksumModel::Classic => 0,
+        ChecksumModel::Enhanced => protected_id(id) as u16,
+    };
+    let sum = data.iter().fold(init, |sum, &byte| {
+        let sum = sum + byte as u16;
+        if sum > 0xff { sum - 0xff } else { sum }
+    });
+    !(sum as u8)
+}
+
+/// Recover the frame identifier from a protected identifier, checking its
+/// parity bits
+#[inline]
+fn check_protected_id(pid: u8) -> Result<u8, LinError> {
+    let id = pid & 0x3f;
+    if protected_id(id) == pid {
+        Ok(id)
+    } else {
+        Err(LinError::IdentifierParity)
+    }
+}
+
+//=============================================================================
+// Uart
+//=============================================================================
+
+impl<P> Uart<Config<P, EightBit>, Duplex>
+where
+    P: ValidPads,
+{
+    /// Check the status flags after a `RXC` or `ERROR` flag, and clear them in
+    /// case of an error
+    #[inline]
+    fn lin_check_status(&mut self) -> Result<(), LinError> {
+        let result = self.read_status().check_bus_error();
+        if result.is_err() {
+            self.clear_status(
+                Status::BUFOVF | Status::FERR | Status::PERR | Status::ISF | Status::COLL,
+            );
+            self.clear_flags(Flags::ERROR);
+        }
+        result.map_err(LinError::Bus)
+    }
+
+    /// Read a received byte, after a `RXC` or `ERROR` flag
+    #[inline]
+    fn lin_read_byte(&mut self) -> Result<u8, LinError> {
+        self.lin_check_status()?;
+        if self.read_flags().contains(Flags::RXBRK) {
+            self.clear_flags(Flags::RXBRK);
+            self.flush_rx_buffer();
+            return Err(LinError::Break);
+        }
+        Ok(unsafe { self.read_data() as u8 })
+    }
+
+    /// Prepare the receiver before waiting for a header
+    #[inline]
+    fn lin_start_header(&mut self) {
+        self.flush_rx_buffer();
+        self.clear_flags(Flags::RXBRK | Flags::ERROR);
+    }
+
+    /// Check the bytes read back from the bus after sending a header
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    fn lin_header_echo(&mut self, pid: u8) -> Result<(), LinError> {
+        // Depending on the transceiver, the break field may or may not be read
+        // back as a byte with a frame error. Only the last two bytes are
+        // checked.
+        let mut echo = [0; 2];
+        while self.read_flags().contains(Flags::RXC) {
+            echo = [echo[1], unsafe { self.read_data() as u8 }];
+        }
+        self.clear_status(Status::BUFOVF | Status::FERR | Status::PERR | Status::ISF);
+        self.clear_flags(Flags::RXBRK | Flags::ERROR);
+
+        if echo[0] != SYNC {
+            Err(LinError::Sync)
+        } else if echo[1] != pid {
+            Err(LinError::Bit)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Transmit a LIN header for the frame identifier `id` (LIN master only)
+    ///
+    /// The break and sync fields are generated by hardware, followed by the
+    /// protected identifier. This method blocks until the whole header has
+    /// been transmitted and read back from the bus.
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub fn lin_send_header(&mut self, id: u8) -> Result<(), LinError> {
+        let pid = protected_id(id);
+        self.lin_start_header();
+        while !self.read_flags().contains(Flags::DRE) {
+            core::hint::spin_loop();
+        }
+        self.config.as_mut().registers.send_lin_header(pid);
+        while !self.read_flags().contains(Flags::TXC) {
+            core::hint::spin_loop();
+        }
+        self.lin_header_echo(pid)
+    }
+
+    /// Wait for a LIN header and return the received frame identifier (LIN
+    /// slave only)
+    ///
+    /// Any data received before the break field is discarded. The baud rate is
+    /// automatically adjusted by hardware from the sync field.
+    #[inline]
+    pub fn lin_read_header(&mut self) -> Result<u8, LinError> {
+        self.lin_start_header();
+        while !self.read_flags().contains(Flags::RXBRK) {
+            core::hint::spin_loop();
+        }
+        self.clear_flags(Flags::RXBRK);
+        while !self.read_flags().intersects(Flags::RXC | Flags::ERROR) {
+            core::hint::spin_loop();
+        }
+        let pid = self.lin_read_byte()?;
+        check_protected_id(pid)
+    }
+
+    /// Transmit a single byte and check that it is read back from the bus
+    #[inline]
+    fn lin_write_byte(&mut self, byte: u8) -> Result<(), LinError> {
+        while !self.read_flags().contains(Flags::DRE) {
+            core::hint::spin_loop();
+        }
+        unsafe { self.write_data(byte.into()) };
+        while !self.read_flags().intersects(Flags::RXC | Flags::ERROR) {
+            core::hint::spin_loop();
+        }
+        if self.lin_read_byte()? != byte {
+            return Err(LinError::Bit);
+        }
+        Ok(())
+    }
+
+    /// Transmit the response of the frame identified by `id`, followed by its
+    /// checksum
+    ///
+    /// Every byte is read back from the bus before the next one is sent.
+    #[inline]
+    pub fn lin_write_response(
+        &mut self,
+        id: u8,
+        data: &[u8],
+        model: ChecksumModel,
+    ) -> Result<(), LinError> {
+        for &byte in data {
+            self.lin_write_byte(byte)?;
+        }
+        self.lin_write_byte(checksum(model, id, data))
+    }
+
+    /// Receive the response of the frame identified by `id`, and verify its
+    /// checksum
+    ///
+    /// The length of the response is given by the length of `data`.
+    #[inline]
+    pub fn lin_read_response(
+        &mut self,
+        id: u8,
+        data: &mut [u8],
+        model: ChecksumModel,
+    ) -> Result<(), LinError> {
+        for byte in data.iter_mut() {
+            while !self.read_flags().intersects(Flags::RXC | Flags::ERROR) {
+                core::hint::spin_loop();
+            }
+            *byte = self.lin_read_byte()?;
+        }
+        while !self.read_flags().intersects(Flags::RXC | Flags::ERROR) {
+            core::hint::spin_loop();
+        }
+        if self.lin_read_byte()? != checksum(model, id, data) {
+            return Err(LinError::Checksum);
+        }
+        Ok(())
+    }
+}
+
+//=============================================================================
+// UartFuture
+//=============================================================================
+
+#[cfg(feature = "async")]
+mod impl_async {
+    use super::*;
+    use crate::sercom::{Sercom, uart::UartFuture};
+
+    impl<P, S> UartFuture<Config<P, EightBit>, Duplex>
+    where
+        P: ValidPads<Sercom = S>,
+        S: Sercom,
+    {
+        /// Transmit a LIN header for the frame identifier `id` (LIN master
+        /// only)
+        ///
+        /// The break and sync fields are generated by hardware, followed by
+        /// the protected identifier. Completes once the whole header has been
+        /// transmitted and read back from the bus.
+        #[hal_cfg("sercom0-d5x")]
+        #[inline]
+        pub async fn lin_send_header(&mut self, id: u8) -> Result<(), LinError> {
+            let pid = protected_id(id);
+            self.uart.lin_start_header();
+            self.wait_flags(Flags::DRE).await;
+            self.uart.config.as_mut().registers.send_lin_header(pid);
+            self.wait_flags(Flags::TXC).await;
+            self.uart.lin_header_echo(pid)
+        }
+
+        /// Wait for a LIN header and return the received frame identifier
+        /// (LIN slave only)
+        ///
+        /// Any data received before the break field is discarded. The baud
+        /// rate is automatically adjusted by hardware from the sync field.
+        #[inline]
+        pub async fn lin_read_header(&mut self) -> Result<u8, LinError> {
+            self.uart.lin_start_header();
+            self.wait_flags(Flags::RXBRK).await;
+            self.uart.clear_flags(Flags::RXBRK);
+            self.wait_flags(Flags::RXC | Flags::ERROR).await;
+            let pid = self.uart.lin_read_byte()?;
+            check_protected_id(pid)
+        }
+
+        /// Transmit a single byte and check that it is read back from the bus
+        #[inline]
+        async fn lin_write_byte(&mut self, byte: u8) -> Result<(), LinError> {
+            self.wait_flags(Flags::DRE).await;
+            unsafe { self.uart.write_data(byte.into()) };
+            self.wait_flags(Flags::RXC | Flags::ERROR).await;
+            if self.uart.lin_read_byte()? != byte {
+                return Err(LinError::Bit);
+            }
+            Ok(())
+        }
+
+        /// Transmit the response of the frame identified by `id`, followed by
+        /// its checksum
+        ///
+        /// Every byte is read back from the bus before the next one is sent.
+        #[inline]
+        pub async fn lin_write_response(
+            &mut self,
+            id: u8,
+            data: &[u8],
+            model: ChecksumModel,
+        ) -> Result<(), LinError> {
+            for &byte in data {
+                self.lin_write_byte(byte).await?;
+            }
+            self.lin_write_byte(checksum(model, id, data)).await
+        }
+
+        /// Receive the response of the frame identified by `id`, and verify
+        /// its checksum
+        ///
+        /// The length of the response is given by the length of `data`.
+        #[inline]
+        pub async fn lin_read_response(
+            &mut self,
+            id: u8,
+            data: &mut [u8],
+            model: ChecksumModel,
+        ) -> Result<(), LinError> {
+            for byte in data.iter_mut() {
+                self.wait_flags(Flags::RXC | Flags::ERROR).await;
+                *byte = self.uart.lin_read_byte()?;
+            }
+            self.wait_flags(Flags::RXC | Flags::ERROR).await;
+            if self.uart.lin_read_byte()? != checksum(model, id, data) {
+                return Err(LinError::Checksum);
+            }
+            Ok(())
+        }
+    }
+}
@@ -1,4 +1,7 @@
 //! UART pad definitions for thumbv6m targets
+//!
+//! The SERCOM of thumbv6m targets has no RS485 mode, so no `TE` pad can be
+//! specified. A `Pad2` `RTS` without `CTS` is used as a regular `RTS` pad.
 
 use atsamd_hal_macros::hal_cfg;
 
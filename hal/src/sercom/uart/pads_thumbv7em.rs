@@ -279,6 +279,30 @@ where
     }
 }
 
+impl<S, RX, TX> Pads<S, RX, TX>
+where
+    S: Sercom,
+    RX: OptionalPad,
+    TX: OptionalPad,
+    (RX, TX, NoneT, NoneT): ShareIoSet,
+{
+    /// Set the RS485 `TE` [`Pad`], which is always [`Pad2`]
+    ///
+    /// `TE` shares its slot with `RTS`. When it is specified without a `CTS`
+    /// pad, the SERCOM is configured in RS485 mode, and drives `TE` high for
+    /// the whole duration of a transmission, including the guard time set
+    /// with [`Config::rs485_guard_time`].
+    #[inline]
+    pub fn te<Id>(self, pin: impl AnyPin<Id = Id>) -> Pads<S, RX, TX, Pad<S, Id>>
+    where
+        Id: GetPad<S>,
+        (RX, TX, Pad<S, Id>, NoneT): ShareIoSet,
+        Pin<Id, <Id as GetPad<S>>::PinMode>: IsPad,
+    {
+        self.rts(pin)
+    }
+}
+
 /// Define a set of [`Pads`] using [`PinId`]s instead of [`Pin`]s
 ///
 /// In some cases, it is more convenient to specify a set of `Pads` using
@@ -420,6 +444,29 @@ where
     type Capability = Duplex;
 }
 
+//=============================================================================
+// Rs485Pads
+//=============================================================================
+
+/// Marker trait for sets of [`Pads`] configured in RS485 mode
+///
+/// This trait labels sets of [`Pads`] that specify a `TX` and a `TE` pad, but
+/// no `CTS` pad. Such sets map to `TXPO` = 3, where the hardware drives `TE`
+/// during transmissions. The guard time can then be set through
+/// [`Config::rs485_guard_time`].
+pub trait Rs485Pads: ValidPads {}
+
+impl<S, RX, TX, TE> Rs485Pads for Pads<S, RX, TX, TE, NoneT>
+where
+    S: Sercom,
+    RX: OptionalPad,
+    TX: SomePad,
+    TE: SomePad,
+    (RX, TX, TE, NoneT): ShareIoSet,
+    Self: ValidPads,
+{
+}
+
 //=============================================================================
 // ValidConfig
 //=============================================================================
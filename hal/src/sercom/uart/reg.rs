@@ -1,8 +1,13 @@
 //! Register-level access to UART configuration
 
-use atsamd_hal_macros::hal_cfg;
+use atsamd_hal_macros::{hal_cfg, hal_macro_helper};
 
-use super::{BaudMode, BitOrder, CharSizeEnum, Flags, Oversampling, Parity, Status, StopBits};
+use super::{
+    BaudMode, BitOrder, CharSizeEnum, Flags, LinMode, Oversampling, Parity, Status, StopBits,
+};
+
+#[hal_cfg("sercom0-d5x")]
+use super::{BreakLength, HeaderDelay};
 
 use crate::pac;
 use crate::sercom::Sercom;
@@ -128,8 +133,6 @@ impl<S: Sercom> Registers<S> {
     /// Change the parity setting
     #[inline]
     pub(super) fn set_parity(&mut self, parity: Parity) {
-        // Use only the first two available settings in the FORM field.
-        // Ignore auto-baud options.
         let enabled = match parity {
             Parity::None => false,
             Parity::Odd => {
@@ -142,9 +145,15 @@ impl<S: Sercom> Registers<S> {
             }
         };
 
-        self.usart()
-            .ctrla()
-            .modify(|_, w| unsafe { w.form().bits(enabled as u8) });
+        // Keep the auto-baud setting of the FORM field. LIN master frames have
+        // no parity bit.
+        self.usart().ctrla().modify(|r, w| {
+            let form = match r.form().bits() {
+                0x2 => 0x2,
+                form => (form & 0x4) | enabled as u8,
+            };
+            unsafe { w.form().bits(form) }
+        });
     }
 
     /// Get the current parity setting
@@ -165,6 +174,152 @@ impl<S: Sercom> Registers<S> {
         }
     }
 
+    /// Change the LIN mode, while keeping the current parity setting
+    #[hal_macro_helper]
+    #[inline]
+    pub(super) fn set_lin_mode(&mut self, mode: LinMode) {
+        self.usart().ctrla().modify(|r, w| {
+            let parity = match r.form().bits() {
+                0x2 => 0,
+                form => form & 0x1,
+            };
+            let form = match mode {
+                LinMode::Disabled => parity,
+                LinMode::Slave => 0x4 | parity,
+                #[hal_cfg("sercom0-d5x")]
+                LinMode::Master => 0x2,
+            };
+            unsafe { w.form().bits(form) }
+        });
+    }
+
+    /// Get the current LIN mode
+    #[hal_macro_helper]
+    #[inline]
+    pub(super) fn get_lin_mode(&self) -> LinMode {
+        match self.usart().ctrla().read().form().bits() {
+            0x4 | 0x5 => LinMode::Slave,
+            #[hal_cfg("sercom0-d5x")]
+            0x2 => LinMode::Master,
+            _ => LinMode::Disabled,
+        }
+    }
+
+    /// Set the length of the break field sent in LIN master mode
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn set_break_length(&mut self, length: BreakLength) {
+        self.usart()
+            .ctrlc()
+            .modify(|_, w| unsafe { w.brklen().bits(length as u8) });
+    }
+
+    /// Get the length of the break field sent in LIN master mode
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn get_break_length(&self) -> BreakLength {
+        match self.usart().ctrlc().read().brklen().bits() {
+            0 => BreakLength::Bits13,
+            1 => BreakLength::Bits17,
+            2 => BreakLength::Bits21,
+            _ => BreakLength::Bits26,
+        }
+    }
+
+    /// Set the delays between the fields of a header sent in LIN master mode
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn set_header_delay(&mut self, delay: HeaderDelay) {
+        self.usart()
+            .ctrlc()
+            .modify(|_, w| unsafe { w.hdrdly().bits(delay as u8) });
+    }
+
+    /// Get the delays between the fields of a header sent in LIN master mode
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn get_header_delay(&self) -> HeaderDelay {
+        match self.usart().ctrlc().read().hdrdly().bits() {
+            0 => HeaderDelay::Bits1,
+            1 => HeaderDelay::Bits4,
+            2 => HeaderDelay::Bits8,
+            _ => HeaderDelay::Bits14,
+        }
+    }
+
+    /// Transmit a LIN header in LIN master mode. The break and sync fields are
+    /// generated by hardware, followed by the given protected identifier.
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn send_lin_header(&mut self, pid: u8) {
+        let usart = self.usart();
+        usart.ctrlb().modify(|_, w| w.lincmd().auto_transmit_cmd());
+        while usart.syncbusy().read().ctrlb().bit_is_set() {}
+        usart.data().write(|w| unsafe { w.data().bits(pid.into()) });
+    }
+
+    /// Set the raw frame format. `0x7` selects the ISO 7816 frame format.
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn set_form(&mut self, form: u8) {
+        self.usart()
+            .ctrla()
+            .modify(|_, w| unsafe { w.form().bits(form) });
+    }
+
+    /// Get the raw frame format
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn get_form(&self) -> u8 {
+        self.usart().ctrla().read().form().bits()
+    }
+
+    /// Inhibit or enable the NACK response to parity errors in ISO 7816 mode
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn set_inhibit_nack(&mut self, inhibit: bool) {
+        self.usart().ctrlc().modify(|_, w| w.inack().bit(inhibit));
+    }
+
+    /// Get the current inhibit NACK setting
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn get_inhibit_nack(&self) -> bool {
+        self.usart().ctrlc().read().inack().bit()
+    }
+
+    /// Disable or enable successive NACKs once the maximum number of
+    /// repetitions is reached in ISO 7816 mode
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn set_disable_successive_nack(&mut self, disable: bool) {
+        self.usart().ctrlc().modify(|_, w| w.dsnack().bit(disable));
+    }
+
+    /// Get the current disable successive NACK setting
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn get_disable_successive_nack(&self) -> bool {
+        self.usart().ctrlc().read().dsnack().bit()
+    }
+
+    /// Set the maximum number of repetitions in ISO 7816 mode. Values above 7
+    /// saturate.
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn set_max_iterations(&mut self, iterations: u8) {
+        self.usart()
+            .ctrlc()
+            .modify(|_, w| unsafe { w.maxiter().bits(iterations.min(7)) });
+    }
+
+    /// Get the maximum number of repetitions in ISO 7816 mode
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn get_max_iterations(&self) -> u8 {
+        self.usart().ctrlc().read().maxiter().bits()
+    }
+
     /// Change the stop bit setting
     #[inline]
     pub(super) fn set_stop_bits(&mut self, stop_bits: StopBits) {
@@ -216,6 +371,36 @@ impl<S: Sercom> Registers<S> {
         self.usart().ctrlb().read().colden().bit()
     }
 
+    /// Set the RS485 guard time, in bit periods.
+    ///
+    /// The `TE` pad will remain asserted for this many bit periods after the
+    /// last stop bit has been transmitted. Values above 7 saturate.
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn set_guard_time(&mut self, bits: u8) {
+        self.usart()
+            .ctrlc()
+            .modify(|_, w| unsafe { w.gtime().bits(bits.min(7)) });
+    }
+
+    /// Get the current RS485 guard time, in bit periods
+    #[hal_cfg("sercom0-d5x")]
+    #[inline]
+    pub(super) fn get_guard_time(&self) -> u8 {
+        self.usart().ctrlc().read().gtime().bits()
+    }
+
+    /// Check whether the `TE` pad of an RS485 transceiver is in use
+    #[cfg(feature = "async")]
+    #[inline]
+    #[hal_macro_helper]
+    pub(super) fn get_rs485(&self) -> bool {
+        #[hal_cfg("sercom0-d5x")]
+        return self.usart().ctrla().read().txpo().bits() == 0x3;
+        #[hal_cfg(any("sercom0-d11", "sercom0-d21"))]
+        return false;
+    }
+
     /// Set the baud rate
     ///
     /// This function will calculate the best BAUD register setting based on the